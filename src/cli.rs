@@ -1,45 +1,300 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use anyhow::Result;
 use clap::Parser;
+use log::LevelFilter;
 
-use crate::server;
+use crate::{
+    config::FileConfig,
+    server::{self, RouteRateLimit, RouteSet},
+};
 
+/// Configuration of the server. Every option can also be given in an environment variable or in
+/// a configuration file. Command line arguments take precedence over environment variables, which
+/// take precedence over the configuration file.
 #[derive(Parser, Clone, Debug)]
 pub struct ServerConfig {
-    database_path: PathBuf,
-    #[arg(long, default_value = "localhost")]
-    url: String,
-    #[arg(long, default_value = "8080")]
-    port: u16,
-    #[arg(long, short = 'l')]
+    #[arg(env = "DEEPDECIPHER_DATABASE_PATH")]
+    database_path: Option<PathBuf>,
+    /// Path to a TOML configuration file.
+    #[arg(long = "config", short = 'c', env = "DEEPDECIPHER_CONFIG")]
+    config_path: Option<PathBuf>,
+    /// [default: localhost]
+    #[arg(long, env = "DEEPDECIPHER_URL")]
+    url: Option<String>,
+    /// [default: 8080]
+    #[arg(long, env = "DEEPDECIPHER_PORT")]
+    port: Option<u16>,
+    #[arg(long, short = 'l', env = "DEEPDECIPHER_LOG_PATH")]
     log_path: Option<PathBuf>,
-    #[arg(short = 'w')]
+    /// Level of messages logged to stdout. [default: info]
+    #[arg(long, env = "DEEPDECIPHER_LOG_LEVEL")]
+    log_level: Option<LevelFilter>,
+    /// Level of messages logged to the log file. [default: debug]
+    #[arg(long, env = "DEEPDECIPHER_LOG_FILE_LEVEL")]
+    log_file_level: Option<LevelFilter>,
+    #[arg(short = 'w', env = "DEEPDECIPHER_NUM_WORKERS")]
     num_workers: Option<usize>,
+    /// Origin allowed to make cross-origin requests. May be given multiple times. `*` allows any
+    /// origin. [default: *]
+    #[arg(
+        long = "allowed-origin",
+        env = "DEEPDECIPHER_ALLOWED_ORIGINS",
+        value_delimiter = ','
+    )]
+    allowed_origins: Vec<String>,
+    /// Groups of routes to serve. May be given multiple times. The groups are `api`, `doc`,
+    /// `export`, `health`, `metrics` and `admin`. [default: all]
+    #[arg(
+        long = "enabled-route",
+        env = "DEEPDECIPHER_ENABLED_ROUTES",
+        value_delimiter = ','
+    )]
+    enabled_routes: Vec<RouteSet>,
+    /// Seconds a client has to send the headers of a request. [default: 5]
+    #[arg(long, env = "DEEPDECIPHER_CLIENT_REQUEST_TIMEOUT")]
+    client_request_timeout: Option<u64>,
+    /// Seconds an idle connection is kept open. [default: 5]
+    #[arg(long, env = "DEEPDECIPHER_KEEP_ALIVE_TIMEOUT")]
+    keep_alive_timeout: Option<u64>,
+    /// Seconds requests are given to finish when the server is shut down. [default: 30]
+    #[arg(long, env = "DEEPDECIPHER_SHUTDOWN_TIMEOUT")]
+    shutdown_timeout: Option<u64>,
+    /// Bearer token granting access to the admin API. May be given multiple times. The admin API
+    /// is disabled if no tokens are given.
+    #[arg(
+        long = "admin-token",
+        env = "DEEPDECIPHER_ADMIN_TOKENS",
+        value_delimiter = ','
+    )]
+    admin_tokens: Vec<String>,
+    /// Rate limit for a group of routes given as `GROUP=BURST/PER_SECOND`, e.g. `neuron=20/5`.
+    /// The groups are `index`, `neuron`, `search` and `bulk`. Groups without a limit are not rate
+    /// limited.
+    #[arg(
+        long = "rate-limit",
+        env = "DEEPDECIPHER_RATE_LIMITS",
+        value_delimiter = ','
+    )]
+    rate_limits: Vec<RouteRateLimit>,
+    /// API key clients can send in the `X-Api-Key` header to be rate limited by key instead of by
+    /// IP address. May be given multiple times.
+    #[arg(long = "api-key", env = "DEEPDECIPHER_API_KEYS", value_delimiter = ',')]
+    api_keys: Vec<String>,
+    /// IP address or API key that is never rate limited. May be given multiple times.
+    #[arg(
+        long = "rate-limit-allow",
+        env = "DEEPDECIPHER_RATE_LIMIT_ALLOW",
+        value_delimiter = ','
+    )]
+    rate_limit_allowlist: Vec<String>,
+    /// Identify clients by the `Forwarded` and `X-Forwarded-For` headers. Only enable this when
+    /// the server is behind a trusted proxy. Use `--trust-proxy-headers=false` to override the
+    /// configuration file.
+    #[arg(
+        long,
+        env = "DEEPDECIPHER_TRUST_PROXY_HEADERS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    trust_proxy_headers: Option<bool>,
+    /// Number of models whose neuron stores are kept deserialized in memory. [default: 4]
+    #[arg(long, env = "DEEPDECIPHER_NEURON_STORE_CACHE_SIZE")]
+    neuron_store_cache_size: Option<usize>,
+    /// Never fetch data missing from the database from its original source, such as neuroscope.io,
+    /// while serving requests. Use `--offline=false` to override the configuration file.
+    #[arg(
+        long,
+        env = "DEEPDECIPHER_OFFLINE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    offline: Option<bool>,
+    /// Seconds a request to an original data source may take. [default: 10]
+    #[arg(long, env = "DEEPDECIPHER_UPSTREAM_TIMEOUT")]
+    upstream_timeout: Option<u64>,
+}
+
+fn or_file<T>(value: Vec<T>, file_value: Option<Vec<T>>) -> Vec<T> {
+    if value.is_empty() {
+        file_value.unwrap_or_default()
+    } else {
+        value
+    }
 }
 
 impl ServerConfig {
     pub async fn start(self) -> Result<()> {
-        server::start_server(self).await
+        server::start_server(self.with_config_file()?).await
     }
 
-    pub fn database_path(&self) -> &Path {
-        self.database_path.as_path()
+    /// Fills in the options not given on the command line or in environment variables from the
+    /// configuration file, if there is one.
+    pub fn with_config_file(self) -> Result<Self> {
+        let Some(config_path) = self.config_path.as_deref() else {
+            return Ok(self);
+        };
+        let file = FileConfig::from_file(config_path)?;
+        Ok(self.with_file_config(file))
+    }
+
+    /// Fills in the options not given on the command line or in environment variables from the
+    /// contents of a configuration file.
+    fn with_file_config(self, file: FileConfig) -> Self {
+        let mut rate_limits = file.rate_limits;
+        rate_limits.retain(|file_limit| {
+            self.rate_limits
+                .iter()
+                .all(|limit| limit.group != file_limit.group)
+        });
+        rate_limits.extend(self.rate_limits);
+        Self {
+            database_path: self.database_path.or(file.database_path),
+            config_path: self.config_path,
+            url: self.url.or(file.url),
+            port: self.port.or(file.port),
+            log_path: self.log_path.or(file.log_path),
+            log_level: self.log_level.or(file.log_level),
+            log_file_level: self.log_file_level.or(file.log_file_level),
+            num_workers: self.num_workers.or(file.num_workers),
+            allowed_origins: or_file(self.allowed_origins, file.allowed_origins),
+            enabled_routes: or_file(self.enabled_routes, file.enabled_routes),
+            client_request_timeout: self.client_request_timeout.or(file.client_request_timeout),
+            keep_alive_timeout: self.keep_alive_timeout.or(file.keep_alive_timeout),
+            shutdown_timeout: self.shutdown_timeout.or(file.shutdown_timeout),
+            admin_tokens: or_file(self.admin_tokens, file.admin_tokens),
+            rate_limits,
+            api_keys: or_file(self.api_keys, file.api_keys),
+            rate_limit_allowlist: or_file(self.rate_limit_allowlist, file.rate_limit_allowlist),
+            trust_proxy_headers: self.trust_proxy_headers.or(file.trust_proxy_headers),
+            neuron_store_cache_size: self
+                .neuron_store_cache_size
+                .or(file.neuron_store_cache_size),
+            offline: self
+                .offline
+                .or(file.upstream_enabled.map(|enabled| !enabled)),
+            upstream_timeout: self.upstream_timeout.or(file.upstream_timeout),
+        }
+    }
+
+    pub fn database_path(&self) -> Option<&Path> {
+        self.database_path.as_deref()
     }
 
     pub fn url(&self) -> &str {
-        &self.url
+        self.url.as_deref().unwrap_or("localhost")
     }
 
     pub fn port(&self) -> u16 {
-        self.port
+        self.port.unwrap_or(8080)
     }
 
     pub fn log_path(&self) -> Option<&Path> {
         self.log_path.as_deref()
     }
 
+    pub fn log_level(&self) -> LevelFilter {
+        self.log_level.unwrap_or(LevelFilter::Info)
+    }
+
+    pub fn log_file_level(&self) -> LevelFilter {
+        self.log_file_level.unwrap_or(LevelFilter::Debug)
+    }
+
     pub fn num_workers(&self) -> Option<usize> {
         self.num_workers
     }
+
+    /// The origins allowed to make cross-origin requests, or `None` if any origin is allowed.
+    pub fn allowed_origins(&self) -> Option<&[String]> {
+        if self.allowed_origins.is_empty()
+            || self.allowed_origins.iter().any(|origin| origin == "*")
+        {
+            None
+        } else {
+            Some(self.allowed_origins.as_slice())
+        }
+    }
+
+    pub fn route_enabled(&self, routes: RouteSet) -> bool {
+        self.enabled_routes.is_empty() || self.enabled_routes.contains(&routes)
+    }
+
+    pub fn client_request_timeout(&self) -> Duration {
+        Duration::from_secs(self.client_request_timeout.unwrap_or(5))
+    }
+
+    pub fn keep_alive_timeout(&self) -> Duration {
+        Duration::from_secs(self.keep_alive_timeout.unwrap_or(5))
+    }
+
+    pub fn shutdown_timeout(&self) -> u64 {
+        self.shutdown_timeout.unwrap_or(30)
+    }
+
+    pub fn admin_tokens(&self) -> &[String] {
+        self.admin_tokens.as_slice()
+    }
+
+    pub fn rate_limits(&self) -> &[RouteRateLimit] {
+        self.rate_limits.as_slice()
+    }
+
+    pub fn api_keys(&self) -> &[String] {
+        self.api_keys.as_slice()
+    }
+
+    pub fn rate_limit_allowlist(&self) -> &[String] {
+        self.rate_limit_allowlist.as_slice()
+    }
+
+    pub fn trust_proxy_headers(&self) -> bool {
+        self.trust_proxy_headers.unwrap_or(false)
+    }
+
+    pub fn neuron_store_cache_size(&self) -> usize {
+        self.neuron_store_cache_size.unwrap_or(4)
+    }
+
+    pub fn offline(&self) -> bool {
+        self.offline.unwrap_or(false)
+    }
+
+    pub fn upstream_timeout(&self) -> Duration {
+        Duration::from_secs(self.upstream_timeout.unwrap_or(10))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn command_line_overrides_file() -> Result<()> {
+        let file = || {
+            FileConfig::from_toml(
+                "[upstream]\nenabled = false\n\n[rate_limit]\ntrust_proxy_headers = true\n",
+            )
+        };
+        let config = ServerConfig::parse_from(["server"]).with_file_config(file()?);
+        assert!(config.offline());
+        assert!(config.trust_proxy_headers());
+
+        let config =
+            ServerConfig::parse_from(["server", "--offline=false", "--trust-proxy-headers=false"])
+                .with_file_config(file()?);
+        assert!(!config.offline());
+        assert!(!config.trust_proxy_headers());
+
+        let config = ServerConfig::parse_from(["server", "--offline", "data.db"]);
+        assert!(config.offline());
+        assert!(!config.trust_proxy_headers());
+        assert_eq!(config.database_path(), Some(Path::new("data.db")));
+        Ok(())
+    }
 }
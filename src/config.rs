@@ -0,0 +1,326 @@
+//! Server configuration files.
+//!
+//! A configuration file is a TOML file such as
+//!
+//! ```toml
+//! database_path = "data.db"
+//! url = "0.0.0.0"
+//! port = 8080
+//! num_workers = 4
+//! allowed_origins = ["https://deepdecipher.org"]
+//! enabled_routes = ["api", "doc", "export", "health", "metrics"]
+//! admin_tokens = ["secret"]
+//!
+//! [log]
+//! path = "logs"
+//! level = "info"
+//! file_level = "debug"
+//!
+//! [timeouts]
+//! client_request = 5
+//! keep_alive = 5
+//! shutdown = 30
+//!
+//! [cache]
+//! neuron_store = 4
+//!
+//! [upstream]
+//! enabled = true
+//! timeout = 10
+//!
+//! [rate_limit]
+//! api_keys = ["pipeline-key"]
+//! allow = ["10.0.0.5"]
+//! trust_proxy_headers = false
+//!
+//! [rate_limit.limits]
+//! neuron = "20/5"
+//! search = "5/0.5"
+//! ```
+//!
+//! Every key is optional. Values given on the command line or in environment variables take
+//! precedence over the file.
+
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{bail, Context, Result};
+use log::LevelFilter;
+use serde::de::DeserializeOwned;
+
+use crate::server::{RateLimit, RouteGroup, RouteRateLimit, RouteSet};
+
+#[derive(Debug, Default)]
+pub struct FileConfig {
+    pub database_path: Option<PathBuf>,
+    pub url: Option<String>,
+    pub port: Option<u16>,
+    pub num_workers: Option<usize>,
+    pub allowed_origins: Option<Vec<String>>,
+    pub enabled_routes: Option<Vec<RouteSet>>,
+    pub admin_tokens: Option<Vec<String>>,
+    pub log_path: Option<PathBuf>,
+    pub log_level: Option<LevelFilter>,
+    pub log_file_level: Option<LevelFilter>,
+    pub client_request_timeout: Option<u64>,
+    pub keep_alive_timeout: Option<u64>,
+    pub shutdown_timeout: Option<u64>,
+    pub neuron_store_cache_size: Option<usize>,
+    pub upstream_enabled: Option<bool>,
+    pub upstream_timeout: Option<u64>,
+    pub rate_limits: Vec<RouteRateLimit>,
+    pub api_keys: Option<Vec<String>>,
+    pub rate_limit_allowlist: Option<Vec<String>>,
+    pub trust_proxy_headers: Option<bool>,
+}
+
+/// Collects every problem found in a configuration file, so they can all be reported at once.
+#[derive(Default)]
+struct Errors(Vec<String>);
+
+impl Errors {
+    fn push(&mut self, key: &str, message: impl Display) {
+        self.0.push(format!("'{key}': {message}"));
+    }
+
+    fn unknown(&mut self, key: &str) {
+        self.push(key, "unknown key");
+    }
+
+    fn set<T: DeserializeOwned>(&mut self, slot: &mut Option<T>, key: &str, value: toml::Value) {
+        match value.try_into() {
+            Ok(value) => *slot = Some(value),
+            Err(error) => self.push(key, error.to_string().trim()),
+        }
+    }
+
+    fn parse<T>(&mut self, key: &str, value: toml::Value) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match value {
+            toml::Value::String(string) => string
+                .parse()
+                .map_err(|error| self.push(key, format!("invalid value '{string}': {error}")))
+                .ok(),
+            value => {
+                self.push(
+                    key,
+                    format!("expected a string, found {}", value.type_str()),
+                );
+                None
+            }
+        }
+    }
+
+    fn parse_list<T>(&mut self, slot: &mut Option<Vec<T>>, key: &str, value: toml::Value)
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match value {
+            toml::Value::Array(values) => {
+                let num_values = values.len();
+                let parsed = values
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(|(index, value)| self.parse(&format!("{key}[{index}]"), value))
+                    .collect::<Vec<_>>();
+                if parsed.len() == num_values {
+                    *slot = Some(parsed);
+                }
+            }
+            value => self.push(
+                key,
+                format!("expected an array, found {}", value.type_str()),
+            ),
+        }
+    }
+
+    fn section(&mut self, key: &str, value: toml::Value) -> toml::Table {
+        match value {
+            toml::Value::Table(table) => table,
+            value => {
+                self.push(key, format!("expected a table, found {}", value.type_str()));
+                toml::Table::new()
+            }
+        }
+    }
+}
+
+impl FileConfig {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read configuration file {path:?}."))?;
+        Self::from_toml(&text).with_context(|| format!("Invalid configuration file {path:?}."))
+    }
+
+    /// Parses a configuration, failing with a list of every invalid key if it is not valid.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let table: toml::Table = text.parse().context("Configuration is not valid TOML.")?;
+        let mut config = Self::default();
+        let mut errors = Errors::default();
+        for (key, value) in table {
+            match key.as_str() {
+                "database_path" => errors.set(&mut config.database_path, &key, value),
+                "url" => errors.set(&mut config.url, &key, value),
+                "port" => errors.set(&mut config.port, &key, value),
+                "num_workers" => errors.set(&mut config.num_workers, &key, value),
+                "allowed_origins" => errors.set(&mut config.allowed_origins, &key, value),
+                "enabled_routes" => errors.parse_list(&mut config.enabled_routes, &key, value),
+                "admin_tokens" => errors.set(&mut config.admin_tokens, &key, value),
+                "log" => {
+                    for (sub_key, value) in errors.section(&key, value) {
+                        let full_key = format!("log.{sub_key}");
+                        match sub_key.as_str() {
+                            "path" => errors.set(&mut config.log_path, &full_key, value),
+                            "level" => config.log_level = errors.parse(&full_key, value),
+                            "file_level" => config.log_file_level = errors.parse(&full_key, value),
+                            _ => errors.unknown(&full_key),
+                        }
+                    }
+                }
+                "timeouts" => {
+                    for (sub_key, value) in errors.section(&key, value) {
+                        let full_key = format!("timeouts.{sub_key}");
+                        match sub_key.as_str() {
+                            "client_request" => {
+                                errors.set(&mut config.client_request_timeout, &full_key, value)
+                            }
+                            "keep_alive" => {
+                                errors.set(&mut config.keep_alive_timeout, &full_key, value)
+                            }
+                            "shutdown" => {
+                                errors.set(&mut config.shutdown_timeout, &full_key, value)
+                            }
+                            _ => errors.unknown(&full_key),
+                        }
+                    }
+                }
+                "cache" => {
+                    for (sub_key, value) in errors.section(&key, value) {
+                        let full_key = format!("cache.{sub_key}");
+                        match sub_key.as_str() {
+                            "neuron_store" => {
+                                errors.set(&mut config.neuron_store_cache_size, &full_key, value)
+                            }
+                            _ => errors.unknown(&full_key),
+                        }
+                    }
+                }
+                "upstream" => {
+                    for (sub_key, value) in errors.section(&key, value) {
+                        let full_key = format!("upstream.{sub_key}");
+                        match sub_key.as_str() {
+                            "enabled" => errors.set(&mut config.upstream_enabled, &full_key, value),
+                            "timeout" => errors.set(&mut config.upstream_timeout, &full_key, value),
+                            _ => errors.unknown(&full_key),
+                        }
+                    }
+                }
+                "rate_limit" => {
+                    for (sub_key, value) in errors.section(&key, value) {
+                        let full_key = format!("rate_limit.{sub_key}");
+                        match sub_key.as_str() {
+                            "limits" => {
+                                for (group, value) in errors.section(&full_key, value) {
+                                    let full_key = format!("rate_limit.limits.{group}");
+                                    let Ok(group) = group.parse::<RouteGroup>() else {
+                                        errors.unknown(&full_key);
+                                        continue;
+                                    };
+                                    if let Some(limit) = errors.parse::<RateLimit>(&full_key, value)
+                                    {
+                                        config.rate_limits.push(RouteRateLimit { group, limit });
+                                    }
+                                }
+                            }
+                            "api_keys" => errors.set(&mut config.api_keys, &full_key, value),
+                            "allow" => {
+                                errors.set(&mut config.rate_limit_allowlist, &full_key, value)
+                            }
+                            "trust_proxy_headers" => {
+                                errors.set(&mut config.trust_proxy_headers, &full_key, value)
+                            }
+                            _ => errors.unknown(&full_key),
+                        }
+                    }
+                }
+                _ => errors.unknown(&key),
+            }
+        }
+
+        if errors.0.is_empty() {
+            Ok(config)
+        } else {
+            bail!(
+                "Found {} invalid configuration keys:\n  {}",
+                errors.0.len(),
+                errors.0.join("\n  ")
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reports_every_invalid_key() {
+        let config = FileConfig::from_toml(
+            r#"
+            port = 8080
+            enabled_routes = ["api", "export"]
+
+            [log]
+            level = "warn"
+
+            [rate_limit.limits]
+            neuron = "20/5"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.port, Some(8080));
+        assert_eq!(
+            config.enabled_routes,
+            Some(vec![RouteSet::Api, RouteSet::Export])
+        );
+        assert_eq!(config.log_level, Some(LevelFilter::Warn));
+        assert_eq!(config.rate_limits.len(), 1);
+
+        let error = FileConfig::from_toml(
+            r#"
+            port = "eighty"
+            colour = "blue"
+            enabled_routes = ["api", "everything"]
+
+            [timeouts]
+            shutdown = 10
+            forever = 1
+
+            [rate_limit.limits]
+            neuron = "fast"
+            pages = "1/1"
+            "#,
+        )
+        .unwrap_err()
+        .to_string();
+        for key in [
+            "'port'",
+            "'colour'",
+            "'enabled_routes[1]'",
+            "'timeouts.forever'",
+            "'rate_limit.limits.neuron'",
+            "'rate_limit.limits.pages'",
+        ] {
+            assert!(error.contains(key), "{key} not reported in: {error}");
+        }
+        assert!(!error.contains("'timeouts.shutdown'"));
+    }
+}
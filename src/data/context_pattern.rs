@@ -0,0 +1,221 @@
+//! Patterns over the tokens around the max-activating token of an example text.
+//!
+//! A pattern is a whitespace separated list of terms, all of which must match the same example.
+//! A term is a token, optionally followed by `@` and its position relative to the max-activating
+//! token, and by `>` and the minimum activation of the neuron on the token. `of@-2 the@0` thus
+//! matches examples with `of` two tokens before a max-activating `the`, and `the>1.5` examples
+//! with a `the` anywhere in the context window that activates the neuron by more than 1.5. Tokens
+//! containing whitespace or the characters `@`, `>` and `"` can be quoted, as in `" of"@-2`, with
+//! `\"` and `\\` escaping quotes and backslashes.
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{bail, Result};
+
+/// Number of tokens on either side of the max-activating token that are indexed.
+pub const CONTEXT_RADIUS: u32 = 5;
+/// Maximum number of terms in a pattern.
+const MAX_TERMS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextTerm {
+    pub token: String,
+    /// Position relative to the max-activating token, or any position in the context window.
+    pub position: Option<i32>,
+    pub min_activation: Option<f32>,
+}
+
+impl Display for ContextTerm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.token)?;
+        if let Some(position) = self.position {
+            write!(f, "@{position}")?;
+        }
+        if let Some(min_activation) = self.min_activation {
+            write!(f, ">{min_activation}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextPattern(pub Vec<ContextTerm>);
+
+impl FromStr for ContextPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(pattern: &str) -> Result<Self> {
+        let mut parser = Parser {
+            text: pattern,
+            position: 0,
+        };
+        let mut terms = vec![];
+        loop {
+            parser.skip_whitespace();
+            if parser.peek().is_none() {
+                break;
+            }
+            terms.push(parser.term()?);
+        }
+        if terms.is_empty() {
+            bail!("Context pattern should contain at least one term.")
+        }
+        if terms.len() > MAX_TERMS {
+            bail!("Context pattern can contain at most {MAX_TERMS} terms.")
+        }
+        Ok(ContextPattern(terms))
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.position += c.len_utf8();
+        }
+        found
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T> {
+        bail!(
+            "Invalid context pattern '{}' at position {}: {message}.",
+            self.text,
+            self.position
+        )
+    }
+
+    /// Consumes the characters while they satisfy the predicate and returns them.
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.position;
+        while self.peek().is_some_and(&predicate) {
+            self.bump();
+        }
+        &self.text[start..self.position]
+    }
+
+    fn token(&mut self) -> Result<String> {
+        if !self.eat('"') {
+            let token = self.take_while(|c| !c.is_whitespace() && !matches!(c, '@' | '>' | '"'));
+            if token.is_empty() {
+                return self.error("expected a token");
+            }
+            return Ok(token.to_owned());
+        }
+        let mut token = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => break,
+                Some('\\') => match self.bump() {
+                    Some(c @ ('"' | '\\')) => token.push(c),
+                    _ => return self.error("expected '\"' or '\\' after '\\'"),
+                },
+                Some(c) => token.push(c),
+                None => return self.error("unterminated quoted token"),
+            }
+        }
+        if token.is_empty() {
+            return self.error("empty token");
+        }
+        Ok(token)
+    }
+
+    fn term(&mut self) -> Result<ContextTerm> {
+        let token = self.token()?;
+        let position = if self.eat('@') {
+            let position = self.take_while(|c| c.is_ascii_digit() || matches!(c, '-' | '+'));
+            match position.parse::<i32>() {
+                Ok(position) if position.unsigned_abs() <= CONTEXT_RADIUS => Some(position),
+                Ok(_) => {
+                    return self.error(&format!(
+                        "position {position} lies outside the context window of {CONTEXT_RADIUS} \
+                         tokens on either side"
+                    ))
+                }
+                Err(_) => return self.error(&format!("invalid position '{position}'")),
+            }
+        } else {
+            None
+        };
+        let min_activation = if self.eat('>') {
+            let activation =
+                self.take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'));
+            match activation.parse::<f32>() {
+                Ok(activation) if activation.is_finite() => Some(activation),
+                _ => return self.error(&format!("invalid activation '{activation}'")),
+            }
+        } else {
+            None
+        };
+        if self.peek().is_some_and(|c| !c.is_whitespace()) {
+            return self.error("expected whitespace between terms");
+        }
+        Ok(ContextTerm {
+            token,
+            position,
+            min_activation,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn term(token: &str, position: Option<i32>, min_activation: Option<f32>) -> ContextTerm {
+        ContextTerm {
+            token: token.to_owned(),
+            position,
+            min_activation,
+        }
+    }
+
+    #[test]
+    fn parses_patterns() {
+        assert_eq!(
+            "of@-2  the@0 \" a\\\"b\">1.5 cat@+1>0"
+                .parse::<ContextPattern>()
+                .unwrap(),
+            ContextPattern(vec![
+                term("of", Some(-2), None),
+                term("the", Some(0), None),
+                term(" a\"b", None, Some(1.5)),
+                term("cat", Some(1), Some(0.)),
+            ])
+        );
+        for invalid in [
+            "",
+            "   ",
+            "the@",
+            "the@6",
+            "the@x",
+            "the>",
+            "the@0@1",
+            "\"the",
+            "\"\"@0",
+            "a b c d e f g h i",
+        ] {
+            assert!(invalid.parse::<ContextPattern>().is_err(), "{invalid}");
+        }
+    }
+}
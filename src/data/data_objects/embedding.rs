@@ -0,0 +1,109 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{bail, Result};
+use half::f16;
+use serde::{Deserialize, Serialize};
+
+use super::{data_object, DataObject};
+use crate::data::SimilarNeuron;
+
+/// The precision embeddings are stored with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EmbeddingPrecision {
+    F16,
+    #[default]
+    F32,
+}
+
+impl FromStr for EmbeddingPrecision {
+    type Err = anyhow::Error;
+
+    fn from_str(precision: &str) -> Result<Self> {
+        match precision {
+            "f16" => Ok(Self::F16),
+            "f32" => Ok(Self::F32),
+            _ => bail!("Invalid embedding precision '{precision}'. Must be 'f16' or 'f32'."),
+        }
+    }
+}
+
+impl Display for EmbeddingPrecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::F16 => write!(f, "f16"),
+            Self::F32 => write!(f, "f32"),
+        }
+    }
+}
+
+/// The embedding vector of a neuron.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Embedding {
+    F16(Vec<f16>),
+    F32(Vec<f32>),
+}
+
+impl Embedding {
+    pub fn new(values: &[f32], precision: EmbeddingPrecision) -> Self {
+        match precision {
+            EmbeddingPrecision::F16 => {
+                Self::F16(values.iter().copied().map(f16::from_f32).collect())
+            }
+            EmbeddingPrecision::F32 => Self::F32(values.to_vec()),
+        }
+    }
+
+    pub fn precision(&self) -> EmbeddingPrecision {
+        match self {
+            Self::F16(_) => EmbeddingPrecision::F16,
+            Self::F32(_) => EmbeddingPrecision::F32,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::F16(values) => values.len(),
+            Self::F32(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn to_f32(&self) -> Vec<f32> {
+        match self {
+            Self::F16(values) => values.iter().copied().map(f16::to_f32).collect(),
+            Self::F32(values) => values.clone(),
+        }
+    }
+}
+
+impl DataObject for Embedding {
+    fn to_binary(&self) -> Result<Vec<u8>> {
+        data_object::to_binary(self, "neuron embedding")
+    }
+
+    fn from_binary(data: impl AsRef<[u8]>) -> Result<Self> {
+        data_object::from_binary(data, "neuron embedding")
+    }
+}
+
+/// The neurons with the embeddings most similar to an embedding, most similar first.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EmbeddingNeighbours {
+    /// Whether the neighbours were found with an approximate index, in which case some of the
+    /// nearest neurons may be missing.
+    pub approximate: bool,
+    pub neighbours: Vec<SimilarNeuron>,
+}
+
+impl DataObject for EmbeddingNeighbours {
+    fn to_binary(&self) -> Result<Vec<u8>> {
+        data_object::to_binary(self, "embedding neighbours")
+    }
+
+    fn from_binary(data: impl AsRef<[u8]>) -> Result<Self> {
+        data_object::from_binary(data, "embedding neighbours")
+    }
+}
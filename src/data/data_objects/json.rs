@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
 use super::{data_object, DataObject};
@@ -15,11 +15,36 @@ impl JsonData {
 }
 
 impl DataObject for JsonData {
+    // Postcard is not self-describing, so it cannot deserialize arbitrary JSON values. The value
+    // is stored as JSON text instead. Values stored directly with postcard, as they were before,
+    // hold no types, so they could never be read back and cannot be migrated either. They have to
+    // be uploaded again.
     fn to_binary(&self) -> Result<Vec<u8>> {
-        data_object::to_binary(self, "JSON object")
+        let text =
+            serde_json::to_string(&self.value).context("Failed to serialize JSON object.")?;
+        data_object::to_binary(&text, "JSON object")
     }
 
     fn from_binary(bytes: impl AsRef<[u8]>) -> Result<Self> {
-        data_object::from_binary(bytes, "JSON object")
+        data_object::from_binary::<String>(bytes, "JSON object")
+            .and_then(|text| serde_json::from_str(&text).context("Failed to parse JSON object."))
+            .map(Self::new)
+            .context(
+                "JSON data stored before JSON was stored as text cannot be read. Upload it again.",
+            )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rejects_old_encoding() -> Result<()> {
+        let data = JsonData::new(serde_json::json!({ "explanation": "French", "score": 0.5 }));
+        assert_eq!(JsonData::from_binary(data.to_binary()?)?.value, data.value);
+        let old_data = data_object::to_binary(&data, "JSON object")?;
+        assert!(JsonData::from_binary(old_data).is_err());
+        Ok(())
     }
 }
@@ -1,12 +1,33 @@
 mod data_object;
 pub use data_object::DataObject;
 
+mod embedding;
+pub use embedding::{Embedding, EmbeddingNeighbours, EmbeddingPrecision};
+
 mod json;
 pub use json::JsonData;
 
 mod neuron_explainer_page;
 pub use neuron_explainer_page::NeuronExplainerPage;
 
+mod neuron_explainer_summary;
+pub use neuron_explainer_summary::{NeuronExplainerSummary, ScoreHistogram, ScoredExplanation};
+
+mod neuron_clusters;
+pub use neuron_clusters::{NeuronCluster, NeuronClusters, NeuronGraphNode, NeuronRole};
+
+mod neuron_correspondence;
+pub use neuron_correspondence::ModelCorrespondence;
+
+mod neuron_query_page;
+pub use neuron_query_page::{NeuronQueryPage, NeuronScalars};
+
+mod neuron_summary;
+pub use neuron_summary::{NeuronSummary, TopToken};
+
+mod random_neurons;
+pub use random_neurons::{RandomNeuron, RandomNeurons};
+
 mod neuron2graph;
 pub use neuron2graph::{Graph, Neuron2GraphData};
 
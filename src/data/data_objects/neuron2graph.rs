@@ -227,6 +227,32 @@ impl Graph {
             graph: graph.collect::<Vec<_>>(),
         })
     }
+
+    pub fn num_nodes(&self) -> usize {
+        self.graph.len()
+    }
+
+    pub fn num_edges(&self) -> usize {
+        self.graph.iter().map(|node| node.required.len()).sum()
+    }
+
+    /// The tokens of the graph's nodes with the highest importance of any node with the token.
+    /// Quoted labels are unquoted, so tokens match those of the neuron store.
+    pub fn token_importances(&self) -> HashMap<String, f32> {
+        let mut importances = HashMap::new();
+        for Node {
+            token, importance, ..
+        } in &self.graph
+        {
+            let token = token
+                .strip_prefix('"')
+                .and_then(|token| token.strip_suffix('"'))
+                .map_or_else(|| token.clone(), |token| token.replace("\\\"", "\""));
+            let max_importance = importances.entry(token).or_insert(*importance);
+            *max_importance = max_importance.max(*importance);
+        }
+        importances
+    }
 }
 
 fn index_to_vertex(index: usize) -> Vertex {
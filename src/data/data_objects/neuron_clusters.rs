@@ -0,0 +1,129 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use strum::AsRefStr;
+
+use super::{data_object, DataObject};
+use crate::data::{NeuronIndex, SimilarNeuron};
+
+/// The role of a neuron in the similarity graph, by how strongly it is connected within its own
+/// cluster and how evenly its connections are spread over clusters, following Guimerà and Amaral.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, AsRefStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum NeuronRole {
+    /// Not similar to any other neuron.
+    Isolated,
+    /// Connected only within its cluster.
+    UltraPeripheral,
+    /// Connected mostly within its cluster.
+    Peripheral,
+    /// Connected to many other clusters.
+    Connector,
+    /// Connected evenly to all clusters, without a clear cluster of its own.
+    Kinless,
+    /// Central to its cluster, connected mostly within it.
+    ProvincialHub,
+    /// Central to its cluster, connected to many other clusters.
+    ConnectorHub,
+    /// Central to its cluster, connected evenly to all clusters.
+    KinlessHub,
+}
+
+impl NeuronRole {
+    /// Within-cluster degree z-score from which a neuron is a hub.
+    pub const HUB_THRESHOLD: f32 = 2.5;
+
+    pub fn new(within_cluster_degree: f32, participation: f32) -> Self {
+        if within_cluster_degree >= Self::HUB_THRESHOLD {
+            match participation {
+                p if p <= 0.3 => Self::ProvincialHub,
+                p if p <= 0.75 => Self::ConnectorHub,
+                _ => Self::KinlessHub,
+            }
+        } else {
+            match participation {
+                p if p <= 0.05 => Self::UltraPeripheral,
+                p if p <= 0.62 => Self::Peripheral,
+                p if p <= 0.8 => Self::Connector,
+                _ => Self::Kinless,
+            }
+        }
+    }
+
+    pub fn is_hub(self) -> bool {
+        matches!(
+            self,
+            Self::ProvincialHub | Self::ConnectorHub | Self::KinlessHub
+        )
+    }
+}
+
+/// A neuron in the similarity graph, with its cluster, its centrality and its role.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NeuronGraphNode {
+    /// The cluster the neuron belongs to. Isolated neurons belong to none.
+    pub cluster: Option<u32>,
+    pub role: NeuronRole,
+    /// Number of neurons the neuron is similar to.
+    pub degree: u32,
+    /// Sum of the similarities of the neuron to its neighbours.
+    pub strength: f32,
+    /// Weighted PageRank of the neuron. Sums to one over all neurons of the model.
+    pub pagerank: f32,
+    /// How evenly the strength of the neuron is spread over clusters, from 0 when it is all within
+    /// one cluster towards 1 when it is spread evenly over many.
+    pub participation: f32,
+    /// The strength of the neuron within its cluster, as a z-score over the neurons of the
+    /// cluster.
+    pub within_cluster_degree: f32,
+    /// The neighbours of the neuron in the graph along with the weights of the edges to them,
+    /// heaviest first.
+    pub neighbours: Vec<SimilarNeuron>,
+}
+
+impl DataObject for NeuronGraphNode {
+    fn to_binary(&self) -> Result<Vec<u8>> {
+        data_object::to_binary(self, "neuron graph node")
+    }
+
+    fn from_binary(data: impl AsRef<[u8]>) -> Result<Self> {
+        data_object::from_binary(data, "neuron graph node")
+    }
+}
+
+/// A cluster of neurons found in the similarity graph.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NeuronCluster {
+    pub id: u32,
+    /// Number of neurons in the cluster.
+    pub size: u32,
+    /// Sum of the weights of the edges within the cluster.
+    pub internal_weight: f32,
+    /// Number of neurons of the cluster in each layer.
+    pub layer_sizes: Vec<u32>,
+    /// The neurons of the cluster, by descending PageRank.
+    pub members: Vec<NeuronIndex>,
+}
+
+/// The clusters of the similarity graph of a model, largest first.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NeuronClusters {
+    /// Modularity of the clustering, from -0.5 to 1. Higher means denser clusters.
+    pub modularity: f32,
+    /// The resolution the clusters were found with. Higher resolutions give smaller clusters.
+    pub resolution: f32,
+    /// Smallest similarity that gives an edge of the graph.
+    pub min_similarity: f32,
+    pub num_edges: u64,
+    pub clusters: Vec<NeuronCluster>,
+}
+
+impl DataObject for NeuronClusters {
+    fn to_binary(&self) -> Result<Vec<u8>> {
+        data_object::to_binary(self, "neuron clusters")
+    }
+
+    fn from_binary(data: impl AsRef<[u8]>) -> Result<Self> {
+        data_object::from_binary(data, "neuron clusters")
+    }
+}
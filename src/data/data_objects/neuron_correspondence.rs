@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+use crate::data::SimilarNeuron;
+
+/// The neurons of another model most similar to a neuron, most similar first. The neuron data of
+/// a neuron correspondence data object is a list of these, one for each other model compared with.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModelCorrespondence {
+    pub model: String,
+    pub similar: Vec<SimilarNeuron>,
+}
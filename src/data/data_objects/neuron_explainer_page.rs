@@ -10,6 +10,10 @@ pub struct NeuronExplainerPage {
 }
 
 impl NeuronExplainerPage {
+    pub fn new(explanation: String, score: f32) -> Self {
+        Self { explanation, score }
+    }
+
     pub fn from_json(explainer_json: serde_json::Value) -> Result<Self> {
         let scored_explanations_array = explainer_json
             .get("scored_explanations")
@@ -38,6 +42,14 @@ impl NeuronExplainerPage {
 
         Ok(Self { explanation, score })
     }
+
+    pub fn explanation(&self) -> &str {
+        &self.explanation
+    }
+
+    pub fn score(&self) -> f32 {
+        self.score
+    }
 }
 
 impl DataObject for NeuronExplainerPage {
@@ -0,0 +1,159 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{data_object, DataObject, NeuronExplainerPage};
+use crate::data::NeuronIndex;
+
+/// Number of equally wide histogram bins between the lowest and highest possible score.
+const NUM_BINS: usize = 20;
+/// Explanation scores are correlations, so they lie between -1 and 1.
+const SCORE_RANGE: (f32, f32) = (-1., 1.);
+const QUANTILES: [f32; 7] = [0., 0.1, 0.25, 0.5, 0.75, 0.9, 1.];
+/// Number of neurons listed with the highest and with the lowest scores.
+const NUM_EXTREME_NEURONS: usize = 10;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScoredExplanation {
+    pub neuron: NeuronIndex,
+    pub score: f32,
+    pub explanation: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScoreHistogram {
+    pub min: f32,
+    pub max: f32,
+    /// Number of scores in each of the equally wide bins between `min` and `max`. Scores outside
+    /// the range are counted in the first or last bin.
+    pub counts: Vec<u32>,
+}
+
+/// Overview of the Neuron Explainer explanations of a layer or a whole model.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NeuronExplainerSummary {
+    num_neurons: u32,
+    num_explained: u32,
+    /// Fraction of the neurons that have an explanation.
+    coverage: f32,
+    mean_score: Option<f32>,
+    histogram: ScoreHistogram,
+    /// Pairs of quantiles and the score at each quantile. Empty if no neuron is explained.
+    quantiles: Vec<(f32, f32)>,
+    top_neurons: Vec<ScoredExplanation>,
+    bottom_neurons: Vec<ScoredExplanation>,
+}
+
+/// The score at the quantile of the sorted scores, interpolating linearly between scores.
+fn quantile(sorted_scores: &[f32], quantile: f32) -> f32 {
+    let position = quantile * (sorted_scores.len() - 1) as f32;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    let fraction = position - lower as f32;
+    sorted_scores[lower] + (sorted_scores[upper] - sorted_scores[lower]) * fraction
+}
+
+impl NeuronExplainerSummary {
+    /// Summarises the explanations of some of the `num_neurons` neurons of a layer or model.
+    pub fn new(num_neurons: u32, pages: &[(NeuronIndex, NeuronExplainerPage)]) -> Self {
+        let num_explained = pages.len() as u32;
+        let mut sorted: Vec<_> = pages.iter().collect();
+        sorted.sort_by(|(index_a, page_a), (index_b, page_b)| {
+            page_b
+                .score()
+                .total_cmp(&page_a.score())
+                .then(index_a.cmp(index_b))
+        });
+        let scores: Vec<f32> = sorted.iter().rev().map(|(_, page)| page.score()).collect();
+
+        let (min, max) = SCORE_RANGE;
+        let mut counts = vec![0; NUM_BINS];
+        for &score in &scores {
+            let bin = ((score - min) / (max - min) * NUM_BINS as f32).floor();
+            counts[(bin.max(0.) as usize).min(NUM_BINS - 1)] += 1;
+        }
+
+        let scored_explanation =
+            |(neuron, page): &&(NeuronIndex, NeuronExplainerPage)| ScoredExplanation {
+                neuron: *neuron,
+                score: page.score(),
+                explanation: page.explanation().to_owned(),
+            };
+        Self {
+            num_neurons,
+            num_explained,
+            coverage: if num_neurons == 0 {
+                0.
+            } else {
+                num_explained as f32 / num_neurons as f32
+            },
+            mean_score: (!scores.is_empty())
+                .then(|| scores.iter().sum::<f32>() / scores.len() as f32),
+            histogram: ScoreHistogram { min, max, counts },
+            quantiles: if scores.is_empty() {
+                vec![]
+            } else {
+                QUANTILES
+                    .iter()
+                    .map(|&q| (q, quantile(&scores, q)))
+                    .collect()
+            },
+            top_neurons: sorted
+                .iter()
+                .take(NUM_EXTREME_NEURONS)
+                .map(scored_explanation)
+                .collect(),
+            bottom_neurons: sorted
+                .iter()
+                .rev()
+                .take(NUM_EXTREME_NEURONS)
+                .map(scored_explanation)
+                .collect(),
+        }
+    }
+}
+
+impl DataObject for NeuronExplainerSummary {
+    fn to_binary(&self) -> Result<Vec<u8>> {
+        data_object::to_binary(self, "Neuron Explainer summary page")
+    }
+
+    fn from_binary(data: impl AsRef<[u8]>) -> Result<Self> {
+        data_object::from_binary(data, "Neuron Explainer summary page")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn summarises_scores() {
+        let pages: Vec<_> = [0.5, -0.2, 0.9, 0.1, 1.]
+            .into_iter()
+            .enumerate()
+            .map(|(neuron, score)| {
+                let page = NeuronExplainerPage::new(format!("explanation {neuron}"), score);
+                (
+                    NeuronIndex {
+                        layer: 0,
+                        neuron: neuron as u32,
+                    },
+                    page,
+                )
+            })
+            .collect();
+        let summary = NeuronExplainerSummary::new(10, &pages);
+
+        assert_eq!(summary.num_explained, 5);
+        assert_eq!(summary.coverage, 0.5);
+        assert_eq!(summary.histogram.counts.iter().sum::<u32>(), 5);
+        assert_eq!(summary.histogram.counts[NUM_BINS - 1], 2);
+        assert_eq!(summary.histogram.counts[8], 1);
+        assert_eq!(summary.quantiles[0], (0., -0.2));
+        assert_eq!(summary.quantiles[3], (0.5, 0.5));
+        assert_eq!(summary.quantiles[6], (1., 1.));
+        assert_eq!(summary.top_neurons[0].neuron.neuron, 4);
+        assert_eq!(summary.bottom_neurons[0].neuron.neuron, 1);
+        assert!(NeuronExplainerSummary::new(10, &[]).mean_score.is_none());
+    }
+}
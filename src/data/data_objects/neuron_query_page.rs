@@ -0,0 +1,33 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{data_object, DataObject};
+
+/// A neuron and all its scalars, keyed by `data_object.name`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NeuronScalars {
+    pub layer: u32,
+    pub neuron: u32,
+    pub scalars: BTreeMap<String, f64>,
+}
+
+/// A page of the neurons matching a neuron query.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NeuronQueryPage {
+    /// Number of matching neurons across all pages.
+    pub total: u64,
+    pub offset: u64,
+    pub neurons: Vec<NeuronScalars>,
+}
+
+impl DataObject for NeuronQueryPage {
+    fn to_binary(&self) -> Result<Vec<u8>> {
+        data_object::to_binary(self, "neuron query page")
+    }
+
+    fn from_binary(data: impl AsRef<[u8]>) -> Result<Self> {
+        data_object::from_binary(data, "neuron query page")
+    }
+}
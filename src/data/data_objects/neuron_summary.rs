@@ -0,0 +1,42 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{data_object, DataObject};
+use crate::data::SimilarNeuron;
+
+/// A token that is the max-activating token of some of a neuron's Neuroscope examples.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TopToken {
+    pub token: String,
+    /// Number of examples with the token as their max-activating token.
+    pub count: u32,
+    pub max_activation: f32,
+}
+
+/// A small overview of a neuron drawn from all its data, for previews. Fields are empty if the
+/// neuron has no data they can be drawn from.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NeuronSummary {
+    pub layer: u32,
+    pub neuron: u32,
+    /// The most common max-activating tokens, most common first.
+    pub top_tokens: Vec<TopToken>,
+    /// The explanation with the highest score.
+    pub explanation: Option<String>,
+    pub score: Option<f32>,
+    pub max_activation: Option<f32>,
+    /// Number of nodes of the neuron's Neuron2Graph graph.
+    pub graph_nodes: Option<u32>,
+    /// The most similar neurons, most similar first.
+    pub similar: Vec<SimilarNeuron>,
+}
+
+impl DataObject for NeuronSummary {
+    fn to_binary(&self) -> Result<Vec<u8>> {
+        data_object::to_binary(self, "neuron summary")
+    }
+
+    fn from_binary(data: impl AsRef<[u8]>) -> Result<Self> {
+        data_object::from_binary(data, "neuron summary")
+    }
+}
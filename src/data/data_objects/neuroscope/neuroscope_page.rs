@@ -203,6 +203,14 @@ impl Text {
         self.max_activation
     }
 
+    pub fn data_index(&self) -> u64 {
+        self.data_index
+    }
+
+    pub fn max_activating_token_index(&self) -> u32 {
+        self.max_activating_token_index
+    }
+
     pub fn tokens(&self) -> &[String] {
         self.tokens.as_slice()
     }
@@ -0,0 +1,33 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{data_object, DataObject};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RandomNeuron {
+    pub layer: u32,
+    pub neuron: u32,
+    /// The value of the attribute the neuron was weighted by, if any.
+    pub weight: Option<f64>,
+}
+
+/// Neurons sampled at random from a model.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RandomNeurons {
+    /// The seed the neurons were sampled with. Sampling again with the same seed and parameters
+    /// gives the same neurons.
+    pub seed: u64,
+    /// Number of neurons that could have been sampled.
+    pub num_candidates: u64,
+    pub neurons: Vec<RandomNeuron>,
+}
+
+impl DataObject for RandomNeurons {
+    fn to_binary(&self) -> Result<Vec<u8>> {
+        data_object::to_binary(self, "random neurons")
+    }
+
+    fn from_binary(data: impl AsRef<[u8]>) -> Result<Self> {
+        data_object::from_binary(data, "random neurons")
+    }
+}
@@ -0,0 +1,392 @@
+//! Inverted index of the tokens around the max-activating token of each Neuroscope example.
+//!
+//! For every text of a Neuroscope page, the tokens within [`CONTEXT_RADIUS`] of its max-activating
+//! token are recorded in the `context_token` table with their position relative to that token,
+//! so examples can be searched for tokens at given positions around their peak.
+//!
+//! This costs up to `2 * CONTEXT_RADIUS + 1` rows per text of every neuron, each holding the token
+//! twice along with its key columns, so the table is typically larger than the compressed
+//! Neuroscope pages it is built from.
+
+use std::ops::RangeInclusive;
+
+use anyhow::{Context, Result};
+use rusqlite::{params_from_iter, types::Value, Transaction};
+use serde::{Deserialize, Serialize};
+
+use super::{data_types::DataType, DataTypeHandle, ModelHandle};
+use crate::data::{
+    data_objects::{DataObject, NeuroscopeNeuronPage},
+    ContextPattern, Normalisation, CONTEXT_RADIUS,
+};
+
+/// Normalisation of the tokens matched when a search is not exact.
+const KEY_NORMALISATION: Normalisation = Normalisation {
+    case_insensitive: true,
+    ignore_whitespace: true,
+};
+
+/// Replaces the context tokens of the neuron with those of Neuroscope data that was just added.
+pub(super) fn index_contexts(
+    transaction: &Transaction,
+    model_id: i64,
+    data_type_id: i64,
+    data_type: &DataType,
+    layer_index: u32,
+    neuron_index: u32,
+    data: &[u8],
+) -> Result<()> {
+    const DELETE_CONTEXTS: &str = r#"
+    DELETE FROM context_token
+    WHERE model_id = ?1 AND data_type_id = ?2 AND layer_index = ?3 AND neuron_index = ?4;
+    "#;
+    // Texts are ordered by activation, so if a sample appears more than once on a page, the context
+    // of its highest activation is kept.
+    const ADD_CONTEXT_TOKEN: &str = r#"
+    INSERT OR IGNORE INTO context_token (
+        model_id,
+        data_type_id,
+        layer_index,
+        neuron_index,
+        data_index,
+        position,
+        token,
+        token_key,
+        activation
+    ) VALUES (
+        ?1,
+        ?2,
+        ?3,
+        ?4,
+        ?5,
+        ?6,
+        ?7,
+        ?8,
+        ?9
+    );
+    "#;
+
+    if *data_type != DataType::Neuroscope {
+        return Ok(());
+    }
+    let index = (model_id, data_type_id, layer_index, neuron_index);
+    transaction
+        .prepare_cached(DELETE_CONTEXTS)?
+        .execute(index)?;
+    let page = match NeuroscopeNeuronPage::from_binary(data) {
+        Ok(page) => page,
+        Err(error) => {
+            log::warn!(
+                "Failed to extract contexts of neuron l{layer_index}n{neuron_index}. Error: \
+                 {error:#}"
+            );
+            return Ok(());
+        }
+    };
+    let mut statement = transaction.prepare_cached(ADD_CONTEXT_TOKEN)?;
+    for text in page.texts() {
+        let peak = text.max_activating_token_index() as usize;
+        let radius = CONTEXT_RADIUS as usize;
+        let window = peak.saturating_sub(radius)..(peak + radius + 1).min(text.tokens().len());
+        for token_index in window {
+            let token = &text.tokens()[token_index];
+            statement.execute((
+                index.0,
+                index.1,
+                index.2,
+                index.3,
+                text.data_index(),
+                token_index as i64 - peak as i64,
+                token,
+                KEY_NORMALISATION.apply(token),
+                text.activations()[token_index],
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// Parameters of a search for neurons whose examples match a context pattern.
+pub struct ContextQuery {
+    pub pattern: ContextPattern,
+    /// Whether tokens must match exactly, rather than ignoring case and surrounding whitespace.
+    pub exact: bool,
+    pub data_type: Option<DataTypeHandle>,
+    pub layers: Option<RangeInclusive<u32>>,
+    /// Minimum activation of the neuron on its max-activating token in a matching example.
+    pub min_activation: Option<f32>,
+    pub limit: usize,
+}
+
+/// A neuron with examples matching a context pattern.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextMatch {
+    pub layer: u32,
+    pub neuron: u32,
+    /// Number of the neuron's examples matching the pattern.
+    pub num_matches: u32,
+    pub num_examples: u32,
+    /// The highest activation of the neuron on a matching example.
+    pub max_activation: f32,
+    /// Data indices of the matching examples, in increasing order.
+    pub data_indices: Vec<u64>,
+}
+
+impl ModelHandle {
+    /// Finds the neurons with examples matching the pattern, ordered by decreasing number of
+    /// matching examples and then by decreasing activation.
+    pub async fn search_contexts(&self, query: &ContextQuery) -> Result<Vec<ContextMatch>> {
+        const SEARCH_CONTEXTS: &str = r#"
+        SELECT
+            t0.layer_index,
+            t0.neuron_index,
+            COUNT(DISTINCT t0.data_index) AS num_matches,
+            (
+                SELECT COUNT(DISTINCT counted.data_index)
+                FROM sample_activation AS counted
+                WHERE counted.model_id = t0.model_id
+                    AND counted.layer_index = t0.layer_index
+                    AND counted.neuron_index = t0.neuron_index
+                    AND $COUNTED_DATA_TYPE
+            ),
+            MAX(sample.activation) AS max_activation,
+            GROUP_CONCAT(DISTINCT t0.data_index)
+        FROM context_token AS t0 $JOINS
+        JOIN sample_activation AS sample
+            ON sample.model_id = t0.model_id
+            AND sample.data_type_id = t0.data_type_id
+            AND sample.layer_index = t0.layer_index
+            AND sample.neuron_index = t0.neuron_index
+            AND sample.data_index = t0.data_index
+        WHERE t0.model_id = ?1
+            $DATA_TYPE
+            AND (?3 IS NULL OR t0.layer_index BETWEEN ?3 AND ?4)
+            AND (?5 IS NULL OR sample.activation >= ?5) $CONDITIONS
+        GROUP BY t0.layer_index, t0.neuron_index
+        ORDER BY num_matches DESC, max_activation DESC, t0.layer_index, t0.neuron_index
+        LIMIT ?6;
+        "#;
+
+        let mut params = vec![
+            Value::from(self.id()),
+            query
+                .data_type
+                .as_ref()
+                .map_or(Value::Null, |data_type| Value::from(data_type.id())),
+            query
+                .layers
+                .as_ref()
+                .map_or(Value::Null, |layers| Value::from(*layers.start())),
+            query
+                .layers
+                .as_ref()
+                .map_or(Value::Null, |layers| Value::from(*layers.end())),
+            query
+                .min_activation
+                .map_or(Value::Null, |activation| Value::from(activation as f64)),
+            Value::from(query.limit as i64),
+        ];
+        let mut param = |value: Value| {
+            params.push(value);
+            format!("?{}", params.len())
+        };
+        let mut joins = String::new();
+        let mut conditions = String::new();
+        for (term_index, term) in query.pattern.0.iter().enumerate() {
+            let table = format!("t{term_index}");
+            if term_index > 0 {
+                joins.push_str(&format!(
+                    " JOIN context_token AS {table} ON {table}.model_id = t0.model_id AND \
+                     {table}.data_type_id = t0.data_type_id AND {table}.layer_index = \
+                     t0.layer_index AND {table}.neuron_index = t0.neuron_index AND \
+                     {table}.data_index = t0.data_index"
+                ));
+            }
+            if query.exact {
+                let token = param(Value::from(term.token.clone()));
+                conditions.push_str(&format!(" AND {table}.token = {token}"));
+            } else {
+                let token = param(Value::from(
+                    KEY_NORMALISATION.apply(&term.token).into_owned(),
+                ));
+                conditions.push_str(&format!(" AND {table}.token_key = {token}"));
+            }
+            if let Some(position) = term.position {
+                let position = param(Value::from(position));
+                conditions.push_str(&format!(" AND {table}.position = {position}"));
+            }
+            if let Some(min_activation) = term.min_activation {
+                let min_activation = param(Value::from(min_activation as f64));
+                conditions.push_str(&format!(" AND {table}.activation > {min_activation}"));
+            }
+        }
+        // A condition like `?2 IS NULL OR data_type_id = ?2` keeps SQLite from using the data type
+        // column of the primary key, so the statement is built with or without the data type.
+        // Without one, examples are counted in the data object of the matching examples.
+        let (counted_data_type, data_type) = if query.data_type.is_some() {
+            ("counted.data_type_id = ?2", "AND t0.data_type_id = ?2")
+        } else {
+            ("counted.data_type_id = t0.data_type_id", "")
+        };
+        let sql = SEARCH_CONTEXTS
+            .replace("$COUNTED_DATA_TYPE", counted_data_type)
+            .replace("$DATA_TYPE", data_type)
+            .replace("$JOINS", &joins)
+            .replace("$CONDITIONS", &conditions);
+
+        let rows = self
+            .database()
+            .connection
+            .call(move |connection| {
+                connection
+                    .prepare(&sql)?
+                    .query_map(params_from_iter(params), |row| {
+                        Ok((
+                            row.get::<_, u32>(0)?,
+                            row.get::<_, u32>(1)?,
+                            row.get::<_, u32>(2)?,
+                            row.get::<_, u32>(3)?,
+                            row.get::<_, f64>(4)?,
+                            row.get::<_, String>(5)?,
+                        ))
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to search example contexts of model '{}'.",
+                    self.name()
+                )
+            })?;
+        rows.into_iter()
+            .map(
+                |(layer, neuron, num_matches, num_examples, max_activation, data_indices)| {
+                    let mut data_indices = data_indices
+                        .split(',')
+                        .map(str::parse)
+                        .collect::<Result<Vec<u64>, _>>()
+                        .context("Failed to parse matching data indices.")?;
+                    data_indices.sort_unstable();
+                    Ok(ContextMatch {
+                        layer,
+                        neuron,
+                        num_matches,
+                        num_examples,
+                        max_activation: max_activation as f32,
+                        data_indices,
+                    })
+                },
+            )
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        data::{
+            database::test_util::{add_test_model, neuroscope_page, neuroscope_text},
+            Database,
+        },
+        Index,
+    };
+
+    #[tokio::test]
+    async fn searches_contexts() -> Result<()> {
+        let database = Database::initialize_in_memory().await?;
+        let mut model = add_test_model(&database, 1, 3).await?;
+        let data_type = database
+            .add_data_type("neuroscope", DataType::Neuroscope)
+            .await?;
+        model.add_data_type(&data_type).await?;
+
+        for (neuron, texts) in [
+            (
+                0,
+                vec![
+                    neuroscope_text(1, &["end", " of", " all", " the", " days"], 3, 4.),
+                    neuroscope_text(2, &[" Of", " the"], 1, 2.),
+                    neuroscope_text(3, &["out", " of", " sight", " The"], 3, 3.),
+                ],
+            ),
+            (1, vec![neuroscope_text(4, &[" of", " x", " the"], 2, 6.)]),
+            (2, vec![neuroscope_text(5, &[" the", " of", " the"], 0, 1.)]),
+        ] {
+            let page = neuroscope_page(0, neuron, texts)?;
+            model
+                .add_data(&data_type, Index::Neuron(0, neuron), page.to_binary()?)
+                .await?;
+        }
+
+        let search = |pattern: &str, exact, min_activation| {
+            let query = ContextQuery {
+                pattern: pattern.parse().unwrap(),
+                exact,
+                data_type: None,
+                layers: None,
+                min_activation,
+                limit: 10,
+            };
+            let model = model.clone();
+            async move {
+                Ok::<_, anyhow::Error>(
+                    model
+                        .search_contexts(&query)
+                        .await?
+                        .into_iter()
+                        .map(|found| (found.neuron, found.data_indices))
+                        .collect::<Vec<_>>(),
+                )
+            }
+        };
+
+        assert_eq!(
+            search("of@-2 the@0", false, None).await?,
+            vec![(0, vec![1, 3]), (1, vec![4])]
+        );
+        assert_eq!(
+            search("of@-2 the@0", false, Some(3.5)).await?,
+            vec![(1, vec![4]), (0, vec![1]),]
+        );
+        assert_eq!(
+            search("\" of\"@-2 \" the\"@0", true, None).await?,
+            vec![(1, vec![4]), (0, vec![1]),]
+        );
+        assert_eq!(
+            search("of@1 the>0.8", false, None).await?,
+            vec![(2, vec![5])]
+        );
+        let matches = model
+            .search_contexts(&ContextQuery {
+                pattern: "the@0".parse()?,
+                exact: false,
+                data_type: None,
+                layers: None,
+                min_activation: None,
+                limit: 1,
+            })
+            .await?;
+        assert_eq!(matches.len(), 1);
+        assert_eq!((matches[0].num_matches, matches[0].num_examples), (3, 3));
+        let matches = model
+            .search_contexts(&ContextQuery {
+                pattern: "of@-2 the@0".parse()?,
+                exact: false,
+                data_type: Some(data_type.clone()),
+                layers: Some(0..=0),
+                min_activation: None,
+                limit: 10,
+            })
+            .await?;
+        assert_eq!(
+            matches
+                .iter()
+                .map(|found| (found.neuron, found.num_examples))
+                .collect::<Vec<_>>(),
+            vec![(0, 3), (1, 1)]
+        );
+        Ok(())
+    }
+}
@@ -1,7 +1,9 @@
 use anyhow::{Context, Result};
 use rusqlite::OptionalExtension;
 
-use super::{data_types::DataType, Database, Operation};
+use super::{
+    data_types::DataType, table_definitions::AUXILIARY_REFERENCE_TABLES, Database, Operation,
+};
 
 #[derive(Clone)]
 pub struct DataTypeHandle {
@@ -118,7 +120,10 @@ impl DataTypeHandle {
 
         let params = (self.id,);
         move |transaction| {
-            for table in REFERENCE_TABLES.iter() {
+            for table in REFERENCE_TABLES
+                .iter()
+                .chain(AUXILIARY_REFERENCE_TABLES.iter())
+            {
                 let mut statement = transaction.prepare(
                     DELETE_DATA_TYPE_REFERENCES
                         .replace("$DATABASE", table)
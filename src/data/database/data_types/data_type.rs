@@ -6,7 +6,7 @@ use strum::{AsRefStr, EnumDiscriminants, EnumString};
 use thiserror::Error;
 
 use crate::{
-    data::{DataTypeHandle, ModelHandle},
+    data::{data_objects::EmbeddingPrecision, DataTypeHandle, ModelHandle},
     Index,
 };
 
@@ -18,6 +18,9 @@ pub enum DataType {
     Neuron2Graph,
     NeuronStore { similarity_threshold: f32 },
     Json,
+    NeuronCorrespondence,
+    Embedding { precision: EmbeddingPrecision },
+    NeuronClusters,
 }
 
 impl DataType {
@@ -65,6 +68,26 @@ impl DataType {
                 );
                 Ok(Self::Json)
             }
+            DataTypeDiscriminants::NeuronCorrespondence => {
+                ensure!(
+                    type_args.is_empty(),
+                    "NeuronCorrespondence data objects do not take type arguments."
+                );
+                Ok(Self::NeuronCorrespondence)
+            }
+            DataTypeDiscriminants::Embedding => {
+                let precision: EmbeddingPrecision = postcard::from_bytes(type_args).context(
+                    "Failed to deserialize precision type argument for Embedding data type.",
+                )?;
+                Ok(Self::Embedding { precision })
+            }
+            DataTypeDiscriminants::NeuronClusters => {
+                ensure!(
+                    type_args.is_empty(),
+                    "NeuronClusters data objects do not take type arguments."
+                );
+                Ok(Self::NeuronClusters)
+            }
         }
     }
 
@@ -77,6 +100,11 @@ impl DataType {
                 similarity_threshold,
             } => postcard::to_allocvec(similarity_threshold).expect("Failed to serialize f32."),
             Self::Json => Vec::new(),
+            Self::NeuronCorrespondence => Vec::new(),
+            Self::Embedding { precision } => {
+                postcard::to_allocvec(precision).expect("Failed to serialize embedding precision.")
+            }
+            Self::NeuronClusters => Vec::new(),
         }
     }
 }
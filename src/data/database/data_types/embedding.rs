@@ -0,0 +1,120 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+
+use super::{data_type::DataValidationError, DataTypeDiscriminants, ModelDataType};
+use crate::data::{
+    data_objects::{DataObject, Embedding as EmbeddingData},
+    DataTypeHandle, ModelHandle, NeuronIndex,
+};
+
+pub struct Embedding {
+    model: ModelHandle,
+    data_type: DataTypeHandle,
+}
+
+#[async_trait]
+impl ModelDataType for Embedding {
+    async fn new(model: ModelHandle, data_type: DataTypeHandle) -> Result<Option<Self>> {
+        match data_type.data_type().into() {
+            DataTypeDiscriminants::Embedding => Ok(Some(Self { model, data_type })),
+            _ => bail!("Invalid type for embedding data object."),
+        }
+    }
+
+    fn data_type() -> DataTypeDiscriminants {
+        DataTypeDiscriminants::Embedding
+    }
+
+    fn model_handle(&self) -> &ModelHandle {
+        &self.model
+    }
+
+    async fn validate(&self) -> anyhow::Result<Result<(), DataValidationError>> {
+        let missing_items: Vec<_> = self
+            .model
+            .missing_neuron_items(&self.data_type)
+            .await?
+            .collect();
+        Ok(if missing_items.is_empty() {
+            Ok(())
+        } else {
+            Err(DataValidationError::MissingItems { missing_items })
+        })
+    }
+}
+
+impl Embedding {
+    /// The embedding of the neuron, or `None` if it has none.
+    pub async fn embedding(
+        &self,
+        layer_index: u32,
+        neuron_index: u32,
+    ) -> Result<Option<EmbeddingData>> {
+        let model_name = self.model.name();
+        self.model
+            .neuron_data(&self.data_type, layer_index, neuron_index)
+            .await?
+            .map(|data| {
+                EmbeddingData::from_binary(data).with_context(|| {
+                    format!(
+                        "Failed to deserialize embedding of neuron l{layer_index}n{neuron_index} \
+                         in model '{model_name}'."
+                    )
+                })
+            })
+            .transpose()
+    }
+
+    /// The embeddings of all neurons that have one, in order.
+    pub async fn embeddings(&self) -> Result<Vec<(NeuronIndex, EmbeddingData)>> {
+        const GET_EMBEDDINGS: &str = r#"
+        SELECT
+            layer_index,
+            neuron_index,
+            data
+        FROM neuron_data
+        WHERE model_id = ?1 AND data_type_id = ?2
+        ORDER BY layer_index, neuron_index;
+        "#;
+
+        let model_name = self.model.name();
+        let data_type_name = self.data_type.name();
+        let params = (self.model.id(), self.data_type.id());
+        let rows = self
+            .model
+            .database()
+            .connection
+            .call(move |connection| {
+                connection
+                    .prepare(GET_EMBEDDINGS)?
+                    .query_map(params, |row| {
+                        Ok((
+                            NeuronIndex {
+                                layer: row.get(0)?,
+                                neuron: row.get(1)?,
+                            },
+                            row.get::<_, Vec<u8>>(2)?,
+                        ))
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to get embeddings of data object '{data_type_name}' for model \
+                     '{model_name}'."
+                )
+            })?;
+        rows.into_iter()
+            .map(|(neuron_index, data)| {
+                let embedding = EmbeddingData::from_binary(data).with_context(|| {
+                    format!(
+                        "Failed to deserialize embedding of neuron {neuron_index} in model \
+                         '{model_name}'."
+                    )
+                })?;
+                Ok((neuron_index, embedding))
+            })
+            .collect()
+    }
+}
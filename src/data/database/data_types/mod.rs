@@ -10,3 +10,9 @@ mod neuron_store;
 pub use neuron_store::NeuronStore;
 mod json;
 pub use json::Json;
+mod neuron_correspondence;
+pub use neuron_correspondence::NeuronCorrespondence;
+mod embedding;
+pub use embedding::Embedding;
+mod neuron_clusters;
+pub use neuron_clusters::NeuronClusters;
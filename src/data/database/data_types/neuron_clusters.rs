@@ -0,0 +1,83 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+
+use super::{data_type::DataValidationError, DataTypeDiscriminants, ModelDataType};
+use crate::data::{
+    data_objects::{DataObject, NeuronClusters as NeuronClustersData, NeuronGraphNode},
+    DataTypeHandle, ModelHandle,
+};
+
+pub struct NeuronClusters {
+    model: ModelHandle,
+    data_type: DataTypeHandle,
+}
+
+#[async_trait]
+impl ModelDataType for NeuronClusters {
+    async fn new(model: ModelHandle, data_type: DataTypeHandle) -> Result<Option<Self>> {
+        match data_type.data_type().into() {
+            DataTypeDiscriminants::NeuronClusters => Ok(Some(Self { model, data_type })),
+            _ => bail!("Invalid type for neuron clusters data object."),
+        }
+    }
+
+    fn data_type() -> DataTypeDiscriminants {
+        DataTypeDiscriminants::NeuronClusters
+    }
+
+    fn model_handle(&self) -> &ModelHandle {
+        &self.model
+    }
+
+    async fn validate(&self) -> anyhow::Result<Result<(), DataValidationError>> {
+        let missing_items: Vec<_> = self
+            .model
+            .missing_model_items(&self.data_type)
+            .await?
+            .chain(self.model.missing_neuron_items(&self.data_type).await?)
+            .collect();
+        Ok(if missing_items.is_empty() {
+            Ok(())
+        } else {
+            Err(DataValidationError::MissingItems { missing_items })
+        })
+    }
+}
+
+impl NeuronClusters {
+    pub async fn clusters(&self) -> Result<NeuronClustersData> {
+        let model_name = self.model.name();
+        let raw_data = self
+            .model
+            .model_data(&self.data_type)
+            .await
+            .with_context(|| format!("Failed to get neuron clusters for model '{model_name}'."))?
+            .with_context(|| {
+                format!("Database has no neuron clusters for model '{model_name}'.")
+            })?;
+        NeuronClustersData::from_binary(raw_data).with_context(|| {
+            format!("Failed to deserialize neuron clusters for model '{model_name}'.")
+        })
+    }
+
+    /// The neuron's place in the similarity graph, or `None` if the neuron has no data.
+    pub async fn node(
+        &self,
+        layer_index: u32,
+        neuron_index: u32,
+    ) -> Result<Option<NeuronGraphNode>> {
+        let model_name = self.model.name();
+        self.model
+            .neuron_data(&self.data_type, layer_index, neuron_index)
+            .await?
+            .map(|data| {
+                NeuronGraphNode::from_binary(data).with_context(|| {
+                    format!(
+                        "Failed to deserialize graph node of neuron l{layer_index}n{neuron_index} \
+                         in model '{model_name}'."
+                    )
+                })
+            })
+            .transpose()
+    }
+}
@@ -0,0 +1,66 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+
+use super::{data_type::DataValidationError, DataTypeDiscriminants, ModelDataType};
+use crate::data::{
+    data_objects::{DataObject, ModelCorrespondence},
+    DataTypeHandle, ModelHandle,
+};
+
+pub struct NeuronCorrespondence {
+    model: ModelHandle,
+    data_type: DataTypeHandle,
+}
+
+#[async_trait]
+impl ModelDataType for NeuronCorrespondence {
+    async fn new(model: ModelHandle, data_type: DataTypeHandle) -> Result<Option<Self>> {
+        match data_type.data_type().into() {
+            DataTypeDiscriminants::NeuronCorrespondence => Ok(Some(Self { model, data_type })),
+            _ => bail!("Invalid type for neuron correspondence data object."),
+        }
+    }
+
+    fn data_type() -> DataTypeDiscriminants {
+        DataTypeDiscriminants::NeuronCorrespondence
+    }
+
+    fn model_handle(&self) -> &ModelHandle {
+        &self.model
+    }
+
+    async fn validate(&self) -> anyhow::Result<Result<(), DataValidationError>> {
+        let missing_items: Vec<_> = self
+            .model
+            .missing_neuron_items(&self.data_type)
+            .await?
+            .collect();
+        Ok(if missing_items.is_empty() {
+            Ok(())
+        } else {
+            Err(DataValidationError::MissingItems { missing_items })
+        })
+    }
+}
+
+impl NeuronCorrespondence {
+    /// The similar neurons of the neuron in each model it has been compared with, or nothing if
+    /// it has not been compared with any.
+    pub async fn correspondences(
+        &self,
+        layer_index: u32,
+        neuron_index: u32,
+    ) -> Result<Vec<ModelCorrespondence>> {
+        let model_name = self.model.name();
+        self.model
+            .neuron_data(&self.data_type, layer_index, neuron_index)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to get neuron correspondence data for neuron \
+                     l{layer_index}n{neuron_index} in model '{model_name}'."
+                )
+            })?
+            .map_or(Ok(vec![]), Vec::<ModelCorrespondence>::from_binary)
+    }
+}
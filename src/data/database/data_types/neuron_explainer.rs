@@ -6,7 +6,7 @@ use super::{
     DataTypeDiscriminants,
 };
 use crate::data::{
-    data_objects::{DataObject, NeuronExplainerPage},
+    data_objects::{DataObject, NeuronExplainerPage, NeuronExplainerSummary},
     database::ModelHandle,
     DataTypeHandle,
 };
@@ -48,6 +48,46 @@ impl ModelDataType for NeuronExplainer {
 }
 
 impl NeuronExplainer {
+    pub fn data_type_handle(&self) -> &DataTypeHandle {
+        &self.data_type
+    }
+
+    pub async fn model_page(&self) -> Result<NeuronExplainerSummary> {
+        let model_name = self.model.name();
+        let raw_data = self
+            .model
+            .model_data(&self.data_type)
+            .await
+            .with_context(|| {
+                format!("Failed to get neuron explainer model data for model '{model_name}'.")
+            })?
+            .with_context(|| {
+                format!("Database has no neuron explainer model data for model '{model_name}'.")
+            })?;
+        NeuronExplainerSummary::from_binary(raw_data.as_slice())
+    }
+
+    pub async fn layer_page(&self, layer_index: u32) -> Result<NeuronExplainerSummary> {
+        let model_name = self.model.name();
+        let raw_data = self
+            .model
+            .layer_data(&self.data_type, layer_index)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to get neuron explainer layer data for layer {layer_index} in model \
+                     '{model_name}'."
+                )
+            })?
+            .with_context(|| {
+                format!(
+                    "Database has no neuron explainer layer data for layer {layer_index} in model \
+                     '{model_name}'."
+                )
+            })?;
+        NeuronExplainerSummary::from_binary(raw_data.as_slice())
+    }
+
     pub async fn neuron_page(
         &self,
         layer_index: u32,
@@ -75,4 +115,29 @@ impl NeuronExplainer {
             })
             .transpose()
     }
+
+    /// Stores a neuron page fetched from the original source while serving a request.
+    pub async fn add_live_fetched_neuron_page(
+        &self,
+        layer_index: u32,
+        neuron_index: u32,
+        page: &NeuronExplainerPage,
+    ) -> Result<()> {
+        self.model
+            .clone()
+            .add_live_fetched_neuron_data(
+                &self.data_type,
+                layer_index,
+                neuron_index,
+                page.to_binary()?,
+            )
+            .await
+    }
+
+    /// Whether the neuron explainer page for the neuron was fetched while serving a request.
+    pub async fn is_live_fetched(&self, layer_index: u32, neuron_index: u32) -> Result<bool> {
+        self.model
+            .is_live_fetched(&self.data_type, layer_index, neuron_index)
+            .await
+    }
 }
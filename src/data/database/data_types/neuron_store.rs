@@ -3,7 +3,8 @@ use async_trait::async_trait;
 
 use super::{data_type::DataValidationError, DataTypeDiscriminants, ModelDataType};
 use crate::data::{
-    neuron_store::SimilarNeurons, DataTypeHandle, ModelHandle, NeuronStore as NeuronStoreData,
+    neuron_store::SimilarNeurons, DataTypeHandle, ModelHandle, NeuronIndex,
+    NeuronStore as NeuronStoreData,
 };
 
 pub struct NeuronStore {
@@ -86,4 +87,54 @@ impl NeuronStore {
             )
         })
     }
+
+    /// The similar neurons of all neurons that have them, in order.
+    pub async fn all_similarities(&self) -> Result<Vec<(NeuronIndex, SimilarNeurons)>> {
+        const GET_SIMILARITIES: &str = r#"
+        SELECT
+            layer_index,
+            neuron_index,
+            data
+        FROM neuron_data
+        WHERE model_id = ?1 AND data_type_id = ?2
+        ORDER BY layer_index, neuron_index;
+        "#;
+
+        let model_name = self.model.name();
+        let params = (self.model.id(), self.data_type.id());
+        let rows = self
+            .model
+            .database()
+            .connection
+            .call(move |connection| {
+                connection
+                    .prepare(GET_SIMILARITIES)?
+                    .query_map(params, |row| {
+                        Ok((
+                            NeuronIndex {
+                                layer: row.get(0)?,
+                                neuron: row.get(1)?,
+                            },
+                            row.get::<_, Vec<u8>>(2)?,
+                        ))
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await
+            .with_context(|| {
+                format!("Failed to get neuron similarities for model '{model_name}'.")
+            })?;
+        rows.into_iter()
+            .map(|(neuron_index, data)| {
+                let similar_neurons =
+                    SimilarNeurons::from_binary(data.as_slice()).with_context(|| {
+                        format!(
+                            "Failed to deserialize neuron similarities for neuron {neuron_index} \
+                             in model '{model_name}'."
+                        )
+                    })?;
+                Ok((neuron_index, similar_neurons))
+            })
+            .collect()
+    }
 }
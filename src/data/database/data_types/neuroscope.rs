@@ -105,4 +105,29 @@ impl Neuroscope {
             })
             .transpose()
     }
+
+    /// Stores a neuron page fetched from the original source while serving a request.
+    pub async fn add_live_fetched_neuron_page(
+        &self,
+        layer_index: u32,
+        neuron_index: u32,
+        page: &NeuroscopeNeuronPage,
+    ) -> Result<()> {
+        self.model
+            .clone()
+            .add_live_fetched_neuron_data(
+                &self.data_type,
+                layer_index,
+                neuron_index,
+                page.to_binary()?,
+            )
+            .await
+    }
+
+    /// Whether the neuroscope page for the neuron was fetched while serving a request.
+    pub async fn is_live_fetched(&self, layer_index: u32, neuron_index: u32) -> Result<bool> {
+        self.model
+            .is_live_fetched(&self.data_type, layer_index, neuron_index)
+            .await
+    }
 }
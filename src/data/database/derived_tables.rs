@@ -0,0 +1,305 @@
+//! Tables derived from neuron data as it is stored: the explanation index, the neuron scalars, the
+//! index from dataset samples to the neurons they activate, the index of the tokens around the
+//! max-activating tokens of those samples and the importance of tokens in neuron graphs.
+
+use anyhow::{Context, Result};
+use rusqlite::{OptionalExtension, Transaction};
+
+use super::{
+    context_index::index_contexts, data_types::DataType, explanation_index::index_explanation,
+    neuron_scalars::store_scalars, sample_index::index_samples,
+    token_importance::index_token_importance, Database,
+};
+use crate::util::cancel;
+
+/// Updates a derived table with neuron data that was just added.
+type IndexNeuronData = fn(&Transaction, i64, i64, &DataType, u32, u32, &[u8]) -> Result<()>;
+
+/// The derived tables along with the functions that update them. If any of them is missing when a
+/// database with neuron data is opened, it is created empty and recorded in
+/// `unfilled_derived_table` until it is filled by [`Database::fill_derived_tables`].
+const DERIVED_TABLES: [(&str, IndexNeuronData); 5] = [
+    ("explanation", index_explanation),
+    ("neuron_scalar", store_scalars),
+    ("sample_activation", index_samples),
+    ("context_token", index_contexts),
+    ("token_importance", index_token_importance),
+];
+
+/// Updates the derived tables with neuron data that was just added. Data that cannot be decoded
+/// is stored anyway, as it always has been, just left out of the derived tables with a warning.
+pub(super) fn index_neuron_data(
+    transaction: &Transaction,
+    model_id: i64,
+    data_type_id: i64,
+    data_type: &DataType,
+    layer_index: u32,
+    neuron_index: u32,
+    data: &[u8],
+) -> Result<()> {
+    DERIVED_TABLES.iter().try_for_each(|(_, index)| {
+        index(
+            transaction,
+            model_id,
+            data_type_id,
+            data_type,
+            layer_index,
+            neuron_index,
+            data,
+        )
+    })
+}
+
+/// Default number of neurons indexed between commits when filling derived tables.
+pub const FILL_BATCH_SIZE: usize = 1000;
+
+/// Key of a row of `neuron_data`: its model, data object, layer and neuron.
+type NeuronDataKey = (i64, i64, u32, u32);
+
+/// Fills the derived table from the batch of neuron data following the key, and returns the key
+/// of the last row of the batch, or `None` if there was no more data.
+fn index_neuron_data_batch(
+    transaction: &mut Transaction,
+    index: IndexNeuronData,
+    after: Option<NeuronDataKey>,
+    batch_size: usize,
+) -> Result<Option<NeuronDataKey>> {
+    const GET_DATA_TYPE: &str = r#"
+    SELECT type, type_args
+    FROM data_type
+    WHERE id = ?1;
+    "#;
+    const GET_NEURON_DATA: &str = r#"
+    SELECT model_id, data_type_id, layer_index, neuron_index, data
+    FROM neuron_data
+    WHERE (model_id, data_type_id, layer_index, neuron_index) > (?1, ?2, ?3, ?4)
+    ORDER BY model_id, data_type_id, layer_index, neuron_index
+    LIMIT ?5;
+    "#;
+
+    let (model_id, data_type_id, layer_index, neuron_index) = after.unwrap_or((-1, -1, 0, 0));
+    let rows = transaction
+        .prepare(GET_NEURON_DATA)?
+        .query_map(
+            (
+                model_id,
+                data_type_id,
+                layer_index,
+                neuron_index,
+                batch_size,
+            ),
+            |row| {
+                Ok((
+                    (row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?),
+                    row.get::<_, Vec<u8>>(4)?,
+                ))
+            },
+        )?
+        .collect::<rusqlite::Result<Vec<(NeuronDataKey, _)>>>()?;
+    let mut data_type: Option<(i64, DataType)> = None;
+    for &((model_id, data_type_id, layer_index, neuron_index), ref data) in &rows {
+        if data_type.as_ref().map(|(id, _)| *id) != Some(data_type_id) {
+            let (type_name, type_args) = transaction
+                .query_row(GET_DATA_TYPE, (data_type_id,), |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?))
+                })
+                .optional()?
+                .with_context(|| format!("Neuron data has unknown data object {data_type_id}."))?;
+            data_type = Some((data_type_id, DataType::from_raw(&type_name, &type_args)?));
+        }
+        let (_, data_type) = data_type.as_ref().expect("Data type was just set.");
+        index(
+            transaction,
+            model_id,
+            data_type_id,
+            data_type,
+            layer_index,
+            neuron_index,
+            data,
+        )?;
+    }
+    Ok(rows.last().map(|&(key, _)| key))
+}
+
+impl Database {
+    /// Names of the derived tables that do not exist yet.
+    pub(super) async fn missing_derived_tables(&self) -> Result<Vec<&'static str>> {
+        const TABLE_EXISTS: &str = r#"
+        SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1);
+        "#;
+
+        self.connection
+            .call(|connection| {
+                let mut missing = vec![];
+                for (table, _) in DERIVED_TABLES {
+                    let exists: bool =
+                        connection.query_row(TABLE_EXISTS, (table,), |row| row.get(0))?;
+                    if !exists {
+                        missing.push(table);
+                    }
+                }
+                Ok(missing)
+            })
+            .await
+            .context("Failed to check whether the derived tables exist.")
+    }
+
+    /// Records the derived tables as unfilled if the database already has neuron data, which
+    /// would be missing from them. Called once the tables have been created.
+    pub(super) async fn mark_unfilled_derived_tables(
+        &self,
+        tables: Vec<&'static str>,
+    ) -> Result<()> {
+        const HAS_NEURON_DATA: &str = r#"
+        SELECT EXISTS(SELECT 1 FROM neuron_data);
+        "#;
+        const MARK_UNFILLED: &str = r#"
+        INSERT OR IGNORE INTO unfilled_derived_table(name) VALUES (?1);
+        "#;
+
+        let marked = self
+            .connection
+            .call(move |connection| {
+                let has_neuron_data: bool =
+                    connection.query_row(HAS_NEURON_DATA, (), |row| row.get(0))?;
+                if has_neuron_data {
+                    for table in &tables {
+                        connection.execute(MARK_UNFILLED, (table,))?;
+                    }
+                }
+                Ok(has_neuron_data)
+            })
+            .await
+            .context("Failed to record unfilled derived tables.")?;
+        if marked {
+            self.warn_unfilled_derived_tables().await?;
+        }
+        Ok(())
+    }
+
+    /// Names of the derived tables that were created for a database that already had neuron data
+    /// and have not been filled from it yet.
+    pub async fn unfilled_derived_tables(&self) -> Result<Vec<String>> {
+        const GET_UNFILLED: &str = r#"
+        SELECT name FROM unfilled_derived_table ORDER BY name;
+        "#;
+
+        self.connection
+            .call(|connection| {
+                connection
+                    .prepare(GET_UNFILLED)?
+                    .query_map([], |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await
+            .context("Failed to get unfilled derived tables.")
+    }
+
+    pub(super) async fn warn_unfilled_derived_tables(&self) -> Result<()> {
+        let unfilled = self.unfilled_derived_tables().await?;
+        if !unfilled.is_empty() {
+            log::warn!(
+                "Derived tables {} do not include the neuron data that was stored before they \
+                 were created. Searches relying on them will miss that data until they are filled \
+                 through the admin API or the Python package.",
+                unfilled.join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    /// Fills the derived tables recorded as unfilled from the neuron data in the database,
+    /// committing after every `batch_size` neurons so other requests are not held up. Filling
+    /// can be interrupted and started again, since indexing a neuron replaces its earlier rows.
+    pub async fn fill_derived_tables(&self, batch_size: usize) -> Result<()> {
+        const MARK_FILLED: &str = r#"
+        DELETE FROM unfilled_derived_table WHERE name = ?1;
+        "#;
+
+        let batch_size = batch_size.max(1);
+        for table in self.unfilled_derived_tables().await? {
+            let Some(&(table, index)) = DERIVED_TABLES.iter().find(|(name, _)| *name == table)
+            else {
+                log::warn!("Unknown derived table '{table}' recorded as unfilled.");
+                continue;
+            };
+            log::info!("Filling derived table '{table}' from existing neuron data.");
+            let mut after = None;
+            let mut num_filled = 0;
+            loop {
+                cancel::check_cancelled()?;
+                let Some(last) = self
+                    .clone()
+                    .execute(move |transaction| {
+                        index_neuron_data_batch(transaction, index, after, batch_size)
+                    })
+                    .await
+                    .with_context(|| format!("Failed to fill derived table '{table}'."))?
+                else {
+                    break;
+                };
+                after = Some(last);
+                num_filled += batch_size;
+                log::debug!("Filled derived table '{table}' with about {num_filled} neurons.");
+            }
+            self.connection
+                .call(move |connection| connection.execute(MARK_FILLED, (table,)))
+                .await
+                .with_context(|| format!("Failed to mark derived table '{table}' as filled."))?;
+            log::info!("Filled derived table '{table}'.");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        data::{
+            data_objects::{DataObject, JsonData},
+            database::test_util::add_test_model,
+        },
+        Index,
+    };
+
+    #[tokio::test]
+    async fn fills_derived_tables() -> Result<()> {
+        const COUNT_SCALARS: &str = r#"
+        SELECT COUNT(*) FROM neuron_scalar;
+        "#;
+
+        let database = Database::initialize_in_memory().await?;
+        let mut model = add_test_model(&database, 2, 2).await?;
+        let data_type = database.add_data_type("scores", DataType::Json).await?;
+        model.add_data_type(&data_type).await?;
+        for (layer, neuron) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            let data = JsonData::new(serde_json::json!({ "score": layer + neuron }));
+            model
+                .add_data(&data_type, Index::Neuron(layer, neuron), data.to_binary()?)
+                .await?;
+        }
+        let count_scalars = || {
+            database.connection().call(|connection| {
+                connection.query_row(COUNT_SCALARS, (), |row| row.get::<_, i64>(0))
+            })
+        };
+        assert_eq!(count_scalars().await?, 4);
+
+        database
+            .connection()
+            .call(|connection| connection.execute("DELETE FROM neuron_scalar;", ()))
+            .await?;
+        database
+            .mark_unfilled_derived_tables(vec!["neuron_scalar"])
+            .await?;
+        assert_eq!(
+            database.unfilled_derived_tables().await?,
+            vec!["neuron_scalar"]
+        );
+        database.fill_derived_tables(3).await?;
+        assert_eq!(count_scalars().await?, 4);
+        assert!(database.unfilled_derived_tables().await?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,303 @@
+//! Full text index of neuron explanations.
+//!
+//! Explanations are extracted from neuron data as it is added, from Neuron Explainer pages and
+//! from the `explanation` and `score` fields of JSON objects, and kept in the `explanation` table
+//! with an FTS5 index over their text.
+
+use std::ops::RangeInclusive;
+
+use anyhow::{Context, Result};
+use rusqlite::{params_from_iter, types::Value, ErrorCode, Transaction};
+use serde::{Deserialize, Serialize};
+
+use super::{data_types::DataType, DataTypeHandle, ModelHandle};
+use crate::data::data_objects::{DataObject, JsonData, NeuronExplainerPage};
+
+/// The explanation and its score in the neuron data, if the data type has explanations.
+fn explanation(data_type: &DataType, data: &[u8]) -> Result<Option<(String, Option<f32>)>> {
+    match data_type {
+        DataType::NeuronExplainer => {
+            let page = NeuronExplainerPage::from_binary(data)?;
+            Ok(Some((page.explanation().to_owned(), Some(page.score()))))
+        }
+        DataType::Json => {
+            let JsonData { value } = JsonData::from_binary(data)?;
+            Ok(value["explanation"].as_str().map(|explanation| {
+                (
+                    explanation.to_owned(),
+                    value["score"].as_f64().map(|score| score as f32),
+                )
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn is_indexed(data_type: &DataType) -> bool {
+    matches!(data_type, DataType::NeuronExplainer | DataType::Json)
+}
+
+/// Updates the index with neuron data that was just added.
+pub(super) fn index_explanation(
+    transaction: &Transaction,
+    model_id: i64,
+    data_type_id: i64,
+    data_type: &DataType,
+    layer_index: u32,
+    neuron_index: u32,
+    data: &[u8],
+) -> Result<()> {
+    const UPSERT_EXPLANATION: &str = r#"
+    INSERT INTO explanation (
+        model_id,
+        data_type_id,
+        layer_index,
+        neuron_index,
+        explanation,
+        score
+    ) VALUES (
+        ?1,
+        ?2,
+        ?3,
+        ?4,
+        ?5,
+        ?6
+    )
+    ON CONFLICT(model_id, data_type_id, layer_index, neuron_index)
+    DO UPDATE SET explanation = excluded.explanation, score = excluded.score;
+    "#;
+    const DELETE_EXPLANATION: &str = r#"
+    DELETE FROM explanation
+    WHERE model_id = ?1 AND data_type_id = ?2 AND layer_index = ?3 AND neuron_index = ?4;
+    "#;
+
+    if !is_indexed(data_type) {
+        return Ok(());
+    }
+    let index = (model_id, data_type_id, layer_index, neuron_index);
+    let explanation = explanation(data_type, data).unwrap_or_else(|error| {
+        log::warn!(
+            "Failed to extract explanation of neuron l{layer_index}n{neuron_index}. Error: \
+             {error:#}"
+        );
+        None
+    });
+    match explanation {
+        Some((explanation, score)) => {
+            transaction.prepare_cached(UPSERT_EXPLANATION)?.execute((
+                index.0,
+                index.1,
+                index.2,
+                index.3,
+                explanation,
+                score,
+            ))?;
+        }
+        None => {
+            transaction
+                .prepare_cached(DELETE_EXPLANATION)?
+                .execute(index)?;
+        }
+    }
+    Ok(())
+}
+
+/// Parameters of a full text search over the explanations of a model.
+pub struct ExplanationQuery {
+    /// FTS5 query, supporting phrases in double quotes, `AND`, `OR`, `NOT`, prefixes ending in `*`
+    /// and parentheses.
+    pub text: String,
+    pub data_type: Option<DataTypeHandle>,
+    pub layers: Option<RangeInclusive<u32>>,
+    pub min_score: Option<f32>,
+    pub max_score: Option<f32>,
+    /// Weight of the explanation score relative to the text relevance when ranking matches.
+    pub score_weight: f32,
+    pub limit: usize,
+}
+
+/// A neuron whose explanation matches a full text search.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExplanationMatch {
+    pub layer: u32,
+    pub neuron: u32,
+    pub data_type: String,
+    pub explanation: String,
+    pub score: Option<f32>,
+    /// How well the explanation matches the text, as given by the BM25 ranking function. Higher
+    /// is better.
+    pub relevance: f32,
+    /// The relevance relative to the most relevant match, plus the weighted explanation score.
+    pub rank: f32,
+}
+
+/// Error for an FTS5 query that SQLite could not parse.
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid text query: {0}")]
+pub struct InvalidTextQuery(String);
+
+impl ModelHandle {
+    /// Finds the neurons whose explanations match the query, ordered by rank.
+    pub async fn search_explanations(
+        &self,
+        query: &ExplanationQuery,
+    ) -> Result<Vec<ExplanationMatch>> {
+        const SEARCH_EXPLANATIONS: &str = r#"
+        SELECT
+            explanation.layer_index,
+            explanation.neuron_index,
+            data_type.name,
+            explanation.explanation,
+            explanation.score,
+            -bm25(explanation_text) AS relevance
+        FROM explanation_text
+        JOIN explanation ON explanation.id = explanation_text.rowid
+        JOIN data_type ON data_type.id = explanation.data_type_id
+        WHERE explanation_text MATCH ?1 AND explanation.model_id = ?2 $FILTERS
+        ORDER BY relevance DESC;
+        "#;
+
+        let mut filters = String::new();
+        let mut params = vec![Value::from(query.text.clone()), Value::from(self.id())];
+        let mut filter = |condition: &str, value: Value| {
+            params.push(value);
+            filters.push_str(&format!(" AND {condition} ?{}", params.len()));
+        };
+        if let Some(data_type) = &query.data_type {
+            filter("explanation.data_type_id =", Value::from(data_type.id()));
+        }
+        if let Some(layers) = &query.layers {
+            filter("explanation.layer_index >=", Value::from(*layers.start()));
+            filter("explanation.layer_index <=", Value::from(*layers.end()));
+        }
+        if let Some(min_score) = query.min_score {
+            filter("explanation.score >=", Value::from(min_score as f64));
+        }
+        if let Some(max_score) = query.max_score {
+            filter("explanation.score <=", Value::from(max_score as f64));
+        }
+        let sql = SEARCH_EXPLANATIONS.replace("$FILTERS", &filters);
+
+        let result = self
+            .database()
+            .connection
+            .call(move |connection| {
+                connection
+                    .prepare(&sql)?
+                    .query_map(params_from_iter(params), |row| {
+                        Ok(ExplanationMatch {
+                            layer: row.get(0)?,
+                            neuron: row.get(1)?,
+                            data_type: row.get(2)?,
+                            explanation: row.get(3)?,
+                            score: row.get(4)?,
+                            relevance: row.get::<_, f64>(5)? as f32,
+                            rank: 0.,
+                        })
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await;
+        let mut matches = match result {
+            Ok(matches) => matches,
+            // The statement itself is valid, so a generic SQL error means the FTS5 query is not.
+            Err(tokio_rusqlite::Error::Rusqlite(error))
+                if error.sqlite_error_code() == Some(ErrorCode::Unknown) =>
+            {
+                return Err(InvalidTextQuery(error.to_string()).into())
+            }
+            Err(error) => {
+                return Err(error).with_context(|| {
+                    format!("Failed to search explanations of model '{}'.", self.name())
+                })
+            }
+        };
+
+        let max_relevance = matches
+            .iter()
+            .map(|found| found.relevance)
+            .fold(f32::EPSILON, f32::max);
+        for found in &mut matches {
+            found.rank = found.relevance / max_relevance
+                + query.score_weight * found.score.unwrap_or_default();
+        }
+        matches.sort_by(|a, b| b.rank.total_cmp(&a.rank));
+        matches.truncate(query.limit);
+        Ok(matches)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        data::{data_objects::JsonData, database::test_util::add_test_model, Database},
+        Index,
+    };
+
+    #[tokio::test]
+    async fn searches_explanations() -> Result<()> {
+        let database = Database::initialize_in_memory().await?;
+        let mut model = add_test_model(&database, 2, 2).await?;
+        let data_type = database
+            .add_data_type("explanations", DataType::Json)
+            .await?;
+        model.add_data_type(&data_type).await?;
+
+        for (layer, neuron, explanation, score) in [
+            (0, 0, "French words", 0.2),
+            (0, 1, "French cities such as Paris", 0.9),
+            (1, 0, "numbers", 0.5),
+            (1, 1, "words in French text", 0.4),
+        ] {
+            let data = JsonData::new(serde_json::json!({
+                "explanation": explanation,
+                "score": score,
+            }));
+            model
+                .add_data(&data_type, Index::Neuron(layer, neuron), data.to_binary()?)
+                .await?;
+        }
+        let replacement = JsonData::new(serde_json::json!({ "explanation": "German words" }));
+        model
+            .replace_data(&data_type, Index::Neuron(0, 0), replacement.to_binary()?)
+            .await?;
+
+        let search = |text: &str, min_score, layers| {
+            let query = ExplanationQuery {
+                text: text.to_owned(),
+                data_type: None,
+                layers,
+                min_score,
+                max_score: None,
+                score_weight: 1.,
+                limit: 10,
+            };
+            let model = model.clone();
+            async move {
+                Ok::<_, anyhow::Error>(
+                    model
+                        .search_explanations(&query)
+                        .await?
+                        .into_iter()
+                        .map(|found| (found.layer, found.neuron))
+                        .collect::<Vec<_>>(),
+                )
+            }
+        };
+
+        assert_eq!(search("french", None, None).await?, vec![(0, 1), (1, 1)]);
+        assert_eq!(search("french", Some(0.5), None).await?, vec![(0, 1)]);
+        assert_eq!(search("french", None, Some(1..=1)).await?, vec![(1, 1)]);
+        assert_eq!(
+            search("\"french words\" OR german", None, None).await?,
+            vec![(0, 0)]
+        );
+        assert_eq!(search("word* NOT german", None, None).await?, vec![(1, 1)]);
+        assert!(search("\"french", None, None)
+            .await
+            .unwrap_err()
+            .is::<InvalidTextQuery>());
+        Ok(())
+    }
+}
@@ -15,10 +15,27 @@ pub use data_type_handle::DataTypeHandle;
 pub mod data_types;
 mod service_handle;
 pub use service_handle::ServiceHandle;
+mod context_index;
+mod derived_tables;
+mod explanation_index;
+mod neuron_scalars;
+mod neuron_summary;
+mod random_neurons;
+mod sample_index;
+mod token_importance;
 mod validation;
+pub use context_index::{ContextMatch, ContextQuery};
+pub use derived_tables::FILL_BATCH_SIZE;
+pub use explanation_index::{ExplanationMatch, ExplanationQuery, InvalidTextQuery};
+pub use neuron_scalars::{NeuronQuery, UnknownDataType};
+pub use random_neurons::RandomNeuronQuery;
+pub use sample_index::{SampleActivation, SharedSamples};
+pub use token_importance::TokenImportance;
 
 mod table_definitions;
-use table_definitions::TABLES;
+#[cfg(test)]
+pub(crate) mod test_util;
+use table_definitions::{AUXILIARY_TABLES, TABLES};
 
 pub trait Operation<R>: FnOnce(&mut Transaction) -> Result<R> + 'static + Send
 where
@@ -60,6 +77,7 @@ impl Database {
                 .call(|connection| connection.execute(table, ()))
                 .await?;
         }
+        database.create_auxiliary_tables().await?;
 
         let metadata_service = Service::new("metadata".to_owned(), ServiceProvider::Metadata);
         database.add_service(metadata_service).await?;
@@ -80,6 +98,7 @@ impl Database {
                 .call(|connection| connection.execute(table, ()))
                 .await?;
         }
+        database.create_auxiliary_tables().await?;
 
         let metadata_service = Service::new("metadata".to_owned(), ServiceProvider::Metadata);
         database.add_service(metadata_service).await?;
@@ -94,15 +113,43 @@ impl Database {
 
         let database = Connection::open(path).await?;
 
-        Ok(Database {
+        let database = Database {
             connection: database,
-        })
+        };
+        database.create_auxiliary_tables().await?;
+
+        Ok(database)
+    }
+
+    async fn create_auxiliary_tables(&self) -> Result<()> {
+        let missing_derived_tables = self.missing_derived_tables().await?;
+        for table in AUXILIARY_TABLES.iter() {
+            self.connection
+                .call(|connection| connection.execute_batch(table))
+                .await
+                .context("Failed to create auxiliary table.")?;
+        }
+        if missing_derived_tables.is_empty() {
+            self.warn_unfilled_derived_tables().await
+        } else {
+            self.mark_unfilled_derived_tables(missing_derived_tables)
+                .await
+        }
     }
 
     pub fn connection(&self) -> &Connection {
         &self.connection
     }
 
+    /// Closes the database once all queued queries have finished. Other handles to the database can
+    /// no longer be used afterwards.
+    pub async fn close(self) -> Result<()> {
+        self.connection
+            .close()
+            .await
+            .context("Failed to close database.")
+    }
+
     async fn execute<R, F>(&mut self, f: F) -> Result<R>
     where
         F: Operation<R>,
@@ -195,6 +242,35 @@ impl Database {
         DataTypeHandle::new(self.clone(), data_type_name.as_ref()).await
     }
 
+    /// Runs a trivial query to check that the database can be queried.
+    pub async fn ping(&self) -> Result<()> {
+        const PING: &str = r#"
+            SELECT COUNT(*) FROM model;
+        "#;
+
+        self.connection
+            .call(|connection| connection.query_row(PING, [], |row| row.get::<_, i64>(0)))
+            .await
+            .context("Failed to query the database.")?;
+        Ok(())
+    }
+
+    pub async fn all_data_type_names(&self) -> Result<Vec<String>> {
+        const GET_ALL_DATA_TYPE_NAMES: &str = r#"
+            SELECT name FROM data_type ORDER BY id ASC;
+        "#;
+
+        self.connection
+            .call(|connection| {
+                connection
+                    .prepare(GET_ALL_DATA_TYPE_NAMES)?
+                    .query_map([], |row| row.get(0))?
+                    .collect::<std::result::Result<Vec<_>, _>>()
+            })
+            .await
+            .context("Failed to get the names of all data objects.")
+    }
+
     pub async fn model_data_type<D>(
         &self,
         model: &ModelHandle,
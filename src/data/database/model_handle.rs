@@ -1,10 +1,36 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use anyhow::{Context, Result};
-use rusqlite::OptionalExtension;
+use rusqlite::{OptionalExtension, Transaction};
 
 use super::{
-    data_types::ModelDataType, service_handle::ServiceHandle, DataTypeHandle, Database, Operation,
+    data_types::ModelDataType, derived_tables::index_neuron_data, service_handle::ServiceHandle,
+    table_definitions::AUXILIARY_REFERENCE_TABLES, DataTypeHandle, Database, Operation,
 };
-use crate::{data::Metadata, Index};
+use crate::{data::Metadata, metrics, Index};
+
+/// Forgets that the neuron data was fetched live, for when it is replaced by data stored any other
+/// way.
+fn clear_live_fetch(
+    transaction: &Transaction,
+    model_id: i64,
+    data_type_id: i64,
+    layer_index: u32,
+    neuron_index: u32,
+) -> rusqlite::Result<()> {
+    const DELETE_LIVE_FETCH: &str = r#"
+    DELETE FROM live_fetch
+    WHERE model_id = ?1 AND data_type_id = ?2 AND layer_index = ?3 AND neuron_index = ?4;
+    "#;
+
+    transaction.prepare_cached(DELETE_LIVE_FETCH)?.execute((
+        model_id,
+        data_type_id,
+        layer_index,
+        neuron_index,
+    ))?;
+    Ok(())
+}
 
 #[derive(Clone)]
 pub struct ModelHandle {
@@ -141,7 +167,10 @@ impl ModelHandle {
 
         let params = (self.id,);
         move |transaction| {
-            for table in REFERENCE_TABLES.iter() {
+            for table in REFERENCE_TABLES
+                .iter()
+                .chain(AUXILIARY_REFERENCE_TABLES.iter())
+            {
                 let mut statement = transaction
                     .prepare(DELETE_MODEL_REFERENCES.replace("$TABLE", table).as_str())?;
                 statement.execute(params)?;
@@ -209,7 +238,10 @@ impl ModelHandle {
 
         let params = (self.id, data_type.id());
         move |transaction| {
-            for table in REFERENCE_TABLES.iter() {
+            for table in REFERENCE_TABLES
+                .iter()
+                .chain(AUXILIARY_REFERENCE_TABLES.iter())
+            {
                 let mut statement =
                     transaction.prepare(DELETE_DATA.replace("$DATABASE", table).as_str())?;
                 statement.execute(params)?;
@@ -387,11 +419,34 @@ impl ModelHandle {
         );
         "#;
 
-        let params = (self.id(), data_type.id(), layer_index, neuron_index, data);
+        let model_id = self.id();
+        let data_type_id = data_type.id();
+        let data_type = data_type.data_type().clone();
 
         move |transaction| {
-            transaction.prepare(ADD_NEURON_DATA)?.insert(params)?;
-            Ok(())
+            transaction.prepare(ADD_NEURON_DATA)?.insert((
+                model_id,
+                data_type_id,
+                layer_index,
+                neuron_index,
+                &data,
+            ))?;
+            clear_live_fetch(
+                transaction,
+                model_id,
+                data_type_id,
+                layer_index,
+                neuron_index,
+            )?;
+            index_neuron_data(
+                transaction,
+                model_id,
+                data_type_id,
+                &data_type,
+                layer_index,
+                neuron_index,
+                &data,
+            )
         }
     }
 
@@ -424,6 +479,125 @@ impl ModelHandle {
         }
     }
 
+    fn replace_data_inner(
+        &self,
+        data_type: &DataTypeHandle,
+        index: Index,
+        data: Vec<u8>,
+    ) -> impl Operation<()> {
+        const REPLACE_MODEL_DATA: &str = r#"
+        INSERT OR REPLACE INTO model_data (
+            model_id,
+            data_type_id,
+            data
+        ) VALUES (
+            ?1,
+            ?2,
+            ?3
+        );
+        "#;
+        const REPLACE_LAYER_DATA: &str = r#"
+        INSERT OR REPLACE INTO layer_data (
+            model_id,
+            data_type_id,
+            layer_index,
+            data
+        ) VALUES (
+            ?1,
+            ?2,
+            ?3,
+            ?4
+        );
+        "#;
+        const REPLACE_NEURON_DATA: &str = r#"
+        INSERT OR REPLACE INTO neuron_data (
+            model_id,
+            data_type_id,
+            layer_index,
+            neuron_index,
+            data
+        ) VALUES (
+            ?1,
+            ?2,
+            ?3,
+            ?4,
+            ?5
+        );
+        "#;
+
+        let model_id = self.id();
+        let data_type_id = data_type.id();
+        let data_type = data_type.data_type().clone();
+
+        move |transaction| {
+            match index {
+                Index::Model => {
+                    transaction.prepare(REPLACE_MODEL_DATA)?.execute((
+                        model_id,
+                        data_type_id,
+                        data,
+                    ))?;
+                }
+                Index::Layer(layer_index) => {
+                    transaction.prepare(REPLACE_LAYER_DATA)?.execute((
+                        model_id,
+                        data_type_id,
+                        layer_index,
+                        data,
+                    ))?;
+                }
+                Index::Neuron(layer_index, neuron_index) => {
+                    transaction.prepare(REPLACE_NEURON_DATA)?.execute((
+                        model_id,
+                        data_type_id,
+                        layer_index,
+                        neuron_index,
+                        &data,
+                    ))?;
+                    clear_live_fetch(
+                        transaction,
+                        model_id,
+                        data_type_id,
+                        layer_index,
+                        neuron_index,
+                    )?;
+                    index_neuron_data(
+                        transaction,
+                        model_id,
+                        data_type_id,
+                        &data_type,
+                        layer_index,
+                        neuron_index,
+                        &data,
+                    )?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Adds data for the given index, replacing any data that already exists for it.
+    pub async fn replace_data(
+        &mut self,
+        data_type: &DataTypeHandle,
+        index: Index,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let model_name = self.name().to_owned();
+        let data_type_name = data_type.name().to_owned();
+
+        self.database
+            .execute(self.replace_data_inner(data_type, index, data))
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to replace '{data_type_name}' data for {index} in model \
+                     '{model_name}'.",
+                    index = index.error_string()
+                )
+            })
+    }
+
     pub async fn model_data(&self, data_type: &DataTypeHandle) -> Result<Option<Vec<u8>>> {
         const GET_MODEL_DATA: &str = r#"
         SELECT
@@ -434,6 +608,7 @@ impl ModelHandle {
 
         let params = (self.id(), data_type.id());
 
+        let _timer = metrics::database_query_timer("model_data");
         self.database
             .connection
             .call(move |connection| {
@@ -463,6 +638,7 @@ impl ModelHandle {
         "#;
 
         let params = (self.id(), data_type.id(), layer_index);
+        let _timer = metrics::database_query_timer("layer_data");
         self.database
             .connection
             .call(move |connection| {
@@ -495,6 +671,7 @@ impl ModelHandle {
 
         let params = (self.id(), data_type.id(), layer_index, neuron_index);
 
+        let _timer = metrics::database_query_timer("neuron_data");
         self.database
             .connection
             .call(move |connection| {
@@ -521,4 +698,169 @@ impl ModelHandle {
             }
         }
     }
+
+    fn add_live_fetched_neuron_data_inner(
+        &self,
+        data_type: &DataTypeHandle,
+        layer_index: u32,
+        neuron_index: u32,
+        data: Vec<u8>,
+        fetched_at: i64,
+    ) -> impl Operation<()> {
+        const REPLACE_NEURON_DATA: &str = r#"
+        INSERT OR REPLACE INTO neuron_data (
+            model_id,
+            data_type_id,
+            layer_index,
+            neuron_index,
+            data
+        ) VALUES (
+            ?1,
+            ?2,
+            ?3,
+            ?4,
+            ?5
+        );
+        "#;
+        const ADD_LIVE_FETCH: &str = r#"
+        INSERT OR REPLACE INTO live_fetch (
+            model_id,
+            data_type_id,
+            layer_index,
+            neuron_index,
+            fetched_at
+        ) VALUES (
+            ?1,
+            ?2,
+            ?3,
+            ?4,
+            ?5
+        );
+        "#;
+
+        let model_id = self.id();
+        let data_type_id = data_type.id();
+        let data_type = data_type.data_type().clone();
+
+        move |transaction| {
+            transaction.prepare(REPLACE_NEURON_DATA)?.execute((
+                model_id,
+                data_type_id,
+                layer_index,
+                neuron_index,
+                &data,
+            ))?;
+            index_neuron_data(
+                transaction,
+                model_id,
+                data_type_id,
+                &data_type,
+                layer_index,
+                neuron_index,
+                &data,
+            )?;
+            transaction.prepare(ADD_LIVE_FETCH)?.execute((
+                model_id,
+                data_type_id,
+                layer_index,
+                neuron_index,
+                fetched_at,
+            ))?;
+            Ok(())
+        }
+    }
+
+    /// Stores neuron data fetched from the original source while serving a request, and records
+    /// that it was fetched live rather than retrieved ahead of time.
+    pub async fn add_live_fetched_neuron_data(
+        &mut self,
+        data_type: &DataTypeHandle,
+        layer_index: u32,
+        neuron_index: u32,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let fetched_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        self.database
+            .execute(self.add_live_fetched_neuron_data_inner(
+                data_type,
+                layer_index,
+                neuron_index,
+                data,
+                fetched_at,
+            ))
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to add live fetched neuron data for neuron \
+                     l{layer_index}n{neuron_index} for data object '{}' for model '{}'.",
+                    data_type.name(),
+                    self.name(),
+                )
+            })
+    }
+
+    /// Whether the neuron data was fetched from the original source while serving a request.
+    pub async fn is_live_fetched(
+        &self,
+        data_type: &DataTypeHandle,
+        layer_index: u32,
+        neuron_index: u32,
+    ) -> Result<bool> {
+        const IS_LIVE_FETCHED: &str = r#"
+        SELECT EXISTS(
+            SELECT 1
+            FROM live_fetch
+            WHERE model_id = ?1 AND data_type_id = ?2 AND layer_index = ?3 AND neuron_index = ?4
+        );
+        "#;
+
+        let params = (self.id(), data_type.id(), layer_index, neuron_index);
+
+        self.database
+            .connection
+            .call(move |connection| {
+                let mut statement = connection.prepare(IS_LIVE_FETCHED)?;
+                statement.query_row(params, |row| row.get(0))
+            })
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to check whether neuron l{layer_index}n{neuron_index} for data object \
+                     '{}' for model '{}' was fetched live.",
+                    data_type.name(),
+                    self.name(),
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::{data_types::DataType, database::test_util::add_test_model};
+
+    #[tokio::test]
+    async fn replacing_live_fetched_data_clears_flag() -> Result<()> {
+        let database = Database::initialize_in_memory().await?;
+        let mut model = add_test_model(&database, 1, 2).await?;
+        let data_type = database.add_data_type("scores", DataType::Json).await?;
+        model.add_data_type(&data_type).await?;
+
+        model
+            .add_live_fetched_neuron_data(&data_type, 0, 0, b"fetched".to_vec())
+            .await?;
+        assert!(model.is_live_fetched(&data_type, 0, 0).await?);
+        model
+            .replace_data(&data_type, Index::Neuron(0, 0), b"curated".to_vec())
+            .await?;
+        assert!(!model.is_live_fetched(&data_type, 0, 0).await?);
+        assert_eq!(
+            model.neuron_data(&data_type, 0, 0).await?,
+            Some(b"curated".to_vec())
+        );
+        Ok(())
+    }
 }
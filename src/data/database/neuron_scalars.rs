@@ -0,0 +1,469 @@
+//! Scalar attributes of neurons, such as explanation scores, activation ranges and graph sizes.
+//!
+//! The scalars are extracted from neuron data as it is added and kept in the `neuron_scalar`
+//! table, so neurons can be filtered and sorted by them without decoding the data of every
+//! neuron.
+
+use std::{collections::HashMap, ops::RangeInclusive};
+
+use anyhow::{Context, Result};
+use rusqlite::{params_from_iter, types::Value, OptionalExtension, Transaction};
+
+use super::{data_types::DataType, ModelHandle};
+use crate::data::{
+    data_objects::{
+        DataObject, Graph, JsonData, NeuronExplainerPage, NeuronGraphNode, NeuronQueryPage,
+        NeuronScalars, NeuroscopeNeuronPage,
+    },
+    NeuronAttribute, NeuronFilter, NeuronOrder,
+};
+
+/// The named scalars in the neuron data of the data type.
+fn scalars(data_type: &DataType, data: &[u8]) -> Result<Vec<(String, f64)>> {
+    let scalars = match data_type {
+        DataType::NeuronExplainer => {
+            let page = NeuronExplainerPage::from_binary(data)?;
+            vec![("score".to_owned(), page.score() as f64)]
+        }
+        DataType::Neuroscope => {
+            let page = NeuroscopeNeuronPage::from_binary(data)?;
+            if page.texts().is_empty() {
+                vec![]
+            } else {
+                let max_activation = page
+                    .texts()
+                    .iter()
+                    .map(|text| text.max_activation())
+                    .fold(f32::NEG_INFINITY, f32::max) as f64;
+                let min_activation = page
+                    .texts()
+                    .iter()
+                    .map(|text| text.min_activation())
+                    .fold(f32::INFINITY, f32::min) as f64;
+                vec![
+                    ("max_activation".to_owned(), max_activation),
+                    ("min_activation".to_owned(), min_activation),
+                    (
+                        "activation_range".to_owned(),
+                        max_activation - min_activation,
+                    ),
+                ]
+            }
+        }
+        DataType::Neuron2Graph => {
+            let graph = Graph::from_binary(data)?;
+            vec![
+                ("nodes".to_owned(), graph.num_nodes() as f64),
+                ("edges".to_owned(), graph.num_edges() as f64),
+            ]
+        }
+        DataType::Json => {
+            let JsonData { value } = JsonData::from_binary(data)?;
+            value
+                .as_object()
+                .into_iter()
+                .flatten()
+                .filter_map(|(name, value)| Some((name.clone(), value.as_f64()?)))
+                .collect()
+        }
+        DataType::NeuronClusters => {
+            let node = NeuronGraphNode::from_binary(data)?;
+            node.cluster
+                .map(|cluster| ("cluster".to_owned(), cluster as f64))
+                .into_iter()
+                .chain([
+                    ("degree".to_owned(), node.degree as f64),
+                    ("strength".to_owned(), node.strength as f64),
+                    ("pagerank".to_owned(), node.pagerank as f64),
+                    ("participation".to_owned(), node.participation as f64),
+                    (
+                        "within_cluster_degree".to_owned(),
+                        node.within_cluster_degree as f64,
+                    ),
+                    ("hub".to_owned(), if node.role.is_hub() { 1. } else { 0. }),
+                ])
+                .collect()
+        }
+        DataType::NeuronStore { .. }
+        | DataType::NeuronCorrespondence
+        | DataType::Embedding { .. } => vec![],
+    };
+    Ok(scalars)
+}
+
+/// Replaces the scalars of the neuron with those in neuron data that was just added.
+pub(super) fn store_scalars(
+    transaction: &Transaction,
+    model_id: i64,
+    data_type_id: i64,
+    data_type: &DataType,
+    layer_index: u32,
+    neuron_index: u32,
+    data: &[u8],
+) -> Result<()> {
+    const DELETE_SCALARS: &str = r#"
+    DELETE FROM neuron_scalar
+    WHERE model_id = ?1 AND data_type_id = ?2 AND layer_index = ?3 AND neuron_index = ?4;
+    "#;
+    const ADD_SCALAR: &str = r#"
+    INSERT INTO neuron_scalar (
+        model_id,
+        data_type_id,
+        layer_index,
+        neuron_index,
+        name,
+        value
+    ) VALUES (
+        ?1,
+        ?2,
+        ?3,
+        ?4,
+        ?5,
+        ?6
+    );
+    "#;
+
+    let index = (model_id, data_type_id, layer_index, neuron_index);
+    transaction.prepare_cached(DELETE_SCALARS)?.execute(index)?;
+    let scalars = scalars(data_type, data).unwrap_or_else(|error| {
+        log::warn!(
+            "Failed to extract scalars of neuron l{layer_index}n{neuron_index}. Error: {error:#}"
+        );
+        vec![]
+    });
+    let mut statement = transaction.prepare_cached(ADD_SCALAR)?;
+    for (name, value) in scalars.into_iter().filter(|(_, value)| value.is_finite()) {
+        statement.execute((index.0, index.1, index.2, index.3, name, value))?;
+    }
+    Ok(())
+}
+
+/// Parameters of a query filtering and sorting the neurons of a model by their scalars.
+pub struct NeuronQuery {
+    pub filter: Option<NeuronFilter>,
+    /// Neurons are ordered by the sort keys, then by their index. Neurons missing a sort key come
+    /// last.
+    pub order: NeuronOrder,
+    pub layers: Option<RangeInclusive<u32>>,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+/// Error for a neuron query referring to a data object that does not exist.
+#[derive(Debug, thiserror::Error)]
+#[error("No data object with name '{0}'.")]
+pub struct UnknownDataType(pub String);
+
+/// Builds SQL expressions for a neuron query, collecting their parameters.
+struct QueryBuilder {
+    data_type_ids: HashMap<String, i64>,
+    params: Vec<Value>,
+}
+
+impl QueryBuilder {
+    fn param(&mut self, value: impl Into<Value>) -> String {
+        self.params.push(value.into());
+        format!("?{}", self.params.len())
+    }
+
+    /// The value of the attribute for neuron `n`, or null if the neuron does not have it.
+    fn attribute(&mut self, attribute: &NeuronAttribute) -> String {
+        match attribute {
+            NeuronAttribute::Layer => "n.layer_index".to_owned(),
+            NeuronAttribute::Neuron => "n.neuron_index".to_owned(),
+            NeuronAttribute::Scalar { data_type, name } => {
+                let data_type_id = self.param(self.data_type_ids[data_type]);
+                let name = self.param(name.clone());
+                format!(
+                    "(SELECT s.value FROM neuron_scalar AS s WHERE s.model_id = n.model_id AND \
+                     s.layer_index = n.layer_index AND s.neuron_index = n.neuron_index AND \
+                     s.data_type_id = {data_type_id} AND s.name = {name})"
+                )
+            }
+        }
+    }
+
+    /// A condition true for the neurons matching the filter. Comparisons with attributes a neuron
+    /// does not have are null, so such neurons do not match them.
+    fn filter(&mut self, filter: &NeuronFilter) -> String {
+        let combine = |builder: &mut Self, filters: &[NeuronFilter], connective: &str| {
+            let conditions: Vec<_> = filters
+                .iter()
+                .map(|filter| builder.filter(filter))
+                .collect();
+            format!("({})", conditions.join(connective))
+        };
+        match filter {
+            NeuronFilter::Compare {
+                attribute,
+                comparison,
+                value,
+            } => {
+                let attribute = self.attribute(attribute);
+                let value = self.param(*value);
+                format!("{attribute} {} {value}", comparison.operator())
+            }
+            NeuronFilter::And(filters) => combine(self, filters, " AND "),
+            NeuronFilter::Or(filters) => combine(self, filters, " OR "),
+        }
+    }
+}
+
+impl ModelHandle {
+    /// Finds the neurons matching the query's filter among the neurons with any scalars, and
+    /// returns the requested page of them in the query's order.
+    pub async fn query_neurons(&self, query: &NeuronQuery) -> Result<NeuronQueryPage> {
+        const GET_DATA_TYPE_ID: &str = r#"
+        SELECT id FROM data_type WHERE name = ?1;
+        "#;
+        const NEURONS: &str = r#"
+        FROM (
+            SELECT DISTINCT model_id, layer_index, neuron_index
+            FROM neuron_scalar
+            WHERE model_id = ?1
+        ) AS n
+        WHERE $CONDITIONS
+        "#;
+        const GET_SCALARS: &str = r#"
+        SELECT data_type.name, neuron_scalar.name, neuron_scalar.value
+        FROM neuron_scalar
+        JOIN data_type ON data_type.id = neuron_scalar.data_type_id
+        WHERE neuron_scalar.model_id = ?1
+            AND neuron_scalar.layer_index = ?2
+            AND neuron_scalar.neuron_index = ?3;
+        "#;
+
+        let mut data_type_names: Vec<String> = query
+            .filter
+            .iter()
+            .flat_map(NeuronFilter::attributes)
+            .chain(query.order.0.iter().map(|key| &key.attribute))
+            .filter_map(|attribute| match attribute {
+                NeuronAttribute::Scalar { data_type, .. } => Some(data_type.clone()),
+                _ => None,
+            })
+            .collect();
+        data_type_names.sort();
+        data_type_names.dedup();
+        let data_type_ids = self
+            .database()
+            .connection
+            .call(move |connection| {
+                let mut statement = connection.prepare(GET_DATA_TYPE_ID)?;
+                data_type_names
+                    .into_iter()
+                    .map(|name| {
+                        let id = statement
+                            .query_row((&name,), |row| row.get::<_, i64>(0))
+                            .optional()?;
+                        Ok((name, id))
+                    })
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await
+            .context("Failed to look up data objects of neuron query.")?;
+        let data_type_ids = data_type_ids
+            .into_iter()
+            .map(|(name, id)| id.map(|id| (name.clone(), id)).ok_or(UnknownDataType(name)))
+            .collect::<Result<HashMap<_, _>, _>>()?;
+
+        let mut builder = QueryBuilder {
+            data_type_ids,
+            params: vec![Value::from(self.id())],
+        };
+        let mut conditions = vec![];
+        if let Some(layers) = &query.layers {
+            let start = builder.param(*layers.start());
+            let end = builder.param(*layers.end());
+            conditions.push(format!("n.layer_index BETWEEN {start} AND {end}"));
+        }
+        if let Some(filter) = &query.filter {
+            conditions.push(builder.filter(filter));
+        }
+        if conditions.is_empty() {
+            conditions.push("TRUE".to_owned());
+        }
+        let neurons = NEURONS.replace("$CONDITIONS", &conditions.join(" AND "));
+        let count_sql = format!("SELECT COUNT(*) {neurons};");
+        let count_params = builder.params.clone();
+
+        let order = query
+            .order
+            .0
+            .iter()
+            .map(|key| {
+                let direction = if key.descending { "DESC" } else { "ASC" };
+                format!(
+                    "{} {direction} NULLS LAST",
+                    builder.attribute(&key.attribute)
+                )
+            })
+            .chain([
+                "n.layer_index ASC".to_owned(),
+                "n.neuron_index ASC".to_owned(),
+            ])
+            .collect::<Vec<_>>()
+            .join(", ");
+        let limit = builder.param(query.limit as i64);
+        let offset = builder.param(query.offset as i64);
+        let page_sql = format!(
+            "SELECT n.layer_index, n.neuron_index {neurons} ORDER BY {order} LIMIT {limit} OFFSET \
+             {offset};"
+        );
+        let page_params = builder.params;
+
+        let model_id = self.id();
+        let (total, neurons) = self
+            .database()
+            .connection
+            .call(move |connection| {
+                let total: u64 =
+                    connection
+                        .query_row(&count_sql, params_from_iter(count_params), |row| row.get(0))?;
+                let indices = connection
+                    .prepare(&page_sql)?
+                    .query_map(params_from_iter(page_params), |row| {
+                        Ok((row.get::<_, u32>(0)?, row.get::<_, u32>(1)?))
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                let mut statement = connection.prepare(GET_SCALARS)?;
+                let neurons = indices
+                    .into_iter()
+                    .map(|(layer, neuron)| {
+                        let scalars = statement
+                            .query_map((model_id, layer, neuron), |row| {
+                                Ok((
+                                    format!(
+                                        "{}.{}",
+                                        row.get::<_, String>(0)?,
+                                        row.get::<_, String>(1)?
+                                    ),
+                                    row.get::<_, f64>(2)?,
+                                ))
+                            })?
+                            .collect::<rusqlite::Result<_>>()?;
+                        Ok(NeuronScalars {
+                            layer,
+                            neuron,
+                            scalars,
+                        })
+                    })
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok((total, neurons))
+            })
+            .await
+            .with_context(|| format!("Failed to query neurons of model '{}'.", self.name()))?;
+
+        Ok(NeuronQueryPage {
+            total,
+            offset: query.offset as u64,
+            neurons,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{
+        data::{database::test_util::add_test_model, Database},
+        Index,
+    };
+
+    #[tokio::test]
+    async fn queries_neurons() -> Result<()> {
+        let database = Database::initialize_in_memory().await?;
+        let mut model = add_test_model(&database, 2, 3).await?;
+        let data_type = database.add_data_type("stats", DataType::Json).await?;
+        model.add_data_type(&data_type).await?;
+
+        for (layer, neuron, score, nodes) in [
+            (0, 0, 0.2, 5),
+            (0, 1, 0.9, 1),
+            (0, 2, 0.7, 4),
+            (1, 0, 0.8, 3),
+            (1, 1, 0.1, 7),
+        ] {
+            let data = JsonData::new(serde_json::json!({
+                "score": score,
+                "nodes": nodes,
+                "explanation": "not a number",
+            }));
+            model
+                .add_data(&data_type, Index::Neuron(layer, neuron), data.to_binary()?)
+                .await?;
+        }
+        let data = JsonData::new(serde_json::json!({ "nodes": 9 }));
+        model
+            .replace_data(&data_type, Index::Neuron(1, 1), data.to_binary()?)
+            .await?;
+
+        let search = |filter: &str, order: &str, layers, offset| {
+            let query = NeuronQuery {
+                filter: (!filter.is_empty()).then(|| filter.parse().unwrap()),
+                order: if order.is_empty() {
+                    NeuronOrder::default()
+                } else {
+                    order.parse().unwrap()
+                },
+                layers,
+                limit: 2,
+                offset,
+            };
+            let model = model.clone();
+            async move {
+                let page = model.query_neurons(&query).await?;
+                Ok::<_, anyhow::Error>((
+                    page.total,
+                    page.neurons
+                        .into_iter()
+                        .map(|found| (found.layer, found.neuron))
+                        .collect::<Vec<_>>(),
+                ))
+            }
+        };
+
+        assert_eq!(
+            search("stats.score > 0.5", "-stats.score", None, 0).await?,
+            (3, vec![(0, 1), (1, 0)])
+        );
+        assert_eq!(
+            search("stats.score > 0.5", "-stats.score", None, 2).await?,
+            (3, vec![(0, 2)])
+        );
+        assert_eq!(
+            search(
+                "stats.score > 0.5 AND stats.nodes >= 3 OR stats.nodes > 8",
+                "stats.score",
+                None,
+                0
+            )
+            .await?,
+            (3, vec![(0, 2), (1, 0)])
+        );
+        assert_eq!(
+            search("", "-stats.score", Some(1..=1), 0).await?,
+            (2, vec![(1, 0), (1, 1)])
+        );
+        let page = model
+            .query_neurons(&NeuronQuery {
+                filter: Some("layer = 1 AND neuron = 1".parse()?),
+                order: NeuronOrder::default(),
+                layers: None,
+                limit: 10,
+                offset: 0,
+            })
+            .await?;
+        assert_eq!(
+            page.neurons[0].scalars,
+            BTreeMap::from([("stats.nodes".to_owned(), 9.)])
+        );
+        assert!(search("missing.score > 0", "", None, 0)
+            .await
+            .unwrap_err()
+            .is::<UnknownDataType>());
+        Ok(())
+    }
+}
@@ -0,0 +1,285 @@
+//! Summaries of neurons drawn from the derived tables, cheap enough to fetch for many neurons at
+//! once.
+
+use anyhow::{Context, Result};
+use rusqlite::OptionalExtension;
+
+use super::ModelHandle;
+use crate::data::{
+    data_objects::{NeuronSummary, TopToken},
+    NeuronIndex, SimilarNeurons,
+};
+
+/// Number of max-activating tokens in a summary.
+const NUM_TOP_TOKENS: usize = 5;
+/// Number of similar neurons in a summary.
+const NUM_SIMILAR: usize = 3;
+
+/// The parts of a summary read from the database, before the similar neurons are decoded.
+struct RawSummary {
+    neuron_index: NeuronIndex,
+    top_tokens: Vec<TopToken>,
+    explanation: Option<(String, Option<f64>)>,
+    max_activation: Option<f64>,
+    graph_nodes: Option<f64>,
+    similar: Option<Vec<u8>>,
+}
+
+impl ModelHandle {
+    /// Summarises each of the neurons, in the order given. Neurons without data get empty
+    /// summaries.
+    pub async fn neuron_summaries(&self, neurons: Vec<NeuronIndex>) -> Result<Vec<NeuronSummary>> {
+        // The derived tables are keyed by data object before neuron, so each query goes through
+        // the data objects of the model. `CROSS JOIN` keeps SQLite from reading the rows of the
+        // whole model instead.
+        const TOP_TOKENS: &str = r#"
+        SELECT
+            context_token.token,
+            COUNT(*) AS count,
+            MAX(context_token.activation) AS max_activation
+        FROM model_data_type
+        CROSS JOIN context_token
+            ON context_token.model_id = model_data_type.model_id
+            AND context_token.data_type_id = model_data_type.data_type_id
+        WHERE model_data_type.model_id = ?1
+            AND context_token.layer_index = ?2
+            AND context_token.neuron_index = ?3
+            AND context_token.position = 0
+        GROUP BY context_token.token
+        ORDER BY count DESC, max_activation DESC, context_token.token
+        LIMIT ?4;
+        "#;
+        const EXPLANATION: &str = r#"
+        SELECT explanation.explanation, explanation.score
+        FROM model_data_type
+        CROSS JOIN explanation
+            ON explanation.model_id = model_data_type.model_id
+            AND explanation.data_type_id = model_data_type.data_type_id
+        WHERE model_data_type.model_id = ?1
+            AND explanation.layer_index = ?2
+            AND explanation.neuron_index = ?3
+        ORDER BY explanation.score IS NULL, explanation.score DESC
+        LIMIT 1;
+        "#;
+        // Scalars are only taken from data objects of the type that defines them, so JSON data
+        // with fields of the same name is not mistaken for them. Their table is keyed by neuron
+        // before data object.
+        const SCALAR: &str = r#"
+        SELECT MAX(neuron_scalar.value)
+        FROM neuron_scalar
+        JOIN data_type ON data_type.id = neuron_scalar.data_type_id
+        WHERE neuron_scalar.model_id = ?1
+            AND neuron_scalar.layer_index = ?2
+            AND neuron_scalar.neuron_index = ?3
+            AND data_type.type = ?4
+            AND neuron_scalar.name = ?5;
+        "#;
+        const SIMILAR: &str = r#"
+        SELECT neuron_data.data
+        FROM data_type
+        CROSS JOIN neuron_data ON neuron_data.data_type_id = data_type.id
+        WHERE neuron_data.model_id = ?1
+            AND neuron_data.layer_index = ?2
+            AND neuron_data.neuron_index = ?3
+            AND data_type.type = 'NeuronStore'
+        LIMIT 1;
+        "#;
+
+        let model_id = self.id();
+        let raw_summaries = self
+            .database()
+            .connection
+            .call(move |connection| {
+                let mut top_tokens = connection.prepare(TOP_TOKENS)?;
+                let mut explanation = connection.prepare(EXPLANATION)?;
+                let mut scalar = connection.prepare(SCALAR)?;
+                let mut similar = connection.prepare(SIMILAR)?;
+                neurons
+                    .into_iter()
+                    .map(|neuron_index| {
+                        let index = (model_id, neuron_index.layer, neuron_index.neuron);
+                        let mut scalar = |data_type: &str, name: &str| {
+                            scalar.query_row((index.0, index.1, index.2, data_type, name), |row| {
+                                row.get(0)
+                            })
+                        };
+                        Ok(RawSummary {
+                            neuron_index,
+                            top_tokens: top_tokens
+                                .query_map(
+                                    (index.0, index.1, index.2, NUM_TOP_TOKENS as i64),
+                                    |row| {
+                                        Ok(TopToken {
+                                            token: row.get(0)?,
+                                            count: row.get(1)?,
+                                            max_activation: row.get::<_, f64>(2)? as f32,
+                                        })
+                                    },
+                                )?
+                                .collect::<rusqlite::Result<_>>()?,
+                            explanation: explanation
+                                .query_row(index, |row| Ok((row.get(0)?, row.get(1)?)))
+                                .optional()?,
+                            max_activation: scalar("Neuroscope", "max_activation")?,
+                            graph_nodes: scalar("Neuron2Graph", "nodes")?,
+                            similar: similar.query_row(index, |row| row.get(0)).optional()?,
+                        })
+                    })
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await
+            .with_context(|| format!("Failed to summarise neurons of model '{}'.", self.name()))?;
+
+        raw_summaries
+            .into_iter()
+            .map(|raw| {
+                let NeuronIndex { layer, neuron } = raw.neuron_index;
+                let similar = match raw.similar {
+                    Some(data) => SimilarNeurons::from_binary(&data).with_context(|| {
+                        format!(
+                            "Failed to deserialize similar neurons of neuron l{layer}n{neuron}."
+                        )
+                    })?,
+                    None => SimilarNeurons::default(),
+                };
+                let (explanation, score) = raw.explanation.unzip();
+                Ok(NeuronSummary {
+                    layer,
+                    neuron,
+                    top_tokens: raw.top_tokens,
+                    explanation,
+                    score: score.flatten().map(|score| score as f32),
+                    max_activation: raw.max_activation.map(|activation| activation as f32),
+                    graph_nodes: raw.graph_nodes.map(|nodes| nodes as u32),
+                    similar: similar
+                        .similar_neurons()
+                        .iter()
+                        .take(NUM_SIMILAR)
+                        .copied()
+                        .collect(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        data::{
+            data_objects::{DataObject, JsonData},
+            data_types::DataType,
+            database::test_util::{add_test_model, neuroscope_page, neuroscope_text},
+            retrieve::{
+                neuron2graph::store_neuron2graph_graph, neuron_store::store_raw_neuron_store,
+            },
+            Database, NeuronStoreRaw, SimilarityConfig, SimilarityMetric,
+        },
+        Index,
+    };
+
+    #[tokio::test]
+    async fn summarises_neurons() -> Result<()> {
+        let database = Database::initialize_in_memory().await?;
+        let mut model = add_test_model(&database, 1, 3).await?;
+
+        let neuroscope = database
+            .add_data_type("neuroscope", DataType::Neuroscope)
+            .await?;
+        model.add_data_type(&neuroscope).await?;
+        let page = neuroscope_page(
+            0,
+            0,
+            vec![
+                neuroscope_text(1, &[" of", " the"], 1, 4.),
+                neuroscope_text(2, &[" a", " dog"], 1, 3.),
+                neuroscope_text(3, &[" the", " cat"], 0, 2.),
+            ],
+        )?;
+        model
+            .add_data(&neuroscope, Index::Neuron(0, 0), page.to_binary()?)
+            .await?;
+
+        for (explanation, score) in [("articles", 0.7), ("nouns", 0.2)] {
+            let data = JsonData::new(serde_json::json!({
+                "explanation": explanation,
+                "score": score,
+                "max_activation": 100.,
+            }));
+            let data_type = database.add_data_type(explanation, DataType::Json).await?;
+            model.add_data_type(&data_type).await?;
+            model
+                .add_data(&data_type, Index::Neuron(0, 0), data.to_binary()?)
+                .await?;
+        }
+
+        let graph = r##"digraph { subgraph cluster_0 {
+            0 [label="the", fillcolor="#ff00ff"];
+            1 [label="of", fillcolor="#ff80ff"];
+        } }"##;
+        store_neuron2graph_graph(
+            &mut model,
+            NeuronIndex {
+                layer: 0,
+                neuron: 0,
+            },
+            graph,
+        )
+        .await?;
+
+        let store: NeuronStoreRaw = serde_json::from_value(serde_json::json!({
+            "activating": { "the": ["0_0", "0_1", "0_2"], "cat": ["0_0", "0_2"] },
+            "important": {},
+        }))?;
+        let config = SimilarityConfig {
+            metric: SimilarityMetric::Jaccard,
+            threshold: None,
+            top_k: Some(5),
+            threads: None,
+            min_hash: None,
+        };
+        store_raw_neuron_store(&database, &mut model, store, &config).await?;
+
+        let summaries = model
+            .neuron_summaries(vec![
+                NeuronIndex {
+                    layer: 0,
+                    neuron: 1,
+                },
+                NeuronIndex {
+                    layer: 0,
+                    neuron: 0,
+                },
+            ])
+            .await?;
+        let [other, summary] = summaries.as_slice() else {
+            panic!("Expected two summaries.")
+        };
+        assert_eq!((summary.layer, summary.neuron), (0, 0));
+        let top_tokens: Vec<_> = summary
+            .top_tokens
+            .iter()
+            .map(|token| (token.token.as_str(), token.count))
+            .collect();
+        assert_eq!(top_tokens, vec![(" the", 2), (" dog", 1)]);
+        assert_eq!(summary.explanation.as_deref(), Some("articles"));
+        assert_eq!(summary.score, Some(0.7));
+        assert_eq!(summary.max_activation, Some(4.));
+        assert_eq!(summary.graph_nodes, Some(2));
+        assert_eq!(
+            summary
+                .similar
+                .iter()
+                .map(|similar| similar.neuron)
+                .collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+
+        assert_eq!((other.layer, other.neuron), (0, 1));
+        assert!(other.top_tokens.is_empty());
+        assert!(other.explanation.is_none() && other.max_activation.is_none());
+        assert!(other.graph_nodes.is_none());
+        Ok(())
+    }
+}
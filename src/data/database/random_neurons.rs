@@ -0,0 +1,263 @@
+//! Reproducible random samples of the neurons of a model.
+//!
+//! Neurons are sampled without replacement by weighted reservoir sampling: each candidate gets the
+//! key `ln(u) / weight` for a number `u` in (0, 1) hashed from the seed and the neuron's index,
+//! and the candidates with the largest keys are picked. As the key of a neuron only depends on the
+//! seed, its index and its weight, a seed gives the same neurons however the candidates are found.
+
+use std::{collections::HashSet, ops::RangeInclusive};
+
+use anyhow::{bail, Context, Result};
+use rusqlite::OptionalExtension;
+
+use super::{neuron_scalars::UnknownDataType, DataTypeHandle, ModelHandle};
+use crate::data::{
+    data_objects::{RandomNeuron, RandomNeurons},
+    neuron_similarity::splitmix64,
+    NeuronAttribute, NeuronIndex,
+};
+
+/// Parameters of a random sample of neurons.
+pub struct RandomNeuronQuery {
+    pub seed: u64,
+    pub count: usize,
+    pub layers: Option<RangeInclusive<u32>>,
+    /// A scalar to weight neurons by. Neurons without it, or with a value that is not positive,
+    /// are never sampled. Without a weight all neurons are equally likely.
+    pub weight: Option<NeuronAttribute>,
+    /// Data types the neurons must have. Data types the model stores no neuron data for, such as
+    /// those only holding model data, only require that the model has them.
+    pub data_types: Vec<DataTypeHandle>,
+}
+
+/// A number in the open interval (0, 1) determined by the seed and the neuron.
+fn uniform(seed: u64, flat_index: usize) -> f64 {
+    let hash = splitmix64(seed ^ splitmix64(flat_index as u64));
+    ((hash >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+}
+
+impl ModelHandle {
+    /// The neurons of the model with data for the data type, or `None` if it has no neuron data
+    /// for it.
+    async fn neurons_with_data(
+        &self,
+        data_type: &DataTypeHandle,
+    ) -> Result<Option<HashSet<NeuronIndex>>> {
+        const GET_NEURONS: &str = r#"
+        SELECT layer_index, neuron_index
+        FROM neuron_data
+        WHERE model_id = ?1 AND data_type_id = ?2;
+        "#;
+
+        let params = (self.id(), data_type.id());
+        let neurons = self
+            .database()
+            .connection
+            .call(move |connection| {
+                connection
+                    .prepare(GET_NEURONS)?
+                    .query_map(params, |row| {
+                        Ok(NeuronIndex {
+                            layer: row.get(0)?,
+                            neuron: row.get(1)?,
+                        })
+                    })?
+                    .collect::<rusqlite::Result<HashSet<_>>>()
+            })
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to get neurons of model '{}' with data for data object '{}'.",
+                    self.name(),
+                    data_type.name()
+                )
+            })?;
+        Ok((!neurons.is_empty()).then_some(neurons))
+    }
+
+    /// The neurons with a positive value of the scalar, along with the value.
+    async fn weighted_neurons(
+        &self,
+        data_type_name: &str,
+        name: &str,
+    ) -> Result<Vec<(NeuronIndex, f64)>> {
+        const GET_DATA_TYPE_ID: &str = r#"
+        SELECT id FROM data_type WHERE name = ?1;
+        "#;
+        const GET_WEIGHTS: &str = r#"
+        SELECT layer_index, neuron_index, value
+        FROM neuron_scalar
+        WHERE model_id = ?1 AND data_type_id = ?2 AND name = ?3 AND value > 0;
+        "#;
+
+        let model_id = self.id();
+        let owned_data_type_name = data_type_name.to_owned();
+        let owned_name = name.to_owned();
+        self.database()
+            .connection
+            .call(move |connection| {
+                let Some(data_type_id) = connection
+                    .query_row(GET_DATA_TYPE_ID, (&owned_data_type_name,), |row| {
+                        row.get::<_, i64>(0)
+                    })
+                    .optional()?
+                else {
+                    return Ok(None);
+                };
+                connection
+                    .prepare(GET_WEIGHTS)?
+                    .query_map((model_id, data_type_id, &owned_name), |row| {
+                        Ok((
+                            NeuronIndex {
+                                layer: row.get(0)?,
+                                neuron: row.get(1)?,
+                            },
+                            row.get::<_, f64>(2)?,
+                        ))
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+                    .map(Some)
+            })
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to get '{data_type_name}.{name}' of neurons in model '{}'.",
+                    self.name()
+                )
+            })?
+            .ok_or_else(|| UnknownDataType(data_type_name.to_owned()).into())
+    }
+
+    /// Samples neurons of the model at random, the neuron with the largest key first. Fails with
+    /// [`UnknownDataType`] if the weight refers to a data object that does not exist.
+    pub async fn random_neurons(&self, query: &RandomNeuronQuery) -> Result<RandomNeurons> {
+        let metadata = self.metadata();
+        let layers = query
+            .layers
+            .clone()
+            .unwrap_or(0..=metadata.num_layers.saturating_sub(1));
+
+        let mut candidates: Vec<(NeuronIndex, Option<f64>)> = match &query.weight {
+            Some(NeuronAttribute::Scalar { data_type, name }) => self
+                .weighted_neurons(data_type, name)
+                .await?
+                .into_iter()
+                .map(|(neuron, weight)| (neuron, Some(weight)))
+                .collect(),
+            Some(attribute) => bail!("Neurons can only be weighted by scalars, not '{attribute}'."),
+            None => NeuronIndex::iter(metadata.num_layers, metadata.layer_size)
+                .map(|neuron| (neuron, None))
+                .collect(),
+        };
+        candidates.retain(|(neuron, _)| layers.contains(&neuron.layer));
+        for data_type in &query.data_types {
+            if !self.has_data_type(data_type).await? {
+                candidates.clear();
+            } else if let Some(neurons) = self.neurons_with_data(data_type).await? {
+                candidates.retain(|(neuron, _)| neurons.contains(neuron));
+            }
+        }
+
+        let mut keyed: Vec<(f64, NeuronIndex, Option<f64>)> = candidates
+            .iter()
+            .map(|&(neuron, weight)| {
+                let u = uniform(query.seed, neuron.flat_index(metadata.layer_size));
+                (u.ln() / weight.unwrap_or(1.), neuron, weight)
+            })
+            .collect();
+        keyed.sort_unstable_by(|(key_a, neuron_a, _), (key_b, neuron_b, _)| {
+            key_b
+                .total_cmp(key_a)
+                .then((neuron_a.layer, neuron_a.neuron).cmp(&(neuron_b.layer, neuron_b.neuron)))
+        });
+        keyed.truncate(query.count);
+
+        Ok(RandomNeurons {
+            seed: query.seed,
+            num_candidates: candidates.len() as u64,
+            neurons: keyed
+                .into_iter()
+                .map(|(_, NeuronIndex { layer, neuron }, weight)| RandomNeuron {
+                    layer,
+                    neuron,
+                    weight,
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        data::{
+            data_objects::{DataObject, JsonData},
+            data_types::DataType,
+            database::test_util::add_test_model,
+            Database,
+        },
+        Index,
+    };
+
+    #[tokio::test]
+    async fn samples_neurons_reproducibly() -> Result<()> {
+        let database = Database::initialize_in_memory().await?;
+        let mut model = add_test_model(&database, 2, 50).await?;
+        let scores = database.add_data_type("scores", DataType::Json).await?;
+        model.add_data_type(&scores).await?;
+        // Only the neurons of layer 1 with an even index have a positive score.
+        for neuron in 0..50 {
+            let score = if neuron % 2 == 0 { neuron as f64 } else { 0. };
+            let data = JsonData::new(serde_json::json!({ "score": score }));
+            model
+                .add_data(&scores, Index::Neuron(1, neuron), data.to_binary()?)
+                .await?;
+        }
+
+        let query =
+            |seed, weight: Option<&str>, data_types: Vec<DataTypeHandle>| RandomNeuronQuery {
+                seed,
+                count: 10,
+                layers: None,
+                weight: weight.map(|weight| weight.parse().unwrap()),
+                data_types,
+            };
+        let indices = |sample: &RandomNeurons| -> Vec<(u32, u32)> {
+            sample
+                .neurons
+                .iter()
+                .map(|neuron| (neuron.layer, neuron.neuron))
+                .collect()
+        };
+
+        let sample = model.random_neurons(&query(7, None, vec![])).await?;
+        assert_eq!(sample.num_candidates, 100);
+        assert_eq!(sample.neurons.len(), 10);
+        let again = model.random_neurons(&query(7, None, vec![])).await?;
+        assert_eq!(indices(&sample), indices(&again));
+        let other = model.random_neurons(&query(8, None, vec![])).await?;
+        assert_ne!(indices(&sample), indices(&other));
+
+        let with_data = model
+            .random_neurons(&query(7, None, vec![scores.clone()]))
+            .await?;
+        assert_eq!(with_data.num_candidates, 50);
+        assert!(with_data.neurons.iter().all(|neuron| neuron.layer == 1));
+
+        let weighted = model
+            .random_neurons(&query(7, Some("scores.score"), vec![]))
+            .await?;
+        assert_eq!(weighted.num_candidates, 24);
+        assert!(weighted
+            .neurons
+            .iter()
+            .all(|neuron| neuron.neuron % 2 == 0 && neuron.weight == Some(neuron.neuron as f64)));
+
+        let unknown = model
+            .random_neurons(&query(7, Some("missing.score"), vec![]))
+            .await;
+        assert!(unknown.unwrap_err().is::<UnknownDataType>());
+        Ok(())
+    }
+}
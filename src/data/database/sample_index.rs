@@ -0,0 +1,412 @@
+//! Index from dataset samples to the neurons they activate.
+//!
+//! Each text of a Neuroscope page is a sample of the training data, identified by its data index.
+//! The texts are recorded in the `sample_activation` table as pages are added, so the neurons
+//! activated by a sample, and the neurons sharing samples with a neuron, can be found without
+//! decoding every page.
+
+use std::ops::RangeInclusive;
+
+use anyhow::{Context, Result};
+use rusqlite::Transaction;
+use serde::{Deserialize, Serialize};
+
+use super::{data_types::DataType, DataTypeHandle, ModelHandle};
+use crate::data::{
+    data_objects::{DataObject, NeuroscopeNeuronPage},
+    NeuronIndex,
+};
+
+/// Replaces the samples of the neuron with the texts of Neuroscope data that was just added.
+pub(super) fn index_samples(
+    transaction: &Transaction,
+    model_id: i64,
+    data_type_id: i64,
+    data_type: &DataType,
+    layer_index: u32,
+    neuron_index: u32,
+    data: &[u8],
+) -> Result<()> {
+    const DELETE_SAMPLES: &str = r#"
+    DELETE FROM sample_activation
+    WHERE model_id = ?1 AND data_type_id = ?2 AND layer_index = ?3 AND neuron_index = ?4;
+    "#;
+    // A sample may appear more than once on a page, in which case its highest activation is kept.
+    const ADD_SAMPLE: &str = r#"
+    INSERT INTO sample_activation (
+        model_id,
+        data_type_id,
+        layer_index,
+        neuron_index,
+        data_index,
+        activation,
+        max_token_index
+    ) VALUES (
+        ?1,
+        ?2,
+        ?3,
+        ?4,
+        ?5,
+        ?6,
+        ?7
+    )
+    ON CONFLICT(model_id, data_type_id, layer_index, neuron_index, data_index)
+    DO UPDATE SET activation = excluded.activation, max_token_index = excluded.max_token_index
+    WHERE excluded.activation > activation;
+    "#;
+
+    if *data_type != DataType::Neuroscope {
+        return Ok(());
+    }
+    let index = (model_id, data_type_id, layer_index, neuron_index);
+    transaction.prepare_cached(DELETE_SAMPLES)?.execute(index)?;
+    let page = match NeuroscopeNeuronPage::from_binary(data) {
+        Ok(page) => page,
+        Err(error) => {
+            log::warn!(
+                "Failed to extract samples of neuron l{layer_index}n{neuron_index}. Error: \
+                 {error:#}"
+            );
+            return Ok(());
+        }
+    };
+    let mut statement = transaction.prepare_cached(ADD_SAMPLE)?;
+    for text in page.texts() {
+        statement.execute((
+            index.0,
+            index.1,
+            index.2,
+            index.3,
+            text.data_index(),
+            text.max_activation(),
+            text.max_activating_token_index(),
+        ))?;
+    }
+    Ok(())
+}
+
+/// A neuron activated by a dataset sample.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SampleActivation {
+    pub layer: u32,
+    pub neuron: u32,
+    pub data_type: String,
+    /// The highest activation of the neuron on the sample.
+    pub activation: f32,
+    /// Position of the token in the sample the neuron activates most on.
+    pub max_token_index: u32,
+}
+
+/// A neuron sharing max-activating samples with another neuron.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedSamples {
+    pub layer: u32,
+    pub neuron: u32,
+    /// Data indices of the samples both neurons have, in increasing order.
+    pub shared: Vec<u64>,
+    pub num_samples: u32,
+    /// Number of shared samples relative to the number of samples of either neuron.
+    pub jaccard: f32,
+}
+
+impl ModelHandle {
+    /// Finds the neurons that have the dataset sample among their max-activating examples, ordered
+    /// by decreasing activation.
+    pub async fn sample_neurons(
+        &self,
+        data_index: u64,
+        data_type: Option<&DataTypeHandle>,
+        layers: Option<RangeInclusive<u32>>,
+        limit: usize,
+    ) -> Result<Vec<SampleActivation>> {
+        const GET_SAMPLE_NEURONS: &str = r#"
+        SELECT
+            sample_activation.layer_index,
+            sample_activation.neuron_index,
+            data_type.name,
+            sample_activation.activation,
+            sample_activation.max_token_index
+        FROM sample_activation
+        JOIN data_type ON data_type.id = sample_activation.data_type_id
+        WHERE sample_activation.model_id = ?1
+            AND sample_activation.data_index = ?2
+            AND (?3 IS NULL OR sample_activation.data_type_id = ?3)
+            AND (?4 IS NULL OR sample_activation.layer_index BETWEEN ?4 AND ?5)
+        ORDER BY
+            sample_activation.activation DESC,
+            sample_activation.layer_index,
+            sample_activation.neuron_index
+        LIMIT ?6;
+        "#;
+
+        let params = (
+            self.id(),
+            data_index,
+            data_type.map(DataTypeHandle::id),
+            layers.as_ref().map(|layers| *layers.start()),
+            layers.as_ref().map(|layers| *layers.end()),
+            limit as i64,
+        );
+        self.database()
+            .connection
+            .call(move |connection| {
+                connection
+                    .prepare(GET_SAMPLE_NEURONS)?
+                    .query_map(params, |row| {
+                        Ok(SampleActivation {
+                            layer: row.get(0)?,
+                            neuron: row.get(1)?,
+                            data_type: row.get(2)?,
+                            activation: row.get::<_, f64>(3)? as f32,
+                            max_token_index: row.get(4)?,
+                        })
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to find neurons activated by sample {data_index} in model '{}'.",
+                    self.name()
+                )
+            })
+    }
+
+    /// Finds the neurons sharing max-activating samples with the neuron, ordered by decreasing
+    /// number of shared samples and then by decreasing Jaccard similarity.
+    pub async fn shared_sample_neurons(
+        &self,
+        neuron: NeuronIndex,
+        data_type: Option<&DataTypeHandle>,
+        layers: Option<RangeInclusive<u32>>,
+        limit: usize,
+    ) -> Result<Vec<SharedSamples>> {
+        const COUNT_SAMPLES: &str = r#"
+        SELECT COUNT(DISTINCT data_index)
+        FROM sample_activation
+        WHERE model_id = ?1
+            AND layer_index = ?2
+            AND neuron_index = ?3
+            AND (?4 IS NULL OR data_type_id = ?4);
+        "#;
+        const GET_SHARED_SAMPLES: &str = r#"
+        WITH own AS (
+            SELECT DISTINCT data_index
+            FROM sample_activation
+            WHERE model_id = ?1
+                AND layer_index = ?2
+                AND neuron_index = ?3
+                AND (?4 IS NULL OR data_type_id = ?4)
+        )
+        SELECT
+            other.layer_index,
+            other.neuron_index,
+            GROUP_CONCAT(DISTINCT other.data_index),
+            (
+                SELECT COUNT(DISTINCT counted.data_index)
+                FROM sample_activation AS counted
+                WHERE counted.model_id = ?1
+                    AND counted.layer_index = other.layer_index
+                    AND counted.neuron_index = other.neuron_index
+                    AND (?4 IS NULL OR counted.data_type_id = ?4)
+            )
+        FROM own
+        JOIN sample_activation AS other ON other.data_index = own.data_index
+        WHERE other.model_id = ?1
+            AND NOT (other.layer_index = ?2 AND other.neuron_index = ?3)
+            AND (?4 IS NULL OR other.data_type_id = ?4)
+            AND (?5 IS NULL OR other.layer_index BETWEEN ?5 AND ?6)
+        GROUP BY other.layer_index, other.neuron_index;
+        "#;
+
+        let NeuronIndex {
+            layer: layer_index,
+            neuron: neuron_index,
+        } = neuron;
+        let params = (
+            self.id(),
+            layer_index,
+            neuron_index,
+            data_type.map(DataTypeHandle::id),
+            layers.as_ref().map(|layers| *layers.start()),
+            layers.as_ref().map(|layers| *layers.end()),
+        );
+        let (num_samples, rows) = self
+            .database()
+            .connection
+            .call(move |connection| {
+                let num_samples: u32 = connection.query_row(
+                    COUNT_SAMPLES,
+                    (params.0, params.1, params.2, params.3),
+                    |row| row.get(0),
+                )?;
+                let rows = connection
+                    .prepare(GET_SHARED_SAMPLES)?
+                    .query_map(params, |row| {
+                        Ok((
+                            row.get::<_, u32>(0)?,
+                            row.get::<_, u32>(1)?,
+                            row.get::<_, String>(2)?,
+                            row.get::<_, u32>(3)?,
+                        ))
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok((num_samples, rows))
+            })
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to find neurons sharing samples with neuron {neuron} in model '{}'.",
+                    self.name()
+                )
+            })?;
+
+        let mut neurons = rows
+            .into_iter()
+            .map(|(layer, neuron, shared, other_num_samples)| {
+                let mut shared = shared
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<Result<Vec<u64>, _>>()
+                    .context("Failed to parse shared data indices.")?;
+                shared.sort_unstable();
+                let union = num_samples + other_num_samples - shared.len() as u32;
+                Ok(SharedSamples {
+                    layer,
+                    neuron,
+                    jaccard: shared.len() as f32 / union as f32,
+                    shared,
+                    num_samples: other_num_samples,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        neurons.sort_by(|a, b| {
+            b.shared
+                .len()
+                .cmp(&a.shared.len())
+                .then(b.jaccard.total_cmp(&a.jaccard))
+                .then((a.layer, a.neuron).cmp(&(b.layer, b.neuron)))
+        });
+        neurons.truncate(limit);
+        Ok(neurons)
+    }
+
+    /// Data indices of the neuron's max-activating samples, in increasing order.
+    pub async fn neuron_samples(&self, neuron: NeuronIndex) -> Result<Vec<u64>> {
+        const GET_SAMPLES: &str = r#"
+        SELECT DISTINCT data_index
+        FROM sample_activation
+        WHERE model_id = ?1 AND layer_index = ?2 AND neuron_index = ?3
+        ORDER BY data_index;
+        "#;
+
+        let params = (self.id(), neuron.layer, neuron.neuron);
+        self.database()
+            .connection
+            .call(move |connection| {
+                connection
+                    .prepare(GET_SAMPLES)?
+                    .query_map(params, |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to get samples of neuron {neuron} in model '{}'.",
+                    self.name()
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        data::{
+            database::test_util::{add_test_model, neuroscope_page},
+            Database,
+        },
+        Index,
+    };
+
+    #[tokio::test]
+    async fn finds_shared_samples() -> Result<()> {
+        let database = Database::initialize_in_memory().await?;
+        let mut model = add_test_model(&database, 2, 2).await?;
+        let data_type = database
+            .add_data_type("neuroscope", DataType::Neuroscope)
+            .await?;
+        model.add_data_type(&data_type).await?;
+
+        for (layer, neuron, samples) in [
+            (0, 0, vec![(1, 5.), (2, 4.), (3, 3.)]),
+            (0, 1, vec![(2, 2.), (3, 6.), (2, 2.5)]),
+            (1, 0, vec![(3, 1.), (4, 7.)]),
+            (1, 1, vec![(5, 1.)]),
+        ] {
+            let texts: Vec<_> = samples
+                .into_iter()
+                .map(|(data_index, activation): (u64, f32)| {
+                    serde_json::json!({
+                        "min_range": 0.,
+                        "max_range": activation,
+                        "min_activation": 0.,
+                        "max_activation": activation,
+                        "data_index": data_index,
+                        "max_activating_token_index": data_index * 10,
+                        "tokens": [],
+                        "activations": [],
+                    })
+                })
+                .collect();
+            let page = neuroscope_page(layer, neuron, texts)?;
+            model
+                .add_data(&data_type, Index::Neuron(layer, neuron), page.to_binary()?)
+                .await?;
+        }
+
+        let activated = model.sample_neurons(3, None, None, 10).await?;
+        assert_eq!(
+            activated
+                .iter()
+                .map(|found| (found.layer, found.neuron, found.activation))
+                .collect::<Vec<_>>(),
+            vec![(0, 1, 6.), (0, 0, 3.), (1, 0, 1.)]
+        );
+        assert_eq!(activated[0].max_token_index, 30);
+        assert_eq!(
+            model.sample_neurons(2, None, Some(1..=1), 10).await?.len(),
+            0
+        );
+        assert_eq!(
+            model.sample_neurons(2, None, None, 10).await?[1].activation,
+            2.5
+        );
+
+        let shared = model
+            .shared_sample_neurons(
+                NeuronIndex {
+                    layer: 0,
+                    neuron: 0,
+                },
+                None,
+                None,
+                10,
+            )
+            .await?;
+        assert_eq!(
+            shared
+                .iter()
+                .map(|found| (
+                    found.layer,
+                    found.neuron,
+                    found.shared.clone(),
+                    found.jaccard
+                ))
+                .collect::<Vec<_>>(),
+            vec![(0, 1, vec![2, 3], 2. / 3.), (1, 0, vec![3], 1. / 4.)]
+        );
+        Ok(())
+    }
+}
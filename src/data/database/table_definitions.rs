@@ -85,3 +85,159 @@ pub const TABLES: [&str; 7] = [
     LAYER_DATA_TABLE,
     NEURON_DATA_TABLE,
 ];
+
+const LIVE_FETCH_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS live_fetch (
+    model_id                INTEGER NOT NULL,
+    data_type_id            INTEGER NOT NULL,
+    layer_index             INTEGER NOT NULL,
+    neuron_index            INTEGER NOT NULL,
+    fetched_at              INTEGER NOT NULL,
+    PRIMARY KEY(model_id, data_type_id, layer_index, neuron_index),
+    FOREIGN KEY(model_id) REFERENCES model(id),
+    FOREIGN KEY(data_type_id) REFERENCES data_type(id)
+  ) STRICT;
+"#;
+
+const EXPLANATION_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS explanation (
+    id                      INTEGER PRIMARY KEY,
+    model_id                INTEGER NOT NULL,
+    data_type_id            INTEGER NOT NULL,
+    layer_index             INTEGER NOT NULL,
+    neuron_index            INTEGER NOT NULL,
+    explanation             TEXT NOT NULL,
+    score                   REAL,
+    UNIQUE(model_id, data_type_id, layer_index, neuron_index),
+    FOREIGN KEY(model_id) REFERENCES model(id),
+    FOREIGN KEY(data_type_id) REFERENCES data_type(id)
+  ) STRICT;
+
+CREATE VIRTUAL TABLE IF NOT EXISTS explanation_text USING fts5(
+    explanation,
+    content = 'explanation',
+    content_rowid = 'id',
+    tokenize = 'porter unicode61'
+);
+
+CREATE TRIGGER IF NOT EXISTS explanation_insert AFTER INSERT ON explanation BEGIN
+    INSERT INTO explanation_text(rowid, explanation) VALUES (new.id, new.explanation);
+END;
+
+CREATE TRIGGER IF NOT EXISTS explanation_delete AFTER DELETE ON explanation BEGIN
+    INSERT INTO explanation_text(explanation_text, rowid, explanation)
+    VALUES ('delete', old.id, old.explanation);
+END;
+
+CREATE TRIGGER IF NOT EXISTS explanation_update AFTER UPDATE ON explanation BEGIN
+    INSERT INTO explanation_text(explanation_text, rowid, explanation)
+    VALUES ('delete', old.id, old.explanation);
+    INSERT INTO explanation_text(rowid, explanation) VALUES (new.id, new.explanation);
+END;
+"#;
+
+const NEURON_SCALAR_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS neuron_scalar (
+    model_id                INTEGER NOT NULL,
+    data_type_id            INTEGER NOT NULL,
+    layer_index             INTEGER NOT NULL,
+    neuron_index            INTEGER NOT NULL,
+    name                    TEXT NOT NULL,
+    value                   REAL NOT NULL,
+    PRIMARY KEY(model_id, layer_index, neuron_index, data_type_id, name),
+    FOREIGN KEY(model_id) REFERENCES model(id),
+    FOREIGN KEY(data_type_id) REFERENCES data_type(id)
+  ) STRICT;
+
+CREATE INDEX IF NOT EXISTS neuron_scalar_value
+ON neuron_scalar(model_id, data_type_id, name, value);
+"#;
+
+const SAMPLE_ACTIVATION_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS sample_activation (
+    model_id                INTEGER NOT NULL,
+    data_type_id            INTEGER NOT NULL,
+    layer_index             INTEGER NOT NULL,
+    neuron_index            INTEGER NOT NULL,
+    data_index              INTEGER NOT NULL,
+    activation              REAL NOT NULL,
+    max_token_index         INTEGER NOT NULL,
+    PRIMARY KEY(model_id, data_type_id, layer_index, neuron_index, data_index),
+    FOREIGN KEY(model_id) REFERENCES model(id),
+    FOREIGN KEY(data_type_id) REFERENCES data_type(id)
+  ) STRICT;
+
+CREATE INDEX IF NOT EXISTS sample_activation_data_index
+ON sample_activation(model_id, data_index);
+"#;
+
+const CONTEXT_TOKEN_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS context_token (
+    model_id                INTEGER NOT NULL,
+    data_type_id            INTEGER NOT NULL,
+    layer_index             INTEGER NOT NULL,
+    neuron_index            INTEGER NOT NULL,
+    data_index              INTEGER NOT NULL,
+    position                INTEGER NOT NULL,
+    token                   TEXT NOT NULL,
+    token_key               TEXT NOT NULL,
+    activation              REAL NOT NULL,
+    PRIMARY KEY(model_id, data_type_id, layer_index, neuron_index, data_index, position),
+    FOREIGN KEY(model_id) REFERENCES model(id),
+    FOREIGN KEY(data_type_id) REFERENCES data_type(id)
+  ) STRICT;
+
+CREATE INDEX IF NOT EXISTS context_token_key
+ON context_token(model_id, token_key, position);
+
+CREATE INDEX IF NOT EXISTS context_token_exact
+ON context_token(model_id, token, position);
+"#;
+
+const TOKEN_IMPORTANCE_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS token_importance (
+    model_id                INTEGER NOT NULL,
+    data_type_id            INTEGER NOT NULL,
+    layer_index             INTEGER NOT NULL,
+    neuron_index            INTEGER NOT NULL,
+    token                   TEXT NOT NULL,
+    importance              REAL NOT NULL,
+    PRIMARY KEY(model_id, data_type_id, layer_index, neuron_index, token),
+    FOREIGN KEY(model_id) REFERENCES model(id),
+    FOREIGN KEY(data_type_id) REFERENCES data_type(id)
+  ) STRICT;
+
+CREATE INDEX IF NOT EXISTS token_importance_token
+ON token_importance(model_id, token, importance);
+"#;
+
+/// Derived tables that were created for a database that already had neuron data, and so still
+/// have to be filled from it.
+const UNFILLED_DERIVED_TABLE_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS unfilled_derived_table (
+    name                    TEXT NOT NULL PRIMARY KEY
+  ) STRICT;
+"#;
+
+/// Tables that are not needed by every database. They are created when a database is opened if
+/// they do not exist yet, so older databases keep working.
+pub const AUXILIARY_TABLES: [&str; 7] = [
+    LIVE_FETCH_TABLE,
+    UNFILLED_DERIVED_TABLE_TABLE,
+    EXPLANATION_TABLE,
+    NEURON_SCALAR_TABLE,
+    SAMPLE_ACTIVATION_TABLE,
+    CONTEXT_TOKEN_TABLE,
+    TOKEN_IMPORTANCE_TABLE,
+];
+
+/// Names of the auxiliary tables referencing models and data objects by `model_id` and
+/// `data_type_id`. Their rows are deleted along with the model or data object.
+pub const AUXILIARY_REFERENCE_TABLES: [&str; 6] = [
+    "live_fetch",
+    "explanation",
+    "neuron_scalar",
+    "sample_activation",
+    "context_token",
+    "token_importance",
+];
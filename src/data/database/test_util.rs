@@ -0,0 +1,59 @@
+//! Fixtures shared by the tests of the database.
+
+use anyhow::Result;
+
+use super::{Database, ModelHandle};
+use crate::data::{data_objects::NeuroscopeNeuronPage, Metadata};
+
+/// Adds a model named `test` with the given number of layers and neurons per layer.
+pub(crate) async fn add_test_model(
+    database: &Database,
+    num_layers: u32,
+    layer_size: u32,
+) -> Result<ModelHandle> {
+    database
+        .add_model(Metadata {
+            name: String::from("test"),
+            num_layers,
+            layer_size,
+            activation_function: String::from("test_act"),
+            num_total_neurons: num_layers * layer_size,
+            num_total_parameters: 100,
+            dataset: String::from("test_dataset"),
+        })
+        .await
+}
+
+/// A text of a Neuroscope page with the given activation on its max-activating token and 0.5 on
+/// every other token.
+pub(crate) fn neuroscope_text(
+    data_index: u64,
+    tokens: &[&str],
+    peak: usize,
+    activation: f32,
+) -> serde_json::Value {
+    let mut activations = vec![0.5; tokens.len()];
+    activations[peak] = activation;
+    serde_json::json!({
+        "min_range": 0.,
+        "max_range": activation,
+        "min_activation": 0.,
+        "max_activation": activation,
+        "data_index": data_index,
+        "max_activating_token_index": peak,
+        "tokens": tokens,
+        "activations": activations,
+    })
+}
+
+/// The Neuroscope page of a neuron with the given texts.
+pub(crate) fn neuroscope_page(
+    layer: u32,
+    neuron: u32,
+    texts: Vec<serde_json::Value>,
+) -> Result<NeuroscopeNeuronPage> {
+    Ok(serde_json::from_value(serde_json::json!({
+        "neuron_index": { "layer": layer, "neuron": neuron },
+        "texts": texts,
+    }))?)
+}
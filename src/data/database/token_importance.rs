@@ -0,0 +1,217 @@
+//! Importance of the tokens in the neuron2graph graph of each neuron.
+//!
+//! Every token in a neuron's graph is recorded in the `token_importance` table with the highest
+//! importance of any node with that token, so the neurons that consider a token most important can
+//! be found without decoding every graph.
+
+use std::ops::RangeInclusive;
+
+use anyhow::{Context, Result};
+use rusqlite::Transaction;
+use serde::{Deserialize, Serialize};
+
+use super::{data_types::DataType, ModelHandle};
+use crate::data::{
+    data_objects::{DataObject, Graph},
+    NeuronIndex,
+};
+
+/// Replaces the token importances of the neuron with those of a neuron2graph graph that was just
+/// added.
+pub(super) fn index_token_importance(
+    transaction: &Transaction,
+    model_id: i64,
+    data_type_id: i64,
+    data_type: &DataType,
+    layer_index: u32,
+    neuron_index: u32,
+    data: &[u8],
+) -> Result<()> {
+    const DELETE_IMPORTANCES: &str = r#"
+    DELETE FROM token_importance
+    WHERE model_id = ?1 AND data_type_id = ?2 AND layer_index = ?3 AND neuron_index = ?4;
+    "#;
+    const ADD_IMPORTANCE: &str = r#"
+    INSERT INTO token_importance (
+        model_id,
+        data_type_id,
+        layer_index,
+        neuron_index,
+        token,
+        importance
+    ) VALUES (
+        ?1,
+        ?2,
+        ?3,
+        ?4,
+        ?5,
+        ?6
+    );
+    "#;
+
+    if *data_type != DataType::Neuron2Graph {
+        return Ok(());
+    }
+    let index = (model_id, data_type_id, layer_index, neuron_index);
+    transaction
+        .prepare_cached(DELETE_IMPORTANCES)?
+        .execute(index)?;
+    let graph = match Graph::from_binary(data) {
+        Ok(graph) => graph,
+        Err(error) => {
+            log::warn!(
+                "Failed to extract token importances of neuron l{layer_index}n{neuron_index}. \
+                 Error: {error:#}"
+            );
+            return Ok(());
+        }
+    };
+    let mut statement = transaction.prepare_cached(ADD_IMPORTANCE)?;
+    for (token, importance) in graph.token_importances() {
+        statement.execute((index.0, index.1, index.2, index.3, token, importance as f64))?;
+    }
+    Ok(())
+}
+
+/// A neuron whose neuron2graph graph contains a token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenImportance {
+    pub layer: u32,
+    pub neuron: u32,
+    /// The highest importance of any node of the graph with the token.
+    pub importance: f32,
+}
+
+impl ModelHandle {
+    /// The neurons whose graphs contain the token, ordered by decreasing importance of the token.
+    pub async fn top_token_neurons(
+        &self,
+        token: &str,
+        layers: Option<RangeInclusive<u32>>,
+        limit: usize,
+    ) -> Result<Vec<TokenImportance>> {
+        const TOP_NEURONS: &str = r#"
+        SELECT layer_index, neuron_index, MAX(importance) AS max_importance
+        FROM token_importance
+        WHERE model_id = ?1
+            AND token = ?2
+            AND (?3 IS NULL OR layer_index BETWEEN ?3 AND ?4)
+        GROUP BY layer_index, neuron_index
+        ORDER BY max_importance DESC, layer_index, neuron_index
+        LIMIT ?5;
+        "#;
+
+        let params = (
+            self.id(),
+            token.to_owned(),
+            layers.as_ref().map(|layers| *layers.start()),
+            layers.as_ref().map(|layers| *layers.end()),
+            limit as i64,
+        );
+        self.database()
+            .connection
+            .call(move |connection| {
+                connection
+                    .prepare(TOP_NEURONS)?
+                    .query_map(params, |row| {
+                        Ok(TokenImportance {
+                            layer: row.get(0)?,
+                            neuron: row.get(1)?,
+                            importance: row.get::<_, f64>(2)? as f32,
+                        })
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to find the neurons of model '{}' that consider token '{token}' \
+                     important.",
+                    self.name()
+                )
+            })
+    }
+
+    /// The tokens of the neuron's graph with their importance, in order of the tokens.
+    pub async fn graph_tokens(&self, neuron: NeuronIndex) -> Result<Vec<(String, f32)>> {
+        const GRAPH_TOKENS: &str = r#"
+        SELECT token, MAX(importance)
+        FROM token_importance
+        WHERE model_id = ?1 AND layer_index = ?2 AND neuron_index = ?3
+        GROUP BY token
+        ORDER BY token;
+        "#;
+
+        let params = (self.id(), neuron.layer, neuron.neuron);
+        self.database()
+            .connection
+            .call(move |connection| {
+                connection
+                    .prepare(GRAPH_TOKENS)?
+                    .query_map(params, |row| {
+                        Ok((row.get(0)?, row.get::<_, f64>(1)? as f32))
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to get graph tokens of neuron {neuron} in model '{}'.",
+                    self.name()
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::{
+        database::test_util::add_test_model, retrieve::neuron2graph::store_neuron2graph_graph,
+        Database,
+    };
+
+    #[tokio::test]
+    async fn finds_important_tokens() -> Result<()> {
+        let database = Database::initialize_in_memory().await?;
+        let mut model = add_test_model(&database, 2, 2).await?;
+
+        let graph = |nodes: &[(&str, &str)]| {
+            let nodes = nodes
+                .iter()
+                .enumerate()
+                .map(|(id, (label, green))| {
+                    format!("{id} [label=\"{label}\", fillcolor=\"#ff{green}ff\"];")
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!("digraph {{ subgraph cluster_0 {{ {nodes} }} }}")
+        };
+        for (layer, neuron, nodes) in [
+            (0, 0, graph(&[("he", "00"), ("she", "ff")])),
+            (0, 1, graph(&[("she", "80"), ("she", "00")])),
+            (1, 0, graph(&[("he", "80")])),
+        ] {
+            store_neuron2graph_graph(&mut model, NeuronIndex { layer, neuron }, &nodes).await?;
+        }
+
+        let neurons = |found: Vec<TokenImportance>| {
+            found
+                .into_iter()
+                .map(|found| (found.layer, found.neuron))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            neurons(model.top_token_neurons("he", None, 10).await?),
+            vec![(0, 0), (1, 0)]
+        );
+        assert_eq!(
+            neurons(model.top_token_neurons("he", Some(1..=1), 10).await?),
+            vec![(1, 0)]
+        );
+        let she = model.top_token_neurons("she", None, 1).await?;
+        assert_eq!(neurons(she.clone()), vec![(0, 1)]);
+        assert_eq!(she[0].importance, 1.);
+        Ok(())
+    }
+}
@@ -0,0 +1,554 @@
+//! Nearest neighbour search over the embeddings of the neurons of a model.
+//!
+//! Searches over at most [`EXACT_SEARCH_LIMIT`] embeddings compare the query with every one of
+//! them. Larger searches use a hierarchical navigable small world (HNSW) graph, which is built for
+//! a metric the first time it is needed and finds most, but not always all, nearest neighbours.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap, HashSet},
+    fmt::Display,
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use anyhow::{bail, ensure, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    data_objects::EmbeddingNeighbours, neuron_similarity::splitmix64, NeuronIndex, SimilarNeuron,
+};
+
+/// Largest number of embeddings searched exhaustively.
+pub const EXACT_SEARCH_LIMIT: usize = 20_000;
+/// Neighbours of each node in the upper layers of the graph. The bottom layer has twice as many.
+const MAX_NEIGHBOURS: usize = 16;
+/// Candidates considered when connecting a node to the graph.
+const EF_CONSTRUCTION: usize = 64;
+/// Least number of candidates considered when searching the graph.
+const EF_SEARCH: usize = 64;
+/// Most candidates considered when searching the graph for neurons in a subset of the layers.
+const MAX_EF_SEARCH: usize = 4096;
+const GRAPH_SEED: u64 = 0x5eed;
+
+/// How the similarity of two embeddings is measured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EmbeddingMetric {
+    /// The cosine of the angle between the embeddings. Embeddings of length 0 have similarity 0
+    /// to all others.
+    #[default]
+    Cosine,
+    /// The dot product of the embeddings.
+    Dot,
+}
+
+impl EmbeddingMetric {
+    fn index(self) -> usize {
+        match self {
+            Self::Cosine => 0,
+            Self::Dot => 1,
+        }
+    }
+}
+
+impl FromStr for EmbeddingMetric {
+    type Err = anyhow::Error;
+
+    fn from_str(metric: &str) -> Result<Self> {
+        match metric {
+            "cosine" => Ok(Self::Cosine),
+            "dot" => Ok(Self::Dot),
+            _ => bail!("Invalid embedding metric '{metric}'. Must be 'cosine' or 'dot'."),
+        }
+    }
+}
+
+impl Display for EmbeddingMetric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cosine => write!(f, "cosine"),
+            Self::Dot => write!(f, "dot"),
+        }
+    }
+}
+
+/// A node of the graph along with its similarity to the query, ordered by similarity.
+#[derive(Debug, Clone, Copy)]
+struct Scored {
+    similarity: f32,
+    node: u32,
+}
+
+impl PartialEq for Scored {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.similarity
+            .total_cmp(&other.similarity)
+            .then(other.node.cmp(&self.node))
+    }
+}
+
+/// A query embedding along with its length.
+struct Query<'a> {
+    values: &'a [f32],
+    norm: f32,
+}
+
+/// Embeddings of the neurons of a model.
+pub struct EmbeddingIndex {
+    dimensions: usize,
+    neurons: Vec<NeuronIndex>,
+    positions: HashMap<NeuronIndex, usize>,
+    values: Vec<f32>,
+    norms: Vec<f32>,
+    /// The graph for each metric, built when first needed.
+    graphs: [OnceLock<Graph>; 2],
+}
+
+impl EmbeddingIndex {
+    pub fn new(embeddings: Vec<(NeuronIndex, Vec<f32>)>) -> Result<Self> {
+        let dimensions = embeddings.first().map_or(0, |(_, values)| values.len());
+        let mut neurons = Vec::with_capacity(embeddings.len());
+        let mut values = Vec::with_capacity(embeddings.len() * dimensions);
+        for (neuron, embedding) in embeddings {
+            ensure!(
+                embedding.len() == dimensions,
+                "Embedding of neuron {neuron} has {} dimensions, but other embeddings have \
+                 {dimensions}.",
+                embedding.len()
+            );
+            ensure!(
+                embedding.iter().all(|value| value.is_finite()),
+                "Embedding of neuron {neuron} has values that are not finite."
+            );
+            neurons.push(neuron);
+            values.extend(embedding);
+        }
+        let norms = if dimensions == 0 {
+            vec![0.; neurons.len()]
+        } else {
+            values
+                .chunks(dimensions)
+                .map(|embedding| dot(embedding, embedding).sqrt())
+                .collect()
+        };
+        let positions = neurons
+            .iter()
+            .enumerate()
+            .map(|(position, &neuron)| (neuron, position))
+            .collect();
+        Ok(Self {
+            dimensions,
+            neurons,
+            positions,
+            values,
+            norms,
+            graphs: [OnceLock::new(), OnceLock::new()],
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.neurons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.neurons.is_empty()
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// The embedding of the neuron, or `None` if it has none.
+    pub fn embedding(&self, neuron: NeuronIndex) -> Option<&[f32]> {
+        self.positions
+            .get(&neuron)
+            .map(|&position| self.values(position))
+    }
+
+    fn values(&self, node: usize) -> &[f32] {
+        &self.values[node * self.dimensions..(node + 1) * self.dimensions]
+    }
+
+    fn similarity(&self, metric: EmbeddingMetric, query: &Query, node: usize) -> f32 {
+        let dot = dot(query.values, self.values(node));
+        match metric {
+            EmbeddingMetric::Dot => dot,
+            EmbeddingMetric::Cosine => {
+                let norms = query.norm * self.norms[node];
+                if norms > 0. {
+                    dot / norms
+                } else {
+                    0.
+                }
+            }
+        }
+    }
+
+    /// The `k` neurons accepted by the filter with the embeddings most similar to the query.
+    pub fn nearest(
+        &self,
+        query: &[f32],
+        k: usize,
+        metric: EmbeddingMetric,
+        filter: impl Fn(NeuronIndex) -> bool,
+    ) -> Result<EmbeddingNeighbours> {
+        ensure!(
+            query.len() == self.dimensions,
+            "Query has {} dimensions, but the embeddings have {}.",
+            query.len(),
+            self.dimensions
+        );
+        let query = Query {
+            values: query,
+            norm: dot(query, query).sqrt(),
+        };
+        let candidates: Vec<usize> = (0..self.len())
+            .filter(|&node| filter(self.neurons[node]))
+            .collect();
+        let approximate = if candidates.len() <= EXACT_SEARCH_LIMIT {
+            None
+        } else {
+            // Only a fraction of the nodes found in the graph are accepted by the filter, so
+            // proportionally more are searched for.
+            let ef = (k.max(EF_SEARCH) * self.len() / candidates.len()).min(MAX_EF_SEARCH);
+            let found: Vec<Scored> = self
+                .graph(metric)
+                .search(self, metric, &query, ef)
+                .into_iter()
+                .filter(|scored| filter(self.neurons[scored.node as usize]))
+                .take(k)
+                .collect();
+            (found.len() >= k.min(candidates.len())).then_some(found)
+        };
+        let found = match &approximate {
+            Some(found) => found.clone(),
+            None => {
+                let mut scored: Vec<Scored> = candidates
+                    .into_iter()
+                    .map(|node| Scored {
+                        similarity: self.similarity(metric, &query, node),
+                        node: node as u32,
+                    })
+                    .collect();
+                if scored.len() > k && k > 0 {
+                    scored.select_nth_unstable_by(k - 1, |a, b| b.cmp(a));
+                }
+                scored.truncate(k);
+                scored.sort_unstable_by(|a, b| b.cmp(a));
+                scored
+            }
+        };
+        Ok(EmbeddingNeighbours {
+            approximate: approximate.is_some(),
+            neighbours: found
+                .into_iter()
+                .map(|scored| {
+                    let NeuronIndex { layer, neuron } = self.neurons[scored.node as usize];
+                    SimilarNeuron {
+                        layer,
+                        neuron,
+                        similarity: scored.similarity,
+                    }
+                })
+                .collect(),
+        })
+    }
+
+    /// The `k` other neurons accepted by the filter with the embeddings most similar to that of
+    /// the neuron, or `None` if the neuron has no embedding.
+    pub fn neighbours(
+        &self,
+        neuron: NeuronIndex,
+        k: usize,
+        metric: EmbeddingMetric,
+        filter: impl Fn(NeuronIndex) -> bool,
+    ) -> Result<Option<EmbeddingNeighbours>> {
+        self.embedding(neuron)
+            .map(|embedding| {
+                self.nearest(embedding, k, metric, |other| {
+                    other != neuron && filter(other)
+                })
+            })
+            .transpose()
+    }
+
+    fn graph(&self, metric: EmbeddingMetric) -> &Graph {
+        self.graphs[metric.index()].get_or_init(|| Graph::new(self, metric))
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// A hierarchical navigable small world graph over the embeddings of an index.
+struct Graph {
+    entry_point: u32,
+    /// The neighbours of each node in each layer it is in, from the bottom layer up.
+    neighbours: Vec<Vec<Vec<u32>>>,
+}
+
+impl Graph {
+    /// The top layer of the node. Each layer has about `1 / MAX_NEIGHBOURS` as many nodes as the
+    /// one below it.
+    fn node_level(node: usize) -> usize {
+        let hash = splitmix64(GRAPH_SEED ^ splitmix64(node as u64));
+        let uniform = ((hash >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+        (-uniform.ln() / (MAX_NEIGHBOURS as f64).ln()) as usize
+    }
+
+    fn max_neighbours(level: usize) -> usize {
+        if level == 0 {
+            2 * MAX_NEIGHBOURS
+        } else {
+            MAX_NEIGHBOURS
+        }
+    }
+
+    fn top_level(&self) -> usize {
+        self.neighbours[self.entry_point as usize].len() - 1
+    }
+
+    fn new(index: &EmbeddingIndex, metric: EmbeddingMetric) -> Self {
+        let mut graph = Self {
+            entry_point: 0,
+            neighbours: Vec::with_capacity(index.len()),
+        };
+        for node in 0..index.len() {
+            let level = Self::node_level(node);
+            graph.neighbours.push(vec![vec![]; level + 1]);
+            if node == 0 {
+                continue;
+            }
+            let query = Query {
+                values: index.values(node),
+                norm: index.norms[node],
+            };
+            let top_level = graph.top_level();
+            let mut entry_points = vec![Scored {
+                similarity: index.similarity(metric, &query, graph.entry_point as usize),
+                node: graph.entry_point,
+            }];
+            for layer in (level + 1..=top_level).rev() {
+                entry_points = graph.search_layer(index, metric, &query, entry_points, 1, layer);
+            }
+            for layer in (0..=level.min(top_level)).rev() {
+                let found =
+                    graph.search_layer(index, metric, &query, entry_points, EF_CONSTRUCTION, layer);
+                let max_neighbours = Self::max_neighbours(layer);
+                let neighbours: Vec<u32> = found
+                    .iter()
+                    .take(max_neighbours)
+                    .map(|scored| scored.node)
+                    .collect();
+                for &neighbour in &neighbours {
+                    graph.connect(index, metric, neighbour, node as u32, layer);
+                }
+                graph.neighbours[node][layer] = neighbours;
+                entry_points = found;
+            }
+            if level > top_level {
+                graph.entry_point = node as u32;
+            }
+        }
+        graph
+    }
+
+    /// Adds the node to the neighbours of another node, dropping its least similar neighbour if it
+    /// has too many.
+    fn connect(
+        &mut self,
+        index: &EmbeddingIndex,
+        metric: EmbeddingMetric,
+        node: u32,
+        neighbour: u32,
+        layer: usize,
+    ) {
+        let neighbours = &mut self.neighbours[node as usize][layer];
+        neighbours.push(neighbour);
+        if neighbours.len() > Self::max_neighbours(layer) {
+            let query = Query {
+                values: index.values(node as usize),
+                norm: index.norms[node as usize],
+            };
+            let (position, _) = neighbours
+                .iter()
+                .enumerate()
+                .map(|(position, &other)| {
+                    (
+                        position,
+                        Scored {
+                            similarity: index.similarity(metric, &query, other as usize),
+                            node: other,
+                        },
+                    )
+                })
+                .min_by_key(|&(_, scored)| scored)
+                .expect("Neighbours are not empty.");
+            neighbours.swap_remove(position);
+        }
+    }
+
+    /// The `ef` nodes in the layer most similar to the query found from the entry points, most
+    /// similar first.
+    fn search_layer(
+        &self,
+        index: &EmbeddingIndex,
+        metric: EmbeddingMetric,
+        query: &Query,
+        entry_points: Vec<Scored>,
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Scored> {
+        let mut visited: HashSet<u32> = entry_points.iter().map(|scored| scored.node).collect();
+        let mut candidates: BinaryHeap<Scored> = entry_points.iter().copied().collect();
+        let mut found: BinaryHeap<Reverse<Scored>> =
+            entry_points.into_iter().map(Reverse).collect();
+        while found.len() > ef {
+            found.pop();
+        }
+        while let Some(candidate) = candidates.pop() {
+            let worst = found.peek().expect("Found nodes are not empty.").0;
+            if candidate < worst && found.len() >= ef {
+                break;
+            }
+            for &neighbour in &self.neighbours[candidate.node as usize][layer] {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+                let scored = Scored {
+                    similarity: index.similarity(metric, query, neighbour as usize),
+                    node: neighbour,
+                };
+                let worst = found.peek().expect("Found nodes are not empty.").0;
+                if found.len() < ef || scored > worst {
+                    candidates.push(scored);
+                    found.push(Reverse(scored));
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        let mut found: Vec<Scored> = found.into_iter().map(|Reverse(scored)| scored).collect();
+        found.sort_unstable_by(|a, b| b.cmp(a));
+        found
+    }
+
+    /// The `ef` nodes most similar to the query found in the graph, most similar first.
+    fn search(
+        &self,
+        index: &EmbeddingIndex,
+        metric: EmbeddingMetric,
+        query: &Query,
+        ef: usize,
+    ) -> Vec<Scored> {
+        let mut entry_points = vec![Scored {
+            similarity: index.similarity(metric, query, self.entry_point as usize),
+            node: self.entry_point,
+        }];
+        for layer in (1..=self.top_level()).rev() {
+            entry_points = self.search_layer(index, metric, query, entry_points, 1, layer);
+        }
+        self.search_layer(index, metric, query, entry_points, ef, 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_nearest_embeddings() {
+        let layer_size = 500;
+        let embeddings: Vec<(NeuronIndex, Vec<f32>)> = (0..4 * layer_size)
+            .map(|flat_index| {
+                let neuron = NeuronIndex::from_flat_index(layer_size as u32, flat_index);
+                let embedding = (0..8)
+                    .map(|dimension| {
+                        let hash = splitmix64(((flat_index as u64) << 8) | dimension);
+                        (hash >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+                    })
+                    .collect();
+                (neuron, embedding)
+            })
+            .collect();
+        let index = EmbeddingIndex::new(embeddings).unwrap();
+        let neuron = NeuronIndex {
+            layer: 1,
+            neuron: 7,
+        };
+        let exact = index
+            .neighbours(neuron, 10, EmbeddingMetric::Cosine, |other| {
+                other.layer >= 1
+            })
+            .unwrap()
+            .unwrap();
+        assert!(!exact.approximate);
+        assert_eq!(exact.neighbours.len(), 10);
+        assert!(exact
+            .neighbours
+            .iter()
+            .all(|similar| similar.layer >= 1 && (similar.layer, similar.neuron) != (1, 7)));
+        assert!(exact
+            .neighbours
+            .windows(2)
+            .all(|pair| pair[0].similarity >= pair[1].similarity));
+
+        // The graph finds most of the exact nearest neighbours.
+        let query = Query {
+            values: index.embedding(neuron).unwrap(),
+            norm: index.norms[index.positions[&neuron]],
+        };
+        let found: HashSet<u32> = index
+            .graph(EmbeddingMetric::Cosine)
+            .search(&index, EmbeddingMetric::Cosine, &query, EF_SEARCH)
+            .into_iter()
+            .take(11)
+            .map(|scored| scored.node)
+            .collect();
+        let exact = index
+            .nearest(query.values, 11, EmbeddingMetric::Cosine, |_| true)
+            .unwrap();
+        let recalled = exact
+            .neighbours
+            .iter()
+            .filter(|similar| {
+                let position = index.positions[&NeuronIndex {
+                    layer: similar.layer,
+                    neuron: similar.neuron,
+                }];
+                found.contains(&(position as u32))
+            })
+            .count();
+        assert!(recalled >= 9, "Only {recalled} of 11 neighbours found.");
+
+        assert!(index
+            .neighbours(
+                NeuronIndex {
+                    layer: 5,
+                    neuron: 0
+                },
+                10,
+                EmbeddingMetric::Dot,
+                |_| true
+            )
+            .unwrap()
+            .is_none());
+        assert!("euclidean".parse::<EmbeddingMetric>().is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use std::{fmt::Display, ops::RangeInclusive, str::FromStr};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::Metadata;
+
+/// An inclusive range of layer indices.
+///
+/// Written either as a single layer index (`"3"`) or as two layer indices separated by a dash
+/// (`"3-7"`). Either end of a range may be left out (`"3-"` or `"-7"`) to mean the first or last
+/// layer of the model.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayerRange {
+    start: Option<u32>,
+    end: Option<u32>,
+}
+
+impl LayerRange {
+    pub fn all() -> Self {
+        Self {
+            start: None,
+            end: None,
+        }
+    }
+
+    pub fn new(start: u32, end: u32) -> Self {
+        Self {
+            start: Some(start),
+            end: Some(end),
+        }
+    }
+
+    pub fn contains(self, layer_index: u32) -> bool {
+        self.start.is_none_or(|start| layer_index >= start)
+            && self.end.is_none_or(|end| layer_index <= end)
+    }
+
+    /// Returns the layers of the model that lie in the range, failing if the range is empty or
+    /// contains layers the model does not have.
+    pub fn layers(self, metadata: &Metadata) -> Result<RangeInclusive<u32>> {
+        let model_name = metadata.name.as_str();
+        let num_layers = metadata.num_layers;
+        let start = self.start.unwrap_or(0);
+        let end = self.end.unwrap_or(num_layers.saturating_sub(1));
+        if start >= num_layers {
+            bail!(
+                "Layer range {self} starts at layer {start} but model '{model_name}' only has \
+                 {num_layers} layers."
+            )
+        }
+        if start > end {
+            bail!("Layer range {self} starts at layer {start} after it ends at layer {end}.")
+        }
+        if end >= num_layers {
+            bail!(
+                "Layer range {self} ends at layer {end} but model '{model_name}' only has \
+                 {num_layers} layers."
+            )
+        }
+        Ok(start..=end)
+    }
+}
+
+impl Default for LayerRange {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl FromStr for LayerRange {
+    type Err = anyhow::Error;
+
+    fn from_str(range_string: &str) -> Result<Self> {
+        let parse_bound = |bound: &str| -> Result<Option<u32>> {
+            let bound = bound.trim();
+            if bound.is_empty() {
+                Ok(None)
+            } else {
+                bound
+                    .parse::<u32>()
+                    .map(Some)
+                    .with_context(|| format!("Layer index '{bound}' is not a valid integer."))
+            }
+        };
+
+        let range = if let Some((start, end)) = range_string.split_once('-') {
+            Self {
+                start: parse_bound(start)?,
+                end: parse_bound(end)?,
+            }
+        } else {
+            let layer_index = parse_bound(range_string)?
+                .context("Layer range should be of the form 'layer' or 'start-end'.")?;
+            Self::new(layer_index, layer_index)
+        };
+        if let (Some(start), Some(end)) = (range.start, range.end) {
+            if start > end {
+                bail!("Layer range '{range_string}' starts after it ends.")
+            }
+        }
+        Ok(range)
+    }
+}
+
+impl Display for LayerRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) if start == end => write!(f, "{start}"),
+            (start, end) => {
+                if let Some(start) = start {
+                    write!(f, "{start}")?;
+                }
+                f.write_str("-")?;
+                if let Some(end) = end {
+                    write!(f, "{end}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn metadata(num_layers: u32) -> Metadata {
+        Metadata {
+            name: String::from("test"),
+            num_layers,
+            layer_size: 4,
+            activation_function: String::from("test_act"),
+            num_total_neurons: num_layers * 4,
+            num_total_parameters: 100,
+            dataset: String::from("test_dataset"),
+        }
+    }
+
+    #[test]
+    fn checks_layer_bounds() -> Result<()> {
+        let metadata = metadata(4);
+        assert_eq!(LayerRange::all().layers(&metadata)?, 0..=3);
+        assert_eq!("1-2".parse::<LayerRange>()?.layers(&metadata)?, 1..=2);
+        assert_eq!("2-".parse::<LayerRange>()?.layers(&metadata)?, 2..=3);
+
+        // Start bound.
+        assert!("4-".parse::<LayerRange>()?.layers(&metadata).is_err());
+        assert!("4".parse::<LayerRange>()?.layers(&metadata).is_err());
+        assert!("1-"
+            .parse::<LayerRange>()?
+            .layers(&self::metadata(1))
+            .is_err());
+        // End bound.
+        assert!("-4".parse::<LayerRange>()?.layers(&metadata).is_err());
+        assert!("2-7".parse::<LayerRange>()?.layers(&metadata).is_err());
+        assert!("3-2".parse::<LayerRange>().is_err());
+        Ok(())
+    }
+}
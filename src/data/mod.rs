@@ -1,7 +1,31 @@
 mod neuron_index;
 pub use neuron_index::NeuronIndex;
+mod layer_range;
+pub use layer_range::LayerRange;
 mod neuron_store;
-pub use neuron_store::{NeuronStore, SimilarNeurons, TokenSearch, TokenSearchType};
+pub use neuron_store::{
+    NeuronSimilarity, NeuronStore, NeuronStoreRaw, NeuroscopeStoreConfig, SimilarNeuron,
+    SimilarNeurons, TokenSearch, TokenSearchType,
+};
+mod neuron_similarity;
+pub use neuron_similarity::{MinHashConfig, SimilarityConfig, SimilarityMetric};
+mod embedding_index;
+pub use embedding_index::{EmbeddingIndex, EmbeddingMetric, EXACT_SEARCH_LIMIT};
+mod neuron_graph;
+pub use neuron_graph::{ClusteringConfig, GraphAnalysis, NodeStatistics, SimilarityGraph};
+mod context_pattern;
+pub use context_pattern::{ContextPattern, ContextTerm, CONTEXT_RADIUS};
+mod neuron_filter;
+pub use neuron_filter::{Comparison, NeuronAttribute, NeuronFilter, NeuronOrder, SortKey};
+mod token_query;
+pub use token_query::{QueryTerm, TokenQuery, TokenQueryMatch};
+mod token_statistics;
+pub use token_statistics::{LayerCount, TokenCounts, TokenStatistics, TokenSummary};
+mod token_vocabulary;
+pub use token_vocabulary::{
+    sort_suggestions, Normalisation, TokenPattern, TokenSuggestion, TokenVocabulary,
+    VocabularyEntry,
+};
 
 pub mod retrieve;
 
@@ -0,0 +1,371 @@
+//! Filters and sort orders over the scalar attributes of neurons.
+//!
+//! An attribute is written `data_object.name`, such as `neuron_explainer.score`, or is one of
+//! `layer` and `neuron` for the index of the neuron. A filter compares attributes to numbers with
+//! `<`, `<=`, `>`, `>=`, `=` and `!=`, and combines comparisons with `AND` (or `&`) and `OR` (or
+//! `|`), grouped with parentheses. And binds tighter than or, so
+//! `layer >= 10 AND neuron_explainer.score > 0.6 OR neuron2graph.nodes >= 3` means
+//! `(layer >= 10 AND neuron_explainer.score > 0.6) OR neuron2graph.nodes >= 3`.
+//!
+//! A sort order is a comma separated list of attributes, each prefixed with `-` to sort in
+//! descending order, such as `-neuron_explainer.score,layer`.
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{bail, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NeuronAttribute {
+    Layer,
+    Neuron,
+    /// A scalar stored for the neuron by a data object.
+    Scalar {
+        data_type: String,
+        name: String,
+    },
+}
+
+impl NeuronAttribute {
+    fn parse(attribute: &str) -> Result<Self> {
+        match attribute {
+            "layer" => Ok(Self::Layer),
+            "neuron" => Ok(Self::Neuron),
+            _ => match attribute.split_once('.') {
+                Some((data_type, name)) if !data_type.is_empty() && !name.is_empty() => {
+                    Ok(Self::Scalar {
+                        data_type: data_type.to_owned(),
+                        name: name.to_owned(),
+                    })
+                }
+                _ => bail!(
+                    "Invalid attribute '{attribute}'. Expected 'layer', 'neuron' or \
+                     'data_object.name'."
+                ),
+            },
+        }
+    }
+}
+
+impl FromStr for NeuronAttribute {
+    type Err = anyhow::Error;
+
+    fn from_str(attribute: &str) -> Result<Self> {
+        Self::parse(attribute.trim())
+    }
+}
+
+impl Display for NeuronAttribute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Layer => write!(f, "layer"),
+            Self::Neuron => write!(f, "neuron"),
+            Self::Scalar { data_type, name } => write!(f, "{data_type}.{name}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    /// The SQL operator of the comparison.
+    pub fn operator(self) -> &'static str {
+        match self {
+            Self::Less => "<",
+            Self::LessOrEqual => "<=",
+            Self::Greater => ">",
+            Self::GreaterOrEqual => ">=",
+            Self::Equal => "=",
+            Self::NotEqual => "!=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NeuronFilter {
+    Compare {
+        attribute: NeuronAttribute,
+        comparison: Comparison,
+        value: f64,
+    },
+    And(Vec<NeuronFilter>),
+    Or(Vec<NeuronFilter>),
+}
+
+impl NeuronFilter {
+    /// All attributes the filter compares.
+    pub fn attributes(&self) -> Vec<&NeuronAttribute> {
+        match self {
+            Self::Compare { attribute, .. } => vec![attribute],
+            Self::And(filters) | Self::Or(filters) => {
+                filters.iter().flat_map(Self::attributes).collect()
+            }
+        }
+    }
+}
+
+impl FromStr for NeuronFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(filter: &str) -> Result<Self> {
+        let mut parser = Parser {
+            text: filter,
+            position: 0,
+        };
+        let neuron_filter = parser.or()?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(neuron_filter),
+            Some(')') => parser.error("unmatched ')'"),
+            Some(_) => parser.error("expected 'AND', 'OR' or the end of the filter"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub attribute: NeuronAttribute,
+    pub descending: bool,
+}
+
+/// The attributes to sort neurons by, the first one taking precedence.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct NeuronOrder(pub Vec<SortKey>);
+
+impl FromStr for NeuronOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(order: &str) -> Result<Self> {
+        order
+            .split(',')
+            .map(|key| {
+                let key = key.trim();
+                let (descending, attribute) = match key.strip_prefix('-') {
+                    Some(attribute) => (true, attribute),
+                    None => (false, key),
+                };
+                Ok(SortKey {
+                    attribute: NeuronAttribute::parse(attribute.trim())?,
+                    descending,
+                })
+            })
+            .collect::<Result<_>>()
+            .map(NeuronOrder)
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T> {
+        bail!(
+            "Invalid filter '{}' at position {}: {message}.",
+            self.text,
+            self.position
+        )
+    }
+
+    /// Consumes the characters while they satisfy the predicate and returns them.
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.position;
+        while self.peek().is_some_and(&predicate) {
+            self.bump();
+        }
+        &self.text[start..self.position]
+    }
+
+    /// Consumes the connective if it comes next, either as a symbol or as a keyword in any case.
+    fn eat_connective(&mut self, symbol: char, keyword: &str) -> bool {
+        self.skip_whitespace();
+        let rest = &self.text[self.position..];
+        if rest.starts_with(symbol) {
+            self.position += symbol.len_utf8();
+            return true;
+        }
+        let is_keyword = rest
+            .get(..keyword.len())
+            .is_some_and(|word| word.eq_ignore_ascii_case(keyword))
+            && !rest[keyword.len()..]
+                .chars()
+                .next()
+                .is_some_and(is_attribute_char);
+        if is_keyword {
+            self.position += keyword.len();
+        }
+        is_keyword
+    }
+
+    fn or(&mut self) -> Result<NeuronFilter> {
+        let mut filters = vec![self.and()?];
+        while self.eat_connective('|', "or") {
+            filters.push(self.and()?);
+        }
+        Ok(if filters.len() == 1 {
+            filters.pop().unwrap()
+        } else {
+            NeuronFilter::Or(filters)
+        })
+    }
+
+    fn and(&mut self) -> Result<NeuronFilter> {
+        let mut filters = vec![self.primary()?];
+        while self.eat_connective('&', "and") {
+            filters.push(self.primary()?);
+        }
+        Ok(if filters.len() == 1 {
+            filters.pop().unwrap()
+        } else {
+            NeuronFilter::And(filters)
+        })
+    }
+
+    fn primary(&mut self) -> Result<NeuronFilter> {
+        self.skip_whitespace();
+        if self.peek() == Some('(') {
+            self.bump();
+            let filter = self.or()?;
+            self.skip_whitespace();
+            if self.peek() == Some(')') {
+                self.bump();
+                Ok(filter)
+            } else {
+                self.error("expected ')'")
+            }
+        } else {
+            self.comparison()
+        }
+    }
+
+    fn comparison(&mut self) -> Result<NeuronFilter> {
+        let start = self.position;
+        let attribute = self.take_while(is_attribute_char);
+        if attribute.is_empty() {
+            return self.error("expected a comparison of the form 'attribute > number'");
+        }
+        let attribute = match NeuronAttribute::parse(attribute) {
+            Ok(attribute) => attribute,
+            Err(error) => {
+                self.position = start;
+                return self.error(&error.to_string());
+            }
+        };
+        self.skip_whitespace();
+        let comparison = match self.take_while(|c| matches!(c, '<' | '>' | '=' | '!')) {
+            "<" => Comparison::Less,
+            "<=" => Comparison::LessOrEqual,
+            ">" => Comparison::Greater,
+            ">=" => Comparison::GreaterOrEqual,
+            "=" | "==" => Comparison::Equal,
+            "!=" => Comparison::NotEqual,
+            "" => return self.error("expected one of '<', '<=', '>', '>=', '=' or '!='"),
+            operator => return self.error(&format!("invalid comparison '{operator}'")),
+        };
+        self.skip_whitespace();
+        let value = self.take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'));
+        match value.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(NeuronFilter::Compare {
+                attribute,
+                comparison,
+                value,
+            }),
+            _ => self.error(&format!("expected a number, found '{value}'")),
+        }
+    }
+}
+
+fn is_attribute_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn compare(attribute: &str, comparison: Comparison, value: f64) -> NeuronFilter {
+        NeuronFilter::Compare {
+            attribute: NeuronAttribute::parse(attribute).unwrap(),
+            comparison,
+            value,
+        }
+    }
+
+    #[test]
+    fn parses_filters() {
+        assert_eq!(
+            "layer >= 10 and neuron_explainer.score>0.6 OR (neuron2graph.nodes >= 3 | \
+             neuroscope.max_activation != -1e1)"
+                .parse::<NeuronFilter>()
+                .unwrap(),
+            NeuronFilter::Or(vec![
+                NeuronFilter::And(vec![
+                    compare("layer", Comparison::GreaterOrEqual, 10.),
+                    compare("neuron_explainer.score", Comparison::Greater, 0.6),
+                ]),
+                NeuronFilter::Or(vec![
+                    compare("neuron2graph.nodes", Comparison::GreaterOrEqual, 3.),
+                    compare("neuroscope.max_activation", Comparison::NotEqual, -10.),
+                ]),
+            ])
+        );
+        assert_eq!(
+            "ordinal.value = 2".parse::<NeuronFilter>().unwrap(),
+            compare("ordinal.value", Comparison::Equal, 2.)
+        );
+        for invalid in [
+            "",
+            "score > 1",
+            "layer >",
+            "layer => 1",
+            "layer > one",
+            "(layer > 1",
+            "layer > 1)",
+            "layer > 1 AND",
+            "layer > 1 neuron < 2",
+        ] {
+            assert!(invalid.parse::<NeuronFilter>().is_err(), "{invalid}");
+        }
+
+        assert_eq!(
+            "-neuron_explainer.score, layer"
+                .parse::<NeuronOrder>()
+                .unwrap(),
+            NeuronOrder(vec![
+                SortKey {
+                    attribute: NeuronAttribute::parse("neuron_explainer.score").unwrap(),
+                    descending: true,
+                },
+                SortKey {
+                    attribute: NeuronAttribute::Layer,
+                    descending: false,
+                },
+            ])
+        );
+        assert!("layer,".parse::<NeuronOrder>().is_err());
+    }
+}
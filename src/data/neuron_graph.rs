@@ -0,0 +1,457 @@
+//! The graph formed by the similar neurons of each neuron, its clusters and the roles of neurons in
+//! it.
+//!
+//! Clusters are found with the Louvain method, which greedily moves nodes between clusters while
+//! that increases modularity and then repeats on the graph of the clusters. Roles follow Guimerà
+//! and Amaral, by the within-cluster degree and the participation coefficient of each neuron.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use super::data_objects::NeuronRole;
+
+/// How the similarity graph of a model is clustered.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusteringConfig {
+    /// Higher resolutions give more and smaller clusters. 1 optimises the usual modularity.
+    pub resolution: f32,
+    /// Similarities below this do not give an edge.
+    pub min_similarity: f32,
+}
+
+impl Default for ClusteringConfig {
+    fn default() -> Self {
+        Self {
+            resolution: 1.,
+            min_similarity: 0.,
+        }
+    }
+}
+
+impl ClusteringConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.resolution.is_finite() && self.resolution > 0.) {
+            bail!("Clustering resolution must be a positive number.")
+        }
+        if !(self.min_similarity.is_finite() && self.min_similarity >= 0.) {
+            bail!("Minimum similarity must be a non-negative number.")
+        }
+        Ok(())
+    }
+}
+
+/// Undirected graph with an edge between each pair of nodes similar to each other.
+pub struct SimilarityGraph {
+    /// The neighbours of each node and the weights of the edges to them, heaviest first.
+    adjacency: Vec<Vec<(usize, f32)>>,
+}
+
+impl SimilarityGraph {
+    /// Builds the graph from the similarities of nodes to others. Similarities need not be
+    /// symmetric. An edge gets the larger of the similarities in its two directions, and is only
+    /// added if that is positive and at least the minimum similarity.
+    pub fn new(
+        num_nodes: usize,
+        similarities: impl IntoIterator<Item = (usize, usize, f32)>,
+        min_similarity: f32,
+    ) -> Self {
+        let mut edges: HashMap<(usize, usize), f32> = HashMap::new();
+        for (node, other, similarity) in similarities {
+            if node == other || node >= num_nodes || other >= num_nodes {
+                continue;
+            }
+            let weight = edges.entry((node.min(other), node.max(other))).or_default();
+            *weight = weight.max(similarity);
+        }
+        let mut adjacency = vec![vec![]; num_nodes];
+        for ((node, other), weight) in edges {
+            if weight > 0. && weight >= min_similarity {
+                adjacency[node].push((other, weight));
+                adjacency[other].push((node, weight));
+            }
+        }
+        for neighbours in adjacency.iter_mut() {
+            neighbours.sort_by(|(node_a, weight_a), (node_b, weight_b)| {
+                weight_b.total_cmp(weight_a).then(node_a.cmp(node_b))
+            });
+        }
+        Self { adjacency }
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.adjacency.len()
+    }
+
+    pub fn num_edges(&self) -> usize {
+        self.adjacency.iter().map(Vec::len).sum::<usize>() / 2
+    }
+
+    pub fn neighbours(&self, node: usize) -> &[(usize, f32)] {
+        &self.adjacency[node]
+    }
+
+    fn strengths(&self) -> Vec<f64> {
+        self.adjacency
+            .iter()
+            .map(|neighbours| neighbours.iter().map(|&(_, weight)| weight as f64).sum())
+            .collect()
+    }
+
+    /// Clusters the graph and computes the centrality and role of each node.
+    pub fn analyse(&self, resolution: f32) -> GraphAnalysis {
+        let strengths = self.strengths();
+        let total_strength: f64 = strengths.iter().sum();
+
+        // Number clusters by decreasing size, leaving isolated nodes out of them.
+        let communities = Level::new(self).louvain(resolution as f64);
+        let mut community_sizes: HashMap<usize, (usize, usize)> = HashMap::new();
+        for (node, &community) in communities.iter().enumerate() {
+            if !self.adjacency[node].is_empty() {
+                community_sizes.entry(community).or_insert((0, node)).0 += 1;
+            }
+        }
+        let mut ordered: Vec<_> = community_sizes.into_iter().collect();
+        ordered.sort_by(|(_, (size_a, first_a)), (_, (size_b, first_b))| {
+            size_b.cmp(size_a).then(first_a.cmp(first_b))
+        });
+        let cluster_ids: HashMap<usize, u32> = ordered
+            .iter()
+            .enumerate()
+            .map(|(id, &(community, _))| (community, id as u32))
+            .collect();
+        let clusters: Vec<Option<u32>> = communities
+            .iter()
+            .enumerate()
+            .map(|(node, community)| {
+                (!self.adjacency[node].is_empty()).then(|| cluster_ids[community])
+            })
+            .collect();
+        let num_clusters = ordered.len();
+
+        // Strength of each node within its cluster and how evenly its strength is spread.
+        let mut internal_strengths = vec![0.; self.num_nodes()];
+        let mut participations = vec![0.; self.num_nodes()];
+        for (node, neighbours) in self.adjacency.iter().enumerate() {
+            let Some(cluster) = clusters[node] else {
+                continue;
+            };
+            let mut cluster_strengths: HashMap<u32, f64> = HashMap::new();
+            for &(neighbour, weight) in neighbours {
+                let neighbour_cluster = clusters[neighbour].expect("Neighbours are not isolated.");
+                *cluster_strengths.entry(neighbour_cluster).or_default() += weight as f64;
+            }
+            internal_strengths[node] = cluster_strengths.get(&cluster).copied().unwrap_or(0.);
+            participations[node] = 1.
+                - cluster_strengths
+                    .values()
+                    .map(|strength| (strength / strengths[node]).powi(2))
+                    .sum::<f64>();
+        }
+
+        // Number of nodes, sum and sum of squares of internal strengths, and total strength of each
+        // cluster.
+        let mut cluster_moments = vec![(0usize, 0f64, 0f64, 0f64); num_clusters];
+        for (node, cluster) in clusters.iter().enumerate() {
+            if let Some(cluster) = cluster {
+                let (count, sum, sum_squares, strength) = &mut cluster_moments[*cluster as usize];
+                *count += 1;
+                *sum += internal_strengths[node];
+                *sum_squares += internal_strengths[node].powi(2);
+                *strength += strengths[node];
+            }
+        }
+        let modularity = if total_strength > 0. {
+            cluster_moments
+                .iter()
+                .map(|&(_, internal, _, strength)| {
+                    internal / total_strength - (strength / total_strength).powi(2)
+                })
+                .sum::<f64>()
+        } else {
+            0.
+        };
+
+        let pageranks = self.pageranks(&strengths);
+        let nodes = (0..self.num_nodes())
+            .map(|node| {
+                let cluster = clusters[node];
+                let (within_cluster_degree, role) = match cluster {
+                    None => (0., NeuronRole::Isolated),
+                    Some(cluster) => {
+                        let (count, sum, sum_squares, _) = cluster_moments[cluster as usize];
+                        let mean = sum / count as f64;
+                        let deviation = (sum_squares / count as f64 - mean.powi(2)).max(0.).sqrt();
+                        let z_score = if deviation > 1e-9 {
+                            ((internal_strengths[node] - mean) / deviation) as f32
+                        } else {
+                            0.
+                        };
+                        let participation = participations[node] as f32;
+                        (z_score, NeuronRole::new(z_score, participation))
+                    }
+                };
+                NodeStatistics {
+                    cluster,
+                    role,
+                    degree: self.adjacency[node].len() as u32,
+                    strength: strengths[node] as f32,
+                    pagerank: pageranks[node] as f32,
+                    participation: participations[node] as f32,
+                    within_cluster_degree,
+                }
+            })
+            .collect();
+
+        GraphAnalysis {
+            modularity: modularity as f32,
+            num_clusters,
+            nodes,
+        }
+    }
+
+    /// Weighted PageRank of each node. Nodes without edges spread their rank over all nodes.
+    fn pageranks(&self, strengths: &[f64]) -> Vec<f64> {
+        const DAMPING: f64 = 0.85;
+        const MAX_ITERATIONS: usize = 100;
+        const TOLERANCE: f64 = 1e-10;
+
+        let num_nodes = self.num_nodes();
+        if num_nodes == 0 {
+            return vec![];
+        }
+        let mut ranks = vec![1. / num_nodes as f64; num_nodes];
+        for _ in 0..MAX_ITERATIONS {
+            let dangling: f64 = ranks
+                .iter()
+                .zip(strengths)
+                .filter(|(_, &strength)| strength == 0.)
+                .map(|(rank, _)| rank)
+                .sum();
+            let base = (1. - DAMPING + DAMPING * dangling) / num_nodes as f64;
+            let mut new_ranks = vec![base; num_nodes];
+            for (node, neighbours) in self.adjacency.iter().enumerate() {
+                for &(neighbour, weight) in neighbours {
+                    new_ranks[neighbour] += DAMPING * ranks[node] * weight as f64 / strengths[node];
+                }
+            }
+            let change: f64 = ranks
+                .iter()
+                .zip(&new_ranks)
+                .map(|(rank, new_rank)| (rank - new_rank).abs())
+                .sum();
+            ranks = new_ranks;
+            if change < TOLERANCE {
+                break;
+            }
+        }
+        ranks
+    }
+}
+
+/// The centrality and role of a node of the graph.
+#[derive(Clone, Debug)]
+pub struct NodeStatistics {
+    pub cluster: Option<u32>,
+    pub role: NeuronRole,
+    pub degree: u32,
+    pub strength: f32,
+    pub pagerank: f32,
+    pub participation: f32,
+    pub within_cluster_degree: f32,
+}
+
+/// The clusters of a graph and the statistics of each of its nodes.
+pub struct GraphAnalysis {
+    pub modularity: f32,
+    /// Clusters are numbered from 0 by decreasing size.
+    pub num_clusters: usize,
+    pub nodes: Vec<NodeStatistics>,
+}
+
+/// A graph at one level of the Louvain method, whose nodes are the clusters of the level below.
+struct Level {
+    adjacency: Vec<Vec<(usize, f64)>>,
+    /// Weight of the edges within each node.
+    self_loops: Vec<f64>,
+}
+
+impl Level {
+    fn new(graph: &SimilarityGraph) -> Self {
+        Self {
+            adjacency: graph
+                .adjacency
+                .iter()
+                .map(|neighbours| {
+                    neighbours
+                        .iter()
+                        .map(|&(neighbour, weight)| (neighbour, weight as f64))
+                        .collect()
+                })
+                .collect(),
+            self_loops: vec![0.; graph.num_nodes()],
+        }
+    }
+
+    fn degrees(&self) -> Vec<f64> {
+        self.adjacency
+            .iter()
+            .zip(&self.self_loops)
+            .map(|(neighbours, self_loop)| {
+                neighbours.iter().map(|(_, weight)| weight).sum::<f64>() + 2. * self_loop
+            })
+            .collect()
+    }
+
+    /// The community of each node of the original graph.
+    fn louvain(mut self, resolution: f64) -> Vec<usize> {
+        let mut membership: Vec<usize> = (0..self.adjacency.len()).collect();
+        let total_degree: f64 = self.degrees().iter().sum();
+        if total_degree == 0. {
+            return membership;
+        }
+        while let Some(communities) = self.move_nodes(resolution, total_degree) {
+            let mut ids = HashMap::new();
+            let communities: Vec<usize> = communities
+                .into_iter()
+                .map(|community| {
+                    let next_id = ids.len();
+                    *ids.entry(community).or_insert(next_id)
+                })
+                .collect();
+            for community in membership.iter_mut() {
+                *community = communities[*community];
+            }
+            self = self.aggregate(&communities, ids.len());
+        }
+        membership
+    }
+
+    /// Moves each node to the neighbouring community that increases modularity the most, until no
+    /// move does. Returns the community of each node, or `None` if no node was moved.
+    fn move_nodes(&self, resolution: f64, total_degree: f64) -> Option<Vec<usize>> {
+        /// Guards against nodes moving back and forth over rounding errors.
+        const MIN_GAIN: f64 = 1e-12;
+        const MAX_PASSES: usize = 100;
+
+        let num_nodes = self.adjacency.len();
+        let degrees = self.degrees();
+        let mut communities: Vec<usize> = (0..num_nodes).collect();
+        let mut community_degrees = degrees.clone();
+        let mut weights = vec![0.; num_nodes];
+        let mut neighbour_communities = vec![];
+        let mut moved_any = false;
+        for _ in 0..MAX_PASSES {
+            let mut moved = false;
+            for node in 0..num_nodes {
+                let current = communities[node];
+                for &(neighbour, weight) in &self.adjacency[node] {
+                    let community = communities[neighbour];
+                    if weights[community] == 0. {
+                        neighbour_communities.push(community);
+                    }
+                    weights[community] += weight;
+                }
+                community_degrees[current] -= degrees[node];
+                let gain = |community: usize| {
+                    weights[community]
+                        - resolution * community_degrees[community] * degrees[node] / total_degree
+                };
+                let mut best = (current, gain(current));
+                for &community in &neighbour_communities {
+                    let community_gain = gain(community);
+                    if community_gain > best.1 + MIN_GAIN {
+                        best = (community, community_gain);
+                    }
+                }
+                community_degrees[best.0] += degrees[node];
+                if best.0 != current {
+                    communities[node] = best.0;
+                    moved = true;
+                }
+                for community in neighbour_communities.drain(..) {
+                    weights[community] = 0.;
+                }
+            }
+            if !moved {
+                break;
+            }
+            moved_any = true;
+        }
+        moved_any.then_some(communities)
+    }
+
+    /// The graph of the communities, with the weight of the edges between them.
+    fn aggregate(&self, communities: &[usize], num_communities: usize) -> Self {
+        let mut edges: Vec<HashMap<usize, f64>> = vec![HashMap::new(); num_communities];
+        let mut self_loops = vec![0.; num_communities];
+        for (node, neighbours) in self.adjacency.iter().enumerate() {
+            let community = communities[node];
+            self_loops[community] += self.self_loops[node];
+            for &(neighbour, weight) in neighbours {
+                let neighbour_community = communities[neighbour];
+                if neighbour_community == community {
+                    // Each edge is seen from both of its ends.
+                    self_loops[community] += weight / 2.;
+                } else {
+                    *edges[community].entry(neighbour_community).or_default() += weight;
+                }
+            }
+        }
+        let adjacency = edges
+            .into_iter()
+            .map(|neighbours| {
+                let mut neighbours: Vec<_> = neighbours.into_iter().collect();
+                neighbours.sort_by_key(|&(neighbour, _)| neighbour);
+                neighbours
+            })
+            .collect();
+        Self {
+            adjacency,
+            self_loops,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn clusters_similarity_graph() {
+        // Two cliques of five joined by a single weak edge, and an isolated node.
+        let mut similarities = vec![];
+        for clique in [0..5, 5..10] {
+            for node in clique.clone() {
+                for other in clique.clone() {
+                    similarities.push((node, other, 0.9));
+                }
+            }
+        }
+        similarities.push((4, 5, 0.1));
+        let graph = SimilarityGraph::new(11, similarities, 0.);
+        assert_eq!(graph.num_edges(), 21);
+
+        let analysis = graph.analyse(1.);
+        assert_eq!(analysis.num_clusters, 2);
+        assert!(analysis.modularity > 0.4);
+        let clusters: Vec<_> = analysis.nodes.iter().map(|node| node.cluster).collect();
+        assert!(clusters[..5].iter().all(|&cluster| cluster == clusters[0]));
+        assert!(clusters[5..10]
+            .iter()
+            .all(|&cluster| cluster == clusters[5]));
+        assert_ne!(clusters[0], clusters[5]);
+        assert_eq!(clusters[10], None);
+        assert_eq!(analysis.nodes[10].role, NeuronRole::Isolated);
+
+        assert_eq!(analysis.nodes[0].role, NeuronRole::UltraPeripheral);
+        assert!(analysis.nodes[4].participation > 0.);
+        assert!(analysis.nodes[4].pagerank > analysis.nodes[0].pagerank);
+        let total_rank: f32 = analysis.nodes.iter().map(|node| node.pagerank).sum();
+        assert!((total_rank - 1.).abs() < 1e-4);
+
+        let thresholded = SimilarityGraph::new(11, [(4, 5, 0.1), (0, 1, 0.5)], 0.2);
+        assert_eq!(thresholded.num_edges(), 1);
+    }
+}
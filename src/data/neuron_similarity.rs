@@ -0,0 +1,710 @@
+//! Similarity between neurons by the tokens they share in a neuron store.
+//!
+//! The tokens of each neuron are its activating tokens and the tokens important to it in its
+//! graph. Neurons sharing no tokens have no similarity and are never listed as similar. Exact
+//! similarities are found by counting shared tokens through the inverted index of the store, in
+//! blocks of neurons spread over several threads. For large models, candidates can instead be
+//! found approximately with MinHash and locality sensitive hashing, after which the similarity of
+//! each candidate is computed exactly.
+//!
+//! Neurons of different models are compared the same way, over the tokens both neuron stores have.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    neuron_store::{NeuronSimilarity, SimilarNeurons},
+    NeuronIndex, NeuronStore, TokenSearchType,
+};
+
+/// Number of neurons a thread takes at a time.
+const BLOCK_SIZE: usize = 256;
+
+/// How the similarity of two neurons is measured from the tokens they share. Shared activating
+/// tokens and shared important tokens are counted separately and then added up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum SimilarityMetric {
+    /// Shared tokens relative to the number of tokens of the neuron with fewer tokens.
+    #[default]
+    Overlap,
+    /// Shared tokens relative to the number of tokens either neuron has.
+    Jaccard,
+    /// Like [`SimilarityMetric::Overlap`], but with each important token counting as much as
+    /// `important_weight` activating tokens.
+    Weighted { important_weight: f32 },
+}
+
+impl SimilarityMetric {
+    /// The similarity of two neurons sharing `common` activating and important tokens, out of
+    /// `sizes` and `other_sizes` tokens of each kind.
+    fn similarity(self, common: [u32; 2], sizes: [u32; 2], other_sizes: [u32; 2]) -> f32 {
+        let weights = match self {
+            Self::Weighted { important_weight } => [1., important_weight],
+            _ => [1., 1.],
+        };
+        let (numerator, denominator) = (0..2).fold((0., 0.), |(numerator, denominator), kind| {
+            let possible = match self {
+                Self::Jaccard => sizes[kind] + other_sizes[kind] - common[kind],
+                _ => sizes[kind].min(other_sizes[kind]),
+            };
+            (
+                numerator + weights[kind] * common[kind] as f32,
+                denominator + weights[kind] * possible as f32,
+            )
+        });
+        if denominator > 0. {
+            numerator / denominator
+        } else {
+            0.
+        }
+    }
+}
+
+impl FromStr for SimilarityMetric {
+    type Err = anyhow::Error;
+
+    /// Parses `overlap`, `jaccard` or `weighted`, the latter optionally followed by `:` and the
+    /// weight of important tokens, which defaults to 2.
+    fn from_str(metric: &str) -> Result<Self> {
+        match metric.split_once(':') {
+            None if metric == "overlap" => Ok(Self::Overlap),
+            None if metric == "jaccard" => Ok(Self::Jaccard),
+            None if metric == "weighted" => Ok(Self::Weighted {
+                important_weight: 2.,
+            }),
+            Some(("weighted", weight)) => {
+                let important_weight = weight
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|weight| weight.is_finite() && *weight >= 0.)
+                    .with_context(|| format!("Invalid weight of important tokens '{weight}'."))?;
+                Ok(Self::Weighted { important_weight })
+            }
+            _ => bail!(
+                "Invalid similarity metric '{metric}'. Must be 'overlap', 'jaccard' or \
+                 'weighted[:weight]'."
+            ),
+        }
+    }
+}
+
+impl Display for SimilarityMetric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Overlap => write!(f, "overlap"),
+            Self::Jaccard => write!(f, "jaccard"),
+            Self::Weighted { important_weight } => write!(f, "weighted:{important_weight}"),
+        }
+    }
+}
+
+/// Parameters of approximate candidate search with MinHash signatures split into bands. Two
+/// neurons become candidates if all hashes in any band of their signatures agree, so more bands
+/// find more candidates and larger bands fewer.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MinHashConfig {
+    pub num_bands: usize,
+    pub band_size: usize,
+    pub seed: u64,
+}
+
+impl Default for MinHashConfig {
+    fn default() -> Self {
+        Self {
+            num_bands: 32,
+            band_size: 4,
+            seed: 0,
+        }
+    }
+}
+
+/// How similar neurons are found. A neuron's similar neurons are those at least as similar as the
+/// threshold, limited to the `top_k` most similar ones. At least one of the two must be given.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimilarityConfig {
+    pub metric: SimilarityMetric,
+    pub threshold: Option<f32>,
+    pub top_k: Option<usize>,
+    /// Number of threads to compute similarities on. Defaults to the available parallelism.
+    pub threads: Option<usize>,
+    /// Finds candidates approximately with MinHash if given, rather than comparing each neuron
+    /// with every neuron sharing a token with it.
+    pub min_hash: Option<MinHashConfig>,
+}
+
+impl SimilarityConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.threshold.is_none() && self.top_k.is_none() {
+            bail!("Either a similarity threshold or a number of most similar neurons is required.")
+        }
+        if self.threads == Some(0) {
+            bail!("Similarities must be computed on at least one thread.")
+        }
+        if let Some(min_hash) = self.min_hash {
+            if min_hash.num_bands == 0 || min_hash.band_size == 0 {
+                bail!("MinHash needs at least one band of at least one hash.")
+            }
+        }
+        Ok(())
+    }
+
+    /// Keeps the candidates passing the threshold, most similar first, limited to the top k.
+    fn select(&self, mut candidates: Vec<(u32, f32)>) -> Vec<(u32, f32)> {
+        if let Some(threshold) = self.threshold {
+            candidates.retain(|&(_, similarity)| similarity >= threshold);
+        }
+        candidates.sort_by(|(index_a, similarity_a), (index_b, similarity_b)| {
+            similarity_b
+                .total_cmp(similarity_a)
+                .then(index_a.cmp(index_b))
+        });
+        if let Some(top_k) = self.top_k {
+            candidates.truncate(top_k);
+        }
+        candidates
+    }
+}
+
+/// Ids of the activating and important tokens that similarities are computed over.
+struct Vocabulary<'a>([HashMap<&'a str, u32>; 2]);
+
+impl<'a> Vocabulary<'a> {
+    /// All tokens of the store.
+    fn new(store: &'a NeuronStore) -> Self {
+        Self(SEARCH_TYPES.map(|search_type| {
+            store
+                .tokens(search_type)
+                .enumerate()
+                .map(|(token_id, (token, _neurons))| (token, token_id as u32))
+                .collect()
+        }))
+    }
+
+    /// The tokens both stores have.
+    fn shared(store: &'a NeuronStore, other: &NeuronStore) -> Self {
+        Self(SEARCH_TYPES.map(|search_type| {
+            store
+                .tokens(search_type)
+                .filter(|(token, _neurons)| other.get(search_type, token).is_some())
+                .enumerate()
+                .map(|(token_id, (token, _neurons))| (token, token_id as u32))
+                .collect()
+        }))
+    }
+}
+
+const SEARCH_TYPES: [TokenSearchType; 2] =
+    [TokenSearchType::Activating, TokenSearchType::Important];
+
+/// The tokens of every neuron by flat index, and the neurons of every token.
+struct TokenSets {
+    /// Sorted ids of the activating and important tokens of each neuron.
+    tokens: [Vec<Vec<u32>>; 2],
+    /// Neurons of each activating and important token.
+    postings: [Vec<Vec<u32>>; 2],
+}
+
+impl TokenSets {
+    /// The tokens of the store's neurons, leaving out tokens outside the vocabulary.
+    fn new(store: &NeuronStore, vocabulary: &Vocabulary) -> Result<Self> {
+        let num_neurons = store.num_layers() as usize * store.layer_size() as usize;
+        let mut tokens = [vec![vec![]; num_neurons], vec![vec![]; num_neurons]];
+        let mut postings = vocabulary.0.each_ref().map(|ids| vec![vec![]; ids.len()]);
+        for (kind, search_type) in SEARCH_TYPES.into_iter().enumerate() {
+            for (token, neurons) in store.tokens(search_type) {
+                let Some(&token_id) = vocabulary.0[kind].get(token) else {
+                    continue;
+                };
+                for neuron in neurons {
+                    let index = neuron.flat_index(store.layer_size());
+                    tokens[kind]
+                        .get_mut(index)
+                        .with_context(|| {
+                            format!("Neuron {neuron} lies outside the model of the neuron store.")
+                        })?
+                        .push(token_id);
+                    postings[kind][token_id as usize].push(index as u32);
+                }
+            }
+        }
+        for neuron_tokens in tokens.iter_mut().flatten() {
+            neuron_tokens.sort_unstable();
+        }
+        Ok(Self { tokens, postings })
+    }
+
+    fn num_neurons(&self) -> usize {
+        self.tokens[0].len()
+    }
+
+    fn sizes(&self, neuron: usize) -> [u32; 2] {
+        [
+            self.tokens[0][neuron].len() as u32,
+            self.tokens[1][neuron].len() as u32,
+        ]
+    }
+
+    fn is_empty(&self, neuron: usize) -> bool {
+        self.sizes(neuron) == [0, 0]
+    }
+}
+
+/// Number of elements two sorted lists have in common.
+fn num_common(a: &[u32], b: &[u32]) -> u32 {
+    let (mut i, mut j, mut common) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                common += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    common
+}
+
+/// A fast, well mixing hash of a 64 bit integer.
+pub(super) fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// The neurons of a store grouped by each band of their MinHash signatures.
+struct Buckets {
+    band_size: usize,
+    seeds: Vec<u64>,
+    bucket_ids: HashMap<u64, usize>,
+    buckets: Vec<Vec<u32>>,
+}
+
+impl Buckets {
+    fn new(token_sets: &TokenSets, config: MinHashConfig) -> Self {
+        let seeds = (0..(config.num_bands * config.band_size) as u64)
+            .map(|index| splitmix64(config.seed ^ splitmix64(index)))
+            .collect();
+        let mut buckets = Self {
+            band_size: config.band_size,
+            seeds,
+            bucket_ids: HashMap::new(),
+            buckets: vec![],
+        };
+        for neuron in 0..token_sets.num_neurons() {
+            for key in buckets.band_keys(token_sets, neuron) {
+                let bucket = *buckets.bucket_ids.entry(key).or_insert_with(|| {
+                    buckets.buckets.push(vec![]);
+                    buckets.buckets.len() - 1
+                });
+                buckets.buckets[bucket].push(neuron as u32);
+            }
+        }
+        buckets
+    }
+
+    /// A key for each band of the neuron's signature, or none if the neuron has no tokens.
+    fn band_keys(&self, token_sets: &TokenSets, neuron: usize) -> Vec<u64> {
+        if token_sets.is_empty(neuron) {
+            return vec![];
+        }
+        // Important tokens get ids after all activating tokens, so both kinds share a signature.
+        let num_activating = token_sets.postings[0].len() as u64;
+        let tokens: Vec<u64> = token_sets.tokens[0][neuron]
+            .iter()
+            .map(|&token| token as u64)
+            .chain(
+                token_sets.tokens[1][neuron]
+                    .iter()
+                    .map(|&token| num_activating + token as u64),
+            )
+            .collect();
+        let signature: Vec<u64> = self
+            .seeds
+            .iter()
+            .map(|&seed| {
+                tokens
+                    .iter()
+                    .map(|&token| splitmix64(token ^ seed))
+                    .min()
+                    .expect("Neurons without tokens are skipped.")
+            })
+            .collect();
+        signature
+            .chunks(self.band_size)
+            .enumerate()
+            .map(|(band, hashes)| {
+                hashes
+                    .iter()
+                    .fold(splitmix64(band as u64), |key, &hash| splitmix64(key ^ hash))
+            })
+            .collect()
+    }
+
+    /// The neurons sharing a band with the given neuron of a store with the same vocabulary.
+    fn neighbours<'a>(
+        &'a self,
+        token_sets: &TokenSets,
+        neuron: usize,
+    ) -> impl Iterator<Item = u32> + 'a {
+        self.band_keys(token_sets, neuron)
+            .into_iter()
+            .filter_map(|key| self.bucket_ids.get(&key))
+            .flat_map(|&bucket| self.buckets[bucket].iter().copied())
+    }
+}
+
+/// Per thread scratch space for finding the candidates of one neuron at a time.
+struct Scratch {
+    /// Shared activating and important tokens of each target neuron with the current neuron.
+    common: Vec<[u32; 2]>,
+    /// Target neurons with an entry in `common` or already taken as candidates.
+    touched: Vec<u32>,
+    seen: Vec<bool>,
+}
+
+impl Scratch {
+    fn new(num_neurons: usize) -> Self {
+        Self {
+            common: vec![[0, 0]; num_neurons],
+            touched: vec![],
+            seen: vec![false; num_neurons],
+        }
+    }
+
+    /// Similarities with all target neurons sharing a token with the neuron, except `skip`.
+    fn exact_candidates(
+        &mut self,
+        query: &TokenSets,
+        target: &TokenSets,
+        metric: SimilarityMetric,
+        neuron: usize,
+        skip: Option<u32>,
+    ) -> Vec<(u32, f32)> {
+        for kind in 0..2 {
+            for &token in &query.tokens[kind][neuron] {
+                for &other in &target.postings[kind][token as usize] {
+                    if Some(other) == skip {
+                        continue;
+                    }
+                    let common = &mut self.common[other as usize];
+                    if *common == [0, 0] {
+                        self.touched.push(other);
+                    }
+                    common[kind] += 1;
+                }
+            }
+        }
+        let sizes = query.sizes(neuron);
+        self.touched
+            .drain(..)
+            .map(|other| {
+                let common = std::mem::take(&mut self.common[other as usize]);
+                let other_sizes = target.sizes(other as usize);
+                (other, metric.similarity(common, sizes, other_sizes))
+            })
+            .collect()
+    }
+
+    /// Similarities with the target neurons sharing a MinHash band and a token with the neuron,
+    /// except `skip`.
+    fn min_hash_candidates(
+        &mut self,
+        query: &TokenSets,
+        target: &TokenSets,
+        buckets: &Buckets,
+        metric: SimilarityMetric,
+        neuron: usize,
+        skip: Option<u32>,
+    ) -> Vec<(u32, f32)> {
+        for other in buckets.neighbours(query, neuron) {
+            if Some(other) != skip && !self.seen[other as usize] {
+                self.seen[other as usize] = true;
+                self.touched.push(other);
+            }
+        }
+        let sizes = query.sizes(neuron);
+        let mut candidates = Vec::with_capacity(self.touched.len());
+        for other in self.touched.drain(..) {
+            self.seen[other as usize] = false;
+            let common = [0, 1].map(|kind| {
+                num_common(
+                    &query.tokens[kind][neuron],
+                    &target.tokens[kind][other as usize],
+                )
+            });
+            if common != [0, 0] {
+                let other_sizes = target.sizes(other as usize);
+                candidates.push((other, metric.similarity(common, sizes, other_sizes)));
+            }
+        }
+        candidates
+    }
+}
+
+/// Finds the similar target neurons of every query neuron. If the query and target are the same
+/// store, neurons are not compared with themselves.
+fn find_similar(
+    query: &TokenSets,
+    target: &TokenSets,
+    same_store: bool,
+    target_layer_size: u32,
+    config: &SimilarityConfig,
+) -> Vec<SimilarNeurons> {
+    let num_neurons = query.num_neurons();
+    let buckets = config
+        .min_hash
+        .map(|min_hash| Buckets::new(target, min_hash));
+    let threads = config
+        .threads
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |threads| threads.get()));
+    log::info!(
+        "Finding similar neurons for {num_neurons} neurons among {} neurons by {} similarity on \
+         {threads} threads{}.",
+        target.num_neurons(),
+        config.metric,
+        if buckets.is_some() {
+            " with MinHash"
+        } else {
+            ""
+        }
+    );
+
+    let next_block = AtomicUsize::new(0);
+    let results = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut scratch = Scratch::new(target.num_neurons());
+                    let mut rows = vec![];
+                    loop {
+                        let block_start = next_block.fetch_add(1, Ordering::Relaxed) * BLOCK_SIZE;
+                        if block_start >= num_neurons {
+                            break rows;
+                        }
+                        for neuron in block_start..(block_start + BLOCK_SIZE).min(num_neurons) {
+                            let skip = same_store.then_some(neuron as u32);
+                            let candidates = match &buckets {
+                                Some(buckets) => scratch.min_hash_candidates(
+                                    query,
+                                    target,
+                                    buckets,
+                                    config.metric,
+                                    neuron,
+                                    skip,
+                                ),
+                                None => scratch.exact_candidates(
+                                    query,
+                                    target,
+                                    config.metric,
+                                    neuron,
+                                    skip,
+                                ),
+                            };
+                            rows.push((neuron, config.select(candidates)));
+                        }
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("Similarity thread panicked."))
+            .collect::<Vec<_>>()
+    });
+
+    let mut similar_neurons = vec![SimilarNeurons::default(); num_neurons];
+    for (neuron, similar) in results.into_iter().flatten() {
+        similar_neurons[neuron] =
+            SimilarNeurons::new(similar.into_iter().map(|(other, similarity)| {
+                (
+                    NeuronIndex::from_flat_index(target_layer_size, other as usize),
+                    similarity,
+                )
+            }));
+    }
+    similar_neurons
+}
+
+impl NeuronStore {
+    /// Finds the similar neurons of every neuron in the store.
+    pub fn neuron_similarity(&self, config: &SimilarityConfig) -> Result<NeuronSimilarity> {
+        config.validate()?;
+        let start = Instant::now();
+        let token_sets = TokenSets::new(self, &Vocabulary::new(self))?;
+        let similar_neurons =
+            find_similar(&token_sets, &token_sets, true, self.layer_size(), config);
+        log::info!("Found similar neurons in {:?}.", start.elapsed());
+        Ok(NeuronSimilarity::new(self.layer_size(), similar_neurons))
+    }
+
+    /// Finds the similar neurons in the other store of every neuron in this store, by the tokens
+    /// both stores have. The stores usually belong to different models.
+    pub fn cross_model_similarity(
+        &self,
+        other: &NeuronStore,
+        config: &SimilarityConfig,
+    ) -> Result<NeuronSimilarity> {
+        config.validate()?;
+        let start = Instant::now();
+        let vocabulary = Vocabulary::shared(self, other);
+        log::info!(
+            "Neuron stores share {} activating and {} important tokens.",
+            vocabulary.0[0].len(),
+            vocabulary.0[1].len()
+        );
+        let query = TokenSets::new(self, &vocabulary)?;
+        let target = TokenSets::new(other, &vocabulary)?;
+        let similar_neurons = find_similar(&query, &target, false, other.layer_size(), config);
+        log::info!("Found similar neurons in {:?}.", start.elapsed());
+        Ok(NeuronSimilarity::new(self.layer_size(), similar_neurons))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn store() -> NeuronStore {
+        let raw: crate::data::NeuronStoreRaw = serde_json::from_value(serde_json::json!({
+            "activating": {
+                "a": ["0_0", "0_1", "0_2"],
+                "b": ["0_1", "0_2"],
+                "c": ["0_1"],
+                "e": ["0_2"],
+            },
+            "important": { "x": ["0_2", "0_3"] },
+        }))
+        .unwrap();
+        NeuronStore::from_raw(raw, 1, 4).unwrap()
+    }
+
+    fn similar(similarity: &NeuronSimilarity, neuron: u32) -> Vec<(u32, f32)> {
+        similarity
+            .similar_neurons(NeuronIndex { layer: 0, neuron })
+            .unwrap()
+            .iter()
+            .map(|(index, similarity)| (index.neuron, (similarity * 1000.).round() / 1000.))
+            .collect()
+    }
+
+    #[test]
+    fn finds_similar_neurons() {
+        let store = store();
+        let config = |metric, threshold, top_k, min_hash| SimilarityConfig {
+            metric,
+            threshold,
+            top_k,
+            threads: Some(2),
+            min_hash,
+        };
+
+        // Neuron 0 has a single token, which neuron 1 shares, so their overlap is complete.
+        let overlap = store
+            .neuron_similarity(&config(SimilarityMetric::Overlap, Some(0.), None, None))
+            .unwrap();
+        assert_eq!(similar(&overlap, 0), vec![(1, 1.), (2, 1.)]);
+        assert_eq!(similar(&overlap, 2), vec![(0, 1.), (3, 1.), (1, 0.667)]);
+
+        let jaccard = store
+            .neuron_similarity(&config(SimilarityMetric::Jaccard, Some(0.3), None, None))
+            .unwrap();
+        assert_eq!(similar(&jaccard, 1), vec![(2, 0.4), (0, 0.333)]);
+        assert_eq!(similar(&jaccard, 3), vec![]);
+
+        let weighted = store
+            .neuron_similarity(&config(
+                SimilarityMetric::Weighted {
+                    important_weight: 3.,
+                },
+                None,
+                Some(2),
+                None,
+            ))
+            .unwrap();
+        assert_eq!(similar(&weighted, 2), vec![(0, 1.), (3, 1.)]);
+        assert_eq!(similar(&weighted, 1), vec![(0, 1.), (2, 0.667)]);
+
+        let min_hash = store
+            .neuron_similarity(&config(
+                SimilarityMetric::Overlap,
+                Some(0.),
+                None,
+                Some(MinHashConfig {
+                    num_bands: 64,
+                    band_size: 1,
+                    seed: 7,
+                }),
+            ))
+            .unwrap();
+        for neuron in 0..4 {
+            assert_eq!(similar(&min_hash, neuron), similar(&overlap, neuron));
+        }
+
+        assert!(store
+            .neuron_similarity(&config(SimilarityMetric::Overlap, None, None, None))
+            .is_err());
+        assert_eq!(
+            "weighted:3".parse::<SimilarityMetric>().unwrap(),
+            SimilarityMetric::Weighted {
+                important_weight: 3.
+            }
+        );
+        assert!("cosine".parse::<SimilarityMetric>().is_err());
+    }
+
+    #[test]
+    fn finds_similar_neurons_in_other_models() {
+        let raw: crate::data::NeuronStoreRaw = serde_json::from_value(serde_json::json!({
+            "activating": {
+                "a": ["0_1", "1_0"],
+                "b": ["1_0"],
+                "z": ["0_0", "0_1"],
+            },
+            "important": { "x": ["1_1"] },
+        }))
+        .unwrap();
+        let other = NeuronStore::from_raw(raw, 2, 2).unwrap();
+        let config = SimilarityConfig {
+            metric: SimilarityMetric::Jaccard,
+            threshold: Some(0.),
+            top_k: None,
+            threads: Some(2),
+            min_hash: None,
+        };
+
+        // Only `a`, `b` and `x` are in both stores, so `z` and `c` do not count.
+        let similarity = store().cross_model_similarity(&other, &config).unwrap();
+        let similar = |neuron| {
+            similarity
+                .similar_neurons(NeuronIndex { layer: 0, neuron })
+                .unwrap()
+                .iter()
+                .map(|(index, similarity)| (index.to_string(), similarity))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            similar(0),
+            vec![("l0n1".to_owned(), 1.), ("l1n0".to_owned(), 0.5)]
+        );
+        assert_eq!(
+            similar(2),
+            vec![
+                ("l1n0".to_owned(), 2. / 3.),
+                ("l0n1".to_owned(), 1. / 3.),
+                ("l1n1".to_owned(), 1. / 3.)
+            ]
+        );
+        assert_eq!(similar(3), vec![("l1n1".to_owned(), 1.)]);
+    }
+}
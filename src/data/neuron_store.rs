@@ -2,10 +2,8 @@ use std::{
     collections::{HashMap, HashSet},
     fmt::Display,
     fs,
-    io::Write,
     path::Path,
     str::FromStr,
-    time::Instant,
 };
 
 use anyhow::{bail, Context, Result};
@@ -13,9 +11,9 @@ use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use snap::raw::{Decoder, Encoder};
 
-use super::NeuronIndex;
+use super::{data_objects::NeuroscopeNeuronPage, NeuronIndex, CONTEXT_RADIUS};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TokenSearchType {
     Activating,
     Important,
@@ -45,7 +43,7 @@ impl Display for TokenSearchType {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TokenSearch {
     pub token: String,
     pub search_types: Vec<TokenSearchType>,
@@ -68,10 +66,10 @@ impl FromStr for TokenSearch {
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
-struct SimilarNeuron {
-    layer: u32,
-    neuron: u32,
-    similarity: f32,
+pub struct SimilarNeuron {
+    pub layer: u32,
+    pub neuron: u32,
+    pub similarity: f32,
 }
 
 impl SimilarNeuron {
@@ -84,13 +82,39 @@ impl SimilarNeuron {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct SimilarNeurons {
     #[serde(rename = "similar")]
     similar_neurons: Vec<SimilarNeuron>,
 }
 
 impl SimilarNeurons {
+    pub(super) fn new(similar_neurons: impl IntoIterator<Item = (NeuronIndex, f32)>) -> Self {
+        Self {
+            similar_neurons: similar_neurons
+                .into_iter()
+                .map(|(neuron_index, similarity)| SimilarNeuron::new(neuron_index, similarity))
+                .collect(),
+        }
+    }
+
+    pub fn similar_neurons(&self) -> &[SimilarNeuron] {
+        &self.similar_neurons
+    }
+
+    /// The similar neurons along with their similarity, most similar first.
+    pub fn iter(&self) -> impl Iterator<Item = (NeuronIndex, f32)> + '_ {
+        self.similar_neurons().iter().map(|similar_neuron| {
+            (
+                NeuronIndex {
+                    layer: similar_neuron.layer,
+                    neuron: similar_neuron.neuron,
+                },
+                similar_neuron.similarity,
+            )
+        })
+    }
+
     pub fn to_binary(&self) -> Result<Vec<u8>> {
         let result = postcard::to_allocvec(self.similar_neurons.as_slice())?;
         Ok(result)
@@ -108,6 +132,13 @@ pub struct NeuronSimilarity {
 }
 
 impl NeuronSimilarity {
+    pub(super) fn new(layer_size: u32, similar_neurons: Vec<SimilarNeurons>) -> Self {
+        Self {
+            layer_size,
+            similar_neurons,
+        }
+    }
+
     pub fn similar_neurons(&self, neuron_index: NeuronIndex) -> Result<&SimilarNeurons> {
         let index = neuron_index.flat_index(self.layer_size);
         self.similar_neurons
@@ -134,6 +165,45 @@ impl NeuronStoreRaw {
     }
 }
 
+/// Thresholds for deriving a neuron store from Neuroscope pages, as fractions of the highest
+/// activation of the neuron over all its texts.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NeuroscopeStoreConfig {
+    /// Tokens activating the neuron at least this much are activating tokens.
+    pub activating_fraction: f32,
+    /// Other tokens within the context window of a text's max-activating token that activate the
+    /// neuron at least this much are important tokens.
+    pub important_fraction: f32,
+}
+
+impl Default for NeuroscopeStoreConfig {
+    fn default() -> Self {
+        Self {
+            activating_fraction: 0.8,
+            important_fraction: 0.4,
+        }
+    }
+}
+
+impl NeuroscopeStoreConfig {
+    pub fn validate(&self) -> Result<()> {
+        let Self {
+            activating_fraction,
+            important_fraction,
+        } = *self;
+        if !(0. < important_fraction
+            && important_fraction <= activating_fraction
+            && activating_fraction <= 1.)
+        {
+            bail!(
+                "Activation fractions must satisfy 0 < important fraction ({important_fraction}) \
+                 <= activating fraction ({activating_fraction}) <= 1."
+            )
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NeuronStore {
     layer_size: u32,
@@ -143,147 +213,62 @@ pub struct NeuronStore {
 }
 
 impl NeuronStore {
-    pub fn neuron_similarity(&self, threshold: f32) -> Result<NeuronSimilarity> {
-        let num_neurons = self.num_layers as usize * self.layer_size as usize;
-
-        println!("Finding activating tokens for all neurons...");
-        std::io::stdout().flush().unwrap();
-        let mut activating_tokens: Vec<Vec<u32>> = (0..num_neurons).map(|_| Vec::new()).collect();
-        for (token_id, (_token, neuron_indices)) in self.activating.iter().enumerate() {
-            for &neuron_index in neuron_indices {
-                let index = neuron_index.flat_index(self.layer_size);
-                activating_tokens
-                    .get_mut(index)
-                    .with_context(|| {
-                        format!(
-                            "Index {index} somehow greater than the number of neurons. This \
-                             should not be possible"
-                        )
-                    })?
-                    .push(token_id as u32);
-            }
-        }
+    pub fn layer_size(&self) -> u32 {
+        self.layer_size
+    }
 
-        println!("Finding important tokens for all neurons...");
-        std::io::stdout().flush().unwrap();
-        let mut important_tokens: Vec<Vec<u32>> = (0..num_neurons).map(|_| Vec::new()).collect();
-        for (token_id, (_token, neuron_indices)) in self.important.iter().enumerate() {
-            for &neuron_index in neuron_indices {
-                let index = neuron_index.flat_index(self.layer_size);
-                important_tokens
-                    .get_mut(index)
-                    .with_context(|| {
-                        format!(
-                            "Index {index} somehow greater than the number of neurons. This \
-                             should not be possible"
-                        )
-                    })?
-                    .push(token_id as u32);
-            }
+    pub fn num_layers(&self) -> u32 {
+        self.num_layers
+    }
+
+    /// A neuron store without any tokens, to be filled with [`NeuronStore::add_neuroscope_page`].
+    pub fn empty(num_layers: u32, layer_size: u32) -> Self {
+        Self {
+            layer_size,
+            num_layers,
+            activating: HashMap::new(),
+            important: HashMap::new(),
         }
+    }
 
-        println!("Finding similar neurons...");
-        std::io::stdout().flush().unwrap();
-        let mut similar_neurons: Vec<SimilarNeurons> = (0..num_neurons)
-            .map(|_| SimilarNeurons {
-                similar_neurons: vec![],
-            })
-            .collect();
-        let start = Instant::now();
-        for (neuron, (this_activating_tokens, this_important_tokens)) in activating_tokens
+    /// Adds the activating and important tokens of a neuron from the texts of its Neuroscope page.
+    /// Neurons that never activate positively get no tokens.
+    pub fn add_neuroscope_page(
+        &mut self,
+        page: &NeuroscopeNeuronPage,
+        config: &NeuroscopeStoreConfig,
+    ) {
+        let neuron_index = page.neuron_index();
+        let max_activation = page
+            .texts()
             .iter()
-            .zip(important_tokens.iter())
-            .enumerate()
-        {
-            let this_neuron_index = NeuronIndex::from_flat_index(self.layer_size, neuron);
-            let neurons_per_second = (neuron as f32) / start.elapsed().as_secs_f32();
-            print!(
-                "Neuron {this_neuron_index}. Neurons per second: {neurons_per_second:.0}        \r"
-            );
-            for (other_neuron, (other_activating_tokens, other_important_tokens)) in
-                activating_tokens
-                    .iter()
-                    .zip(important_tokens.iter())
-                    .enumerate()
-                    .skip(neuron + 1)
+            .map(|text| text.max_activation())
+            .fold(0., f32::max);
+        if max_activation <= 0. {
+            return;
+        }
+        let activating_threshold = config.activating_fraction * max_activation;
+        let important_threshold = config.important_fraction * max_activation;
+        for text in page.texts() {
+            let peak = text.max_activating_token_index() as usize;
+            let window =
+                peak.saturating_sub(CONTEXT_RADIUS as usize)..=peak + CONTEXT_RADIUS as usize;
+            for (position, (token, &activation)) in
+                text.tokens().iter().zip(text.activations()).enumerate()
             {
-                let mut total_common = 0;
-
-                let mut this_activating_iter = this_activating_tokens.iter().copied().peekable();
-                let mut other_activating_iter = other_activating_tokens.iter().copied().peekable();
-                while let (Some(this_activating), Some(other_activating)) =
-                    (this_activating_iter.peek(), other_activating_iter.peek())
-                {
-                    match this_activating.cmp(other_activating) {
-                        std::cmp::Ordering::Less => {
-                            this_activating_iter.next();
-                        }
-                        std::cmp::Ordering::Equal => {
-                            total_common += 1;
-                            this_activating_iter.next();
-                            other_activating_iter.next();
-                        }
-                        std::cmp::Ordering::Greater => {
-                            other_activating_iter.next();
-                        }
-                    }
-                }
-                let mut this_important_iter = this_important_tokens.iter().copied().peekable();
-                let mut other_important_iter = other_important_tokens.iter().copied().peekable();
-                while let (Some(this_important), Some(other_important)) =
-                    (this_important_iter.peek(), other_important_iter.peek())
-                {
-                    match this_important.cmp(other_important) {
-                        std::cmp::Ordering::Less => {
-                            this_important_iter.next();
-                        }
-                        std::cmp::Ordering::Equal => {
-                            total_common += 1;
-                            this_important_iter.next();
-                            other_important_iter.next();
-                        }
-                        std::cmp::Ordering::Greater => {
-                            other_important_iter.next();
-                        }
-                    }
-                }
-
-                let possible_common = activating_tokens.len().min(other_activating_tokens.len())
-                    + important_tokens.len().min(other_important_tokens.len());
-                let similarity = (total_common as f32) / (possible_common as f32);
-                if similarity >= threshold {
-                    let other_neuron_index =
-                        NeuronIndex::from_flat_index(self.layer_size, other_neuron);
-                    similar_neurons
-                        .get_mut(neuron)
-                        .with_context(|| {
-                            format!(
-                                "Index {neuron} of neuron somehow greater than the number of \
-                                 neurons. This should not be possible"
-                            )
-                        })?
-                        .similar_neurons
-                        .push(SimilarNeuron::new(other_neuron_index, similarity));
-                    similar_neurons
-                        .get_mut(other_neuron)
-                        .with_context(|| {
-                            format!(
-                                "Index {other_neuron} of other neuron somehow greater than the \
-                                 number of neurons. This should not be possible"
-                            )
-                        })?
-                        .similar_neurons
-                        .push(SimilarNeuron::new(this_neuron_index, similarity));
-                }
+                let tokens = if activation >= activating_threshold {
+                    &mut self.activating
+                } else if activation >= important_threshold && window.contains(&position) {
+                    &mut self.important
+                } else {
+                    continue;
+                };
+                tokens
+                    .entry(token.clone())
+                    .or_default()
+                    .insert(neuron_index);
             }
         }
-        println!("Found similar neurons.                                       ");
-        std::io::stdout().flush().unwrap();
-
-        Ok(NeuronSimilarity {
-            layer_size: self.layer_size,
-            similar_neurons,
-        })
     }
 
     pub fn from_raw(raw: NeuronStoreRaw, num_layers: u32, layer_size: u32) -> Result<Self> {
@@ -346,4 +331,81 @@ impl NeuronStore {
             TokenSearchType::Important => self.important.get(token),
         }
     }
+
+    /// The tokens of the given search type of the neuron, in order.
+    pub fn neuron_tokens(&self, search_type: TokenSearchType, neuron: NeuronIndex) -> Vec<&str> {
+        let mut tokens: Vec<&str> = self
+            .tokens(search_type)
+            .filter(|(_, neurons)| neurons.contains(&neuron))
+            .map(|(token, _)| token)
+            .collect();
+        tokens.sort_unstable();
+        tokens
+    }
+
+    /// All tokens of the given search type along with the neurons they belong to.
+    pub fn tokens(
+        &self,
+        search_type: TokenSearchType,
+    ) -> impl Iterator<Item = (&str, &HashSet<NeuronIndex>)> {
+        match search_type {
+            TokenSearchType::Activating => &self.activating,
+            TokenSearchType::Important => &self.important,
+        }
+        .iter()
+        .map(|(token, neurons)| (token.as_str(), neurons))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn derives_from_neuroscope_pages() {
+        let text = |tokens: &[&str], activations: &[f32], peak: u32| {
+            serde_json::json!({
+                "min_range": 0.,
+                "max_range": 4.,
+                "min_activation": activations.iter().copied().fold(f32::INFINITY, f32::min),
+                "max_activation": activations.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+                "data_index": 0,
+                "max_activating_token_index": peak,
+                "tokens": tokens,
+                "activations": activations,
+            })
+        };
+        let page: NeuroscopeNeuronPage = serde_json::from_value(serde_json::json!({
+            "neuron_index": { "layer": 1, "neuron": 2 },
+            "texts": [
+                text(&[" of", " the", " cat"], &[2., 4., 0.5], 1),
+                text(&[" a", " dog", " the"], &[1.8, 3.5, 0.], 1),
+            ],
+        }))
+        .unwrap();
+        let mut store = NeuronStore::empty(2, 4);
+        store.add_neuroscope_page(&page, &NeuroscopeStoreConfig::default());
+
+        let neuron = NeuronIndex {
+            layer: 1,
+            neuron: 2,
+        };
+        let tokens = |search_type| {
+            let mut tokens: Vec<_> = store
+                .tokens(search_type)
+                .filter(|(_, neurons)| neurons.contains(&neuron))
+                .map(|(token, _)| token.to_owned())
+                .collect();
+            tokens.sort();
+            tokens
+        };
+        assert_eq!(tokens(TokenSearchType::Activating), vec![" dog", " the"]);
+        assert_eq!(tokens(TokenSearchType::Important), vec![" a", " of"]);
+        assert!(NeuroscopeStoreConfig {
+            activating_fraction: 0.3,
+            important_fraction: 0.5,
+        }
+        .validate()
+        .is_err());
+    }
 }
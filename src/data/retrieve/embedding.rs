@@ -0,0 +1,357 @@
+//! Embedding vectors of neurons, read from `.npy` files or computed from the token sets of a
+//! neuron store.
+
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context, Result};
+use regex::Regex;
+use tokio::fs;
+
+use super::neuron_store::model_neuron_store;
+use crate::{
+    data::{
+        data_objects::{DataObject, Embedding, EmbeddingPrecision},
+        data_types::DataType,
+        neuron_similarity::splitmix64,
+        DataTypeHandle, ModelHandle, NeuronStore, TokenSearchType,
+    },
+    util::cancel,
+    Index,
+};
+
+/// Default number of dimensions of embeddings computed from a neuron store.
+pub const DEFAULT_TOKEN_EMBEDDING_DIMENSIONS: usize = 256;
+
+/// The shape and values of an array of floats in the `.npy` format, converted to `f32`.
+fn parse_npy(bytes: &[u8]) -> Result<(Vec<usize>, Vec<f32>)> {
+    const MAGIC: &[u8] = b"\x93NUMPY";
+
+    ensure!(
+        bytes.len() >= 10 && bytes.starts_with(MAGIC),
+        "Not a .npy file."
+    );
+    let (header_length, header_start) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        2 | 3 => {
+            ensure!(bytes.len() >= 12, "Truncated .npy header.");
+            (
+                u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize,
+                12,
+            )
+        }
+        version => bail!("Unsupported .npy format version {version}."),
+    };
+    let data_start = header_start + header_length;
+    ensure!(bytes.len() >= data_start, "Truncated .npy header.");
+    let header = std::str::from_utf8(&bytes[header_start..data_start])
+        .context("The .npy header is not valid text.")?;
+
+    let field = |name: &str, pattern: &str| -> Result<String> {
+        let regex = Regex::new(&format!(r#"['"]{name}['"]\s*:\s*{pattern}"#))
+            .context("Failed to compile regex. This should never happen.")?;
+        regex
+            .captures(header)
+            .and_then(|captures| captures.get(1))
+            .map(|capture| capture.as_str().to_owned())
+            .with_context(|| format!("The .npy header has no valid '{name}' field."))
+    };
+    let descr = field("descr", r#"['"]([^'"]*)['"]"#)?;
+    if field("fortran_order", r"(True|False)")? == "True" {
+        bail!("Arrays in Fortran order are not supported.")
+    }
+    let shape = field("shape", r"\(([^)]*)\)")?
+        .split(',')
+        .map(str::trim)
+        .filter(|dimension| !dimension.is_empty())
+        .map(|dimension| {
+            dimension
+                .parse::<usize>()
+                .with_context(|| format!("Invalid dimension '{dimension}' in .npy shape."))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let data = &bytes[data_start..];
+    let num_values: usize = shape.iter().product();
+    let (little_endian, size) = match descr.as_str() {
+        "<f2" => (true, 2),
+        ">f2" => (false, 2),
+        "<f4" => (true, 4),
+        ">f4" => (false, 4),
+        "<f8" => (true, 8),
+        ">f8" => (false, 8),
+        _ => bail!("Unsupported .npy data type '{descr}'. Expected 16, 32 or 64 bit floats."),
+    };
+    ensure!(
+        data.len() == num_values * size,
+        "The .npy file should have {num_values} values of {size} bytes for shape {shape:?}, but \
+         has {} bytes of data.",
+        data.len()
+    );
+    let values = data
+        .chunks_exact(size)
+        .map(|chunk| match (size, little_endian) {
+            (2, true) => half::f16::from_le_bytes([chunk[0], chunk[1]]).to_f32(),
+            (2, false) => half::f16::from_be_bytes([chunk[0], chunk[1]]).to_f32(),
+            (4, true) => f32::from_le_bytes(chunk.try_into().expect("Chunk has 4 bytes.")),
+            (4, false) => f32::from_be_bytes(chunk.try_into().expect("Chunk has 4 bytes.")),
+            (_, true) => f64::from_le_bytes(chunk.try_into().expect("Chunk has 8 bytes.")) as f32,
+            (_, false) => f64::from_be_bytes(chunk.try_into().expect("Chunk has 8 bytes.")) as f32,
+        })
+        .collect();
+    Ok((shape, values))
+}
+
+/// The embedding data object with the given name, created if it does not exist. Fails if a data
+/// object with the name exists but does not hold embeddings, or holds them with another precision
+/// than the one given.
+async fn embedding_data_type(
+    model_handle: &mut ModelHandle,
+    data_type_name: &str,
+    precision: Option<EmbeddingPrecision>,
+) -> Result<DataTypeHandle> {
+    let database = model_handle.database().clone();
+    let data_type = match database.data_type(data_type_name).await? {
+        Some(data_type) => {
+            match (data_type.data_type(), precision) {
+                (DataType::Embedding { precision }, Some(requested)) if *precision != requested => {
+                    bail!(
+                        "Data object '{data_type_name}' holds {precision} embeddings, not \
+                         {requested}."
+                    )
+                }
+                (DataType::Embedding { .. }, _) => {}
+                _ => bail!("Data object '{data_type_name}' does not hold embeddings."),
+            }
+            data_type
+        }
+        None => {
+            database
+                .add_data_type(
+                    data_type_name,
+                    DataType::Embedding {
+                        precision: precision.unwrap_or_default(),
+                    },
+                )
+                .await?
+        }
+    };
+    if !model_handle.has_data_type(&data_type).await? {
+        model_handle
+            .add_data_type(&data_type)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to add embedding data object '{data_type_name}' to model '{}'.",
+                    model_handle.name()
+                )
+            })?;
+    }
+    Ok(data_type)
+}
+
+/// Stores the embeddings of all neurons of the model, given in order of their flat index, under
+/// the data object with the given name. Existing embeddings of the data object are replaced.
+pub async fn store_embeddings(
+    model_handle: &mut ModelHandle,
+    data_type_name: &str,
+    precision: Option<EmbeddingPrecision>,
+    dimensions: usize,
+    values: &[f32],
+) -> Result<()> {
+    let model_name = model_handle.name().to_owned();
+    let num_neurons = model_handle.metadata().num_total_neurons as usize;
+    ensure!(
+        dimensions > 0,
+        "Embeddings must have at least one dimension."
+    );
+    ensure!(
+        values.len() == num_neurons * dimensions,
+        "Expected {dimensions} values for each of the {num_neurons} neurons of model \
+         '{model_name}', but got {} values.",
+        values.len()
+    );
+    ensure!(
+        values.iter().all(|value| value.is_finite()),
+        "Embeddings have values that are not finite."
+    );
+    let data_type = embedding_data_type(model_handle, data_type_name, precision).await?;
+    let DataType::Embedding { precision } = *data_type.data_type() else {
+        unreachable!("Data object was checked to hold embeddings.")
+    };
+
+    for (neuron_index, embedding) in model_handle
+        .metadata()
+        .neuron_indices()
+        .zip(values.chunks(dimensions))
+        .collect::<Vec<_>>()
+    {
+        cancel::check_cancelled()?;
+        let data = Embedding::new(embedding, precision).to_binary()?;
+        model_handle
+            .replace_data(&data_type, Index::from(neuron_index), data)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to store embedding of neuron {neuron_index} in model '{model_name}'."
+                )
+            })?;
+    }
+    Ok(())
+}
+
+/// Stores embeddings given as the bytes of a `.npy` file of floats with shape
+/// `(num_layers, layer_size, dimensions)` or `(num_total_neurons, dimensions)`.
+pub async fn store_npy_embeddings(
+    model_handle: &mut ModelHandle,
+    data_type_name: &str,
+    precision: Option<EmbeddingPrecision>,
+    npy: &[u8],
+) -> Result<()> {
+    let (shape, values) = parse_npy(npy)?;
+    let metadata = model_handle.metadata();
+    let (num_layers, layer_size) = (metadata.num_layers as usize, metadata.layer_size as usize);
+    let dimensions = match shape[..] {
+        [layers, neurons, dimensions] if layers == num_layers && neurons == layer_size => {
+            dimensions
+        }
+        [neurons, dimensions] if neurons == num_layers * layer_size => dimensions,
+        _ => bail!(
+            "Embeddings of model '{}' should have shape ({num_layers}, {layer_size}, dimensions) \
+             or ({}, dimensions). Found {shape:?}.",
+            model_handle.name(),
+            num_layers * layer_size
+        ),
+    };
+    store_embeddings(model_handle, data_type_name, precision, dimensions, &values).await
+}
+
+/// Stores embeddings read from a `.npy` file. See [`store_npy_embeddings`].
+pub async fn retrieve_npy_embeddings(
+    model_handle: &mut ModelHandle,
+    data_type_name: &str,
+    precision: Option<EmbeddingPrecision>,
+    path: impl AsRef<Path>,
+) -> Result<()> {
+    let path = path.as_ref();
+    let npy = fs::read(path)
+        .await
+        .with_context(|| format!("Failed to read embeddings from '{}'.", path.display()))?;
+    store_npy_embeddings(model_handle, data_type_name, precision, &npy).await
+}
+
+/// A stable 64 bit hash of a token.
+fn hash_token(token: &str) -> u64 {
+    // FNV-1a, as the hashes of the standard library may change between releases.
+    token.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Embeds each neuron of the store by hashing its activating and important tokens into a vector
+/// of the given number of dimensions, with the two kinds of tokens hashed differently. Each token
+/// adds 1 to one dimension, so neurons sharing many tokens get similar embeddings. The embeddings
+/// are scaled to length 1, except those of neurons without tokens.
+fn token_embeddings(
+    neuron_store: &NeuronStore,
+    num_layers: u32,
+    layer_size: u32,
+    dimensions: usize,
+) -> Vec<f32> {
+    let mut values = vec![0f32; (num_layers * layer_size) as usize * dimensions];
+    for (salt, search_type) in [
+        (0, TokenSearchType::Activating),
+        (1, TokenSearchType::Important),
+    ] {
+        for (token, neurons) in neuron_store.tokens(search_type) {
+            let hash = splitmix64(hash_token(token) ^ salt);
+            let dimension = (hash % dimensions as u64) as usize;
+            for neuron in neurons {
+                values[neuron.flat_index(layer_size) * dimensions + dimension] += 1.;
+            }
+        }
+    }
+    for embedding in values.chunks_mut(dimensions) {
+        let norm = embedding
+            .iter()
+            .map(|value| value * value)
+            .sum::<f32>()
+            .sqrt();
+        if norm > 0. {
+            embedding.iter_mut().for_each(|value| *value /= norm);
+        }
+    }
+    values
+}
+
+/// Computes embeddings of the neurons of the model from its neuron store. See
+/// [`token_embeddings`].
+pub async fn compute_token_embeddings(
+    model_handle: &mut ModelHandle,
+    data_type_name: &str,
+    precision: Option<EmbeddingPrecision>,
+    dimensions: usize,
+) -> Result<()> {
+    ensure!(
+        dimensions > 0,
+        "Embeddings must have at least one dimension."
+    );
+    let neuron_store = model_neuron_store(model_handle).await?;
+    let metadata = model_handle.metadata();
+    let values = token_embeddings(
+        &neuron_store,
+        metadata.num_layers,
+        metadata.layer_size,
+        dimensions,
+    );
+    store_embeddings(model_handle, data_type_name, precision, dimensions, &values)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to store embeddings computed from the neuron store of model '{}'.",
+                model_handle.name()
+            )
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::NeuronStoreRaw;
+
+    #[test]
+    fn reads_npy_and_embeds_tokens() {
+        let mut npy = b"\x93NUMPY\x01\x00".to_vec();
+        let header = "{'descr': '<f4', 'fortran_order': False, 'shape': (2, 3), }";
+        let header = format!("{header:<117}\n");
+        npy.extend((header.len() as u16).to_le_bytes());
+        npy.extend(header.as_bytes());
+        for value in [0., 1., 2., 3., 4., 5.5f32] {
+            npy.extend(value.to_le_bytes());
+        }
+        let (shape, values) = parse_npy(&npy).unwrap();
+        assert_eq!(shape, vec![2, 3]);
+        assert_eq!(values, vec![0., 1., 2., 3., 4., 5.5]);
+        assert!(parse_npy(&npy[..npy.len() - 1]).is_err());
+        assert!(parse_npy(b"not an npy file").is_err());
+
+        let raw: NeuronStoreRaw = serde_json::from_value(serde_json::json!({
+            "activating": { "he": ["0_0", "0_1"], "she": ["0_0", "0_1"], "it": ["1_0"] },
+            "important": { "he": ["0_0"] },
+        }))
+        .unwrap();
+        let store = NeuronStore::from_raw(raw, 2, 2).unwrap();
+        let values = token_embeddings(&store, 2, 2, 64);
+        let embedding = |flat_index: usize| &values[flat_index * 64..(flat_index + 1) * 64];
+        let similarity = |a: usize, b: usize| -> f32 {
+            embedding(a)
+                .iter()
+                .zip(embedding(b))
+                .map(|(a, b)| a * b)
+                .sum()
+        };
+        assert!((similarity(0, 0) - 1.).abs() < 1e-6);
+        assert!(similarity(0, 1) > 0.5);
+        assert_eq!(similarity(1, 2), 0.);
+        assert!(embedding(3).iter().all(|&value| value == 0.));
+    }
+}
@@ -9,12 +9,12 @@ use crate::{
     Index,
 };
 
-pub async fn store_json_data(
-    model_handle: &mut ModelHandle,
+fn json_data_binary(
+    model_handle: &ModelHandle,
     data_type_handle: &DataTypeHandle,
     index: Index,
     json: serde_json::Value,
-) -> Result<()> {
+) -> Result<Vec<u8>> {
     let model_name = model_handle.name();
     let data_type_name = data_type_handle.name();
     match data_type_handle.data_type() {
@@ -22,17 +22,34 @@ pub async fn store_json_data(
         _ => bail!("Data object must have type JSON."),
     }
     let data = JsonData::new(json);
-    model_handle
-        .add_data(
-            data_type_handle,
-            index,
-            data.to_binary().with_context(|| {
-                format!(
-                    "Failed to serialize JSON data of data object '{data_type_name}' for {index} \
-                     in model '{model_name}'.",
-                    index = index.error_string()
-                )
-            })?,
+    data.to_binary().with_context(|| {
+        format!(
+            "Failed to serialize JSON data of data object '{data_type_name}' for {index} in model \
+             '{model_name}'.",
+            index = index.error_string()
         )
+    })
+}
+
+pub async fn store_json_data(
+    model_handle: &mut ModelHandle,
+    data_type_handle: &DataTypeHandle,
+    index: Index,
+    json: serde_json::Value,
+) -> Result<()> {
+    let data = json_data_binary(model_handle, data_type_handle, index, json)?;
+    model_handle.add_data(data_type_handle, index, data).await
+}
+
+/// Like [`store_json_data`], but replaces any JSON data already stored for the index.
+pub async fn replace_json_data(
+    model_handle: &mut ModelHandle,
+    data_type_handle: &DataTypeHandle,
+    index: Index,
+    json: serde_json::Value,
+) -> Result<()> {
+    let data = json_data_binary(model_handle, data_type_handle, index, json)?;
+    model_handle
+        .replace_data(data_type_handle, index, data)
         .await
 }
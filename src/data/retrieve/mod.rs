@@ -1,5 +1,7 @@
+pub mod embedding;
 pub mod json;
 pub mod neuron2graph;
+pub mod neuron_clusters;
 pub mod neuron_explainer;
 pub mod neuron_store;
 pub mod neuroscope;
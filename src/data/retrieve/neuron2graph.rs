@@ -4,10 +4,14 @@ use anyhow::{bail, Context, Result};
 use regex::Regex;
 use tokio::fs;
 
-use crate::data::{
-    data_objects::{DataObject, Graph},
-    data_types::DataType,
-    DataTypeHandle, ModelHandle, NeuronIndex,
+use crate::{
+    data::{
+        data_objects::{DataObject, Graph},
+        data_types::DataType,
+        DataTypeHandle, Database, ModelHandle, NeuronIndex,
+    },
+    util::cancel,
+    Index,
 };
 
 fn neuron_path(root: impl AsRef<Path>, neuron_index: NeuronIndex) -> PathBuf {
@@ -17,6 +21,27 @@ fn neuron_path(root: impl AsRef<Path>, neuron_index: NeuronIndex) -> PathBuf {
         .join("graph")
 }
 
+fn parse_graph(graph_string: &str, model_name: &str, neuron_index: NeuronIndex) -> Result<Graph> {
+    let regex = Regex::new(r#"\[label=([^"]\S*)"#)
+        .context("Failed to compile regex. This should never happen.")?;
+    let graph_str = regex.replace_all(graph_string, r#"[label="$1""#);
+    let graph = match graphviz_rust::parse(graph_str.as_ref()) {
+        Ok(graph) => graph,
+        Err(parse_error) => {
+            bail!(
+                "Failed to parse graph for neuron {neuron_index} in model '{model_name}'. Error: \
+                 '{parse_error}'"
+            )
+        }
+    };
+    Graph::from_dot(graph).with_context(|| {
+        format!(
+            "Succesfully parsed graph, but graph is not a valid neuron2graph grpah. Neuron \
+             {neuron_index} in model '{model_name}'."
+        )
+    })
+}
+
 async fn retrieve_neuron2graph_neuron(
     model_handle: &mut ModelHandle,
     data_type: &DataTypeHandle,
@@ -25,28 +50,7 @@ async fn retrieve_neuron2graph_neuron(
 ) -> Result<bool> {
     let path = neuron_path(root, neuron_index);
     let graph: Graph = match fs::read_to_string(path).await {
-        Ok(graph_string) => {
-            let regex = Regex::new(r#"\[label=([^"]\S*)"#)
-                .context("Failed to compile regex. This should never happen.")?;
-            let graph_str = regex.replace_all(graph_string.as_str(), r#"[label="$1""#);
-            let graph = match graphviz_rust::parse(graph_str.as_ref()) {
-                Ok(graph) => graph,
-                Err(parse_error) => {
-                    bail!(
-                        "Failed to parse graph for neuron {neuron_index} in model '{}'. Error: \
-                         '{parse_error}'",
-                        model_handle.name()
-                    )
-                }
-            };
-            Graph::from_dot(graph).with_context(|| {
-                format!(
-                    "Succesfully parsed graph, but graph is not a valid neuron2graph grpah. \
-                     Neuron {neuron_index} in model '{}'.",
-                    model_handle.name()
-                )
-            })?
-        }
+        Ok(graph_string) => parse_graph(graph_string.as_str(), model_handle.name(), neuron_index)?,
         Err(read_err) => {
             if read_err.kind() == std::io::ErrorKind::NotFound {
                 return Ok(false);
@@ -72,6 +76,44 @@ async fn retrieve_neuron2graph_neuron(
         .map(|_| true)
 }
 
+async fn neuron2graph_data_type(database: &Database) -> Result<DataTypeHandle> {
+    if let Some(data_type) = database.data_type("neuron2graph").await? {
+        Ok(data_type)
+    } else {
+        database
+            .add_data_type("neuron2graph", DataType::Neuron2Graph)
+            .await
+    }
+}
+
+/// Stores a single neuron2graph graph given in the DOT format, adding the neuron2graph data object
+/// to the model if it does not already have it. Any existing graph for the neuron is replaced.
+pub async fn store_neuron2graph_graph(
+    model_handle: &mut ModelHandle,
+    neuron_index: NeuronIndex,
+    graph_string: &str,
+) -> Result<()> {
+    Index::from(neuron_index).valid_in_model(model_handle.metadata())?;
+    let graph = parse_graph(graph_string, model_handle.name(), neuron_index)?;
+
+    let data_type = neuron2graph_data_type(model_handle.database()).await?;
+    if !model_handle.has_data_type(&data_type).await? {
+        model_handle
+            .add_data_type(&data_type)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to add neuron2graph data object to model '{}'.",
+                    model_handle.name(),
+                )
+            })?
+    }
+
+    model_handle
+        .replace_data(&data_type, neuron_index.into(), graph.to_binary()?)
+        .await
+}
+
 pub async fn retrieve_neuron2graph(
     model_handle: &mut ModelHandle,
     path: impl AsRef<Path>,
@@ -82,15 +124,7 @@ pub async fn retrieve_neuron2graph(
         bail!("Path '{}' is not a directory.", path.display())
     }
 
-    let database = model_handle.database();
-
-    let data_type = if let Some(data_type) = database.data_type("neuron2graph").await? {
-        data_type
-    } else {
-        database
-            .add_data_type("neuron2graph", DataType::Neuron2Graph)
-            .await?
-    };
+    let data_type = neuron2graph_data_type(model_handle.database()).await?;
 
     if model_handle.has_data_type(&data_type).await? {
         bail!(
@@ -118,6 +152,7 @@ pub async fn retrieve_neuron2graph(
     print!("Storing neuron graphs: 0/{num_total_neurons}");
     let mut num_missing = 0;
     for neuron_index in model_handle.metadata().neuron_indices() {
+        cancel::check_cancelled()?;
         if !retrieve_neuron2graph_neuron(model_handle, &data_type, path, neuron_index).await? {
             num_missing += 1
         }
@@ -0,0 +1,184 @@
+//! Clusters of the graph formed by the similar neurons of each neuron in a model, along with the
+//! centrality and role of each neuron in it.
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    data::{
+        data_objects::{DataObject, NeuronCluster, NeuronClusters, NeuronGraphNode},
+        data_types::{self, DataType},
+        ClusteringConfig, DataTypeHandle, ModelHandle, NeuronIndex, SimilarNeuron, SimilarityGraph,
+    },
+    util::cancel,
+    Index,
+};
+
+/// Name of the data object holding the clusters of the similarity graph of each model.
+pub const NEURON_CLUSTERS: &str = "neuron_clusters";
+
+async fn neuron_clusters_data_type(model_handle: &mut ModelHandle) -> Result<DataTypeHandle> {
+    let database = model_handle.database().clone();
+    let data_type = match database.data_type(NEURON_CLUSTERS).await? {
+        Some(data_type) => {
+            if !matches!(data_type.data_type(), DataType::NeuronClusters) {
+                bail!("Data object '{NEURON_CLUSTERS}' does not hold neuron clusters.")
+            }
+            data_type
+        }
+        None => {
+            database
+                .add_data_type(NEURON_CLUSTERS, DataType::NeuronClusters)
+                .await?
+        }
+    };
+    if !model_handle.has_data_type(&data_type).await? {
+        model_handle
+            .add_data_type(&data_type)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to add neuron clusters data object to model '{}'.",
+                    model_handle.name()
+                )
+            })?;
+    }
+    Ok(data_type)
+}
+
+/// Builds the similarity graph of the model from the similar neurons in its neuron store, clusters
+/// it and stores the clusters along with the centrality and role of each neuron, replacing any
+/// previous clusters.
+pub async fn store_neuron_clusters(
+    model_handle: &mut ModelHandle,
+    clustering_config: &ClusteringConfig,
+) -> Result<()> {
+    clustering_config.validate()?;
+    let model_name = model_handle.name().to_owned();
+    let metadata = model_handle.metadata().clone();
+    let store_data_type = model_handle
+        .database()
+        .data_type("neuron_store")
+        .await?
+        .context("Database has no neuron store data object.")?;
+    if !model_handle.has_data_type(&store_data_type).await? {
+        bail!("Model '{model_name}' has no neuron store.")
+    }
+    let similarities = model_handle
+        .data_type::<data_types::NeuronStore>(&store_data_type)
+        .await?
+        .all_similarities()
+        .await
+        .with_context(|| format!("Failed to load similar neurons of model '{model_name}'."))?;
+
+    // Building and clustering the graph of a large model takes a while.
+    let layer_size = metadata.layer_size;
+    let clustering_config = clustering_config.clone();
+    let (graph, analysis, clusters) = tokio::task::spawn_blocking(move || {
+        let graph = SimilarityGraph::new(
+            metadata.num_total_neurons as usize,
+            similarities
+                .iter()
+                .flat_map(|(neuron_index, similar_neurons)| {
+                    let flat_index = neuron_index.flat_index(layer_size);
+                    similar_neurons.iter().map(move |(other, similarity)| {
+                        (flat_index, other.flat_index(layer_size), similarity)
+                    })
+                }),
+            clustering_config.min_similarity,
+        );
+        let analysis = graph.analyse(clustering_config.resolution);
+
+        let mut clusters: Vec<_> = (0..analysis.num_clusters as u32)
+            .map(|id| NeuronCluster {
+                id,
+                size: 0,
+                internal_weight: 0.,
+                layer_sizes: vec![0; metadata.num_layers as usize],
+                members: vec![],
+            })
+            .collect();
+        for (flat_index, node) in analysis.nodes.iter().enumerate() {
+            let Some(cluster_id) = node.cluster else {
+                continue;
+            };
+            let neuron_index = NeuronIndex::from_flat_index(layer_size, flat_index);
+            let cluster = &mut clusters[cluster_id as usize];
+            cluster.size += 1;
+            cluster.layer_sizes[neuron_index.layer as usize] += 1;
+            cluster.members.push(neuron_index);
+            cluster.internal_weight += graph
+                .neighbours(flat_index)
+                .iter()
+                .filter(|&&(neighbour, _)| {
+                    neighbour > flat_index && analysis.nodes[neighbour].cluster == Some(cluster_id)
+                })
+                .map(|&(_, weight)| weight)
+                .sum::<f32>();
+        }
+        for cluster in clusters.iter_mut() {
+            cluster.members.sort_by(|neuron_a, neuron_b| {
+                let pagerank =
+                    |neuron: &NeuronIndex| analysis.nodes[neuron.flat_index(layer_size)].pagerank;
+                pagerank(neuron_b)
+                    .total_cmp(&pagerank(neuron_a))
+                    .then(neuron_a.cmp(neuron_b))
+            });
+        }
+        let clusters = NeuronClusters {
+            modularity: analysis.modularity,
+            resolution: clustering_config.resolution,
+            min_similarity: clustering_config.min_similarity,
+            num_edges: graph.num_edges() as u64,
+            clusters,
+        };
+        (graph, analysis, clusters)
+    })
+    .await
+    .context("Clustering the similarity graph panicked.")?;
+    log::info!(
+        "Found {} clusters among the neurons of model '{model_name}' with modularity {:.3}.",
+        analysis.num_clusters,
+        analysis.modularity
+    );
+
+    let data_type = neuron_clusters_data_type(model_handle).await?;
+    model_handle
+        .replace_data(&data_type, Index::Model, clusters.to_binary()?)
+        .await
+        .with_context(|| format!("Failed to store neuron clusters of model '{model_name}'."))?;
+    for (flat_index, node) in analysis.nodes.into_iter().enumerate() {
+        cancel::check_cancelled()?;
+        let neuron_index = NeuronIndex::from_flat_index(layer_size, flat_index);
+        let node = NeuronGraphNode {
+            cluster: node.cluster,
+            role: node.role,
+            degree: node.degree,
+            strength: node.strength,
+            pagerank: node.pagerank,
+            participation: node.participation,
+            within_cluster_degree: node.within_cluster_degree,
+            neighbours: graph
+                .neighbours(flat_index)
+                .iter()
+                .map(|&(neighbour, weight)| {
+                    let NeuronIndex { layer, neuron } =
+                        NeuronIndex::from_flat_index(layer_size, neighbour);
+                    SimilarNeuron {
+                        layer,
+                        neuron,
+                        similarity: weight,
+                    }
+                })
+                .collect(),
+        };
+        model_handle
+            .replace_data(&data_type, Index::from(neuron_index), node.to_binary()?)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to store graph node of neuron {neuron_index} in model '{model_name}'."
+                )
+            })?;
+    }
+    Ok(())
+}
@@ -10,11 +10,12 @@ use tokio::{sync::Semaphore, task::JoinSet};
 
 use crate::{
     data::{
-        data_objects::{DataObject, NeuronExplainerPage},
+        data_objects::{DataObject, NeuronExplainerPage, NeuronExplainerSummary},
         data_types::DataType,
         DataTypeHandle, ModelHandle, NeuronIndex,
     },
-    util::Progress,
+    util::{cancel, Progress},
+    Index,
 };
 
 const SMALL_NUM_LAYERS: u32 = 12;
@@ -67,6 +68,7 @@ async fn fetch(
     let mut progress = Progress::start((num_layers * layer_size) as u64, "Fetching data");
     progress.print();
     for index in NeuronIndex::iter(num_layers, layer_size) {
+        cancel::check_cancelled()?;
         if model_handle
             .neuron_data(data_type, index.layer, index.neuron)
             .await?
@@ -110,6 +112,7 @@ async fn fetch(
     }
 
     while let Some(join_result) = join_set.join_next().await {
+        cancel::check_cancelled()?;
         let (NeuronIndex { layer, neuron }, page) = match join_result {
             Ok(scrape_result) => scrape_result,
             Err(join_error) => {
@@ -132,6 +135,76 @@ async fn fetch(
     Ok(())
 }
 
+/// Summarises the explanations of the layer and stores the summary as the layer data of the data
+/// object, replacing any earlier summary. Returns the pages of the layer.
+async fn summarise_layer_pages(
+    model_handle: &mut ModelHandle,
+    data_type: &DataTypeHandle,
+    layer_index: u32,
+) -> Result<Vec<(NeuronIndex, NeuronExplainerPage)>> {
+    let layer_size = model_handle.metadata().layer_size;
+    let mut layer_pages = Vec::new();
+    for neuron_index in 0..layer_size {
+        if let Some(data) = model_handle
+            .neuron_data(data_type, layer_index, neuron_index)
+            .await?
+        {
+            let index = NeuronIndex {
+                layer: layer_index,
+                neuron: neuron_index,
+            };
+            layer_pages.push((index, NeuronExplainerPage::from_binary(data)?));
+        }
+    }
+    let layer_summary = NeuronExplainerSummary::new(layer_size, &layer_pages);
+    model_handle
+        .replace_data(
+            data_type,
+            Index::Layer(layer_index),
+            layer_summary.to_binary()?,
+        )
+        .await?;
+    Ok(layer_pages)
+}
+
+/// Summarises the explanations of a single layer, replacing its earlier summary. Used when a
+/// neuron's explanation is stored on its own. The model summary covers every neuron, so it is
+/// only updated by [`summarise_to_database`].
+pub async fn summarise_layer(
+    model_handle: &mut ModelHandle,
+    data_type: &DataTypeHandle,
+    layer_index: u32,
+) -> Result<()> {
+    summarise_layer_pages(model_handle, data_type, layer_index).await?;
+    Ok(())
+}
+
+/// Summarises the explanations of each layer and of the whole model, and stores the summaries as
+/// the layer and model data of the data object, replacing any earlier summaries.
+pub async fn summarise_to_database(
+    model_handle: &mut ModelHandle,
+    data_type: &DataTypeHandle,
+) -> Result<()> {
+    let num_layers = model_handle.metadata().num_layers;
+    let layer_size = model_handle.metadata().layer_size;
+    let mut progress =
+        Progress::start((num_layers * layer_size) as u64, "Summarising explanations");
+    let mut model_pages = Vec::new();
+    for layer_index in 0..num_layers {
+        cancel::check_cancelled()?;
+        let layer_pages = summarise_layer_pages(model_handle, data_type, layer_index).await?;
+        progress.increment_by(layer_size as u64);
+        progress.print();
+        model_pages.extend(layer_pages);
+    }
+    let model_summary = NeuronExplainerSummary::new(num_layers * layer_size, &model_pages);
+    model_handle
+        .replace_data(data_type, Index::Model, model_summary.to_binary()?)
+        .await?;
+    println!("Summarised explanations.                                ");
+    Ok(())
+}
+
 async fn fetch_to_database(
     model_handle: &mut ModelHandle,
     url: impl Fn(NeuronIndex) -> String,
@@ -151,6 +224,7 @@ async fn fetch_to_database(
     fetch(model_handle, &data_type, num_layers, layer_size, url).await?;
     let fetch_time = start.elapsed();
     println!("Fetched data in {:?}", fetch_time);
+    summarise_to_database(model_handle, &data_type).await?;
 
     if !model_handle.has_data_type(&data_type).await? {
         model_handle.add_data_type(&data_type).await?;
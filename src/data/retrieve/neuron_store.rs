@@ -2,30 +2,94 @@ use std::path::Path;
 
 use anyhow::{bail, Context, Result};
 
-use crate::data::{
-    data_types::DataType, neuron_store::NeuronStoreRaw, DataTypeHandle, Database, ModelHandle,
-    NeuronStore,
+use crate::{
+    data::{
+        data_objects::{DataObject, JsonData, ModelCorrespondence},
+        data_types::{self, DataType},
+        neuron_store::NeuronStoreRaw,
+        DataTypeHandle, Database, ModelHandle, NeuronIndex, NeuronSimilarity, NeuronStore,
+        NeuroscopeStoreConfig, SimilarityConfig,
+    },
+    util::cancel,
+    Index,
 };
 
+/// Finds the similar neurons of every neuron in the store on a blocking thread, as it can take
+/// minutes for large models.
+async fn neuron_similarity(
+    neuron_store: NeuronStore,
+    similarity_config: &SimilarityConfig,
+) -> Result<NeuronSimilarity> {
+    let similarity_config = similarity_config.clone();
+    tokio::task::spawn_blocking(move || neuron_store.neuron_similarity(&similarity_config))
+        .await
+        .context("Neuron similarity computation panicked.")?
+}
+
+/// Name of the data object recording how the similar neurons of each model were found.
+pub const NEURON_SIMILARITY_CONFIG: &str = "neuron_similarity_config";
+
+/// Records the configuration the similar neurons of the model were found with as the model data of
+/// the [`NEURON_SIMILARITY_CONFIG`] JSON data object, replacing any earlier configuration.
+async fn store_similarity_config(
+    model_handle: &mut ModelHandle,
+    similarity_config: &SimilarityConfig,
+) -> Result<()> {
+    let database = model_handle.database().clone();
+    let data_type = match database.data_type(NEURON_SIMILARITY_CONFIG).await? {
+        Some(data_type) => {
+            if !matches!(data_type.data_type(), DataType::Json) {
+                bail!("Data object '{NEURON_SIMILARITY_CONFIG}' does not hold JSON data.")
+            }
+            data_type
+        }
+        None => {
+            database
+                .add_data_type(NEURON_SIMILARITY_CONFIG, DataType::Json)
+                .await?
+        }
+    };
+    if !model_handle.has_data_type(&data_type).await? {
+        model_handle.add_data_type(&data_type).await?;
+    }
+    let SimilarityConfig {
+        metric,
+        threshold,
+        top_k,
+        min_hash,
+        ..
+    } = similarity_config;
+    let config = JsonData::new(serde_json::json!({
+        "metric": metric,
+        "threshold": threshold,
+        "top_k": top_k,
+        "min_hash": min_hash,
+    }));
+    model_handle
+        .replace_data(&data_type, Index::Model, config.to_binary()?)
+        .await
+}
+
+/// Stores the similar neurons of every neuron in the model, replacing any previous ones, and
+/// records the configuration they were found with.
 pub async fn store_similar_neurons(
     model_handle: &mut ModelHandle,
     data_type_handle: &DataTypeHandle,
     neuron_store: NeuronStore,
-    similarity_threshold: f32,
+    similarity_config: &SimilarityConfig,
 ) -> Result<()> {
     let model_name = model_handle.name().to_owned();
     let model_name = model_name.as_str();
-    print!("Calculating neuron similarities...");
-    let neuron_relatedness = neuron_store
-        .neuron_similarity(similarity_threshold)
+    let neuron_relatedness = neuron_similarity(neuron_store, similarity_config)
+        .await
         .with_context(|| {
             format!("Failed to calculate neuron similarities for model '{model_name}'.",)
         })?;
 
-    let num_neurons = model_handle.metadata().num_total_neurons;
-    let mut num_completed = 0;
-    print!("Adding neuron similarities to database: {num_completed}/{num_neurons}",);
+    let num_layers = model_handle.metadata().num_layers;
+    let layer_size = model_handle.metadata().layer_size;
     for neuron_index in model_handle.metadata().neuron_indices() {
+        cancel::check_cancelled()?;
         let similar_neurons = neuron_relatedness
             .similar_neurons(neuron_index)
             .with_context(|| {
@@ -41,10 +105,9 @@ pub async fn store_similar_neurons(
             )
         })?;
         model_handle
-            .add_neuron_data(
+            .replace_data(
                 data_type_handle,
-                neuron_index.layer,
-                neuron_index.neuron,
+                Index::Neuron(neuron_index.layer, neuron_index.neuron),
                 data,
             )
             .await
@@ -54,21 +117,26 @@ pub async fn store_similar_neurons(
                      {model_name} to database."
                 )
             })?;
-
-        num_completed += 1;
-        print!("\rAdding neuron similarities to database: {num_completed}/{num_neurons}",);
+        if neuron_index.neuron + 1 == layer_size {
+            log::info!(
+                "Stored similar neurons of layer {}/{num_layers} of model '{model_name}'.",
+                neuron_index.layer + 1
+            );
+        }
     }
-    println!(
-        "\rAdding neuron similarities to database: {num_completed}/{num_neurons}                  ",
-    );
-    Ok(())
+
+    store_similarity_config(model_handle, similarity_config)
+        .await
+        .with_context(|| {
+            format!("Failed to record similarity configuration of model '{model_name}'.")
+        })
 }
 
 pub async fn store_neuron_store(
     database: &Database,
     model_handle: &mut ModelHandle,
     neuron_store: NeuronStore,
-    similarity_threshold: f32,
+    similarity_config: &SimilarityConfig,
 ) -> Result<()> {
     let data_type = if let Some(data_type) = database.data_type("neuron_store").await? {
         data_type
@@ -77,7 +145,7 @@ pub async fn store_neuron_store(
             .add_data_type(
                 "neuron_store",
                 DataType::NeuronStore {
-                    similarity_threshold,
+                    similarity_threshold: similarity_config.threshold.unwrap_or(0.),
                 },
             )
             .await?
@@ -102,7 +170,7 @@ pub async fn store_neuron_store(
             format!("Failed to add neuron store data for model '{model_name}' to database.",)
         })?;
 
-    store_similar_neurons(model_handle, &data_type, neuron_store, similarity_threshold)
+    store_similar_neurons(model_handle, &data_type, neuron_store, similarity_config)
         .await
         .context("Failed to store similar neurons.")
 }
@@ -111,25 +179,25 @@ pub async fn store_raw_neuron_store(
     database: &Database,
     model_handle: &mut ModelHandle,
     neuron_store: NeuronStoreRaw,
-    similarity_threshold: f32,
+    similarity_config: &SimilarityConfig,
 ) -> Result<()> {
     let metadata = model_handle.metadata();
     let neuron_store =
         NeuronStore::from_raw(neuron_store, metadata.num_layers, metadata.layer_size)
             .context("Failed to convert raw neuron store")?;
-    store_neuron_store(database, model_handle, neuron_store, similarity_threshold).await
+    store_neuron_store(database, model_handle, neuron_store, similarity_config).await
 }
 
 pub async fn retrieve_neuron_store(
     model_handle: &mut ModelHandle,
     neuron_store_path: impl AsRef<Path>,
-    similarity_threshold: f32,
+    similarity_config: &SimilarityConfig,
 ) -> Result<()> {
     let neuron_store_path = neuron_store_path.as_ref();
     let neuron_store = NeuronStoreRaw::load(neuron_store_path)
         .with_context(|| format!("Failed to load neuron store from '{neuron_store_path:?}'."))?;
     let database = model_handle.database().clone();
-    store_raw_neuron_store(&database, model_handle, neuron_store, similarity_threshold)
+    store_raw_neuron_store(&database, model_handle, neuron_store, similarity_config)
         .await
         .with_context(|| {
             format!(
@@ -138,3 +206,308 @@ pub async fn retrieve_neuron_store(
             )
         })
 }
+
+/// Builds a neuron store from the Neuroscope pages of a model, for models without Neuron2Graph
+/// output, and stores it along with the similar neurons of each neuron.
+pub async fn derive_neuron_store(
+    model_handle: &mut ModelHandle,
+    store_config: &NeuroscopeStoreConfig,
+    similarity_config: &SimilarityConfig,
+) -> Result<()> {
+    store_config.validate()?;
+    similarity_config.validate()?;
+    let model_name = model_handle.name().to_owned();
+    let database = model_handle.database().clone();
+    let data_type = database
+        .data_type("neuroscope")
+        .await?
+        .context("Database has no neuroscope data object.")?;
+    if !model_handle.has_data_type(&data_type).await? {
+        bail!("Model '{model_name}' has no Neuroscope data.")
+    }
+    let neuroscope = model_handle
+        .data_type::<data_types::Neuroscope>(&data_type)
+        .await?;
+
+    // Decoding and scanning every page of a large model takes a while, so the store is built on a
+    // blocking thread that waits for each page in turn.
+    let metadata = model_handle.metadata().clone();
+    let store_config = *store_config;
+    let runtime = tokio::runtime::Handle::current();
+    let (neuron_store, num_missing) = tokio::task::spawn_blocking(move || -> Result<_> {
+        let mut neuron_store = NeuronStore::empty(metadata.num_layers, metadata.layer_size);
+        let mut num_missing = 0;
+        for NeuronIndex { layer, neuron } in metadata.neuron_indices() {
+            cancel::check_cancelled()?;
+            match runtime.block_on(neuroscope.neuron_page(layer, neuron))? {
+                Some(page) => neuron_store.add_neuroscope_page(&page, &store_config),
+                None => num_missing += 1,
+            }
+        }
+        Ok((neuron_store, num_missing))
+    })
+    .await
+    .context("Building the neuron store panicked.")??;
+    if num_missing > 0 {
+        log::warn!(
+            "{num_missing} neurons in model '{model_name}' have no Neuroscope page and get no \
+             tokens in the derived neuron store."
+        );
+    }
+    store_neuron_store(&database, model_handle, neuron_store, similarity_config)
+        .await
+        .with_context(|| format!("Failed to store derived neuron store for model '{model_name}'."))
+}
+
+/// Name of the data object holding the similar neurons of each neuron in other models.
+const NEURON_CORRESPONDENCE: &str = "neuron_correspondence";
+
+/// The neuron store the model already has.
+pub(super) async fn model_neuron_store(model_handle: &ModelHandle) -> Result<NeuronStore> {
+    let model_name = model_handle.name();
+    let data_type = model_handle
+        .database()
+        .data_type("neuron_store")
+        .await?
+        .context("Database has no neuron store data object.")?;
+    if !model_handle.has_data_type(&data_type).await? {
+        bail!("Model '{model_name}' has no neuron store.")
+    }
+    model_handle
+        .data_type::<data_types::NeuronStore>(&data_type)
+        .await?
+        .get_store()
+        .await
+        .with_context(|| format!("Failed to load neuron store of model '{model_name}'."))
+}
+
+/// Recomputes the similar neurons of a model from the neuron store it already has, replacing the
+/// previous similarities and the configuration recorded for them.
+pub async fn recompute_similar_neurons(
+    model_handle: &mut ModelHandle,
+    similarity_config: &SimilarityConfig,
+) -> Result<()> {
+    similarity_config.validate()?;
+    let model_name = model_handle.name().to_owned();
+    let data_type = model_handle
+        .database()
+        .data_type("neuron_store")
+        .await?
+        .context("Database has no neuron store data object.")?;
+    let neuron_store = model_neuron_store(model_handle).await?;
+    store_similar_neurons(model_handle, &data_type, neuron_store, similarity_config)
+        .await
+        .with_context(|| format!("Failed to recompute similar neurons for model '{model_name}'."))
+}
+
+/// Stores the similar neurons in the other model of every neuron in the model, replacing those
+/// previously found in that model and keeping those in any other models.
+async fn store_model_correspondence(
+    model_handle: &mut ModelHandle,
+    data_type: &DataTypeHandle,
+    other_model_name: &str,
+    similarity: &NeuronSimilarity,
+) -> Result<()> {
+    let model_name = model_handle.name().to_owned();
+    if !model_handle.has_data_type(data_type).await? {
+        model_handle
+            .add_data_type(data_type)
+            .await
+            .with_context(|| {
+                format!("Failed to add neuron correspondence data object to model '{model_name}'.")
+            })?;
+    }
+    let correspondence = model_handle
+        .data_type::<data_types::NeuronCorrespondence>(data_type)
+        .await?;
+    for neuron_index in model_handle.metadata().neuron_indices() {
+        cancel::check_cancelled()?;
+        let NeuronIndex { layer, neuron } = neuron_index;
+        let mut correspondences = correspondence.correspondences(layer, neuron).await?;
+        correspondences.retain(|correspondence| correspondence.model != other_model_name);
+        correspondences.push(ModelCorrespondence {
+            model: other_model_name.to_owned(),
+            similar: similarity
+                .similar_neurons(neuron_index)?
+                .similar_neurons()
+                .to_vec(),
+        });
+        correspondences.sort_by(|a, b| a.model.cmp(&b.model));
+        model_handle
+            .replace_data(
+                data_type,
+                Index::Neuron(layer, neuron),
+                correspondences.to_binary()?,
+            )
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to store neurons similar to neuron {neuron_index} of model \
+                     '{model_name}' in model '{other_model_name}'."
+                )
+            })?;
+    }
+    Ok(())
+}
+
+/// Finds the most similar neurons of each neuron in the other model, in both directions, from the
+/// neuron stores of the two models.
+pub async fn store_neuron_correspondence(
+    model_handle: &mut ModelHandle,
+    other_model_handle: &mut ModelHandle,
+    similarity_config: &SimilarityConfig,
+) -> Result<()> {
+    similarity_config.validate()?;
+    let model_name = model_handle.name().to_owned();
+    let other_model_name = other_model_handle.name().to_owned();
+    if model_name == other_model_name {
+        bail!("Cannot find corresponding neurons of model '{model_name}' in itself.")
+    }
+    let neuron_store = model_neuron_store(model_handle).await?;
+    let other_neuron_store = model_neuron_store(other_model_handle).await?;
+
+    let database = model_handle.database().clone();
+    let data_type = if let Some(data_type) = database.data_type(NEURON_CORRESPONDENCE).await? {
+        data_type
+    } else {
+        database
+            .add_data_type(NEURON_CORRESPONDENCE, DataType::NeuronCorrespondence)
+            .await?
+    };
+
+    // Comparing every neuron with those of another model can take minutes for large models.
+    let similarity_config = similarity_config.clone();
+    let (similarity, other_similarity) = tokio::task::spawn_blocking({
+        let model_name = model_name.clone();
+        let other_model_name = other_model_name.clone();
+        move || -> Result<_> {
+            let similarity = neuron_store
+                .cross_model_similarity(&other_neuron_store, &similarity_config)
+                .with_context(|| {
+                    format!(
+                        "Failed to find neurons of model '{other_model_name}' similar to those of \
+                         model '{model_name}'."
+                    )
+                })?;
+            let other_similarity = other_neuron_store
+                .cross_model_similarity(&neuron_store, &similarity_config)
+                .with_context(|| {
+                    format!(
+                        "Failed to find neurons of model '{model_name}' similar to those of model \
+                         '{other_model_name}'."
+                    )
+                })?;
+            Ok((similarity, other_similarity))
+        }
+    })
+    .await
+    .context("Cross model similarity computation panicked.")??;
+    store_model_correspondence(model_handle, &data_type, &other_model_name, &similarity).await?;
+    store_model_correspondence(
+        other_model_handle,
+        &data_type,
+        &model_name,
+        &other_similarity,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::{
+        database::test_util::{add_test_model, neuroscope_page, neuroscope_text},
+        SimilarityMetric,
+    };
+
+    #[tokio::test]
+    async fn recomputes_similar_neurons() -> Result<()> {
+        let database = Database::initialize_in_memory().await?;
+        let mut model = add_test_model(&database, 1, 3).await?;
+        let raw: NeuronStoreRaw = serde_json::from_value(serde_json::json!({
+            "activating": { "a": ["0_0", "0_1"], "b": ["0_1", "0_2"] },
+            "important": {},
+        }))?;
+        let config = |threshold| SimilarityConfig {
+            metric: SimilarityMetric::Jaccard,
+            threshold: Some(threshold),
+            top_k: None,
+            threads: Some(1),
+            min_hash: None,
+        };
+        store_raw_neuron_store(&database, &mut model, raw, &config(0.2)).await?;
+        recompute_similar_neurons(&mut model, &config(0.6)).await?;
+
+        let data_type = database.data_type("neuron_store").await?.unwrap();
+        assert_eq!(
+            data_type.data_type(),
+            &DataType::NeuronStore {
+                similarity_threshold: 0.2
+            }
+        );
+        let config_type = database.data_type(NEURON_SIMILARITY_CONFIG).await?.unwrap();
+        let config = JsonData::from_binary(model.model_data(&config_type).await?.unwrap())?;
+        assert_eq!(
+            config.value["threshold"]
+                .as_f64()
+                .map(|threshold| threshold as f32),
+            Some(0.6)
+        );
+        assert_eq!(config.value["top_k"], serde_json::Value::Null);
+        let similarities = model
+            .data_type::<data_types::NeuronStore>(&data_type)
+            .await?
+            .all_similarities()
+            .await?;
+        assert!(similarities
+            .iter()
+            .all(|(_, similar_neurons)| similar_neurons.iter().next().is_none()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn derives_neuron_store() -> Result<()> {
+        let database = Database::initialize_in_memory().await?;
+        let mut model = add_test_model(&database, 1, 3).await?;
+        let data_type = database
+            .add_data_type("neuroscope", DataType::Neuroscope)
+            .await?;
+        model.add_data_type(&data_type).await?;
+        for neuron in 0..2 {
+            let page = neuroscope_page(0, neuron, vec![neuroscope_text(0, &["a", "b"], 1, 2.)])?;
+            model
+                .add_data(&data_type, Index::Neuron(0, neuron), page.to_binary()?)
+                .await?;
+        }
+        let similarity_config = SimilarityConfig {
+            metric: SimilarityMetric::Jaccard,
+            threshold: Some(0.5),
+            top_k: None,
+            threads: Some(1),
+            min_hash: None,
+        };
+        derive_neuron_store(
+            &mut model,
+            &NeuroscopeStoreConfig::default(),
+            &similarity_config,
+        )
+        .await?;
+
+        let neuron_store = model_neuron_store(&model).await?;
+        assert_eq!(neuron_store.num_layers(), 1);
+        let store_data_type = database.data_type("neuron_store").await?.unwrap();
+        let similar_neurons = model
+            .data_type::<data_types::NeuronStore>(&store_data_type)
+            .await?
+            .neuron_similarities(0, 0)
+            .await?;
+        assert_eq!(
+            similar_neurons
+                .iter()
+                .map(|(index, _)| index.neuron)
+                .collect::<Vec<_>>(),
+            vec![1]
+        );
+        Ok(())
+    }
+}
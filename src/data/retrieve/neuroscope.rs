@@ -13,7 +13,7 @@ use crate::{
         data_types::DataType,
         DataTypeHandle, Metadata, ModelHandle, NeuronIndex,
     },
-    util::Progress,
+    util::{cancel, Progress},
     Index,
 };
 
@@ -29,11 +29,11 @@ fn neuron_page_url(model: &str, neuron_index: NeuronIndex) -> String {
 }
 
 pub async fn scrape_neuron_page<S: AsRef<str>>(
+    client: &Client,
     model: S,
     neuron_index: NeuronIndex,
 ) -> Result<NeuroscopeNeuronPage> {
     let url = neuron_page_url(model.as_ref(), neuron_index);
-    let client = Client::new();
     let res = client.get(&url).send().await?;
     let page = res.text().await?;
     let page = NeuroscopeNeuronPage::from_html_str(&page, neuron_index)?;
@@ -41,6 +41,7 @@ pub async fn scrape_neuron_page<S: AsRef<str>>(
 }
 
 async fn scrape_neuron_page_to_database(
+    client: &Client,
     model: &mut ModelHandle,
     data_type: &DataTypeHandle,
     neuron_index: NeuronIndex,
@@ -51,7 +52,7 @@ async fn scrape_neuron_page_to_database(
     {
         NeuroscopeNeuronPage::from_binary(page_data)?
     } else {
-        let page = scrape_neuron_page(model.name(), neuron_index).await?;
+        let page = scrape_neuron_page(client, model.name(), neuron_index).await?;
         model
             .add_neuron_data(
                 data_type,
@@ -82,6 +83,7 @@ async fn scrape_neuron_page_to_database(
 }
 
 async fn scrape_layer_to_database(
+    client: &Client,
     model: &mut ModelHandle,
     data_type: &DataTypeHandle,
     layer_index: u32,
@@ -105,15 +107,23 @@ async fn scrape_layer_to_database(
                 neuron: neuron_index,
             };
 
+            let client = client.clone();
             let mut model = model.clone();
             let data_type = data_type.clone();
 
             let semaphore = Arc::clone(&semaphore);
             join_set.spawn(async move {
                 let permit = semaphore.acquire_owned().await.unwrap();
+                cancel::check_cancelled()?;
                 let mut retries = 0;
                 let result = loop {
-                    match scrape_neuron_page_to_database(&mut model, &data_type, neuron_index).await
+                    match scrape_neuron_page_to_database(
+                        &client,
+                        &mut model,
+                        &data_type,
+                        neuron_index,
+                    )
+                    .await
                     {
                         Ok(result) => break result,
                         Err(err) => {
@@ -236,8 +246,11 @@ pub async fn scrape_model_to_database(model: &mut ModelHandle) -> Result<()> {
         );
         let mut layer_pages = Vec::with_capacity(model.metadata().num_layers as usize);
         let layer_size = model.metadata().layer_size;
+        let client = Client::new();
         for layer_index in 0..model.metadata().num_layers {
+            cancel::check_cancelled()?;
             let layer_page = scrape_layer_to_database(
+                &client,
                 &mut model.clone(),
                 &data_type,
                 layer_index,
@@ -275,7 +288,9 @@ pub async fn scrape_indices_to_database(
     let indices = indices.collect::<Vec<_>>();
 
     let mut progress = Progress::start(indices.len() as u64, "Scraping missing neuroscope items");
+    let client = Client::new();
     for index in indices {
+        cancel::check_cancelled()?;
         match index {
             Index::Model => {
                 bail!("Cannot handle model index.")
@@ -285,6 +300,7 @@ pub async fn scrape_indices_to_database(
             }
             Index::Neuron(layer_index, neuron_index) => {
                 scrape_neuron_page_to_database(
+                    &client,
                     model,
                     data_type,
                     NeuronIndex {
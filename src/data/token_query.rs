@@ -0,0 +1,588 @@
+//! Boolean queries over the tokens of a neuron store.
+//!
+//! A query combines terms of the form `search_type:token` with `,` or `&` (and), `|` (or) and `!`
+//! (not), grouped with parentheses. And binds tighter than or, so
+//! `activating:the, important:cat | activating:dog` means
+//! `(activating:the, important:cat) | activating:dog`.
+//!
+//! A token extends to the next `,`, `&`, `|` or `)` and trailing whitespace is ignored, while
+//! leading whitespace is part of the token. Tokens containing those characters or ending in
+//! whitespace can be quoted, as in `activating:" |"`, with `\"` and `\\` escaping quotes and
+//! backslashes.
+//!
+//! Instead of a single token, a term can match several tokens, as described by a
+//! [`TokenPattern`]: `the*` matches tokens starting with `the`, `/^[0-9]+$/` tokens matching a
+//! regular expression, with `\/` escaping slashes, and `teh~2` tokens within an edit distance of
+//! 2 of `teh`, with `teh~` meaning `teh~1`. A quoted token can also be followed by `*` or `~`.
+//!
+//! A query must not match every neuron except a few, as `!activating:the` or
+//! `activating:the | !important:cat` do, since that would list nearly every neuron of the model.
+
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    ops::RangeInclusive,
+    str::FromStr,
+};
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    token_vocabulary::{Normalisation, TokenPattern, TokenVocabulary},
+    NeuronIndex, NeuronStore, TokenSearchType,
+};
+
+/// A term of a token query, matching neurons by the tokens matching its pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryTerm {
+    pub search_types: Vec<TokenSearchType>,
+    pub pattern: TokenPattern,
+}
+
+impl std::fmt::Display for QueryTerm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.search_types.as_slice() {
+            [search_type] => write!(f, "{search_type}:{}", self.pattern),
+            _ => write!(f, "any:{}", self.pattern),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenQuery {
+    Term(QueryTerm),
+    Not(Box<TokenQuery>),
+    And(Vec<TokenQuery>),
+    Or(Vec<TokenQuery>),
+}
+
+/// A neuron matching a token query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenQueryMatch {
+    pub layer: u32,
+    pub neuron: u32,
+    /// One point for each search type a term matched the neuron with. A token that both activates
+    /// the neuron and is important to it in the neuron's graph thus counts twice.
+    pub score: f32,
+    /// The terms that matched the neuron, not counting negated terms.
+    pub matched_terms: Vec<String>,
+}
+
+/// The neurons matching a query, represented either by the matching neurons or, for negated
+/// queries, by the neurons not matching it.
+enum Matches {
+    Include(HashSet<NeuronIndex>),
+    Exclude(HashSet<NeuronIndex>),
+}
+
+impl Matches {
+    fn not(self) -> Self {
+        match self {
+            Self::Include(neurons) => Self::Exclude(neurons),
+            Self::Exclude(neurons) => Self::Include(neurons),
+        }
+    }
+
+    fn and(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Include(a), Self::Include(b)) => Self::Include(&a & &b),
+            (Self::Include(a), Self::Exclude(b)) | (Self::Exclude(b), Self::Include(a)) => {
+                Self::Include(&a - &b)
+            }
+            (Self::Exclude(a), Self::Exclude(b)) => Self::Exclude(&a | &b),
+        }
+    }
+
+    fn or(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Include(a), Self::Include(b)) => Self::Include(&a | &b),
+            (Self::Include(a), Self::Exclude(b)) | (Self::Exclude(b), Self::Include(a)) => {
+                Self::Exclude(&b - &a)
+            }
+            (Self::Exclude(a), Self::Exclude(b)) => Self::Exclude(&a & &b),
+        }
+    }
+}
+
+/// The neurons matched by a term, along with the number of search types each was matched with.
+fn term_neurons(
+    store: &NeuronStore,
+    vocabulary: &TokenVocabulary,
+    normalisation: Normalisation,
+    term: &QueryTerm,
+) -> Result<HashMap<NeuronIndex, f32>> {
+    let tokens = vocabulary.matching(&term.pattern, normalisation)?;
+    let mut neurons: HashMap<NeuronIndex, f32> = HashMap::new();
+    for &search_type in &term.search_types {
+        let search_type_neurons: HashSet<NeuronIndex> = tokens
+            .iter()
+            .filter_map(|(entry, _)| store.get(search_type, entry.token.as_str()))
+            .flatten()
+            .copied()
+            .collect();
+        for neuron in search_type_neurons {
+            *neurons.entry(neuron).or_default() += 1.;
+        }
+    }
+    Ok(neurons)
+}
+
+impl TokenQuery {
+    /// Whether the query matches every neuron except those matched by its terms, like the
+    /// [`Matches::Exclude`] of [`Self::matches`].
+    fn is_unbounded(&self) -> bool {
+        match self {
+            Self::Term(_) => false,
+            Self::Not(query) => !query.is_unbounded(),
+            Self::And(queries) => queries.iter().all(Self::is_unbounded),
+            Self::Or(queries) => queries.iter().any(Self::is_unbounded),
+        }
+    }
+
+    /// The terms of the query along with whether they are negated, in the order [`Self::matches`]
+    /// visits them.
+    fn terms(&self, negated: bool) -> Vec<(&QueryTerm, bool)> {
+        match self {
+            Self::Term(term) => vec![(term, negated)],
+            Self::Not(query) => query.terms(!negated),
+            Self::And(queries) | Self::Or(queries) => queries
+                .iter()
+                .flat_map(|query| query.terms(negated))
+                .collect(),
+        }
+    }
+
+    /// The neurons matching the query, given the neurons matched by each of its terms in the order
+    /// of [`Self::terms`].
+    fn matches<'a>(
+        &self,
+        term_neurons: &mut impl Iterator<Item = &'a HashMap<NeuronIndex, f32>>,
+    ) -> Matches {
+        match self {
+            Self::Term(_) => Matches::Include(
+                term_neurons
+                    .next()
+                    .expect("There are neurons for every term.")
+                    .keys()
+                    .copied()
+                    .collect(),
+            ),
+            Self::Not(query) => query.matches(term_neurons).not(),
+            Self::And(queries) => queries
+                .iter()
+                .map(|query| query.matches(term_neurons))
+                .reduce(Matches::and)
+                .expect("Queries are never empty."),
+            Self::Or(queries) => queries
+                .iter()
+                .map(|query| query.matches(term_neurons))
+                .reduce(Matches::or)
+                .expect("Queries are never empty."),
+        }
+    }
+
+    /// Finds the neurons in the given layers matching the query, ranked by score. Tokens are
+    /// looked up in the vocabulary of the store after applying the normalisation to them.
+    pub fn search(
+        &self,
+        store: &NeuronStore,
+        vocabulary: &TokenVocabulary,
+        normalisation: Normalisation,
+        layers: RangeInclusive<u32>,
+    ) -> Result<Vec<TokenQueryMatch>> {
+        let terms = self.terms(false);
+        let term_neurons = terms
+            .iter()
+            .map(|(term, _)| term_neurons(store, vocabulary, normalisation, term))
+            .collect::<Result<Vec<_>>>()?;
+
+        let neurons: Vec<NeuronIndex> = match self.matches(&mut term_neurons.iter()) {
+            Matches::Include(neurons) => neurons
+                .into_iter()
+                .filter(|neuron| layers.contains(&neuron.layer))
+                .collect(),
+            Matches::Exclude(_) => {
+                bail!("Token query matches every neuron except those matched by its terms.")
+            }
+        };
+
+        let mut term_scores: HashMap<NeuronIndex, (f32, Vec<String>)> = HashMap::new();
+        for ((term, _), term_neurons) in terms
+            .iter()
+            .zip(&term_neurons)
+            .filter(|((_, negated), _)| !negated)
+        {
+            for (&neuron, &score) in term_neurons {
+                let (total_score, matched_terms) = term_scores.entry(neuron).or_default();
+                *total_score += score;
+                matched_terms.push(term.to_string());
+            }
+        }
+
+        let mut results: Vec<_> = neurons
+            .into_iter()
+            .map(|neuron| {
+                let (score, matched_terms) = term_scores.remove(&neuron).unwrap_or_default();
+                TokenQueryMatch {
+                    layer: neuron.layer,
+                    neuron: neuron.neuron,
+                    score,
+                    matched_terms,
+                }
+            })
+            .collect();
+        results.sort_unstable_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(Ordering::Equal)
+                .then((a.layer, a.neuron).cmp(&(b.layer, b.neuron)))
+        });
+        Ok(results)
+    }
+}
+
+impl FromStr for TokenQuery {
+    type Err = anyhow::Error;
+
+    fn from_str(query: &str) -> Result<Self> {
+        let mut parser = Parser {
+            text: query,
+            position: 0,
+        };
+        let token_query = parser.or()?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            None if token_query.is_unbounded() => bail!(
+                "Invalid token query '{query}': it matches every neuron except those matched by \
+                 its terms. Combine negated terms with a term that is not negated, as in \
+                 'activating:the, !activating:cat'."
+            ),
+            None => Ok(token_query),
+            Some(')') => parser.error("unmatched ')'"),
+            Some(_) => parser.error("expected ',', '&', '|' or the end of the query"),
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.position += c.len_utf8();
+        }
+        found
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T> {
+        bail!(
+            "Invalid token query '{}' at position {}: {message}.",
+            self.text,
+            self.position
+        )
+    }
+
+    /// A fuzzy pattern with the given maximum edit distance, which defaults to 1 if empty.
+    fn fuzzy_pattern(&self, token: String, distance: &str) -> Result<TokenPattern> {
+        let distance = if distance.is_empty() {
+            1
+        } else {
+            match distance.parse() {
+                Ok(distance) => distance,
+                Err(_) => return self.error(&format!("invalid edit distance '{distance}'")),
+            }
+        };
+        Ok(TokenPattern::Fuzzy(token, distance))
+    }
+
+    fn or(&mut self) -> Result<TokenQuery> {
+        let mut queries = vec![self.and()?];
+        loop {
+            self.skip_whitespace();
+            if !self.eat('|') {
+                break;
+            }
+            queries.push(self.and()?);
+        }
+        Ok(if queries.len() == 1 {
+            queries.pop().unwrap()
+        } else {
+            TokenQuery::Or(queries)
+        })
+    }
+
+    fn and(&mut self) -> Result<TokenQuery> {
+        let mut queries = vec![self.unary()?];
+        loop {
+            self.skip_whitespace();
+            if !(self.eat(',') || self.eat('&')) {
+                break;
+            }
+            queries.push(self.unary()?);
+        }
+        Ok(if queries.len() == 1 {
+            queries.pop().unwrap()
+        } else {
+            TokenQuery::And(queries)
+        })
+    }
+
+    fn unary(&mut self) -> Result<TokenQuery> {
+        self.skip_whitespace();
+        if self.eat('!') {
+            Ok(TokenQuery::Not(Box::new(self.unary()?)))
+        } else if self.eat('(') {
+            let query = self.or()?;
+            self.skip_whitespace();
+            if self.eat(')') {
+                Ok(query)
+            } else {
+                self.error("expected ')'")
+            }
+        } else {
+            self.term()
+        }
+    }
+
+    fn term(&mut self) -> Result<TokenQuery> {
+        let start = self.position;
+        while self.peek().is_some_and(char::is_alphabetic) {
+            self.bump();
+        }
+        let search_type = &self.text[start..self.position];
+        if search_type.is_empty() {
+            return self.error("expected a term of the form 'search_type:token'");
+        }
+        let Ok(search_types) = TokenSearchType::list_from_str(search_type) else {
+            self.position = start;
+            return self.error(&format!(
+                "invalid search type '{search_type}', expected 'activating', 'important' or 'any'"
+            ));
+        };
+        if !self.eat(':') {
+            return self.error("expected ':'");
+        }
+
+        let pattern_start = self.position;
+        let pattern = if self.eat('"') {
+            let mut token = String::new();
+            loop {
+                match self.bump() {
+                    Some('"') => break,
+                    Some('\\') => match self.bump() {
+                        Some(c @ ('"' | '\\')) => token.push(c),
+                        _ => return self.error("expected '\"' or '\\' after '\\'"),
+                    },
+                    Some(c) => token.push(c),
+                    None => return self.error("expected a closing '\"'"),
+                }
+            }
+            if token.is_empty() {
+                return self.error("expected a token");
+            }
+            if self.eat('*') {
+                TokenPattern::Prefix(token)
+            } else if self.eat('~') {
+                let start = self.position;
+                while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    self.bump();
+                }
+                let distance = &self.text[start..self.position];
+                self.fuzzy_pattern(token, distance)?
+            } else {
+                TokenPattern::Exact(token)
+            }
+        } else if self.eat('/') {
+            let mut regex = String::new();
+            loop {
+                match self.bump() {
+                    Some('/') => break,
+                    Some('\\') => match self.bump() {
+                        Some('/') => regex.push('/'),
+                        Some(c) => {
+                            regex.push('\\');
+                            regex.push(c);
+                        }
+                        None => return self.error("expected a closing '/'"),
+                    },
+                    Some(c) => regex.push(c),
+                    None => return self.error("expected a closing '/'"),
+                }
+            }
+            TokenPattern::Regex(regex)
+        } else {
+            let start = self.position;
+            while self.peek().is_some_and(|c| !",&|)".contains(c)) {
+                self.bump();
+            }
+            let token = self.text[start..self.position].trim_end();
+            if token.is_empty() {
+                return self.error("expected a token");
+            }
+            if let Some(prefix) = token.strip_suffix('*').filter(|prefix| !prefix.is_empty()) {
+                TokenPattern::Prefix(prefix.to_owned())
+            } else if let Some((token, distance)) =
+                token.rsplit_once('~').filter(|(token, distance)| {
+                    !token.is_empty() && distance.chars().all(|c| c.is_ascii_digit())
+                })
+            {
+                self.fuzzy_pattern(token.to_owned(), distance)?
+            } else {
+                TokenPattern::Exact(token.to_owned())
+            }
+        };
+        if let Err(error) = pattern.validate() {
+            self.position = pattern_start;
+            return self.error(&format!("{error:#}"));
+        }
+        Ok(TokenQuery::Term(QueryTerm {
+            search_types,
+            pattern,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn term(search_type: TokenSearchType, token: &str) -> TokenQuery {
+        pattern_term(search_type, TokenPattern::Exact(token.to_owned()))
+    }
+
+    fn pattern_term(search_type: TokenSearchType, pattern: TokenPattern) -> TokenQuery {
+        TokenQuery::Term(QueryTerm {
+            search_types: vec![search_type],
+            pattern,
+        })
+    }
+
+    #[test]
+    fn ranks_matches() {
+        let raw: crate::data::NeuronStoreRaw = serde_json::from_value(serde_json::json!({
+            "activating": { "the": ["0_0", "0_1", "1_0"], "cat": ["0_1"] },
+            "important": { "the": ["0_1"], "dog": ["1_1"] },
+        }))
+        .unwrap();
+        let store = NeuronStore::from_raw(raw, 2, 2).unwrap();
+        let vocabulary = TokenVocabulary::new(&store);
+        let search = |query: &str, layers| {
+            query
+                .parse::<TokenQuery>()
+                .unwrap()
+                .search(&store, &vocabulary, Normalisation::default(), layers)
+                .unwrap()
+                .into_iter()
+                .map(|found| (found.layer, found.neuron, found.score, found.matched_terms))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            search("any:the | activating:cat", 0..=1),
+            vec![
+                (
+                    0,
+                    1,
+                    3.,
+                    vec!["any:the".to_owned(), "activating:cat".to_owned()]
+                ),
+                (0, 0, 1., vec!["any:the".to_owned()]),
+                (1, 0, 1., vec!["any:the".to_owned()]),
+            ]
+        );
+        assert_eq!(
+            search("activating:the, !activating:cat", 0..=0),
+            vec![(0, 0, 1., vec!["activating:the".to_owned()])]
+        );
+        for query in ["!activating:the", "activating:the | !important:dog"] {
+            assert!(query.parse::<TokenQuery>().is_err());
+        }
+        assert_eq!(
+            search("activating:/^(c|d)/ | important:dgo~2", 0..=1),
+            vec![
+                (0, 1, 1., vec!["activating:/^(c|d)/".to_owned()]),
+                (1, 1, 1., vec!["important:dgo~2".to_owned()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_queries() {
+        use TokenSearchType::{Activating, Important};
+
+        assert_eq!(
+            "activating:the,important: cat"
+                .parse::<TokenQuery>()
+                .unwrap(),
+            TokenQuery::And(vec![term(Activating, "the"), term(Important, " cat")])
+        );
+        assert_eq!(
+            "activating:a | important:b & !(activating:c | activating:\" |\")"
+                .parse::<TokenQuery>()
+                .unwrap(),
+            TokenQuery::Or(vec![
+                term(Activating, "a"),
+                TokenQuery::And(vec![
+                    term(Important, "b"),
+                    TokenQuery::Not(Box::new(TokenQuery::Or(vec![
+                        term(Activating, "c"),
+                        term(Activating, " |")
+                    ])))
+                ])
+            ])
+        );
+        assert_eq!(
+            "activating:the*, important:teh~ | any:\"a|\"~2 | activating:/\\d+\\/s/"
+                .parse::<TokenQuery>()
+                .unwrap(),
+            TokenQuery::Or(vec![
+                TokenQuery::And(vec![
+                    pattern_term(Activating, TokenPattern::Prefix("the".to_owned())),
+                    pattern_term(Important, TokenPattern::Fuzzy("teh".to_owned(), 1)),
+                ]),
+                TokenQuery::Term(QueryTerm {
+                    search_types: vec![Activating, Important],
+                    pattern: TokenPattern::Fuzzy("a|".to_owned(), 2),
+                }),
+                pattern_term(Activating, TokenPattern::Regex("\\d+/s".to_owned())),
+            ])
+        );
+        for (query, position) in [
+            ("", 0),
+            ("activating:", 11),
+            ("relevant:the", 0),
+            ("(activating:the", 15),
+            ("activating:the)", 14),
+            ("activating:\"the", 15),
+            ("activating:/(/", 11),
+            ("activating:/the", 15),
+        ] {
+            let error = query.parse::<TokenQuery>().unwrap_err().to_string();
+            assert!(
+                error.contains(&format!("at position {position}:")),
+                "Unexpected error for '{query}': {error}"
+            );
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! Counts of the neurons each token of a neuron store activates or is important for, by layer.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::RangeInclusive,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{database::TokenImportance, NeuronIndex, NeuronStore, TokenSearchType};
+
+/// Number of neurons in a layer that a token activates or is important for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayerCount {
+    pub layer: u32,
+    pub activating: u32,
+    pub important: u32,
+    /// Neurons the token both activates and is important for.
+    pub both: u32,
+}
+
+/// Number of neurons a token activates or is important for in each layer.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenCounts {
+    /// Layers with at least one such neuron, in increasing order.
+    layers: Vec<LayerCount>,
+}
+
+impl TokenCounts {
+    /// Counts the given neurons. Neurons given more than once are only counted once.
+    pub fn from_neurons(
+        activating: impl IntoIterator<Item = NeuronIndex>,
+        important: impl IntoIterator<Item = NeuronIndex>,
+    ) -> Self {
+        fn layer(layers: &mut BTreeMap<u32, LayerCount>, layer_index: u32) -> &mut LayerCount {
+            layers.entry(layer_index).or_insert(LayerCount {
+                layer: layer_index,
+                ..LayerCount::default()
+            })
+        }
+
+        let activating: HashSet<NeuronIndex> = activating.into_iter().collect();
+        let important: HashSet<NeuronIndex> = important.into_iter().collect();
+        let mut layers = BTreeMap::new();
+        for neuron in &activating {
+            layer(&mut layers, neuron.layer).activating += 1;
+        }
+        for neuron in &important {
+            let count = layer(&mut layers, neuron.layer);
+            count.important += 1;
+            if activating.contains(neuron) {
+                count.both += 1;
+            }
+        }
+        Self {
+            layers: layers.into_values().collect(),
+        }
+    }
+
+    pub fn layers(&self) -> &[LayerCount] {
+        &self.layers
+    }
+
+    /// The counts restricted to the given layers.
+    pub fn in_layers(&self, layers: &RangeInclusive<u32>) -> Self {
+        Self {
+            layers: self
+                .layers
+                .iter()
+                .filter(|count| layers.contains(&count.layer))
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// The counts summed over all layers. The layer of the total is 0.
+    pub fn total(&self) -> LayerCount {
+        self.layers
+            .iter()
+            .fold(LayerCount::default(), |total, count| LayerCount {
+                layer: 0,
+                activating: total.activating + count.activating,
+                important: total.important + count.important,
+                both: total.both + count.both,
+            })
+    }
+
+    /// The mean layer of the neurons the token activates or is important for, or `None` if there
+    /// are none.
+    pub fn mean_layer(&self, search_type: TokenSearchType) -> Option<f32> {
+        let count = |layer: &LayerCount| match search_type {
+            TokenSearchType::Activating => layer.activating,
+            TokenSearchType::Important => layer.important,
+        };
+        let num_neurons: u32 = self.layers.iter().map(count).sum();
+        (num_neurons > 0).then(|| {
+            self.layers
+                .iter()
+                .map(|layer| layer.layer as f32 * count(layer) as f32)
+                .sum::<f32>()
+                / num_neurons as f32
+        })
+    }
+}
+
+/// The [`TokenCounts`] of every token of a neuron store.
+pub struct TokenStatistics {
+    counts: HashMap<String, TokenCounts>,
+}
+
+impl TokenStatistics {
+    pub fn new(store: &NeuronStore) -> Self {
+        let neurons =
+            |search_type, token| store.get(search_type, token).into_iter().flatten().copied();
+        let counts = store
+            .tokens(TokenSearchType::Activating)
+            .chain(store.tokens(TokenSearchType::Important))
+            .map(|(token, _)| token)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .map(|token| {
+                (
+                    token.to_owned(),
+                    TokenCounts::from_neurons(
+                        neurons(TokenSearchType::Activating, token),
+                        neurons(TokenSearchType::Important, token),
+                    ),
+                )
+            })
+            .collect();
+        Self { counts }
+    }
+
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// The counts of the token, or `None` if it is not in the neuron store.
+    pub fn get(&self, token: &str) -> Option<&TokenCounts> {
+        self.counts.get(token)
+    }
+}
+
+/// The neurons a token activates or is important for in a model, along with the neurons whose
+/// neuron2graph graphs consider it most important.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenSummary {
+    pub token: String,
+    /// The tokens of the neuron store whose neurons are counted. More than one if tokens are
+    /// normalised before they are compared.
+    pub matched_tokens: Vec<String>,
+    pub activating: u32,
+    pub important: u32,
+    pub both: u32,
+    pub mean_activating_layer: Option<f32>,
+    pub mean_important_layer: Option<f32>,
+    pub layers: Vec<LayerCount>,
+    pub top_neurons: Vec<TokenImportance>,
+}
+
+impl TokenSummary {
+    pub fn new(
+        token: String,
+        matched_tokens: Vec<String>,
+        counts: &TokenCounts,
+        top_neurons: Vec<TokenImportance>,
+    ) -> Self {
+        let total = counts.total();
+        Self {
+            token,
+            matched_tokens,
+            activating: total.activating,
+            important: total.important,
+            both: total.both,
+            mean_activating_layer: counts.mean_layer(TokenSearchType::Activating),
+            mean_important_layer: counts.mean_layer(TokenSearchType::Important),
+            layers: counts.layers().to_vec(),
+            top_neurons,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::NeuronStoreRaw;
+
+    #[test]
+    fn counts_tokens_by_layer() {
+        let raw: NeuronStoreRaw = serde_json::from_value(serde_json::json!({
+            "activating": {
+                "he": ["0_0", "0_1", "1_0"],
+                "she": ["2_1"],
+            },
+            "important": {
+                "he": ["0_1", "2_0"],
+                "it": ["1_1"],
+            },
+        }))
+        .unwrap();
+        let store = NeuronStore::from_raw(raw, 3, 2).unwrap();
+        let statistics = TokenStatistics::new(&store);
+        assert_eq!(statistics.len(), 3);
+
+        let he = statistics.get("he").unwrap();
+        let count = |layer, activating, important, both| LayerCount {
+            layer,
+            activating,
+            important,
+            both,
+        };
+        assert_eq!(
+            he.layers(),
+            [count(0, 2, 1, 1), count(1, 1, 0, 0), count(2, 0, 1, 0)]
+        );
+        assert_eq!(he.total(), count(0, 3, 2, 1));
+        assert_eq!(he.in_layers(&(1..=2)).total(), count(0, 1, 1, 0));
+        assert_eq!(he.mean_layer(TokenSearchType::Activating), Some(1. / 3.));
+        assert_eq!(he.mean_layer(TokenSearchType::Important), Some(1.));
+        assert_eq!(
+            statistics
+                .get("she")
+                .unwrap()
+                .mean_layer(TokenSearchType::Activating),
+            Some(2.)
+        );
+        assert_eq!(
+            statistics
+                .get("it")
+                .unwrap()
+                .mean_layer(TokenSearchType::Activating),
+            None
+        );
+        assert!(statistics.get("they").is_none());
+    }
+}
@@ -0,0 +1,357 @@
+//! The vocabulary of tokens in a neuron store, for finding tokens by prefix, regular expression
+//! or edit distance.
+
+use std::{borrow::Cow, fmt::Display, str::FromStr};
+
+use anyhow::{bail, Context, Result};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use super::{NeuronStore, TokenSearchType};
+
+/// Characters marking whitespace in byte pair encoded tokens, such as `Ġ` in GPT-2 and `▁` in
+/// SentencePiece vocabularies.
+const WHITESPACE_MARKERS: [char; 3] = ['Ġ', 'Ċ', '▁'];
+
+/// How tokens are normalised before they are compared.
+///
+/// Written as a comma separated list of `case`, to compare tokens case insensitively, and
+/// `whitespace`, to ignore leading and trailing whitespace, including whitespace markers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Normalisation {
+    pub case_insensitive: bool,
+    pub ignore_whitespace: bool,
+}
+
+impl Normalisation {
+    pub fn is_identity(self) -> bool {
+        !self.case_insensitive && !self.ignore_whitespace
+    }
+
+    pub fn apply(self, token: &str) -> Cow<'_, str> {
+        let token = if self.ignore_whitespace {
+            token.trim_matches(|c: char| c.is_whitespace() || WHITESPACE_MARKERS.contains(&c))
+        } else {
+            token
+        };
+        if self.case_insensitive {
+            Cow::Owned(token.to_lowercase())
+        } else {
+            Cow::Borrowed(token)
+        }
+    }
+}
+
+impl FromStr for Normalisation {
+    type Err = anyhow::Error;
+
+    fn from_str(normalisation_string: &str) -> Result<Self> {
+        let mut normalisation = Self::default();
+        for option in normalisation_string
+            .split(',')
+            .map(str::trim)
+            .filter(|option| !option.is_empty())
+        {
+            match option {
+                "case" => normalisation.case_insensitive = true,
+                "whitespace" => normalisation.ignore_whitespace = true,
+                _ => bail!("Invalid normalisation '{option}'. Must be 'case' or 'whitespace'."),
+            }
+        }
+        Ok(normalisation)
+    }
+}
+
+/// A pattern matching tokens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenPattern {
+    Exact(String),
+    Prefix(String),
+    Regex(String),
+    /// Tokens within the given edit distance.
+    Fuzzy(String, usize),
+}
+
+impl Display for TokenPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exact(token) => f.write_str(token),
+            Self::Prefix(prefix) => write!(f, "{prefix}*"),
+            Self::Regex(regex) => write!(f, "/{regex}/"),
+            Self::Fuzzy(token, distance) => write!(f, "{token}~{distance}"),
+        }
+    }
+}
+
+fn build_regex(regex: &str, case_insensitive: bool) -> Result<Regex> {
+    RegexBuilder::new(regex)
+        .case_insensitive(case_insensitive)
+        .size_limit(1 << 20)
+        .build()
+        .with_context(|| format!("Invalid regular expression '{regex}'."))
+}
+
+enum Matcher {
+    Exact(String),
+    Prefix(String),
+    Regex(Regex),
+    Fuzzy(Vec<char>, usize),
+}
+
+impl Matcher {
+    /// The edit distance between the pattern and the normalised token, if it matches. Patterns
+    /// that are not fuzzy match with distance 0.
+    fn distance(&self, token: &str) -> Option<usize> {
+        match self {
+            Self::Exact(pattern) => (token == pattern).then_some(0),
+            Self::Prefix(prefix) => token.starts_with(prefix.as_str()).then_some(0),
+            Self::Regex(regex) => regex.is_match(token).then_some(0),
+            Self::Fuzzy(pattern, max_distance) => {
+                bounded_edit_distance(pattern, token, *max_distance)
+            }
+        }
+    }
+}
+
+impl TokenPattern {
+    /// Checks that the pattern is valid, i.e. that a regular expression compiles.
+    pub fn validate(&self) -> Result<()> {
+        if let Self::Regex(regex) = self {
+            build_regex(regex, false)?;
+        }
+        Ok(())
+    }
+
+    fn matcher(&self, normalisation: Normalisation) -> Result<Matcher> {
+        Ok(match self {
+            Self::Exact(token) => Matcher::Exact(normalisation.apply(token).into_owned()),
+            Self::Prefix(prefix) => {
+                // Only the start of a prefix is trimmed, as trailing whitespace is part of it.
+                let prefix = Normalisation {
+                    ignore_whitespace: false,
+                    ..normalisation
+                }
+                .apply(if normalisation.ignore_whitespace {
+                    prefix.trim_start_matches(|c: char| {
+                        c.is_whitespace() || WHITESPACE_MARKERS.contains(&c)
+                    })
+                } else {
+                    prefix
+                });
+                Matcher::Prefix(prefix.into_owned())
+            }
+            Self::Regex(regex) => {
+                Matcher::Regex(build_regex(regex, normalisation.case_insensitive)?)
+            }
+            Self::Fuzzy(token, max_distance) => {
+                Matcher::Fuzzy(normalisation.apply(token).chars().collect(), *max_distance)
+            }
+        })
+    }
+}
+
+/// The Levenshtein distance between the pattern and the token, or `None` if it is greater than
+/// `max_distance`.
+fn bounded_edit_distance(pattern: &[char], token: &str, max_distance: usize) -> Option<usize> {
+    let token: Vec<char> = token.chars().collect();
+    if pattern.len().abs_diff(token.len()) > max_distance {
+        return None;
+    }
+    let mut previous: Vec<usize> = (0..=token.len()).collect();
+    let mut current = vec![0; token.len() + 1];
+    for (i, &pattern_char) in pattern.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, &token_char) in token.iter().enumerate() {
+            let substitution = previous[j] + usize::from(pattern_char != token_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        if current.iter().all(|&distance| distance > max_distance) {
+            return None;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    let distance = previous[token.len()];
+    (distance <= max_distance).then_some(distance)
+}
+
+/// A token along with the number of neurons it activates and is important for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VocabularyEntry {
+    pub token: String,
+    pub activating: usize,
+    pub important: usize,
+}
+
+/// A token matching a pattern.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenSuggestion {
+    pub token: String,
+    pub activating: usize,
+    pub important: usize,
+    /// Edit distance from the pattern, which is 0 unless the pattern is fuzzy.
+    pub distance: usize,
+}
+
+pub struct TokenVocabulary {
+    /// Sorted by token.
+    entries: Vec<VocabularyEntry>,
+}
+
+impl TokenVocabulary {
+    pub fn new(store: &NeuronStore) -> Self {
+        let mut entries: Vec<VocabularyEntry> = store
+            .tokens(TokenSearchType::Activating)
+            .map(|(token, neurons)| VocabularyEntry {
+                token: token.to_owned(),
+                activating: neurons.len(),
+                important: store
+                    .get(TokenSearchType::Important, token)
+                    .map_or(0, |neurons| neurons.len()),
+            })
+            .collect();
+        entries.extend(
+            store
+                .tokens(TokenSearchType::Important)
+                .filter(|(token, _)| store.get(TokenSearchType::Activating, token).is_none())
+                .map(|(token, neurons)| VocabularyEntry {
+                    token: token.to_owned(),
+                    activating: 0,
+                    important: neurons.len(),
+                }),
+        );
+        entries.sort_unstable_by(|a, b| a.token.cmp(&b.token));
+        Self { entries }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The tokens matching the pattern, along with their edit distance to it.
+    pub fn matching(
+        &self,
+        pattern: &TokenPattern,
+        normalisation: Normalisation,
+    ) -> Result<Vec<(&VocabularyEntry, usize)>> {
+        if normalisation.is_identity() {
+            match pattern {
+                TokenPattern::Exact(token) => {
+                    return Ok(self
+                        .entries
+                        .binary_search_by(|entry| entry.token.as_str().cmp(token))
+                        .map(|index| vec![(&self.entries[index], 0)])
+                        .unwrap_or_default())
+                }
+                TokenPattern::Prefix(prefix) => {
+                    let start = self
+                        .entries
+                        .partition_point(|entry| entry.token.as_str() < prefix.as_str());
+                    return Ok(self.entries[start..]
+                        .iter()
+                        .take_while(|entry| entry.token.starts_with(prefix.as_str()))
+                        .map(|entry| (entry, 0))
+                        .collect());
+                }
+                _ => {}
+            }
+        }
+        let matcher = pattern.matcher(normalisation)?;
+        Ok(self
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                matcher
+                    .distance(&normalisation.apply(&entry.token))
+                    .map(|distance| (entry, distance))
+            })
+            .collect())
+    }
+
+    /// The tokens matching the pattern, closest and most common first.
+    pub fn suggest(
+        &self,
+        pattern: &TokenPattern,
+        normalisation: Normalisation,
+        limit: usize,
+    ) -> Result<Vec<TokenSuggestion>> {
+        let mut suggestions: Vec<_> = self
+            .matching(pattern, normalisation)?
+            .into_iter()
+            .map(|(entry, distance)| TokenSuggestion {
+                token: entry.token.clone(),
+                activating: entry.activating,
+                important: entry.important,
+                distance,
+            })
+            .collect();
+        sort_suggestions(&mut suggestions);
+        suggestions.truncate(limit);
+        Ok(suggestions)
+    }
+}
+
+/// Sorts suggestions by distance, then by how many neurons the token activates or is important
+/// for, and then by token.
+pub fn sort_suggestions(suggestions: &mut [TokenSuggestion]) {
+    suggestions.sort_unstable_by(|a, b| {
+        a.distance
+            .cmp(&b.distance)
+            .then((b.activating + b.important).cmp(&(a.activating + a.important)))
+            .then(a.token.cmp(&b.token))
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::NeuronStoreRaw;
+
+    #[test]
+    fn finds_tokens() {
+        let raw: NeuronStoreRaw = serde_json::from_value(serde_json::json!({
+            "activating": { " the": ["0_0", "0_1"], "The": ["0_1"], "then": ["1_0"], "cat": ["0_0"] },
+            "important": { " the": ["1_1"], "ĠThe": ["1_1"] },
+        }))
+        .unwrap();
+        let store = NeuronStore::from_raw(raw, 2, 2).unwrap();
+        let vocabulary = TokenVocabulary::new(&store);
+        assert_eq!(vocabulary.len(), 5);
+
+        let tokens = |pattern: TokenPattern, normalisation: &str| {
+            vocabulary
+                .suggest(&pattern, normalisation.parse().unwrap(), 10)
+                .unwrap()
+                .into_iter()
+                .map(|suggestion| (suggestion.token, suggestion.distance))
+                .collect::<Vec<_>>()
+        };
+        let exact = |token: &str| TokenPattern::Exact(token.to_owned());
+
+        assert_eq!(tokens(exact("the"), ""), vec![]);
+        assert_eq!(
+            tokens(exact("the"), "case,whitespace"),
+            vec![
+                (" the".to_owned(), 0),
+                ("The".to_owned(), 0),
+                ("ĠThe".to_owned(), 0)
+            ]
+        );
+        assert_eq!(
+            tokens(TokenPattern::Prefix("the".to_owned()), "whitespace"),
+            vec![(" the".to_owned(), 0), ("then".to_owned(), 0)]
+        );
+        assert_eq!(
+            tokens(TokenPattern::Regex("^[Tt]he$".to_owned()), ""),
+            vec![("The".to_owned(), 0)]
+        );
+        assert_eq!(
+            tokens(TokenPattern::Fuzzy("teh".to_owned(), 2), "case"),
+            vec![("The".to_owned(), 2), ("then".to_owned(), 2)]
+        );
+        assert!(TokenPattern::Regex("(".to_owned()).validate().is_err());
+    }
+}
@@ -6,5 +6,7 @@ mod index;
 pub mod server;
 pub use index::Index;
 pub mod cli;
+pub mod config;
 pub mod logging;
+pub mod metrics;
 pub mod util;
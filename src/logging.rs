@@ -2,6 +2,7 @@
 
 use std::{
     fs,
+    future::Future,
     io::Write,
     path::{Path, PathBuf},
 };
@@ -12,6 +13,22 @@ use multi_log::MultiLogger;
 
 use crate::cli;
 
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Runs the future with the request ID attached to every line it logs.
+pub async fn with_request_id<F: Future>(request_id: String, future: F) -> F::Output {
+    REQUEST_ID.scope(request_id, future).await
+}
+
+/// The ID of the request currently being handled, formatted as a log line prefix.
+fn request_id_prefix() -> String {
+    REQUEST_ID
+        .try_with(|request_id| format!("[{request_id}] "))
+        .unwrap_or_default()
+}
+
 fn log_file_path<P>(dir: P, index: u32) -> PathBuf
 where
     P: AsRef<Path>,
@@ -48,7 +65,7 @@ where
         .unwrap()
 }
 
-fn create_write_logger<P>(dir: P) -> Box<Logger>
+fn create_write_logger<P>(dir: P, level: LevelFilter) -> Box<Logger>
 where
     P: AsRef<Path>,
 {
@@ -65,23 +82,24 @@ where
         .format(|buf, record| {
             writeln!(
                 buf,
-                "{}:{} [{}] {} {}",
+                "{}:{} [{}] {} {}{}",
                 record.file().unwrap_or("unknown"),
                 record.line().unwrap_or(0),
                 record.level(),
                 buf.timestamp_millis(),
+                request_id_prefix(),
                 record.args()
             )
         })
         .filter_level(LevelFilter::Off)
-        .filter_module("deepdecipher", LevelFilter::Debug)
+        .filter_module("deepdecipher", level)
         .target(Target::Pipe(log_file_target))
         .build();
 
     Box::new(write_logger)
 }
 
-fn create_stdout_logger() -> Box<Logger> {
+fn create_stdout_logger(level: LevelFilter) -> Box<Logger> {
     let stdout_logger = env_logger::builder()
         .format(|buf, record| {
             let mut style = buf.style();
@@ -93,10 +111,16 @@ fn create_stdout_logger() -> Box<Logger> {
                 Level::Trace => style.set_color(Color::Cyan),
             };
             style.set_bold(true);
-            writeln!(buf, "[{}] {}", style.value(record.level()), record.args())
+            writeln!(
+                buf,
+                "[{}] {}{}",
+                style.value(record.level()),
+                request_id_prefix(),
+                record.args()
+            )
         })
         .filter_level(LevelFilter::Off)
-        .filter_module("deepdecipher", LevelFilter::Info)
+        .filter_module("deepdecipher", level)
         .target(Target::Stdout)
         .build();
 
@@ -104,12 +128,20 @@ fn create_stdout_logger() -> Box<Logger> {
 }
 
 pub fn log_init(log_path: Option<impl AsRef<Path>>) {
+    log_init_with_levels(log_path, LevelFilter::Info, LevelFilter::Debug)
+}
+
+pub fn log_init_with_levels(
+    log_path: Option<impl AsRef<Path>>,
+    stdout_level: LevelFilter,
+    file_level: LevelFilter,
+) {
     // Log to stdout.
-    let mut loggers: Vec<Box<dyn Log + 'static>> = vec![create_stdout_logger()];
+    let mut loggers: Vec<Box<dyn Log + 'static>> = vec![create_stdout_logger(stdout_level)];
 
     // Log to file.
     if let Some(log_path) = log_path {
-        loggers.push(create_write_logger(log_path));
+        loggers.push(create_write_logger(log_path, file_level));
     }
 
     // Initialize multiple loggers.
@@ -120,5 +152,9 @@ pub fn log_init(log_path: Option<impl AsRef<Path>>) {
 }
 
 pub fn log_init_config(config: &cli::ServerConfig) {
-    log_init(config.log_path());
+    log_init_with_levels(
+        config.log_path(),
+        config.log_level(),
+        config.log_file_level(),
+    );
 }
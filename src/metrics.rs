@@ -0,0 +1,110 @@
+//! Prometheus metrics collected by the server.
+
+use std::sync::LazyLock;
+
+use prometheus::{
+    histogram_opts, opts, Encoder, HistogramTimer, HistogramVec, IntCounterVec, Registry,
+    TextEncoder,
+};
+
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+fn register<M>(metric: M) -> M
+where
+    M: prometheus::core::Collector + Clone + 'static,
+{
+    REGISTRY
+        .register(Box::new(metric.clone()))
+        .expect("Metric should only be registered once.");
+    metric
+}
+
+static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(
+        IntCounterVec::new(
+            opts!(
+                "deepdecipher_http_requests_total",
+                "Number of HTTP requests handled."
+            ),
+            &["route", "service", "model", "status"],
+        )
+        .unwrap(),
+    )
+});
+
+static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(
+        HistogramVec::new(
+            histogram_opts!(
+                "deepdecipher_http_request_duration_seconds",
+                "Time taken to handle HTTP requests."
+            ),
+            &["route", "service", "model"],
+        )
+        .unwrap(),
+    )
+});
+
+static DATABASE_QUERY_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(
+        HistogramVec::new(
+            histogram_opts!(
+                "deepdecipher_database_query_duration_seconds",
+                "Time taken by database queries.",
+                vec![0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0]
+            ),
+            &["query"],
+        )
+        .unwrap(),
+    )
+});
+
+static CACHE_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(
+        IntCounterVec::new(
+            opts!(
+                "deepdecipher_cache_requests_total",
+                "Number of cache lookups by whether they hit or missed."
+            ),
+            &["cache", "result"],
+        )
+        .unwrap(),
+    )
+});
+
+/// Records a handled HTTP request. `route` should be the route pattern rather than the requested
+/// path to keep the number of label values bounded.
+pub fn observe_request(route: &str, service: &str, model: &str, status: u16, seconds: f64) {
+    HTTP_REQUESTS
+        .with_label_values(&[route, service, model, &status.to_string()])
+        .inc();
+    HTTP_REQUEST_DURATION
+        .with_label_values(&[route, service, model])
+        .observe(seconds);
+}
+
+/// Starts timing a database query. The time is recorded when the returned timer is dropped.
+pub fn database_query_timer(query: &str) -> HistogramTimer {
+    DATABASE_QUERY_DURATION
+        .with_label_values(&[query])
+        .start_timer()
+}
+
+pub fn observe_cache_lookup(cache: &str, hit: bool) {
+    CACHE_REQUESTS
+        .with_label_values(&[cache, if hit { "hit" } else { "miss" }])
+        .inc();
+}
+
+/// Renders all metrics in the Prometheus text format.
+pub fn render() -> String {
+    LazyLock::force(&HTTP_REQUESTS);
+    LazyLock::force(&HTTP_REQUEST_DURATION);
+    LazyLock::force(&DATABASE_QUERY_DURATION);
+    LazyLock::force(&CACHE_REQUESTS);
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&REGISTRY.gather(), &mut buffer)
+        .expect("Encoding metrics as text should not fail.");
+    String::from_utf8(buffer).expect("Prometheus text format is UTF-8.")
+}
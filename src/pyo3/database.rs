@@ -4,11 +4,11 @@ use tokio::runtime::Runtime;
 
 use super::{
     data_type::PyDataType, data_type_handle::PyDataTypeHandle, model_handle::PyModelHandle,
-    model_metadata::PyModelMetadata, service_handle::PyServiceHandle,
+    model_metadata::PyModelMetadata, run_cancellable, service_handle::PyServiceHandle,
     service_provider::PyServiceProvider,
 };
 use crate::{
-    data::{data_types::DataType, Database},
+    data::{data_types::DataType, database::FILL_BATCH_SIZE, Database},
     server::Service,
 };
 
@@ -35,6 +35,15 @@ impl PyDatabase {
         Ok(PyDatabase { database })
     }
 
+    fn fill_derived_tables(&self, batch_size: Option<usize>) -> PyResult<()> {
+        run_cancellable("fill derived tables", async {
+            self.database
+                .fill_derived_tables(batch_size.unwrap_or(FILL_BATCH_SIZE))
+                .await
+        })?;
+        Ok(())
+    }
+
     fn add_model(&mut self, model_metadata: &PyModelMetadata) -> PyResult<PyModelHandle> {
         let result = Runtime::new()
             .context("Failed to start async runtime to add model.")?
@@ -1,8 +1,8 @@
-use std::ffi::OsString;
+use std::{ffi::OsString, future::Future};
 
 use anyhow::Context;
 use clap::Parser;
-use pyo3::prelude::*;
+use pyo3::{exceptions::PyKeyboardInterrupt, prelude::*};
 
 mod database;
 use database::PyDatabase;
@@ -22,13 +22,41 @@ mod index;
 use index::PyIndex;
 use tokio::runtime::Runtime;
 
-use crate::{cli::ServerConfig, logging};
+use crate::{cli::ServerConfig, logging, util::cancel};
 
+/// Runs an operation that is cancelled if a keyboard interrupt is received while it runs. The
+/// cancellation is raised as a `KeyboardInterrupt` in Python.
+pub(crate) fn run_cancellable<T>(
+    description: &str,
+    operation: impl Future<Output = anyhow::Result<T>>,
+) -> PyResult<T> {
+    let _operation = cancel::start_operation();
+    Runtime::new()
+        .with_context(|| format!("Failed to start async runtime to {description}."))?
+        .block_on(operation)
+        .map_err(|error| {
+            if cancel::is_cancellation(&error) {
+                PyKeyboardInterrupt::new_err(format!("Cancelled operation to {description}."))
+            } else {
+                error.into()
+            }
+        })
+}
+
+/// Makes keyboard interrupts cancel the running operation, stopping it once no write is half done.
+/// If no operation is running, or a second keyboard interrupt is received, the process exits.
 #[pyfunction]
 fn setup_keyboard_interrupt() {
     if let Err(error) = ctrlc::set_handler(move || {
-        println!("Keyboard interrupt received, exiting...");
-        std::process::abort();
+        if cancel::is_cancelled() || !cancel::request_cancel() {
+            println!("Keyboard interrupt received, exiting...");
+            log::logger().flush();
+            std::process::exit(130);
+        }
+        println!(
+            "Keyboard interrupt received, cancelling the running operation. Press Ctrl-C again to \
+             exit immediately."
+        );
     }) {
         match error {
             ctrlc::Error::MultipleHandlers => {
@@ -55,7 +83,10 @@ fn log_init(log_path: Option<&str>) {
 fn start_server(cli_arguments: Vec<OsString>) -> PyResult<()> {
     Runtime::new()
         .context("Failed to start async runtime to start server.")?
-        .block_on(async { ServerConfig::parse_from(cli_arguments).start().await })?;
+        .block_on(async {
+            let _operation = cancel::start_operation();
+            ServerConfig::parse_from(cli_arguments).start().await
+        })?;
 
     Ok(())
 }
@@ -4,9 +4,12 @@ use tokio::runtime::Runtime;
 
 use super::{
     data_type_handle::PyDataTypeHandle, index::PyIndex, model_metadata::PyModelMetadata,
-    service_handle::PyServiceHandle,
+    run_cancellable, service_handle::PyServiceHandle,
+};
+use crate::data::{
+    retrieve, ClusteringConfig, MinHashConfig, ModelHandle, NeuroscopeStoreConfig,
+    SimilarityConfig, SimilarityMetric,
 };
-use crate::data::{retrieve, ModelHandle};
 
 #[pyclass(name = "ModelHandle")]
 pub struct PyModelHandle {
@@ -19,6 +22,27 @@ impl PyModelHandle {
     }
 }
 
+fn similarity_config(
+    threshold: Option<f32>,
+    metric: Option<&str>,
+    top_k: Option<usize>,
+    threads: Option<usize>,
+    min_hash: Option<bool>,
+) -> anyhow::Result<SimilarityConfig> {
+    let config = SimilarityConfig {
+        metric: metric
+            .map(str::parse)
+            .transpose()?
+            .unwrap_or(SimilarityMetric::default()),
+        threshold,
+        top_k,
+        threads,
+        min_hash: min_hash.unwrap_or(false).then(MinHashConfig::default),
+    };
+    config.validate()?;
+    Ok(config)
+}
+
 #[pymethods]
 impl PyModelHandle {
     pub fn metadata(&self) -> PyModelMetadata {
@@ -40,9 +64,8 @@ impl PyModelHandle {
 
     pub fn scrape_neuroscope_model(&mut self) -> PyResult<()> {
         let model_name = self.model.name().to_owned();
-        Runtime::new()
-            .context("Failed to start async runtime to scrape neuroscope.")?
-            .block_on(async {
+        run_cancellable("scrape neuroscope", async {
+            async {
                 let model = &mut self.model;
                 println!("Scraping model '{model_name}' to database.");
                 retrieve::neuroscope::scrape_model_to_database(model)
@@ -51,16 +74,17 @@ impl PyModelHandle {
                         format!("Failed to scrape data for model '{model_name}' from Neuroscope.")
                     })?;
                 anyhow::Ok(model)
-            })
-            .with_context(|| format!("Failed to scrape neuroscope model '{model_name}'."))?;
+            }
+            .await
+            .with_context(|| format!("Failed to scrape neuroscope model '{model_name}'."))
+        })?;
         Ok(())
     }
 
     pub fn scrape_missing_neuroscope_items(&mut self) -> PyResult<()> {
         let model_name = self.model.name().to_owned();
-        Runtime::new()
-            .context("Failed to start async runtime to scrape neuroscope.")?
-            .block_on(async {
+        run_cancellable("scrape neuroscope", async {
+            async {
                 let model = &mut self.model;
                 let data_type = model
                     .database()
@@ -89,54 +113,172 @@ impl PyModelHandle {
                     missing_indices = model.missing_neuron_items(&data_type).await?.collect();
                 }
                 anyhow::Ok(())
-            })
-            .with_context(|| format!("Failed to scrape neuroscope model '{model_name}'."))?;
+            }
+            .await
+            .with_context(|| format!("Failed to scrape neuroscope model '{model_name}'."))
+        })?;
         Ok(())
     }
 
     pub fn add_neuron_store(
         &mut self,
         neuron_store_path: &str,
-        similarity_threshold: f32,
+        similarity_threshold: Option<f32>,
+        metric: Option<&str>,
+        top_k: Option<usize>,
+        threads: Option<usize>,
+        min_hash: Option<bool>,
     ) -> PyResult<()> {
-        Runtime::new()
-            .context("Failed to start async runtime to add neuron store.")?
-            .block_on(async {
-                retrieve::neuron_store::retrieve_neuron_store(
-                    &mut self.model,
-                    neuron_store_path,
-                    similarity_threshold,
-                )
+        let similarity_config =
+            similarity_config(similarity_threshold, metric, top_k, threads, min_hash)?;
+        run_cancellable("add neuron store", async {
+            retrieve::neuron_store::retrieve_neuron_store(
+                &mut self.model,
+                neuron_store_path,
+                &similarity_config,
+            )
+            .await
+        })?;
+        Ok(())
+    }
+
+    pub fn derive_neuron_store(
+        &mut self,
+        activating_fraction: Option<f32>,
+        important_fraction: Option<f32>,
+        similarity_threshold: Option<f32>,
+        metric: Option<&str>,
+        top_k: Option<usize>,
+    ) -> PyResult<()> {
+        let default = NeuroscopeStoreConfig::default();
+        let store_config = NeuroscopeStoreConfig {
+            activating_fraction: activating_fraction.unwrap_or(default.activating_fraction),
+            important_fraction: important_fraction.unwrap_or(default.important_fraction),
+        };
+        let similarity_config = similarity_config(similarity_threshold, metric, top_k, None, None)?;
+        run_cancellable("derive neuron store", async {
+            retrieve::neuron_store::derive_neuron_store(
+                &mut self.model,
+                &store_config,
+                &similarity_config,
+            )
+            .await
+        })?;
+        Ok(())
+    }
+
+    pub fn recompute_neuron_similarities(
+        &mut self,
+        similarity_threshold: Option<f32>,
+        metric: Option<&str>,
+        top_k: Option<usize>,
+        threads: Option<usize>,
+        min_hash: Option<bool>,
+    ) -> PyResult<()> {
+        let similarity_config =
+            similarity_config(similarity_threshold, metric, top_k, threads, min_hash)?;
+        run_cancellable("recompute neuron similarities", async {
+            retrieve::neuron_store::recompute_similar_neurons(&mut self.model, &similarity_config)
                 .await
-            })?;
+        })?;
+        Ok(())
+    }
+
+    pub fn add_neuron_correspondence(
+        &mut self,
+        other_model: &mut PyModelHandle,
+        similarity_threshold: Option<f32>,
+        metric: Option<&str>,
+        top_k: Option<usize>,
+        threads: Option<usize>,
+        min_hash: Option<bool>,
+    ) -> PyResult<()> {
+        let similarity_config =
+            similarity_config(similarity_threshold, metric, top_k, threads, min_hash)?;
+        run_cancellable("add neuron correspondence", async {
+            retrieve::neuron_store::store_neuron_correspondence(
+                &mut self.model,
+                &mut other_model.model,
+                &similarity_config,
+            )
+            .await
+        })?;
+        Ok(())
+    }
+
+    pub fn compute_neuron_clusters(
+        &mut self,
+        resolution: Option<f32>,
+        min_similarity: Option<f32>,
+    ) -> PyResult<()> {
+        let default = ClusteringConfig::default();
+        let clustering_config = ClusteringConfig {
+            resolution: resolution.unwrap_or(default.resolution),
+            min_similarity: min_similarity.unwrap_or(default.min_similarity),
+        };
+        run_cancellable("compute neuron clusters", async {
+            retrieve::neuron_clusters::store_neuron_clusters(&mut self.model, &clustering_config)
+                .await
+        })?;
         Ok(())
     }
 
     pub fn add_neuron2graph_graphs(&mut self, neuron2graph_path: &str) -> PyResult<()> {
-        Runtime::new()
-            .context("Failed to start async runtime to add neuron2graph graphs.")?
-            .block_on(async {
-                retrieve::neuron2graph::retrieve_neuron2graph(&mut self.model, neuron2graph_path)
-                    .await
-            })?;
+        run_cancellable("add neuron2graph graphs", async {
+            retrieve::neuron2graph::retrieve_neuron2graph(&mut self.model, neuron2graph_path).await
+        })?;
+        Ok(())
+    }
+
+    pub fn add_embeddings(
+        &mut self,
+        data_type_name: &str,
+        npy_path: &str,
+        precision: Option<&str>,
+    ) -> PyResult<()> {
+        let precision = precision.map(str::parse).transpose()?;
+        run_cancellable("add embeddings", async {
+            retrieve::embedding::retrieve_npy_embeddings(
+                &mut self.model,
+                data_type_name,
+                precision,
+                npy_path,
+            )
+            .await
+        })?;
+        Ok(())
+    }
+
+    pub fn compute_token_embeddings(
+        &mut self,
+        data_type_name: &str,
+        dimensions: Option<usize>,
+        precision: Option<&str>,
+    ) -> PyResult<()> {
+        let precision = precision.map(str::parse).transpose()?;
+        run_cancellable("compute token embeddings", async {
+            retrieve::embedding::compute_token_embeddings(
+                &mut self.model,
+                data_type_name,
+                precision,
+                dimensions.unwrap_or(retrieve::embedding::DEFAULT_TOKEN_EMBEDDING_DIMENSIONS),
+            )
+            .await
+        })?;
         Ok(())
     }
 
     pub fn add_neuron_explainer_small(&mut self) -> PyResult<()> {
-        Runtime::new()
-            .context("Failed to start async runtime to add neuron explainer.")?
-            .block_on(async {
-                retrieve::neuron_explainer::retrieve_neuron_explainer_small(&mut self.model).await
-            })?;
+        run_cancellable("add neuron explainer", async {
+            retrieve::neuron_explainer::retrieve_neuron_explainer_small(&mut self.model).await
+        })?;
         Ok(())
     }
 
     pub fn add_neuron_explainer_xl(&mut self) -> PyResult<()> {
-        Runtime::new()
-            .context("Failed to start async runtime to add neuron explainer.")?
-            .block_on(async {
-                retrieve::neuron_explainer::retrieve_neuron_explainer_xl(&mut self.model).await
-            })?;
+        run_cancellable("add neuron explainer", async {
+            retrieve::neuron_explainer::retrieve_neuron_explainer_xl(&mut self.model).await
+        })?;
         Ok(())
     }
 
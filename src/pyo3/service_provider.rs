@@ -39,6 +39,69 @@ impl PyServiceProvider {
         }
     }
 
+    #[staticmethod]
+    pub fn explanation_search() -> Self {
+        PyServiceProvider {
+            provider: ServiceProvider::ExplanationSearch,
+        }
+    }
+
+    #[staticmethod]
+    pub fn neuron_query() -> Self {
+        PyServiceProvider {
+            provider: ServiceProvider::NeuronQuery,
+        }
+    }
+
+    #[staticmethod]
+    pub fn sample_index() -> Self {
+        PyServiceProvider {
+            provider: ServiceProvider::SampleIndex,
+        }
+    }
+
+    #[staticmethod]
+    pub fn neuron_correspondence() -> Self {
+        PyServiceProvider {
+            provider: ServiceProvider::NeuronCorrespondence,
+        }
+    }
+
+    #[staticmethod]
+    pub fn context_search() -> Self {
+        PyServiceProvider {
+            provider: ServiceProvider::ContextSearch,
+        }
+    }
+
+    #[staticmethod]
+    pub fn token_statistics() -> Self {
+        PyServiceProvider {
+            provider: ServiceProvider::TokenStatistics,
+        }
+    }
+
+    #[staticmethod]
+    pub fn neuron_summary() -> Self {
+        PyServiceProvider {
+            provider: ServiceProvider::NeuronSummary,
+        }
+    }
+
+    #[staticmethod]
+    pub fn explore() -> Self {
+        PyServiceProvider {
+            provider: ServiceProvider::Explore,
+        }
+    }
+
+    #[staticmethod]
+    pub fn neuron_clusters() -> Self {
+        PyServiceProvider {
+            provider: ServiceProvider::NeuronClusters,
+        }
+    }
+
     #[staticmethod]
     pub fn json(data_type: &PyDataTypeHandle) -> Self {
         PyServiceProvider {
@@ -46,6 +109,13 @@ impl PyServiceProvider {
         }
     }
 
+    #[staticmethod]
+    pub fn embedding_search(data_type: &PyDataTypeHandle) -> Self {
+        PyServiceProvider {
+            provider: ServiceProvider::embedding_search(data_type.data_type.name().to_owned()),
+        }
+    }
+
     pub fn __repr__(&self) -> &str {
         self.provider.as_ref()
     }
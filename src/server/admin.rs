@@ -0,0 +1,1059 @@
+//! Authenticated write API mirroring the operations of the Python bindings.
+//!
+//! Every route is mounted under `/admin` and requires an `Authorization: Bearer <token>` header
+//! with one of the tokens given to the server with `--admin-token`.
+
+use actix_web::{
+    body::MessageBody,
+    delete,
+    dev::{ServiceRequest, ServiceResponse},
+    get,
+    http::{header, Method},
+    middleware::Next,
+    post, put, web, HttpMessage, HttpRequest, HttpResponse, Responder,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{response::Response, Service, ServiceProvider, State};
+use crate::{
+    data::{
+        data_objects::EmbeddingPrecision, data_types::DataType, database::FILL_BATCH_SIZE,
+        retrieve, ClusteringConfig, DataTypeHandle, Database, Metadata, MinHashConfig, ModelHandle,
+        NeuronIndex, NeuronStoreRaw, NeuroscopeStoreConfig, SimilarityConfig, SimilarityMetric,
+    },
+    Index,
+};
+
+/// Maximum size of an uploaded payload. Neuron stores and model level data can be large.
+pub const MAX_PAYLOAD_SIZE: usize = 1 << 30;
+
+pub async fn require_admin_token(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let authorized = request
+        .app_data::<web::Data<State>>()
+        .zip(request.headers().get(header::AUTHORIZATION))
+        .and_then(|(state, authorization)| {
+            let token = authorization.to_str().ok()?.strip_prefix("Bearer ")?;
+            Some(state.is_admin_token(token.trim()))
+        })
+        .unwrap_or(false);
+    if authorized {
+        let response = next.call(request).await?;
+        if response.request().method() != Method::GET {
+            if let Some(state) = response.request().app_data::<web::Data<State>>() {
+                state.clear_caches();
+            }
+        }
+        Ok(response.map_into_left_body())
+    } else {
+        log::info!(
+            "Rejected unauthorized admin request to '{}'.",
+            request.path()
+        );
+        let response = HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+            .body("A valid admin token is required.");
+        Ok(request.into_response(response).map_into_right_body())
+    }
+}
+
+type AdminResult = Result<serde_json::Value, Response>;
+
+fn respond(result: AdminResult) -> Response {
+    match result {
+        Ok(value) => Response::success(value),
+        Err(response) => response,
+    }
+}
+
+fn internal_error(error: anyhow::Error) -> Response {
+    Response::error(error, StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn bad_request(error: anyhow::Error) -> Response {
+    Response::error(error, StatusCode::BAD_REQUEST)
+}
+
+async fn model(database: &Database, model_name: &str) -> Result<ModelHandle, Response> {
+    database
+        .model(model_name)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| {
+            Response::error(
+                anyhow!("Model '{model_name}' not found."),
+                StatusCode::NOT_FOUND,
+            )
+        })
+}
+
+async fn data_type(database: &Database, data_type_name: &str) -> Result<DataTypeHandle, Response> {
+    database
+        .data_type(data_type_name)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| {
+            Response::error(
+                anyhow!("Data object '{data_type_name}' not found."),
+                StatusCode::NOT_FOUND,
+            )
+        })
+}
+
+#[derive(Deserialize)]
+struct ModelParameters {
+    name: String,
+    num_layers: u32,
+    layer_size: u32,
+    activation_function: String,
+    num_total_parameters: u32,
+    dataset: String,
+}
+
+#[post("/models")]
+async fn add_model(state: web::Data<State>, body: web::Json<ModelParameters>) -> impl Responder {
+    let ModelParameters {
+        name,
+        num_layers,
+        layer_size,
+        activation_function,
+        num_total_parameters,
+        dataset,
+    } = body.into_inner();
+    respond(
+        async {
+            let num_total_neurons = num_layers.checked_mul(layer_size).ok_or_else(|| {
+                bad_request(anyhow!(
+                    "Model with {num_layers} layers of {layer_size} neurons has too many neurons."
+                ))
+            })?;
+            let metadata = Metadata {
+                name,
+                num_layers,
+                layer_size,
+                activation_function,
+                num_total_neurons,
+                num_total_parameters,
+                dataset,
+            };
+            let model_handle = state
+                .database()
+                .add_model(metadata)
+                .await
+                .map_err(bad_request)?;
+            Ok(json!(model_handle.metadata()))
+        }
+        .await,
+    )
+}
+
+#[delete("/models/{model_name}")]
+async fn delete_model(state: web::Data<State>, model_name: web::Path<String>) -> impl Responder {
+    respond(
+        async {
+            model(state.database(), &model_name)
+                .await?
+                .delete()
+                .await
+                .map_err(internal_error)?;
+            Ok(json!({ "deleted": model_name.as_str() }))
+        }
+        .await,
+    )
+}
+
+/// Starts retrieving data for the model from one of its original sources. Retrieval can take
+/// hours, so it runs in the background and the response is sent as soon as it has started.
+#[post("/models/{model_name}/retrieve/{source}")]
+async fn retrieve_source(state: web::Data<State>, path: web::Path<(String, String)>) -> Response {
+    let (model_name, source) = path.into_inner();
+    let started = async {
+        let mut model_handle = model(state.database(), &model_name).await?;
+        let data_type = match source.as_str() {
+            "neuroscope" | "neuron_explainer_small" | "neuron_explainer_xl" => None,
+            "neuroscope_missing" => Some(data_type(state.database(), "neuroscope").await?),
+            "neuron_explainer_summary" => {
+                Some(data_type(state.database(), "neuron_explainer").await?)
+            }
+            _ => {
+                return Err(Response::error(
+                    anyhow!(
+                        "Unknown source '{source}'. Must be one of 'neuroscope', \
+                         'neuroscope_missing', 'neuron_explainer_small', 'neuron_explainer_xl' or \
+                         'neuron_explainer_summary'."
+                    ),
+                    StatusCode::NOT_FOUND,
+                ))
+            }
+        };
+        let state = state.clone();
+        let source = source.clone();
+        tokio::spawn(async move {
+            let result = match (source.as_str(), data_type) {
+                ("neuroscope", _) => {
+                    retrieve::neuroscope::scrape_model_to_database(&mut model_handle).await
+                }
+                ("neuroscope_missing", Some(data_type)) => {
+                    retrieve::neuroscope::scrape_missing_indices(&mut model_handle, &data_type)
+                        .await
+                }
+                ("neuron_explainer_small", _) => {
+                    retrieve::neuron_explainer::retrieve_neuron_explainer_small(&mut model_handle)
+                        .await
+                }
+                ("neuron_explainer_xl", _) => {
+                    retrieve::neuron_explainer::retrieve_neuron_explainer_xl(&mut model_handle)
+                        .await
+                }
+                ("neuron_explainer_summary", Some(data_type)) => {
+                    retrieve::neuron_explainer::summarise_to_database(&mut model_handle, &data_type)
+                        .await
+                }
+                _ => unreachable!("Source '{source}' was checked before retrieval started."),
+            };
+            // The caches were cleared when the request finished, before most of the data was
+            // written.
+            state.clear_caches();
+            let model_name = model_handle.name();
+            match result {
+                Ok(()) => log::info!("Retrieved '{source}' for model '{model_name}'."),
+                Err(error) => log::error!(
+                    "Failed to retrieve '{source}' for model '{model_name}'. Error: {error:?}"
+                ),
+            }
+        });
+        Ok(())
+    }
+    .await;
+    match started {
+        Ok(()) => Response::accepted(json!({ "retrieving": source })),
+        Err(response) => response,
+    }
+}
+
+#[get("/data_types")]
+async fn data_types(state: web::Data<State>) -> impl Responder {
+    respond(
+        async {
+            let data_type_names = state
+                .database()
+                .all_data_type_names()
+                .await
+                .map_err(internal_error)?;
+            Ok(json!(data_type_names))
+        }
+        .await,
+    )
+}
+
+#[derive(Deserialize)]
+struct DataTypeParameters {
+    name: String,
+    data_type: String,
+}
+
+/// Only JSON data objects can be created directly. Other data objects are created by the routes
+/// that retrieve or upload their data.
+#[post("/data_types")]
+async fn add_data_type(
+    state: web::Data<State>,
+    body: web::Json<DataTypeParameters>,
+) -> impl Responder {
+    let DataTypeParameters { name, data_type } = body.into_inner();
+    respond(
+        async {
+            if !data_type.eq_ignore_ascii_case(DataType::Json.as_ref()) {
+                return Err(bad_request(anyhow!(
+                    "Objects of data type '{data_type}' should be added with the appropriate \
+                     route."
+                )));
+            }
+            state
+                .database()
+                .add_data_type(&name, DataType::Json)
+                .await
+                .with_context(|| format!("Failed to create data object '{name}'."))
+                .map_err(bad_request)?;
+            Ok(json!({ "name": name, "data_type": data_type }))
+        }
+        .await,
+    )
+}
+
+#[delete("/data_types/{data_type_name}")]
+async fn delete_data_type(
+    state: web::Data<State>,
+    data_type_name: web::Path<String>,
+) -> impl Responder {
+    respond(
+        async {
+            data_type(state.database(), &data_type_name)
+                .await?
+                .delete()
+                .await
+                .map_err(internal_error)?;
+            Ok(json!({ "deleted": data_type_name.as_str() }))
+        }
+        .await,
+    )
+}
+
+#[get("/services")]
+async fn services(state: web::Data<State>) -> impl Responder {
+    respond(
+        async {
+            let service_names = state
+                .database()
+                .all_service_names()
+                .await
+                .map_err(internal_error)?
+                .collect::<Vec<_>>();
+            Ok(json!(service_names))
+        }
+        .await,
+    )
+}
+
+#[derive(Deserialize)]
+struct ServiceParameters {
+    name: String,
+    provider: String,
+    data_type: Option<String>,
+}
+
+#[post("/services")]
+async fn add_service(
+    state: web::Data<State>,
+    body: web::Json<ServiceParameters>,
+) -> impl Responder {
+    let ServiceParameters {
+        name,
+        provider,
+        data_type: data_type_name,
+    } = body.into_inner();
+    respond(
+        async {
+            let provider = match (provider.as_str(), data_type_name) {
+                ("neuroscope", None) => ServiceProvider::Neuroscope,
+                ("neuron_explainer", None) => ServiceProvider::NeuronExplainer,
+                ("neuron2graph", None) => ServiceProvider::Neuron2Graph,
+                ("neuron2graph_search", None) => ServiceProvider::Neuron2GraphSearch,
+                ("explanation_search", None) => ServiceProvider::ExplanationSearch,
+                ("neuron_query", None) => ServiceProvider::NeuronQuery,
+                ("sample_index", None) => ServiceProvider::SampleIndex,
+                ("context_search", None) => ServiceProvider::ContextSearch,
+                ("neuron_correspondence", None) => ServiceProvider::NeuronCorrespondence,
+                ("token_statistics", None) => ServiceProvider::TokenStatistics,
+                ("neuron_summary", None) => ServiceProvider::NeuronSummary,
+                ("explore", None) => ServiceProvider::Explore,
+                ("neuron_clusters", None) => ServiceProvider::NeuronClusters,
+                ("json", Some(data_type_name)) => {
+                    let data_type = data_type(state.database(), &data_type_name).await?;
+                    ServiceProvider::json(data_type.name().to_owned())
+                }
+                ("json", None) => {
+                    return Err(bad_request(anyhow!(
+                        "JSON services require the name of their data object."
+                    )))
+                }
+                ("embedding_search", Some(data_type_name)) => {
+                    let data_type = data_type(state.database(), &data_type_name).await?;
+                    if !matches!(data_type.data_type(), DataType::Embedding { .. }) {
+                        return Err(bad_request(anyhow!(
+                            "Data object '{data_type_name}' does not hold embeddings."
+                        )));
+                    }
+                    ServiceProvider::embedding_search(data_type.name().to_owned())
+                }
+                ("embedding_search", None) => {
+                    return Err(bad_request(anyhow!(
+                        "Embedding search services require the name of their data object."
+                    )))
+                }
+                (_, Some(_)) => {
+                    return Err(bad_request(anyhow!(
+                        "Only JSON and embedding search services take a data object."
+                    )))
+                }
+                (provider, None) => {
+                    return Err(bad_request(anyhow!(
+                        "Unknown service provider '{provider}'."
+                    )))
+                }
+            };
+            state
+                .database()
+                .add_service(Service::new(name.clone(), provider))
+                .await
+                .with_context(|| format!("Failed to create service '{name}'."))
+                .map_err(bad_request)?;
+            Ok(json!({ "name": name }))
+        }
+        .await,
+    )
+}
+
+#[delete("/services/{service_name}")]
+async fn delete_service(
+    state: web::Data<State>,
+    service_name: web::Path<String>,
+) -> impl Responder {
+    respond(
+        async {
+            state
+                .database()
+                .service(service_name.as_str())
+                .await
+                .map_err(internal_error)?
+                .ok_or_else(|| {
+                    Response::error(
+                        anyhow!("Service '{service_name}' not found."),
+                        StatusCode::NOT_FOUND,
+                    )
+                })?
+                .delete()
+                .await
+                .map_err(internal_error)?;
+            Ok(json!({ "deleted": service_name.as_str() }))
+        }
+        .await,
+    )
+}
+
+#[get("/models/{model_name}/missing_data_types/{service_name}")]
+async fn missing_data_types(
+    state: web::Data<State>,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (model_name, service_name) = path.into_inner();
+    respond(
+        async {
+            let model_handle = model(state.database(), &model_name).await?;
+            let service_handle = state
+                .database()
+                .service(&service_name)
+                .await
+                .map_err(internal_error)?
+                .ok_or_else(|| {
+                    Response::error(
+                        anyhow!("Service '{service_name}' not found."),
+                        StatusCode::NOT_FOUND,
+                    )
+                })?;
+            let missing_data_types = model_handle
+                .missing_data_types(&service_handle)
+                .await
+                .map_err(internal_error)?;
+            Ok(json!(missing_data_types))
+        }
+        .await,
+    )
+}
+
+#[put("/models/{model_name}/data_types/{data_type_name}")]
+async fn add_model_data_type(
+    state: web::Data<State>,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (model_name, data_type_name) = path.into_inner();
+    respond(
+        async {
+            let mut model_handle = model(state.database(), &model_name).await?;
+            let data_type = data_type(state.database(), &data_type_name).await?;
+            if !model_handle
+                .has_data_type(&data_type)
+                .await
+                .map_err(internal_error)?
+            {
+                model_handle
+                    .add_data_type(&data_type)
+                    .await
+                    .map_err(internal_error)?;
+            }
+            Ok(json!({ "model": model_name, "data_type": data_type_name }))
+        }
+        .await,
+    )
+}
+
+#[delete("/models/{model_name}/data_types/{data_type_name}")]
+async fn delete_model_data_type(
+    state: web::Data<State>,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (model_name, data_type_name) = path.into_inner();
+    respond(
+        async {
+            let mut model_handle = model(state.database(), &model_name).await?;
+            let data_type = data_type(state.database(), &data_type_name).await?;
+            model_handle
+                .delete_data_type(&data_type)
+                .await
+                .map_err(internal_error)?;
+            Ok(json!({ "deleted": data_type_name }))
+        }
+        .await,
+    )
+}
+
+/// Stores the body as the data of the data object for the index, replacing any existing data.
+///
+/// JSON bodies are stored as JSON data, while any other body is stored as is and must already be
+/// in the binary format of the data object.
+async fn upload_data(
+    state: &State,
+    request: &HttpRequest,
+    model_name: &str,
+    data_type_name: &str,
+    index: Index,
+    body: web::Bytes,
+) -> AdminResult {
+    let mut model_handle = model(state.database(), model_name).await?;
+    let data_type = data_type(state.database(), data_type_name).await?;
+    index
+        .valid_in_model(model_handle.metadata())
+        .map_err(bad_request)?;
+    if !model_handle
+        .has_data_type(&data_type)
+        .await
+        .map_err(internal_error)?
+    {
+        return Err(bad_request(anyhow!(
+            "Model '{model_name}' does not have data object '{data_type_name}'. Add it to the \
+             model first."
+        )));
+    }
+    if request.content_type() == "application/json" {
+        let json = serde_json::from_slice(&body)
+            .context("Body is not valid JSON.")
+            .map_err(bad_request)?;
+        retrieve::json::replace_json_data(&mut model_handle, &data_type, index, json)
+            .await
+            .map_err(bad_request)?;
+    } else {
+        model_handle
+            .replace_data(&data_type, index, body.to_vec())
+            .await
+            .map_err(internal_error)?;
+    }
+    if let (DataType::NeuronExplainer, Index::Neuron(layer_index, _)) =
+        (data_type.data_type(), index)
+    {
+        retrieve::neuron_explainer::summarise_layer(&mut model_handle, &data_type, layer_index)
+            .await
+            .map_err(internal_error)?;
+    }
+    Ok(json!({ "stored": body.len() }))
+}
+
+#[put("/models/{model_name}/data/{data_type_name}")]
+async fn upload_model_data(
+    state: web::Data<State>,
+    request: HttpRequest,
+    path: web::Path<(String, String)>,
+    body: web::Bytes,
+) -> impl Responder {
+    let (model_name, data_type_name) = path.into_inner();
+    respond(
+        upload_data(
+            &state,
+            &request,
+            &model_name,
+            &data_type_name,
+            Index::Model,
+            body,
+        )
+        .await,
+    )
+}
+
+#[put("/models/{model_name}/data/{data_type_name}/{layer_index}")]
+async fn upload_layer_data(
+    state: web::Data<State>,
+    request: HttpRequest,
+    path: web::Path<(String, String, u32)>,
+    body: web::Bytes,
+) -> impl Responder {
+    let (model_name, data_type_name, layer_index) = path.into_inner();
+    respond(
+        upload_data(
+            &state,
+            &request,
+            &model_name,
+            &data_type_name,
+            Index::Layer(layer_index),
+            body,
+        )
+        .await,
+    )
+}
+
+#[put("/models/{model_name}/data/{data_type_name}/{layer_index}/{neuron_index}")]
+async fn upload_neuron_data(
+    state: web::Data<State>,
+    request: HttpRequest,
+    path: web::Path<(String, String, u32, u32)>,
+    body: web::Bytes,
+) -> impl Responder {
+    let (model_name, data_type_name, layer_index, neuron_index) = path.into_inner();
+    respond(
+        upload_data(
+            &state,
+            &request,
+            &model_name,
+            &data_type_name,
+            Index::Neuron(layer_index, neuron_index),
+            body,
+        )
+        .await,
+    )
+}
+
+#[derive(Deserialize)]
+struct NeuronStoreQuery {
+    similarity_threshold: Option<f32>,
+    /// One of `overlap`, `jaccard` or `weighted`.
+    metric: Option<String>,
+    /// Weight of important tokens for the weighted metric.
+    important_weight: Option<f32>,
+    top_k: Option<usize>,
+    threads: Option<usize>,
+    /// Whether to find candidates approximately with MinHash.
+    #[serde(default)]
+    min_hash: bool,
+    num_bands: Option<usize>,
+    band_size: Option<usize>,
+    seed: Option<u64>,
+}
+
+impl NeuronStoreQuery {
+    fn similarity_config(&self) -> Result<SimilarityConfig> {
+        let mut metric = match &self.metric {
+            Some(metric) => metric.parse()?,
+            None => SimilarityMetric::default(),
+        };
+        if let Some(weight) = self.important_weight {
+            match &mut metric {
+                SimilarityMetric::Weighted { important_weight } => *important_weight = weight,
+                _ => bail!("An important token weight requires the weighted metric."),
+            }
+        }
+        let min_hash = self.min_hash.then(|| {
+            let default = MinHashConfig::default();
+            MinHashConfig {
+                num_bands: self.num_bands.unwrap_or(default.num_bands),
+                band_size: self.band_size.unwrap_or(default.band_size),
+                seed: self.seed.unwrap_or(default.seed),
+            }
+        });
+        let config = SimilarityConfig {
+            metric,
+            threshold: self.similarity_threshold,
+            top_k: self.top_k,
+            threads: self.threads,
+            min_hash,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[post("/models/{model_name}/neuron_store")]
+async fn upload_neuron_store(
+    state: web::Data<State>,
+    model_name: web::Path<String>,
+    query: web::Query<NeuronStoreQuery>,
+    body: web::Json<NeuronStoreRaw>,
+) -> impl Responder {
+    respond(
+        async {
+            let similarity_config = query.similarity_config().map_err(bad_request)?;
+            let mut model_handle = model(state.database(), &model_name).await?;
+            retrieve::neuron_store::store_raw_neuron_store(
+                state.database(),
+                &mut model_handle,
+                body.into_inner(),
+                &similarity_config,
+            )
+            .await
+            .map_err(bad_request)?;
+            Ok(json!({ "model": model_name.as_str() }))
+        }
+        .await,
+    )
+}
+
+#[derive(Deserialize)]
+struct NeuroscopeStoreQuery {
+    activating_fraction: Option<f32>,
+    important_fraction: Option<f32>,
+}
+
+#[post("/models/{model_name}/neuron_store/from_neuroscope")]
+async fn derive_neuron_store(
+    state: web::Data<State>,
+    model_name: web::Path<String>,
+    query: web::Query<NeuronStoreQuery>,
+    store_query: web::Query<NeuroscopeStoreQuery>,
+) -> impl Responder {
+    respond(
+        async {
+            let similarity_config = query.similarity_config().map_err(bad_request)?;
+            let default = NeuroscopeStoreConfig::default();
+            let store_config = NeuroscopeStoreConfig {
+                activating_fraction: store_query
+                    .activating_fraction
+                    .unwrap_or(default.activating_fraction),
+                important_fraction: store_query
+                    .important_fraction
+                    .unwrap_or(default.important_fraction),
+            };
+            let mut model_handle = model(state.database(), &model_name).await?;
+            retrieve::neuron_store::derive_neuron_store(
+                &mut model_handle,
+                &store_config,
+                &similarity_config,
+            )
+            .await
+            .map_err(bad_request)?;
+            Ok(json!({ "model": model_name.as_str() }))
+        }
+        .await,
+    )
+}
+
+#[post("/models/{model_name}/neuron_store/similarities")]
+async fn recompute_neuron_similarities(
+    state: web::Data<State>,
+    model_name: web::Path<String>,
+    query: web::Query<NeuronStoreQuery>,
+) -> impl Responder {
+    respond(
+        async {
+            let similarity_config = query.similarity_config().map_err(bad_request)?;
+            let mut model_handle = model(state.database(), &model_name).await?;
+            retrieve::neuron_store::recompute_similar_neurons(
+                &mut model_handle,
+                &similarity_config,
+            )
+            .await
+            .map_err(bad_request)?;
+            Ok(json!({ "model": model_name.as_str() }))
+        }
+        .await,
+    )
+}
+
+#[post("/models/{model_name}/neuron_correspondence/{other_model_name}")]
+async fn add_neuron_correspondence(
+    state: web::Data<State>,
+    path: web::Path<(String, String)>,
+    query: web::Query<NeuronStoreQuery>,
+) -> impl Responder {
+    let (model_name, other_model_name) = path.into_inner();
+    respond(
+        async {
+            let similarity_config = query.similarity_config().map_err(bad_request)?;
+            let mut model_handle = model(state.database(), &model_name).await?;
+            let mut other_model_handle = model(state.database(), &other_model_name).await?;
+            retrieve::neuron_store::store_neuron_correspondence(
+                &mut model_handle,
+                &mut other_model_handle,
+                &similarity_config,
+            )
+            .await
+            .map_err(bad_request)?;
+            Ok(json!({ "model": model_name, "other_model": other_model_name }))
+        }
+        .await,
+    )
+}
+
+#[put("/models/{model_name}/neuron2graph/{layer_index}/{neuron_index}")]
+async fn upload_neuron2graph_graph(
+    state: web::Data<State>,
+    path: web::Path<(String, u32, u32)>,
+    body: String,
+) -> impl Responder {
+    let (model_name, layer, neuron) = path.into_inner();
+    respond(
+        async {
+            let mut model_handle = model(state.database(), &model_name).await?;
+            retrieve::neuron2graph::store_neuron2graph_graph(
+                &mut model_handle,
+                NeuronIndex { layer, neuron },
+                &body,
+            )
+            .await
+            .map_err(bad_request)?;
+            Ok(json!({ "layer": layer, "neuron": neuron }))
+        }
+        .await,
+    )
+}
+
+#[derive(Deserialize)]
+struct EmbeddingQuery {
+    /// One of `f16` and `f32`. Defaults to the precision of an existing data object, or `f32`.
+    precision: Option<String>,
+    /// Number of dimensions of embeddings computed from a neuron store.
+    dimensions: Option<usize>,
+}
+
+impl EmbeddingQuery {
+    fn precision(&self) -> Result<Option<EmbeddingPrecision>> {
+        self.precision.as_deref().map(str::parse).transpose()
+    }
+}
+
+/// Stores embeddings of all neurons of the model given as a `.npy` file of floats with shape
+/// `(num_layers, layer_size, dimensions)` or `(num_total_neurons, dimensions)`.
+#[put("/models/{model_name}/embeddings/{data_type_name}")]
+async fn upload_embeddings(
+    state: web::Data<State>,
+    path: web::Path<(String, String)>,
+    query: web::Query<EmbeddingQuery>,
+    body: web::Bytes,
+) -> impl Responder {
+    let (model_name, data_type_name) = path.into_inner();
+    respond(
+        async {
+            let precision = query.precision().map_err(bad_request)?;
+            let mut model_handle = model(state.database(), &model_name).await?;
+            retrieve::embedding::store_npy_embeddings(
+                &mut model_handle,
+                &data_type_name,
+                precision,
+                &body,
+            )
+            .await
+            .map_err(bad_request)?;
+            Ok(json!({ "model": model_name, "data_type": data_type_name }))
+        }
+        .await,
+    )
+}
+
+#[post("/models/{model_name}/embeddings/{data_type_name}/from_neuron_store")]
+async fn compute_token_embeddings(
+    state: web::Data<State>,
+    path: web::Path<(String, String)>,
+    query: web::Query<EmbeddingQuery>,
+) -> impl Responder {
+    let (model_name, data_type_name) = path.into_inner();
+    respond(
+        async {
+            let precision = query.precision().map_err(bad_request)?;
+            let mut model_handle = model(state.database(), &model_name).await?;
+            retrieve::embedding::compute_token_embeddings(
+                &mut model_handle,
+                &data_type_name,
+                precision,
+                query
+                    .dimensions
+                    .unwrap_or(retrieve::embedding::DEFAULT_TOKEN_EMBEDDING_DIMENSIONS),
+            )
+            .await
+            .map_err(bad_request)?;
+            Ok(json!({ "model": model_name, "data_type": data_type_name }))
+        }
+        .await,
+    )
+}
+
+#[derive(Deserialize)]
+struct ClusteringQuery {
+    /// Higher resolutions give more and smaller clusters. Defaults to 1.
+    resolution: Option<f32>,
+    /// Similarities below this do not give an edge. Defaults to 0.
+    min_similarity: Option<f32>,
+}
+
+/// Clusters the graph of the similar neurons of each neuron in the model and stores the clusters
+/// along with the centrality and role of each neuron.
+#[post("/models/{model_name}/neuron_clusters")]
+async fn compute_neuron_clusters(
+    state: web::Data<State>,
+    model_name: web::Path<String>,
+    query: web::Query<ClusteringQuery>,
+) -> impl Responder {
+    respond(
+        async {
+            let default = ClusteringConfig::default();
+            let clustering_config = ClusteringConfig {
+                resolution: query.resolution.unwrap_or(default.resolution),
+                min_similarity: query.min_similarity.unwrap_or(default.min_similarity),
+            };
+            let mut model_handle = model(state.database(), &model_name).await?;
+            retrieve::neuron_clusters::store_neuron_clusters(&mut model_handle, &clustering_config)
+                .await
+                .map_err(bad_request)?;
+            Ok(json!({ "model": model_name.as_str() }))
+        }
+        .await,
+    )
+}
+
+#[derive(Deserialize)]
+struct FillQuery {
+    /// Number of neurons indexed between commits. Defaults to 1000.
+    batch_size: Option<usize>,
+}
+
+/// Starts filling the derived tables that were created for a database that already had neuron
+/// data. Filling reads every neuron, so it runs in the background.
+#[post("/derived_tables/fill")]
+async fn fill_derived_tables(state: web::Data<State>, query: web::Query<FillQuery>) -> Response {
+    let unfilled = match state.database().unfilled_derived_tables().await {
+        Ok(unfilled) => unfilled,
+        Err(error) => return internal_error(error),
+    };
+    if !unfilled.is_empty() {
+        let state = state.clone();
+        let batch_size = query.batch_size.unwrap_or(FILL_BATCH_SIZE);
+        tokio::spawn(async move {
+            let result = state.database().fill_derived_tables(batch_size).await;
+            state.clear_caches();
+            if let Err(error) = result {
+                log::error!("Failed to fill derived tables. Error: {error:?}");
+            }
+        });
+    }
+    Response::accepted(json!({ "filling": unfilled }))
+}
+
+pub fn configure(config: &mut web::ServiceConfig) {
+    config
+        .app_data(web::PayloadConfig::new(MAX_PAYLOAD_SIZE))
+        .app_data(web::JsonConfig::default().limit(MAX_PAYLOAD_SIZE))
+        .service(add_model)
+        .service(delete_model)
+        .service(retrieve_source)
+        .service(data_types)
+        .service(add_data_type)
+        .service(delete_data_type)
+        .service(services)
+        .service(add_service)
+        .service(delete_service)
+        .service(missing_data_types)
+        .service(add_model_data_type)
+        .service(delete_model_data_type)
+        .service(upload_model_data)
+        .service(upload_layer_data)
+        .service(upload_neuron_data)
+        .service(upload_neuron_store)
+        .service(derive_neuron_store)
+        .service(recompute_neuron_similarities)
+        .service(add_neuron_correspondence)
+        .service(upload_neuron2graph_graph)
+        .service(upload_embeddings)
+        .service(compute_token_embeddings)
+        .service(compute_neuron_clusters)
+        .service(fill_derived_tables);
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::{http::StatusCode, middleware, test, App};
+    use clap::Parser;
+
+    use super::*;
+    use crate::cli::ServerConfig;
+
+    #[actix_web::test]
+    async fn rejects_invalid_requests() -> Result<()> {
+        let config = ServerConfig::parse_from(["server", "--admin-token", "token"]);
+        let state = State::new(Database::initialize_in_memory().await?, &config)?;
+        let app = test::init_service(
+            App::new().app_data(web::Data::new(state)).service(
+                web::scope("/admin")
+                    .wrap(middleware::from_fn(require_admin_token))
+                    .configure(configure),
+            ),
+        )
+        .await;
+        let request = |method: Method, path: &str| {
+            test::TestRequest::default()
+                .method(method)
+                .uri(path)
+                .insert_header((header::AUTHORIZATION, "Bearer token"))
+        };
+
+        let response = test::call_service(
+            &app,
+            test::TestRequest::get().uri("/admin/services").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = test::call_service(
+            &app,
+            request(Method::POST, "/admin/models")
+                .set_json(json!({
+                    "name": "huge",
+                    "num_layers": 100000,
+                    "layer_size": 100000,
+                    "activation_function": "gelu",
+                    "num_total_parameters": 1,
+                    "dataset": "none",
+                }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = test::call_service(
+            &app,
+            request(Method::POST, "/admin/models")
+                .set_json(json!({
+                    "name": "small",
+                    "num_layers": 2,
+                    "layer_size": 3,
+                    "activation_function": "gelu",
+                    "num_total_parameters": 1,
+                    "dataset": "none",
+                }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = test::call_service(
+            &app,
+            request(Method::POST, "/admin/models/missing/retrieve/neuroscope").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = test::call_service(
+            &app,
+            request(Method::POST, "/admin/models/small/retrieve/unknown").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = test::call_service(
+            &app,
+            request(Method::POST, "/admin/services")
+                .set_json(json!({ "name": "unknown", "provider": "unknown" }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = test::call_service(
+            &app,
+            request(Method::POST, "/admin/services")
+                .set_json(json!({ "name": "json", "provider": "json" }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        Ok(())
+    }
+}
@@ -13,6 +13,12 @@ use utoipa::OpenApi;
         super::response::all_model,
         super::response::all_layer,
         super::response::all_neuron,
+        super::export::export,
+        super::export::export_graphml,
+        super::search::search_tokens,
+        super::search::suggest_tokens,
+        super::search::search_token_statistics,
+        super::compare::compare,
         super::response::api_doc,
     )
 )]
@@ -0,0 +1,116 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use tokio::sync::OnceCell;
+
+use crate::metrics;
+
+/// The cached value of a model, or the load of it that is in progress.
+type Entry<T> = (String, Arc<OnceCell<Arc<T>>>);
+
+/// A small least recently used cache of values that are expensive to deserialize, such as neuron
+/// stores, keyed by model name.
+///
+/// Concurrent requests for a model that is not cached share a single load. Clearing the cache
+/// drops loads in progress along with the cached values, so a load that started before the cache
+/// was cleared never puts its value in the cache.
+pub struct ModelCache<T> {
+    name: &'static str,
+    capacity: usize,
+    entries: Mutex<VecDeque<Entry<T>>>,
+}
+
+impl<T> ModelCache<T> {
+    pub fn new(name: &'static str, capacity: usize) -> Self {
+        Self {
+            name,
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<Entry<T>>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Returns the cached value for the model, loading and caching it if it is not cached.
+    pub async fn get_or_load<F>(&self, model_name: &str, load: F) -> Result<Arc<T>>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        if self.capacity == 0 {
+            metrics::observe_cache_lookup(self.name, false);
+            return Ok(Arc::new(load.await?));
+        }
+        let cell = {
+            let mut entries = self.lock();
+            let entry = match entries.iter().position(|(name, _)| name == model_name) {
+                Some(position) => entries.remove(position).expect("Position was just found."),
+                None => (model_name.to_owned(), Arc::new(OnceCell::new())),
+            };
+            let cell = entry.1.clone();
+            entries.truncate(self.capacity - 1);
+            entries.push_front(entry);
+            cell
+        };
+        let mut loaded = false;
+        let value = cell
+            .get_or_try_init(|| {
+                loaded = true;
+                async { Ok::<_, anyhow::Error>(Arc::new(load.await?)) }
+            })
+            .await?
+            .clone();
+        metrics::observe_cache_lookup(self.name, !loaded);
+        Ok(value)
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn loads_once_and_drops_stale_loads() -> Result<()> {
+        let cache = ModelCache::new("test", 2);
+        let num_loads = AtomicUsize::new(0);
+        let load = |value: u32| {
+            let num_loads = &num_loads;
+            async move {
+                num_loads.fetch_add(1, Ordering::SeqCst);
+                tokio::task::yield_now().await;
+                Ok(value)
+            }
+        };
+
+        let (a, b) = tokio::join!(
+            cache.get_or_load("model", load(1)),
+            cache.get_or_load("model", load(2))
+        );
+        assert_eq!((*a?, *b?), (1, 1));
+        assert_eq!(num_loads.load(Ordering::SeqCst), 1);
+
+        // A load that was running when the cache was cleared does not fill the cache.
+        let stale = cache.get_or_load("other", async {
+            tokio::task::yield_now().await;
+            Ok(3)
+        });
+        let clear = async { cache.clear() };
+        let (stale, ()) = tokio::join!(stale, clear);
+        assert_eq!(*stale?, 3);
+        assert_eq!(*cache.get_or_load("other", load(4)).await?, 4);
+        Ok(())
+    }
+}
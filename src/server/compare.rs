@@ -0,0 +1,279 @@
+//! Side-by-side comparison of neurons, possibly from different models.
+
+use std::collections::BTreeSet;
+
+use actix_web::{get, web, Responder};
+use anyhow::{bail, Result};
+use serde::Serialize;
+use serde_json::json;
+
+use super::{
+    response::{service_error_status, Response},
+    service_providers::{string_field, InvalidQuery},
+    State,
+};
+use crate::{
+    data::{ModelHandle, NeuronIndex, TokenSearchType},
+    Index,
+};
+
+/// Maximum number of neurons compared at once.
+const MAX_NEURONS: usize = 8;
+
+/// The elements all of a list of sets have in common and those only one of them has.
+#[derive(Debug, PartialEq, Serialize)]
+struct Comparison<T> {
+    /// Elements of every set, in order.
+    shared: Vec<T>,
+    /// For each set, the elements no other set has, in order.
+    distinct: Vec<Vec<T>>,
+    /// Number of shared elements relative to the number of elements of any set, or `None` if
+    /// all sets are empty.
+    jaccard: Option<f32>,
+}
+
+impl<T: Ord + Clone> Comparison<T> {
+    fn new(sets: &[BTreeSet<T>]) -> Self {
+        let union: BTreeSet<&T> = sets.iter().flatten().collect();
+        let shared: Vec<T> = union
+            .iter()
+            .filter(|element| sets.iter().all(|set| set.contains(element)))
+            .map(|&element| element.clone())
+            .collect();
+        let distinct = sets
+            .iter()
+            .enumerate()
+            .map(|(index, set)| {
+                set.iter()
+                    .filter(|element| {
+                        sets.iter().enumerate().all(|(other_index, other)| {
+                            other_index == index || !other.contains(element)
+                        })
+                    })
+                    .cloned()
+                    .collect()
+            })
+            .collect();
+        let jaccard = (!union.is_empty()).then(|| shared.len() as f32 / union.len() as f32);
+        Self {
+            shared,
+            distinct,
+            jaccard,
+        }
+    }
+}
+
+/// Parses neurons written `model:layer_neuron`, or `layer_neuron` for neurons of the default
+/// model.
+fn parse_neurons(
+    neurons: &str,
+    default_model: Option<&str>,
+) -> Result<Vec<(String, NeuronIndex)>, InvalidQuery> {
+    neurons
+        .split(',')
+        .map(str::trim)
+        .map(|neuron| {
+            let (model_name, neuron_index) = match neuron.rsplit_once(':') {
+                Some((model_name, neuron_index)) => (model_name, neuron_index),
+                None => (
+                    default_model.ok_or_else(|| {
+                        InvalidQuery(format!(
+                            "Neuron '{neuron}' has no model. Write it as 'model:layer_neuron' or \
+                             give a default model with 'model'."
+                        ))
+                    })?,
+                    neuron,
+                ),
+            };
+            let neuron_index = neuron_index
+                .parse::<NeuronIndex>()
+                .map_err(|error| InvalidQuery(format!("Invalid neuron '{neuron}'. {error}")))?;
+            Ok((model_name.to_owned(), neuron_index))
+        })
+        .collect()
+}
+
+async fn compare_neurons(state: &State, query: &serde_json::Value) -> Result<serde_json::Value> {
+    let neurons = string_field(query, "neurons")?.ok_or_else(|| {
+        InvalidQuery(
+            "Query should contain an entry 'neurons' with a comma separated list of neurons such \
+             as 'gpt2-small:3_12,solu-1l:0_5'."
+                .to_owned(),
+        )
+    })?;
+    let neurons = parse_neurons(neurons, string_field(query, "model")?)?;
+    if !(2..=MAX_NEURONS).contains(&neurons.len()) {
+        bail!(InvalidQuery(format!(
+            "Between 2 and {MAX_NEURONS} neurons can be compared. Found {}.",
+            neurons.len()
+        )))
+    }
+
+    let database = state.database();
+    let neuron_store_type = database.data_type("neuron_store").await?;
+    let mut models: Vec<ModelHandle> = Vec::with_capacity(neurons.len());
+    for (model_name, neuron_index) in &neurons {
+        let model = database
+            .model(model_name)
+            .await?
+            .ok_or_else(|| InvalidQuery(format!("Model '{model_name}' not found.")))?;
+        Index::from(*neuron_index)
+            .valid_in_model(model.metadata())
+            .map_err(|error| InvalidQuery(format!("{error:#}")))?;
+        models.push(model);
+    }
+
+    let mut summaries = Vec::with_capacity(neurons.len());
+    let mut activating = Some(Vec::with_capacity(neurons.len()));
+    let mut important = Some(Vec::with_capacity(neurons.len()));
+    let mut graph_node_tokens = Vec::with_capacity(neurons.len());
+    let mut samples = Vec::with_capacity(neurons.len());
+    for (model, &(_, neuron_index)) in models.iter().zip(&neurons) {
+        let has_neuron_store = match &neuron_store_type {
+            Some(data_type) => model.has_data_type(data_type).await?,
+            None => false,
+        };
+        // Tokens are only compared if every neuron has a neuron store to take them from.
+        if has_neuron_store {
+            let neuron_store = state.neuron_store(model).await?;
+            let tokens = |search_type| -> BTreeSet<String> {
+                neuron_store
+                    .neuron_tokens(search_type, neuron_index)
+                    .into_iter()
+                    .map(str::to_owned)
+                    .collect()
+            };
+            if let Some(activating) = activating.as_mut() {
+                activating.push(tokens(TokenSearchType::Activating));
+            }
+            if let Some(important) = important.as_mut() {
+                important.push(tokens(TokenSearchType::Important));
+            }
+        } else {
+            activating = None;
+            important = None;
+        }
+        // Nodes of different graphs are only comparable by their tokens.
+        graph_node_tokens.push(
+            model
+                .graph_tokens(neuron_index)
+                .await?
+                .into_iter()
+                .map(|(token, _)| token)
+                .collect::<BTreeSet<_>>(),
+        );
+        samples.push(
+            model
+                .neuron_samples(neuron_index)
+                .await?
+                .into_iter()
+                .collect::<BTreeSet<_>>(),
+        );
+        summaries.extend(model.neuron_summaries(vec![neuron_index]).await?);
+    }
+    // Data indices only refer to the same samples in models trained on the same dataset.
+    let same_dataset = models
+        .iter()
+        .all(|model| model.metadata().dataset == models[0].metadata().dataset);
+
+    let neuron_results: Vec<_> = models
+        .iter()
+        .zip(&summaries)
+        .enumerate()
+        .map(|(index, (model, summary))| {
+            json!({
+                "model": model.name(),
+                "layer": summary.layer,
+                "neuron": summary.neuron,
+                "explanation": summary.explanation,
+                "score": summary.score,
+                "num_activating_tokens": activating.as_ref().map(|tokens| tokens[index].len()),
+                "num_important_tokens": important.as_ref().map(|tokens| tokens[index].len()),
+                "num_graph_node_tokens": graph_node_tokens[index].len(),
+                "num_samples": samples[index].len(),
+            })
+        })
+        .collect();
+    Ok(json!({
+        "neurons": neuron_results,
+        "activating_tokens": activating.as_deref().map(Comparison::new),
+        "important_tokens": important.as_deref().map(Comparison::new),
+        "graph_node_tokens": Comparison::new(&graph_node_tokens),
+        "samples": same_dataset.then(|| Comparison::new(&samples)),
+    }))
+}
+
+/// Compares neurons, possibly from different models, by their tokens, the tokens of their graph
+/// nodes, their samples and their explanations.
+#[utoipa::path(
+    operation_id = "compare_neurons",
+    responses(
+        (status = 200, description = "The neurons with their explanations, and for their activating and important tokens, the tokens of the nodes of their Neuron2Graph graphs and their samples the elements they all share, those only one of them has and the Jaccard similarity. Tokens are null unless every neuron's model has a neuron store and samples are null unless the models share a dataset.", body = String, content_type = "application/json"),
+        (status = "4XX", description = "The query is invalid or a neuron does not exist.", body = String),
+        (status = "5XX", description = "Failed to compare the neurons.", body = String)
+    ),
+    params(
+        ("neurons" = String, Query, description = "Comma separated list of 2 to 8 neurons written 'model:layer_neuron', such as 'gpt2-small:3_12,solu-1l:0_5'."),
+        ("model" = Option<String>, Query, description = "Model of the neurons written without a model, as in 'model=gpt2-small&neurons=3_12,3_40'.")
+    )
+)]
+#[get("/api/compare")]
+pub async fn compare(
+    state: web::Data<State>,
+    query: web::Query<serde_json::Value>,
+) -> impl Responder {
+    match compare_neurons(state.as_ref(), &query).await {
+        Ok(value) => Response::success(value),
+        Err(error) => {
+            let status = service_error_status(&error);
+            Response::error(error, status)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compares_sets() {
+        let sets = [
+            BTreeSet::from(["a", "b", "c"]),
+            BTreeSet::from(["b", "c", "d"]),
+            BTreeSet::from(["c", "e"]),
+        ];
+        assert_eq!(
+            Comparison::new(&sets),
+            Comparison {
+                shared: vec!["c"],
+                distinct: vec![vec!["a"], vec!["d"], vec!["e"]],
+                jaccard: Some(0.2),
+            }
+        );
+        let empty = Comparison::new(&[BTreeSet::<u64>::new(), BTreeSet::new()]);
+        assert_eq!(empty.jaccard, None);
+        assert_eq!(empty.distinct, vec![Vec::<u64>::new(), vec![]]);
+
+        assert_eq!(
+            parse_neurons("gpt2-small:3_12, 0_5", Some("solu-1l")).unwrap(),
+            vec![
+                (
+                    "gpt2-small".to_owned(),
+                    NeuronIndex {
+                        layer: 3,
+                        neuron: 12
+                    }
+                ),
+                (
+                    "solu-1l".to_owned(),
+                    NeuronIndex {
+                        layer: 0,
+                        neuron: 5
+                    }
+                ),
+            ]
+        );
+        assert!(parse_neurons("3_12", None).is_err());
+        assert!(parse_neurons("a:3-12", None).is_err());
+    }
+}
@@ -0,0 +1,434 @@
+use std::{collections::HashSet, fmt::Display, iter};
+
+use actix_web::{
+    error::ErrorInternalServerError, get, web, web::Bytes, Either, HttpResponse, Responder,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use futures_util::{stream, StreamExt};
+use itertools::Itertools;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{
+    response::{preprocess_model, service_json, Response},
+    Service, ServiceProvider, State,
+};
+use crate::{
+    data::{
+        data_objects::NeuronGraphNode, data_types, LayerRange, Metadata, ModelHandle, NeuronIndex,
+    },
+    Index,
+};
+
+#[derive(Clone, Copy)]
+enum Granularity {
+    Layer,
+    Neuron,
+}
+
+/// The parameters of an export request. Any query parameters not used by the export itself are
+/// passed on to the service.
+struct ExportParameters {
+    layers: LayerRange,
+    granularity: Granularity,
+    fields: Option<Vec<String>>,
+    service_query: serde_json::Value,
+}
+
+impl ExportParameters {
+    fn from_query(mut query: serde_json::Value) -> Result<Self> {
+        let query_object = query.as_object_mut().context("Query is not an object.")?;
+        let layers = match query_object.remove("layers") {
+            Some(serde_json::Value::String(layers)) => layers
+                .parse::<LayerRange>()
+                .with_context(|| format!("Invalid layer range '{layers}'."))?,
+            Some(layers) => bail!("Query field 'layers' should be a string. Found: {layers}"),
+            None => LayerRange::all(),
+        };
+        let granularity = match query_object.remove("granularity") {
+            Some(serde_json::Value::String(granularity)) => match granularity.as_str() {
+                "layer" => Granularity::Layer,
+                "neuron" => Granularity::Neuron,
+                _ => bail!(
+                    "Invalid granularity '{granularity}'. Must be either 'layer' or 'neuron'."
+                ),
+            },
+            Some(granularity) => {
+                bail!("Query field 'granularity' should be a string. Found: {granularity}")
+            }
+            None => Granularity::Neuron,
+        };
+        let fields = match query_object.remove("fields") {
+            Some(serde_json::Value::String(fields)) => Some(
+                fields
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|field| !field.is_empty())
+                    .map(str::to_owned)
+                    .collect(),
+            ),
+            Some(fields) => bail!("Query field 'fields' should be a string. Found: {fields}"),
+            None => None,
+        };
+        Ok(Self {
+            layers,
+            granularity,
+            fields,
+            service_query: query,
+        })
+    }
+
+    fn indices(&self, metadata: &Metadata) -> Result<impl Iterator<Item = Index> + 'static> {
+        let layers = self.layers.layers(metadata)?;
+        let layer_size = metadata.layer_size;
+        let granularity = self.granularity;
+        Ok(layers.flat_map(move |layer_index| match granularity {
+            Granularity::Layer => itertools::Either::Left(iter::once(Index::Layer(layer_index))),
+            Granularity::Neuron => itertools::Either::Right(
+                (0..layer_size).map(move |neuron_index| Index::Neuron(layer_index, neuron_index)),
+            ),
+        }))
+    }
+}
+
+fn project(mut value: serde_json::Value, fields: Option<&[String]>) -> Result<serde_json::Value> {
+    if let Some(fields) = fields {
+        let object = value
+            .as_object_mut()
+            .context("Can only select fields from a JSON object.")?;
+        Ok(fields
+            .iter()
+            .filter_map(|field| {
+                object
+                    .remove(field)
+                    .map(|field_value| (field.clone(), field_value))
+            })
+            .collect::<serde_json::Map<_, _>>()
+            .into())
+    } else {
+        Ok(value)
+    }
+}
+
+async fn export_line(
+    state: &State,
+    parameters: &ExportParameters,
+    model_handle: &ModelHandle,
+    service: &Service,
+    index: Index,
+) -> Bytes {
+    let mut line = match index {
+        Index::Model => json!({}),
+        Index::Layer(layer_index) => json!({ "layer": layer_index }),
+        Index::Neuron(layer_index, neuron_index) => {
+            json!({ "layer": layer_index, "neuron": neuron_index })
+        }
+    };
+    match service_json(
+        state,
+        &parameters.service_query,
+        model_handle,
+        service,
+        index,
+    )
+    .await
+    .and_then(|value| project(value, parameters.fields.as_deref()))
+    {
+        Ok(data) => line["data"] = data,
+        Err(error) => line["error"] = json!(format!("{error:?}")),
+    }
+    let mut line = line.to_string().into_bytes();
+    line.push(b'\n');
+    Bytes::from(line)
+}
+
+/// The model and service to export, or the response to give if either does not exist or the model
+/// lacks data for the service.
+async fn exported_service(
+    state: &State,
+    model_name: &str,
+    service_name: &str,
+) -> Result<(ModelHandle, Service), Response> {
+    let database = state.database();
+    let model_handle = match preprocess_model(model_name, database, Index::Model).await {
+        Ok(model_handle) => model_handle,
+        Err(error) => return Err(Response::error(error, StatusCode::NOT_FOUND)),
+    };
+    let service_handle = match database.service(service_name).await {
+        Ok(Some(service_handle)) => service_handle,
+        Ok(None) => {
+            return Err(Response::error(
+                anyhow!("Service '{service_name}' not found."),
+                StatusCode::NOT_FOUND,
+            ))
+        }
+        Err(error) => return Err(Response::error(error, StatusCode::INTERNAL_SERVER_ERROR)),
+    };
+    match model_handle.missing_data_types(&service_handle).await {
+        Ok(missing_data_types) if missing_data_types.is_empty() => {}
+        Ok(missing_data_types) => {
+            return Err(Response::error(
+                anyhow!(
+                    "Model '{model_name}' is missing data objects {missing_data_types:?} for \
+                     service '{service_name}'."
+                ),
+                StatusCode::NOT_FOUND,
+            ))
+        }
+        Err(error) => return Err(Response::error(error, StatusCode::INTERNAL_SERVER_ERROR)),
+    }
+    match service_handle.service().await {
+        Ok(service) => Ok((model_handle, service)),
+        Err(error) => Err(Response::error(error, StatusCode::INTERNAL_SERVER_ERROR)),
+    }
+}
+
+/// Streams the data of a service for every neuron or layer of a model as newline delimited JSON.
+///
+/// Lines are only computed when the client is ready to receive them, so the export never holds
+/// more than a single page in memory.
+#[utoipa::path(
+    operation_id = "export_service",
+    responses(
+        (status = 200, description = "Successfully started exporting the service data. Each line is a JSON object containing the index of the page and either its data or an error.", body = String, content_type = "application/x-ndjson"),
+        (status = "4XX", description = "The model or service does not exist, the model lacks data for the service, or the query is invalid.", body = String),
+        (status = "5XX", description = "Failed to start the export.", body = String)
+    ),
+    params(
+        ("model_name" = String, Path, description = "The name of the model to export data for."),
+        ("service_name" = String, Path, description = "The name of the service to export data for."),
+        ("layers" = Option<String>, Query, description = "The layers to export, e.g. '3' or '2-5'. Defaults to all layers."),
+        ("granularity" = Option<String>, Query, description = "Whether to export a line per 'neuron' (default) or per 'layer'."),
+        ("fields" = Option<String>, Query, description = "Comma separated list of fields to keep from each page. Defaults to all fields.")
+    )
+)]
+#[get("/export/{model_name}/{service_name}")]
+pub async fn export(
+    state: web::Data<State>,
+    indices: web::Path<(String, String)>,
+    query: web::Query<serde_json::Value>,
+) -> impl Responder {
+    let (model_name, service_name) = indices.into_inner();
+    log::debug!("Received export request for service '{service_name}' for model '{model_name}'.");
+
+    let parameters = match ExportParameters::from_query(query.into_inner()) {
+        Ok(parameters) => parameters,
+        Err(error) => return Either::Left(Response::error(error, StatusCode::BAD_REQUEST)),
+    };
+
+    let (model_handle, service) = match exported_service(&state, &model_name, &service_name).await {
+        Ok(exported) => exported,
+        Err(response) => return Either::Left(response),
+    };
+    let indices = match parameters.indices(model_handle.metadata()) {
+        Ok(indices) => indices,
+        Err(error) => return Either::Left(Response::error(error, StatusCode::BAD_REQUEST)),
+    };
+
+    let lines = stream::unfold(
+        (indices, state, parameters, model_handle, service),
+        |(mut indices, state, parameters, model_handle, service)| async move {
+            let index = indices.next()?;
+            let line = export_line(&state, &parameters, &model_handle, &service, index).await;
+            Some((
+                Ok::<_, actix_web::Error>(line),
+                (indices, state, parameters, model_handle, service),
+            ))
+        },
+    );
+
+    Either::Right(
+        HttpResponse::Ok()
+            .content_type("application/x-ndjson")
+            .streaming(lines),
+    )
+}
+
+/// Escapes text for use in XML attributes and elements.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn graphml_header(model_name: &str) -> Bytes {
+    Bytes::from(format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://graphml.graphdrawing.org/xmlns http://graphml.graphdrawing.org/xmlns/1.0/graphml.xsd">
+  <key id="layer" for="node" attr.name="layer" attr.type="int"/>
+  <key id="neuron" for="node" attr.name="neuron" attr.type="int"/>
+  <key id="cluster" for="node" attr.name="cluster" attr.type="int"/>
+  <key id="role" for="node" attr.name="role" attr.type="string"/>
+  <key id="degree" for="node" attr.name="degree" attr.type="int"/>
+  <key id="strength" for="node" attr.name="strength" attr.type="double"/>
+  <key id="pagerank" for="node" attr.name="pagerank" attr.type="double"/>
+  <key id="participation" for="node" attr.name="participation" attr.type="double"/>
+  <key id="within_cluster_degree" for="node" attr.name="within_cluster_degree" attr.type="double"/>
+  <key id="weight" for="edge" attr.name="weight" attr.type="double"/>
+  <graph id="{}" edgedefault="undirected">
+"#,
+        escape_xml(model_name)
+    ))
+}
+
+const GRAPHML_FOOTER: &str = "  </graph>\n</graphml>\n";
+
+/// The GraphML of a node and of its edges to later neurons, so each edge is written once.
+fn graphml_node(
+    neuron_index: NeuronIndex,
+    node: &NeuronGraphNode,
+    members: Option<&HashSet<NeuronIndex>>,
+) -> Bytes {
+    let NeuronIndex { layer, neuron } = neuron_index;
+    let mut graphml = format!("    <node id=\"l{layer}n{neuron}\">\n");
+    let mut data = |key: &str, value: &dyn Display| {
+        graphml.push_str(&format!("      <data key=\"{key}\">{value}</data>\n"));
+    };
+    data("layer", &layer);
+    data("neuron", &neuron);
+    if let Some(cluster) = node.cluster {
+        data("cluster", &cluster);
+    }
+    data("role", &node.role.as_ref());
+    data("degree", &node.degree);
+    data("strength", &node.strength);
+    data("pagerank", &node.pagerank);
+    data("participation", &node.participation);
+    data("within_cluster_degree", &node.within_cluster_degree);
+    graphml.push_str("    </node>\n");
+    for neighbour in &node.neighbours {
+        let neighbour_index = NeuronIndex {
+            layer: neighbour.layer,
+            neuron: neighbour.neuron,
+        };
+        if neighbour_index <= neuron_index
+            || members.is_some_and(|members| !members.contains(&neighbour_index))
+        {
+            continue;
+        }
+        graphml.push_str(&format!(
+            "    <edge source=\"l{layer}n{neuron}\" target=\"l{}n{}\">\n      <data \
+             key=\"weight\">{}</data>\n    </edge>\n",
+            neighbour.layer, neighbour.neuron, neighbour.similarity
+        ));
+    }
+    Bytes::from(graphml)
+}
+
+#[derive(Deserialize)]
+pub struct GraphmlQuery {
+    /// Only export the neurons of this cluster and the edges between them.
+    cluster: Option<u32>,
+}
+
+/// Streams the similarity graph of a model clustered by a neuron clusters service as GraphML, with
+/// the cluster, centrality and role of each neuron.
+///
+/// Nodes are only read when the client is ready to receive them, so the export never holds more
+/// than a single neuron in memory.
+#[utoipa::path(
+    operation_id = "export_graphml",
+    responses(
+        (status = 200, description = "Successfully started exporting the graph.", body = String, content_type = "application/graphml+xml"),
+        (status = "4XX", description = "The model or service does not exist, the service is not a neuron clusters service, the model lacks data for it, or the query is invalid.", body = String),
+        (status = "5XX", description = "Failed to start the export.", body = String)
+    ),
+    params(
+        ("model_name" = String, Path, description = "The name of the model to export the graph of."),
+        ("service_name" = String, Path, description = "The name of the neuron clusters service to export the graph of."),
+        ("cluster" = Option<u32>, Query, description = "Only export the neurons of this cluster and the edges between them. Defaults to the whole graph.")
+    )
+)]
+#[get("/export/{model_name}/{service_name}/graphml")]
+pub async fn export_graphml(
+    state: web::Data<State>,
+    indices: web::Path<(String, String)>,
+    query: web::Query<GraphmlQuery>,
+) -> impl Responder {
+    let (model_name, service_name) = indices.into_inner();
+    log::debug!(
+        "Received GraphML export request for service '{service_name}' for model '{model_name}'."
+    );
+
+    let (model_handle, service) = match exported_service(&state, &model_name, &service_name).await {
+        Ok(exported) => exported,
+        Err(response) => return Either::Left(response),
+    };
+    if !matches!(service.provider, ServiceProvider::NeuronClusters) {
+        return Either::Left(Response::error(
+            anyhow!("Service '{service_name}' is not a neuron clusters service."),
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+    let clusters = match service.required_data_types(state.database()).await {
+        Ok(data_types) => match data_types.first() {
+            Some(data_type) => {
+                model_handle
+                    .data_type::<data_types::NeuronClusters>(data_type)
+                    .await
+            }
+            None => Err(anyhow!("Neuron clusters service has no data object.")),
+        },
+        Err(error) => Err(error),
+    };
+    let clusters = match clusters {
+        Ok(clusters) => clusters,
+        Err(error) => {
+            return Either::Left(Response::error(error, StatusCode::INTERNAL_SERVER_ERROR))
+        }
+    };
+    let members = match query.cluster {
+        Some(cluster) => match clusters.clusters().await {
+            Ok(clusters) => match clusters
+                .clusters
+                .into_iter()
+                .find(|other| other.id == cluster)
+            {
+                Some(cluster) => Some(cluster.members),
+                None => {
+                    return Either::Left(Response::error(
+                        anyhow!("Model '{model_name}' has no cluster {cluster}."),
+                        StatusCode::BAD_REQUEST,
+                    ))
+                }
+            },
+            Err(error) => {
+                return Either::Left(Response::error(error, StatusCode::INTERNAL_SERVER_ERROR))
+            }
+        },
+        None => None,
+    };
+    let neuron_indices: Vec<NeuronIndex> = match &members {
+        Some(members) => members.iter().copied().sorted().collect(),
+        None => model_handle.metadata().neuron_indices().collect(),
+    };
+    let members = members.map(HashSet::<NeuronIndex>::from_iter);
+
+    let nodes = stream::unfold(
+        (neuron_indices.into_iter(), clusters, members),
+        |(mut neuron_indices, clusters, members)| async move {
+            let neuron_index = neuron_indices.next()?;
+            let NeuronIndex { layer, neuron } = neuron_index;
+            let node = match clusters.node(layer, neuron).await {
+                Ok(Some(node)) => Ok(graphml_node(neuron_index, &node, members.as_ref())),
+                Ok(None) => Err(ErrorInternalServerError(format!(
+                    "Neuron {neuron_index} has no neuron cluster data."
+                ))),
+                Err(error) => Err(ErrorInternalServerError(format!("{error:?}"))),
+            };
+            Some((node, (neuron_indices, clusters, members)))
+        },
+    );
+    let graphml = stream::once(async move { Ok(graphml_header(&model_name)) })
+        .chain(nodes)
+        .chain(stream::once(async { Ok(Bytes::from(GRAPHML_FOOTER)) }));
+
+    Either::Right(
+        HttpResponse::Ok()
+            .content_type("application/graphml+xml")
+            .streaming(graphml),
+    )
+}
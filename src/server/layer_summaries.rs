@@ -0,0 +1,117 @@
+use std::sync::{Arc, Mutex};
+
+use crate::data::{retrieve::neuron_explainer, DataTypeHandle, ModelHandle};
+
+/// A layer whose Neuron Explainer summary is out of date.
+struct StaleLayer {
+    model: ModelHandle,
+    data_type: DataTypeHandle,
+    layer_index: u32,
+}
+
+impl StaleLayer {
+    fn is(&self, other: &StaleLayer) -> bool {
+        self.model.name() == other.model.name()
+            && self.data_type.name() == other.data_type.name()
+            && self.layer_index == other.layer_index
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    stale: Vec<StaleLayer>,
+    running: bool,
+}
+
+/// Resummarises layers of Neuron Explainer data in the background after pages are fetched live.
+///
+/// Summarising reads every page of the layer, so a layer is queued at most once however many of
+/// its pages are fetched, and a single task summarises the queued layers one at a time. A layer
+/// that gets another page while it is being summarised is queued again.
+#[derive(Clone, Default)]
+pub struct LayerSummaries {
+    queue: Arc<Mutex<Queue>>,
+}
+
+impl LayerSummaries {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Queue> {
+        self.queue
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Queues the layer to be summarised again, starting the background task if it is not
+    /// running.
+    pub fn mark_stale(&self, model: ModelHandle, data_type: DataTypeHandle, layer_index: u32) {
+        let layer = StaleLayer {
+            model,
+            data_type,
+            layer_index,
+        };
+        let mut queue = self.lock();
+        if !queue.stale.iter().any(|other| other.is(&layer)) {
+            queue.stale.push(layer);
+        }
+        if !queue.running {
+            queue.running = true;
+            tokio::spawn(self.clone().summarise_stale());
+        }
+    }
+
+    async fn summarise_stale(self) {
+        loop {
+            let StaleLayer {
+                mut model,
+                data_type,
+                layer_index,
+            } = {
+                let mut queue = self.lock();
+                if queue.stale.is_empty() {
+                    queue.running = false;
+                    return;
+                }
+                queue.stale.remove(0)
+            };
+            if let Err(error) =
+                neuron_explainer::summarise_layer(&mut model, &data_type, layer_index).await
+            {
+                log::error!(
+                    "Failed to summarise layer {layer_index} of neuron explainer data for model \
+                     '{}'. Error: {error:?}",
+                    model.name()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::data::{
+        data_types::DataType,
+        database::{test_util::add_test_model, Database},
+    };
+
+    #[tokio::test]
+    async fn queues_each_layer_once() -> Result<()> {
+        let database = Database::initialize_in_memory().await?;
+        let model = add_test_model(&database, 2, 2).await?;
+        let data_type = database
+            .add_data_type("neuron_explainer", DataType::NeuronExplainer)
+            .await?;
+
+        let layer_summaries = LayerSummaries::default();
+        for layer_index in [0, 1, 0, 0, 1] {
+            layer_summaries.mark_stale(model.clone(), data_type.clone(), layer_index);
+        }
+        assert_eq!(layer_summaries.lock().stale.len(), 2);
+        while layer_summaries.lock().running {
+            tokio::task::yield_now().await;
+        }
+        assert!(layer_summaries.lock().stale.is_empty());
+        Ok(())
+    }
+}
@@ -1,26 +1,92 @@
-use anyhow::{bail, Result};
+use std::sync::Arc;
 
-use crate::data::database::Database;
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    cli::ServerConfig,
+    data::{
+        data_types::{Embedding as EmbeddingObject, NeuronStore as NeuronStoreObject},
+        database::Database,
+        DataTypeHandle, EmbeddingIndex, ModelHandle, NeuronStore, TokenStatistics, TokenVocabulary,
+    },
+};
 
 mod service;
 pub use service::Service;
 mod service_providers;
 pub use service_providers::ServiceProvider;
 mod start;
-pub use start::start_server;
+pub use start::{start_server, RouteSet};
+mod admin;
 mod api_doc;
+mod cache;
+mod compare;
+mod export;
+mod layer_summaries;
+mod monitoring;
+mod rate_limit;
+mod search;
+pub use rate_limit::{RateLimit, RouteGroup, RouteRateLimit};
 pub mod response;
 pub use api_doc::api_doc;
 
 pub struct State {
     api_doc: utoipa::openapi::OpenApi,
     database: Database,
+    admin_tokens: Vec<String>,
+    rate_limiter: rate_limit::RateLimiter,
+    neuron_store_cache: cache::ModelCache<NeuronStore>,
+    token_vocabulary_cache: cache::ModelCache<TokenVocabulary>,
+    token_statistics_cache: cache::ModelCache<TokenStatistics>,
+    /// Keyed by model name and data object name, separated by a `/`.
+    embedding_index_cache: cache::ModelCache<EmbeddingIndex>,
+    upstream_client: Option<reqwest::Client>,
+    layer_summaries: layer_summaries::LayerSummaries,
 }
 
 impl State {
-    pub fn new(database: Database) -> Result<Self> {
+    pub fn new(database: Database, config: &ServerConfig) -> Result<Self> {
         let api_doc = api_doc();
-        Ok(Self { api_doc, database })
+        let upstream_client = if config.offline() {
+            None
+        } else {
+            Some(
+                reqwest::Client::builder()
+                    .timeout(config.upstream_timeout())
+                    .connect_timeout(config.upstream_timeout())
+                    .build()
+                    .context("Failed to create client for upstream requests.")?,
+            )
+        };
+        Ok(Self {
+            api_doc,
+            database,
+            admin_tokens: config.admin_tokens().to_vec(),
+            rate_limiter: rate_limit::RateLimiter::new(
+                config.rate_limits().iter().copied(),
+                config.api_keys().iter().cloned(),
+                config.rate_limit_allowlist().iter().cloned(),
+                config.trust_proxy_headers(),
+            ),
+            neuron_store_cache: cache::ModelCache::new(
+                "neuron_store",
+                config.neuron_store_cache_size(),
+            ),
+            token_vocabulary_cache: cache::ModelCache::new(
+                "token_vocabulary",
+                config.neuron_store_cache_size(),
+            ),
+            token_statistics_cache: cache::ModelCache::new(
+                "token_statistics",
+                config.neuron_store_cache_size(),
+            ),
+            embedding_index_cache: cache::ModelCache::new(
+                "embedding_index",
+                config.neuron_store_cache_size(),
+            ),
+            upstream_client,
+            layer_summaries: layer_summaries::LayerSummaries::default(),
+        })
     }
 
     pub fn database(&self) -> &Database {
@@ -30,6 +96,122 @@ impl State {
     pub fn api_doc(&self) -> &utoipa::openapi::OpenApi {
         &self.api_doc
     }
+
+    pub fn rate_limiter(&self) -> &rate_limit::RateLimiter {
+        &self.rate_limiter
+    }
+
+    /// The neuron store of the model, deserialized from the database if it is not cached.
+    pub async fn neuron_store(&self, model: &ModelHandle) -> Result<Arc<NeuronStore>> {
+        let database = self.database();
+        self.neuron_store_cache
+            .get_or_load(model.name(), async {
+                let neuron_store_object = database
+                    .data_type("neuron_store")
+                    .await
+                    .context("Could not get neuron store data object from database.")?
+                    .context("No data object named 'neuron_store' in database.")?;
+                let neuron_store_object: NeuronStoreObject = database
+                    .model_data_type(model, &neuron_store_object)
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "Model '{}' has no 'neuron_store' data object.",
+                            model.name()
+                        )
+                    })?;
+                neuron_store_object.get_store().await
+            })
+            .await
+    }
+
+    /// The vocabulary of the model's neuron store, built from the neuron store if it is not cached.
+    pub async fn token_vocabulary(&self, model: &ModelHandle) -> Result<Arc<TokenVocabulary>> {
+        self.token_vocabulary_cache
+            .get_or_load(model.name(), async {
+                let neuron_store = self.neuron_store(model).await?;
+                Ok(TokenVocabulary::new(&neuron_store))
+            })
+            .await
+    }
+
+    /// The per-token counts of the model's neuron store, computed from the neuron store if they
+    /// are not cached.
+    pub async fn token_statistics(&self, model: &ModelHandle) -> Result<Arc<TokenStatistics>> {
+        self.token_statistics_cache
+            .get_or_load(model.name(), async {
+                let neuron_store = self.neuron_store(model).await?;
+                Ok(TokenStatistics::new(&neuron_store))
+            })
+            .await
+    }
+
+    /// The embeddings of the data object for the model, loaded from the database if they are not
+    /// cached.
+    pub async fn embedding_index(
+        &self,
+        model: &ModelHandle,
+        data_type: &DataTypeHandle,
+    ) -> Result<Arc<EmbeddingIndex>> {
+        let key = format!("{}/{}", model.name(), data_type.name());
+        self.embedding_index_cache
+            .get_or_load(&key, async {
+                let embedding_object: EmbeddingObject =
+                    self.database().model_data_type(model, data_type).await?;
+                let embeddings = embedding_object
+                    .embeddings()
+                    .await?
+                    .into_iter()
+                    .map(|(neuron_index, embedding)| (neuron_index, embedding.to_f32()))
+                    .collect();
+                EmbeddingIndex::new(embeddings).with_context(|| {
+                    format!(
+                        "Invalid embeddings in data object '{}' for model '{}'.",
+                        data_type.name(),
+                        model.name()
+                    )
+                })
+            })
+            .await
+    }
+
+    /// Client for fetching data missing from the database from its original source, or `None` if
+    /// the server is offline.
+    pub fn upstream_client(&self) -> Option<&reqwest::Client> {
+        self.upstream_client.as_ref()
+    }
+
+    /// Empties all caches. Called whenever the database is changed through the admin API. Pages
+    /// fetched from their source do not clear the caches, as no cached value is derived from them.
+    pub fn clear_caches(&self) {
+        self.neuron_store_cache.clear();
+        self.token_vocabulary_cache.clear();
+        self.token_statistics_cache.clear();
+        self.embedding_index_cache.clear();
+    }
+
+    /// Layers of Neuron Explainer data waiting to be summarised again.
+    pub fn layer_summaries(&self) -> &layer_summaries::LayerSummaries {
+        &self.layer_summaries
+    }
+
+    pub fn admin_enabled(&self) -> bool {
+        !self.admin_tokens.is_empty()
+    }
+
+    /// Whether the token grants access to the admin API. The comparison takes the same time no
+    /// matter where the token differs from the configured tokens.
+    pub fn is_admin_token(&self, token: &str) -> bool {
+        self.admin_tokens.iter().fold(false, |found, admin_token| {
+            let matches = admin_token.len() == token.len()
+                && admin_token
+                    .bytes()
+                    .zip(token.bytes())
+                    .fold(0, |difference, (a, b)| difference | (a ^ b))
+                    == 0;
+            found | matches
+        })
+    }
 }
 
 #[derive(Clone, Copy)]
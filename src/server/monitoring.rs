@@ -0,0 +1,119 @@
+//! Health checks, metrics and request tracing.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock,
+    },
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    get,
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    web, HttpResponse, Responder,
+};
+use reqwest::StatusCode;
+use serde_json::json;
+
+use super::{response::Response, State};
+use crate::{logging, metrics};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Distinguishes request IDs from different runs of the server.
+static REQUEST_ID_PREFIX: LazyLock<String> = LazyLock::new(|| {
+    let start = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("{:08x}", start.as_millis() as u32)
+});
+static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Uses the request ID sent by the client if it is reasonable, and otherwise generates a new one.
+fn request_id(request: &ServiceRequest) -> String {
+    request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|request_id| request_id.to_str().ok())
+        .filter(|request_id| {
+            !request_id.is_empty()
+                && request_id.len() <= 64
+                && request_id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+        .map(str::to_owned)
+        .unwrap_or_else(|| {
+            let count = REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed);
+            format!("{}-{count:x}", *REQUEST_ID_PREFIX)
+        })
+}
+
+/// Attaches a request ID to the request and its log lines, and records request metrics.
+pub async fn track_request(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let request_id = request_id(&request);
+    let start = Instant::now();
+    let mut response = logging::with_request_id(request_id.clone(), next.call(request)).await?;
+    let seconds = start.elapsed().as_secs_f64();
+
+    let status = response.status();
+    let http_request = response.request();
+    let route = http_request
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_owned());
+    // Only label successful requests with model and service names, as names from failed requests
+    // are arbitrary user input.
+    let (service, model) = if status.is_success() {
+        let match_info = http_request.match_info();
+        (
+            match_info
+                .get("service_name")
+                .or_else(|| match_info.get("service"))
+                .unwrap_or_default()
+                .to_owned(),
+            match_info.get("model_name").unwrap_or_default().to_owned(),
+        )
+    } else {
+        Default::default()
+    };
+    metrics::observe_request(&route, &service, &model, status.as_u16(), seconds);
+
+    if let Ok(request_id) = HeaderValue::from_str(&request_id) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static(REQUEST_ID_HEADER), request_id);
+    }
+    Ok(response)
+}
+
+/// Succeeds as long as the server is running.
+#[get("/healthz")]
+pub async fn healthz() -> impl Responder {
+    Response::success(json!({ "status": "ok" }))
+}
+
+/// Succeeds if the database can be queried.
+#[get("/readyz")]
+pub async fn readyz(state: web::Data<State>) -> impl Responder {
+    match state.database().ping().await {
+        Ok(()) => Response::success(json!({ "status": "ready" })),
+        Err(error) => {
+            log::error!("Readiness check failed: {error:?}");
+            Response::error(error, StatusCode::SERVICE_UNAVAILABLE)
+        }
+    }
+}
+
+#[get("/metrics")]
+pub async fn prometheus_metrics() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics::render())
+}
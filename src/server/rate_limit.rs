@@ -0,0 +1,362 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    net::IpAddr,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header,
+    middleware::Next,
+    web, HttpResponse,
+};
+use anyhow::{bail, Context, Result};
+use strum::{AsRefStr, EnumString};
+
+use super::State;
+
+/// Header clients can use to identify themselves with an API key instead of their IP address.
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// How often buckets that have refilled completely are removed.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Groups of routes that are rate limited separately.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum RouteGroup {
+    /// The API index, documentation and model pages.
+    Index,
+    /// Pages for a single neuron.
+    Neuron,
+    /// Model pages with a query, such as token searches, and neuron comparisons.
+    Search,
+    /// Exports and pages covering whole layers or every service of a model.
+    Bulk,
+}
+
+impl RouteGroup {
+    /// Returns the route group of a request path, or `None` if the path is never rate limited.
+    pub fn classify(path: &str, query: &str) -> Option<Self> {
+        let segments = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>();
+        match segments.as_slice() {
+            [] | ["api"] | ["doc", ..] => Some(Self::Index),
+            ["admin", ..] | ["healthz"] | ["readyz"] | ["metrics"] => None,
+            ["export", ..] => Some(Self::Bulk),
+            // Comparing neurons reads the data of several neurons, possibly in several models.
+            ["api", "compare"] => Some(Self::Search),
+            [_, _, _, _, _] => Some(Self::Neuron),
+            [_, _, "all"] | [_, _, "all", _] => Some(Self::Bulk),
+            // Layer pages stay in the bulk group whatever their query, so a query cannot be used
+            // to move a scrape of whole layers into another group.
+            [_, _, _, _] => Some(Self::Bulk),
+            [_, _, _] if !query.is_empty() => Some(Self::Search),
+            [_, _, _] => Some(Self::Index),
+            _ => Some(Self::Index),
+        }
+    }
+}
+
+/// A token bucket allowing bursts of `burst` requests, refilled at `per_second` requests per second.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    burst: f64,
+    per_second: f64,
+}
+
+impl RateLimit {
+    pub fn new(burst: f64, per_second: f64) -> Result<Self> {
+        if !(burst >= 1.0 && burst.is_finite()) {
+            bail!("Rate limit burst must be at least 1. Found: {burst}")
+        }
+        if !(per_second > 0.0 && per_second.is_finite()) {
+            bail!("Rate limit rate must be positive. Found: {per_second}")
+        }
+        Ok(Self { burst, per_second })
+    }
+
+    /// The time it takes an empty bucket to refill completely.
+    fn refill_time(self) -> Duration {
+        Duration::from_secs_f64(self.burst / self.per_second)
+    }
+}
+
+impl FromStr for RateLimit {
+    type Err = anyhow::Error;
+
+    fn from_str(limit_string: &str) -> Result<Self> {
+        let (burst, per_second) = limit_string
+            .split_once('/')
+            .context("Rate limit should be of the form 'BURST/PER_SECOND'.")?;
+        let burst = burst
+            .trim()
+            .parse::<f64>()
+            .with_context(|| format!("Rate limit burst '{burst}' is not a number."))?;
+        let per_second = per_second
+            .trim()
+            .parse::<f64>()
+            .with_context(|| format!("Rate limit rate '{per_second}' is not a number."))?;
+        Self::new(burst, per_second)
+    }
+}
+
+impl Display for RateLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.burst, self.per_second)
+    }
+}
+
+/// A rate limit for a route group, written as `GROUP=BURST/PER_SECOND`, e.g. `neuron=20/5`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RouteRateLimit {
+    pub group: RouteGroup,
+    pub limit: RateLimit,
+}
+
+impl FromStr for RouteRateLimit {
+    type Err = anyhow::Error;
+
+    fn from_str(limit_string: &str) -> Result<Self> {
+        let (group, limit) = limit_string
+            .split_once('=')
+            .context("Route rate limit should be of the form 'GROUP=BURST/PER_SECOND'.")?;
+        let group = group.trim().parse::<RouteGroup>().with_context(|| {
+            format!(
+                "Unknown route group '{group}'. Must be one of 'index', 'neuron', 'search' or \
+                 'bulk'."
+            )
+        })?;
+        let limit = limit.parse()?;
+        Ok(Self { group, limit })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum ClientKey {
+    ApiKey(String),
+    Ip(IpAddr),
+    Unknown,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.burst,
+            updated: now,
+        }
+    }
+
+    /// Takes a token from the bucket, returning how long to wait for one if it is empty.
+    fn take(&mut self, limit: RateLimit, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.per_second).min(limit.burst);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / limit.per_second,
+            ))
+        }
+    }
+}
+
+struct Buckets {
+    buckets: HashMap<(RouteGroup, ClientKey), Bucket>,
+    last_pruned: Instant,
+}
+
+pub struct RateLimiter {
+    limits: HashMap<RouteGroup, RateLimit>,
+    api_keys: HashSet<String>,
+    allowlist: HashSet<String>,
+    trust_proxy_headers: bool,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(
+        limits: impl IntoIterator<Item = RouteRateLimit>,
+        api_keys: impl IntoIterator<Item = String>,
+        allowlist: impl IntoIterator<Item = String>,
+        trust_proxy_headers: bool,
+    ) -> Self {
+        Self {
+            limits: limits
+                .into_iter()
+                .map(|RouteRateLimit { group, limit }| (group, limit))
+                .collect(),
+            api_keys: api_keys.into_iter().collect(),
+            allowlist: allowlist.into_iter().collect(),
+            trust_proxy_headers,
+            buckets: Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                last_pruned: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.limits.is_empty()
+    }
+
+    /// Identifies the client of a request by its API key if it sent a known or allowlisted one, and
+    /// otherwise by its IP address.
+    fn client_key(&self, request: &ServiceRequest) -> ClientKey {
+        if let Some(api_key) = request
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|api_key| api_key.to_str().ok())
+            .filter(|api_key| self.api_keys.contains(*api_key) || self.allowlist.contains(*api_key))
+        {
+            return ClientKey::ApiKey(api_key.to_owned());
+        }
+        let connection_info = request.connection_info();
+        let address = if self.trust_proxy_headers {
+            connection_info.realip_remote_addr()
+        } else {
+            connection_info.peer_addr()
+        };
+        address
+            .and_then(|address| {
+                address.parse::<IpAddr>().ok().or_else(|| {
+                    address
+                        .parse::<std::net::SocketAddr>()
+                        .ok()
+                        .map(|address| address.ip())
+                })
+            })
+            .map_or(ClientKey::Unknown, ClientKey::Ip)
+    }
+
+    fn is_allowed(&self, client_key: &ClientKey) -> bool {
+        match client_key {
+            ClientKey::ApiKey(api_key) => self.allowlist.contains(api_key),
+            ClientKey::Ip(ip) => self.allowlist.contains(&ip.to_string()),
+            ClientKey::Unknown => false,
+        }
+    }
+
+    fn take(&self, group: RouteGroup, client_key: ClientKey) -> Result<(), Duration> {
+        let Some(&limit) = self.limits.get(&group) else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let mut buckets = self
+            .buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if now.saturating_duration_since(buckets.last_pruned) >= PRUNE_INTERVAL {
+            let limits = &self.limits;
+            buckets.buckets.retain(|(group, _), bucket| {
+                limits.get(group).is_some_and(|limit| {
+                    now.saturating_duration_since(bucket.updated) < limit.refill_time()
+                })
+            });
+            buckets.last_pruned = now;
+        }
+        buckets
+            .buckets
+            .entry((group, client_key))
+            .or_insert_with(|| Bucket::full(limit, now))
+            .take(limit, now)
+    }
+}
+
+pub async fn limit_rate(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let limited = request
+        .app_data::<web::Data<State>>()
+        .map(|state| state.rate_limiter())
+        .filter(|rate_limiter| rate_limiter.is_enabled())
+        .and_then(|rate_limiter| {
+            let group = RouteGroup::classify(request.path(), request.query_string())?;
+            let client_key = rate_limiter.client_key(&request);
+            if rate_limiter.is_allowed(&client_key) {
+                return None;
+            }
+            rate_limiter
+                .take(group, client_key.clone())
+                .err()
+                .map(|retry_after| (group, client_key, retry_after))
+        });
+    if let Some((group, client_key, retry_after)) = limited {
+        log::debug!(
+            "Rate limited client {client_key:?} for route group '{}'.",
+            group.as_ref()
+        );
+        let retry_after = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        let response = HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, retry_after))
+            .body(format!(
+                "Too many requests. Retry after {retry_after} seconds."
+            ));
+        Ok(request.into_response(response).map_into_right_body())
+    } else {
+        next.call(request)
+            .await
+            .map(ServiceResponse::map_into_left_body)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn route_groups_and_buckets() {
+        assert_eq!(RouteGroup::classify("/api", ""), Some(RouteGroup::Index));
+        assert_eq!(
+            RouteGroup::classify("/api/solu-1l/neuroscope/0/12", ""),
+            Some(RouteGroup::Neuron)
+        );
+        assert_eq!(
+            RouteGroup::classify("/api/solu-1l/neuron2graph-search", "query=any:the"),
+            Some(RouteGroup::Search)
+        );
+        assert_eq!(
+            RouteGroup::classify("/api/solu-1l/neuroscope/0", ""),
+            Some(RouteGroup::Bulk)
+        );
+        assert_eq!(
+            RouteGroup::classify("/api/solu-1l/neuroscope/0", "x=1"),
+            Some(RouteGroup::Bulk)
+        );
+        assert_eq!(
+            RouteGroup::classify("/export/solu-1l/neuroscope", "layers=0"),
+            Some(RouteGroup::Bulk)
+        );
+        assert_eq!(
+            RouteGroup::classify("/api/compare", "neurons=solu-1l:0_1,solu-1l:0_2"),
+            Some(RouteGroup::Search)
+        );
+        assert_eq!(RouteGroup::classify("/admin/models", ""), None);
+
+        let RouteRateLimit { group, limit } = "search=2/0.5".parse().unwrap();
+        assert_eq!(group, RouteGroup::Search);
+        assert!("search=0/1".parse::<RouteRateLimit>().is_err());
+        assert!("pages=1/1".parse::<RouteRateLimit>().is_err());
+
+        let start = Instant::now();
+        let mut bucket = Bucket::full(limit, start);
+        assert!(bucket.take(limit, start).is_ok());
+        assert!(bucket.take(limit, start).is_ok());
+        assert_eq!(bucket.take(limit, start), Err(Duration::from_secs(2)));
+        assert!(bucket.take(limit, start + Duration::from_secs(2)).is_ok());
+    }
+}
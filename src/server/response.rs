@@ -6,7 +6,7 @@ use anyhow::{anyhow, Result};
 use reqwest::StatusCode;
 use serde_json::json;
 
-use super::{RequestType, Service};
+use super::{service_providers::InvalidQuery, RequestType, Service};
 use crate::{
     data::{data_objects::MetadataObject, Database, ModelHandle, ServiceHandle},
     server::State,
@@ -51,7 +51,7 @@ impl From<Body> for BoxBody {
     }
 }
 
-struct Response {
+pub(super) struct Response {
     body: Body,
     status: StatusCode,
 }
@@ -64,6 +64,14 @@ impl Response {
         }
     }
 
+    /// Response to a request whose work continues after the response is sent.
+    pub fn accepted(body: impl Into<Body>) -> Self {
+        Self {
+            body: body.into(),
+            status: StatusCode::ACCEPTED,
+        }
+    }
+
     pub fn error(error: impl fmt::Debug, status: StatusCode) -> Self {
         assert!(status.is_client_error() || status.is_server_error());
         Self {
@@ -79,13 +87,12 @@ impl Responder for Response {
     fn respond_to(self, req: &actix_web::HttpRequest) -> HttpResponse<Self::Body> {
         HttpResponse::build(self.status)
             .content_type(self.body.content_type())
-            .append_header(("Access-Control-Allow-Origin", "*"))
             .body(BoxBody::from(self.body))
             .respond_to(req)
     }
 }
 
-async fn service_json(
+pub(super) async fn service_json(
     state: &State,
     query: &serde_json::Value,
     model_handle: &ModelHandle,
@@ -129,7 +136,7 @@ async fn service_binary(
     }
 }
 
-async fn preprocess_model(
+pub(super) async fn preprocess_model(
     model_name: impl AsRef<str>,
     database: &Database,
     page_index: Index,
@@ -161,6 +168,14 @@ async fn service_value(
     }
 }
 
+pub(super) fn service_error_status(error: &anyhow::Error) -> StatusCode {
+    if error.chain().any(|cause| cause.is::<InvalidQuery>()) {
+        StatusCode::BAD_REQUEST
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
 async fn response(
     state: web::Data<State>,
     query: &serde_json::Value,
@@ -222,7 +237,10 @@ async fn response(
             };
             match service_json {
                 Ok(page) => Response::success(Body::Json(page)),
-                Err(error) => Response::error(error, StatusCode::INTERNAL_SERVER_ERROR),
+                Err(error) => {
+                    let status = service_error_status(&error);
+                    Response::error(error, status)
+                }
             }
         }
         RequestType::Binary => {
@@ -230,7 +248,10 @@ async fn response(
                 service_binary(state.as_ref(), query, &model_handle, &service, page_index).await;
             match data {
                 Ok(data) => Response::success(Body::Binary(data)),
-                Err(error) => Response::error(error, StatusCode::INTERNAL_SERVER_ERROR),
+                Err(error) => {
+                    let status = service_error_status(&error);
+                    Response::error(error, status)
+                }
             }
         }
     }
@@ -0,0 +1,527 @@
+//! Searches spanning multiple models.
+
+use std::{collections::HashMap, ops::RangeInclusive, str::FromStr};
+
+use actix_web::{get, web, Responder};
+use anyhow::{bail, Context, Result};
+use serde_json::json;
+
+use super::{
+    response::{service_error_status, Response},
+    service_providers::{limit_field, query_tokens, string_field, token_summaries, InvalidQuery},
+    State,
+};
+use crate::data::{
+    sort_suggestions, ModelHandle, Normalisation, TokenPattern, TokenQuery, TokenSuggestion,
+};
+
+/// Position of a layer relative to the depth of its model, from 0 for the first layer to 1 for
+/// the last.
+fn normalised_depth(layer_index: u32, num_layers: u32) -> f32 {
+    if num_layers <= 1 {
+        0.
+    } else {
+        layer_index as f32 / (num_layers - 1) as f32
+    }
+}
+
+/// A range of normalised layer depths, so layers can be compared across models with different
+/// numbers of layers.
+///
+/// Written as `early`, `middle` or `late` for the first, second and last third of the layers, or
+/// as two depths between 0 and 1 separated by a dash (`"0.25-0.5"`). The end of a range is
+/// exclusive unless it is 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthRange {
+    start: f32,
+    end: f32,
+}
+
+impl DepthRange {
+    fn contains(self, depth: f32) -> bool {
+        depth >= self.start && (depth < self.end || (self.end >= 1. && depth <= 1.))
+    }
+
+    /// The layers of a model with the given number of layers whose depth lies in the range, or
+    /// `None` if there are none.
+    pub fn layers(self, num_layers: u32) -> Option<RangeInclusive<u32>> {
+        let mut layers =
+            (0..num_layers).filter(|&layer| self.contains(normalised_depth(layer, num_layers)));
+        let start = layers.next()?;
+        let end = layers.next_back().unwrap_or(start);
+        Some(start..=end)
+    }
+}
+
+impl FromStr for DepthRange {
+    type Err = anyhow::Error;
+
+    fn from_str(range_string: &str) -> Result<Self> {
+        let (start, end) = match range_string.trim() {
+            "early" => (0., 1. / 3.),
+            "middle" => (1. / 3., 2. / 3.),
+            "late" => (2. / 3., 1.),
+            range_string => {
+                let (start, end) = range_string.split_once('-').context(
+                    "Depth range should be 'early', 'middle', 'late' or of the form 'start-end'.",
+                )?;
+                let parse_bound = |bound: &str| -> Result<f32> {
+                    let bound = bound.trim();
+                    let depth = bound
+                        .parse::<f32>()
+                        .with_context(|| format!("Depth '{bound}' is not a valid number."))?;
+                    if !(0. ..=1.).contains(&depth) {
+                        bail!("Depth '{bound}' is not between 0 and 1.")
+                    }
+                    Ok(depth)
+                };
+                (parse_bound(start)?, parse_bound(end)?)
+            }
+        };
+        if start > end {
+            bail!("Depth range '{range_string}' starts after it ends.")
+        }
+        Ok(Self { start, end })
+    }
+}
+
+/// The `models`, `normalise` and `limit` fields shared by the token searches.
+fn common_fields(
+    query: &serde_json::Value,
+    default_limit: usize,
+) -> Result<(Option<Vec<String>>, Normalisation, usize), InvalidQuery> {
+    let models = string_field(query, "models")?.map(|models| {
+        models
+            .split(',')
+            .map(str::trim)
+            .filter(|model| !model.is_empty())
+            .map(str::to_owned)
+            .collect()
+    });
+    let normalisation = string_field(query, "normalise")?
+        .map(|normalisation| {
+            normalisation
+                .parse::<Normalisation>()
+                .map_err(|error| InvalidQuery(error.to_string()))
+        })
+        .transpose()?
+        .unwrap_or_default();
+    let limit = limit_field(query, default_limit)?;
+    Ok((models, normalisation, limit))
+}
+
+struct TokenSearchParameters {
+    query: TokenQuery,
+    models: Option<Vec<String>>,
+    depth: Option<DepthRange>,
+    normalisation: Normalisation,
+    limit: usize,
+}
+
+impl TokenSearchParameters {
+    fn from_query(query: &serde_json::Value) -> Result<Self, InvalidQuery> {
+        let token_query = string_field(query, "query")?
+            .ok_or_else(|| {
+                InvalidQuery(
+                    "Query should contain an entry 'query' with a string value.".to_owned(),
+                )
+            })?
+            .parse::<TokenQuery>()
+            .map_err(|error| InvalidQuery(error.to_string()))?;
+        let (models, normalisation, limit) = common_fields(query, 100)?;
+        let depth = depth_field(query)?;
+        Ok(Self {
+            query: token_query,
+            models,
+            depth,
+            normalisation,
+            limit,
+        })
+    }
+}
+
+/// The models to search, failing if a requested model does not exist or has no neuron store.
+async fn searchable_models(
+    state: &State,
+    requested: Option<&[String]>,
+) -> Result<Vec<ModelHandle>> {
+    let database = state.database();
+    let Some(data_type) = database.data_type("neuron_store").await? else {
+        if let Some(model_name) = requested.and_then(<[String]>::first) {
+            bail!(InvalidQuery(format!(
+                "Model '{model_name}' has no neuron store."
+            )))
+        }
+        return Ok(vec![]);
+    };
+
+    let models = match requested {
+        Some(model_names) => {
+            let mut models = Vec::with_capacity(model_names.len());
+            for model_name in model_names {
+                let model = database
+                    .model(model_name)
+                    .await?
+                    .ok_or_else(|| InvalidQuery(format!("Model '{model_name}' not found.")))?;
+                if !model.has_data_type(&data_type).await? {
+                    bail!(InvalidQuery(format!(
+                        "Model '{model_name}' has no neuron store."
+                    )))
+                }
+                models.push(model);
+            }
+            models
+        }
+        None => {
+            let mut models = Vec::new();
+            for model in database.all_models().await? {
+                if model.has_data_type(&data_type).await? {
+                    models.push(model);
+                }
+            }
+            models
+        }
+    };
+    Ok(models)
+}
+
+async fn token_search(state: &State, query: &serde_json::Value) -> Result<serde_json::Value> {
+    let parameters = TokenSearchParameters::from_query(query)?;
+    let models = searchable_models(state, parameters.models.as_deref()).await?;
+
+    let mut model_results = Vec::with_capacity(models.len());
+    for model in models {
+        let num_layers = model.metadata().num_layers;
+        let layers = match parameters.depth {
+            Some(depth) => depth.layers(num_layers),
+            None => (num_layers > 0).then(|| 0..=num_layers - 1),
+        };
+        let (layer_counts, total, results) = if let Some(layers) = layers {
+            let neuron_store = state.neuron_store(&model).await?;
+            let vocabulary = state.token_vocabulary(&model).await?;
+            let matches = parameters.query.search(
+                &neuron_store,
+                &vocabulary,
+                parameters.normalisation,
+                layers.clone(),
+            )?;
+            let layer_counts = layers
+                .map(|layer_index| {
+                    let count = matches
+                        .iter()
+                        .filter(|found| found.layer == layer_index)
+                        .count();
+                    json!({
+                        "layer": layer_index,
+                        "depth": normalised_depth(layer_index, num_layers),
+                        "count": count,
+                    })
+                })
+                .collect();
+            let total = matches.len();
+            let results = matches
+                .into_iter()
+                .take(parameters.limit)
+                .map(|found| {
+                    json!({
+                        "layer": found.layer,
+                        "neuron": found.neuron,
+                        "depth": normalised_depth(found.layer, num_layers),
+                        "score": found.score,
+                        "matched_terms": found.matched_terms,
+                    })
+                })
+                .collect();
+            (layer_counts, total, results)
+        } else {
+            (vec![], 0, vec![])
+        };
+        model_results.push(json!({
+            "model": model.name(),
+            "num_layers": num_layers,
+            "total": total,
+            "layer_counts": layer_counts,
+            "results": results,
+        }));
+    }
+
+    Ok(json!({
+        "depth": query.get("depth"),
+        "models": model_results,
+    }))
+}
+
+/// Searches every model with a neuron store for neurons by the tokens that activate them or are
+/// important in their graphs.
+#[utoipa::path(
+    operation_id = "search_tokens",
+    responses(
+        (status = 200, description = "Successfully searched the models. Results are grouped by model, with the number of matches in each layer.", body = String, content_type = "application/json"),
+        (status = "4XX", description = "The query is invalid or a requested model has no neuron store.", body = String),
+        (status = "5XX", description = "Failed to search the models.", body = String)
+    ),
+    params(
+        ("query" = String, Query, description = "Token query such as 'activating:the | (important:cat, !activating:dog)'."),
+        ("models" = Option<String>, Query, description = "Comma separated list of models to search. Defaults to every model with a neuron store."),
+        ("depth" = Option<String>, Query, description = "Only search layers at the given relative depth: 'early', 'middle', 'late' or a range such as '0.25-0.5'."),
+        ("normalise" = Option<String>, Query, description = "Comma separated normalisations applied before comparing tokens: 'case' and 'whitespace'."),
+        ("limit" = Option<usize>, Query, description = "Maximum number of results per model. Defaults to 100.")
+    )
+)]
+#[get("/api/search/tokens")]
+pub async fn search_tokens(
+    state: web::Data<State>,
+    query: web::Query<serde_json::Value>,
+) -> impl Responder {
+    match token_search(state.as_ref(), &query).await {
+        Ok(value) => Response::success(value),
+        Err(error) => {
+            let status = service_error_status(&error);
+            Response::error(error, status)
+        }
+    }
+}
+
+struct SuggestionParameters {
+    pattern: TokenPattern,
+    models: Option<Vec<String>>,
+    normalisation: Normalisation,
+    limit: usize,
+}
+
+impl SuggestionParameters {
+    fn from_query(query: &serde_json::Value) -> Result<Self, InvalidQuery> {
+        let text = string_field(query, "q")?
+            .filter(|text| !text.is_empty())
+            .ok_or_else(|| InvalidQuery("Query should contain a non-empty entry 'q'.".to_owned()))?
+            .to_owned();
+        let distance = string_field(query, "distance")?
+            .map(|distance| {
+                distance.parse::<usize>().map_err(|_| {
+                    InvalidQuery(format!("Distance '{distance}' is not a valid integer."))
+                })
+            })
+            .transpose()?
+            .unwrap_or(2);
+        let pattern = match string_field(query, "mode")?.unwrap_or("prefix") {
+            "prefix" => TokenPattern::Prefix(text),
+            "exact" => TokenPattern::Exact(text),
+            "regex" => TokenPattern::Regex(text),
+            "fuzzy" => TokenPattern::Fuzzy(text, distance),
+            mode => {
+                return Err(InvalidQuery(format!(
+                    "Invalid mode '{mode}'. Must be 'prefix', 'exact', 'regex' or 'fuzzy'."
+                )))
+            }
+        };
+        pattern
+            .validate()
+            .map_err(|error| InvalidQuery(format!("{error:#}")))?;
+        let (models, normalisation, limit) = common_fields(query, 20)?;
+        Ok(Self {
+            pattern,
+            models,
+            normalisation,
+            limit,
+        })
+    }
+}
+
+async fn token_suggestions(state: &State, query: &serde_json::Value) -> Result<serde_json::Value> {
+    let parameters = SuggestionParameters::from_query(query)?;
+    let models = searchable_models(state, parameters.models.as_deref()).await?;
+
+    let mut suggestions: HashMap<String, TokenSuggestion> = HashMap::new();
+    for model in &models {
+        let vocabulary = state.token_vocabulary(model).await?;
+        for (entry, distance) in
+            vocabulary.matching(&parameters.pattern, parameters.normalisation)?
+        {
+            let suggestion =
+                suggestions
+                    .entry(entry.token.clone())
+                    .or_insert_with(|| TokenSuggestion {
+                        token: entry.token.clone(),
+                        activating: 0,
+                        important: 0,
+                        distance,
+                    });
+            suggestion.activating += entry.activating;
+            suggestion.important += entry.important;
+        }
+    }
+    let total = suggestions.len();
+    let mut suggestions: Vec<_> = suggestions.into_values().collect();
+    sort_suggestions(&mut suggestions);
+    suggestions.truncate(parameters.limit);
+
+    Ok(json!({
+        "pattern": parameters.pattern.to_string(),
+        "models": models.iter().map(ModelHandle::name).collect::<Vec<_>>(),
+        "total": total,
+        "suggestions": suggestions,
+    }))
+}
+
+/// Suggests tokens from the vocabularies of the models' neuron stores, for autocompleting token
+/// searches.
+#[utoipa::path(
+    operation_id = "suggest_tokens",
+    responses(
+        (status = 200, description = "Tokens matching the text, closest and most common first. Counts of neurons are summed over the models.", body = String, content_type = "application/json"),
+        (status = "4XX", description = "The query is invalid or a requested model has no neuron store.", body = String),
+        (status = "5XX", description = "Failed to find tokens.", body = String)
+    ),
+    params(
+        ("q" = String, Query, description = "Text to match tokens against."),
+        ("mode" = Option<String>, Query, description = "How to match tokens: 'prefix', 'exact', 'regex' or 'fuzzy'. Defaults to 'prefix'."),
+        ("distance" = Option<usize>, Query, description = "Maximum edit distance of fuzzy matches. Defaults to 2."),
+        ("models" = Option<String>, Query, description = "Comma separated list of models whose tokens to suggest. Defaults to every model with a neuron store."),
+        ("normalise" = Option<String>, Query, description = "Comma separated normalisations applied before comparing tokens: 'case' and 'whitespace'."),
+        ("limit" = Option<usize>, Query, description = "Maximum number of suggestions. Defaults to 20.")
+    )
+)]
+#[get("/api/search/suggest")]
+pub async fn suggest_tokens(
+    state: web::Data<State>,
+    query: web::Query<serde_json::Value>,
+) -> impl Responder {
+    match token_suggestions(state.as_ref(), &query).await {
+        Ok(value) => Response::success(value),
+        Err(error) => {
+            let status = service_error_status(&error);
+            Response::error(error, status)
+        }
+    }
+}
+
+/// The `depth` field of a query, if present.
+fn depth_field(query: &serde_json::Value) -> Result<Option<DepthRange>, InvalidQuery> {
+    string_field(query, "depth")?
+        .map(|depth| {
+            depth
+                .parse::<DepthRange>()
+                .map_err(|error| InvalidQuery(format!("Invalid depth range '{depth}'. {error}")))
+        })
+        .transpose()
+}
+
+async fn token_statistics(state: &State, query: &serde_json::Value) -> Result<serde_json::Value> {
+    let tokens = query_tokens(query)?;
+    let (models, normalisation, limit) = common_fields(query, 10)?;
+    let depth = depth_field(query)?;
+    let models = searchable_models(state, models.as_deref()).await?;
+
+    let mut model_results = Vec::with_capacity(models.len());
+    for model in models {
+        let num_layers = model.metadata().num_layers;
+        let layers = match depth {
+            Some(depth) => depth.layers(num_layers),
+            None => (num_layers > 0).then(|| 0..=num_layers - 1),
+        };
+        let summaries = match layers {
+            Some(layers) => {
+                token_summaries(state, &model, &tokens, normalisation, layers, limit).await?
+            }
+            None => vec![],
+        };
+        // The mean layer of a model with a single layer is 0.
+        let depth = |layer: Option<f32>| {
+            layer.map(|layer| layer / num_layers.saturating_sub(1).max(1) as f32)
+        };
+        let summaries: Vec<_> = summaries
+            .into_iter()
+            .map(|summary| {
+                let layers: Vec<_> = summary
+                    .layers
+                    .iter()
+                    .map(|count| {
+                        json!({
+                            "layer": count.layer,
+                            "depth": normalised_depth(count.layer, num_layers),
+                            "activating": count.activating,
+                            "important": count.important,
+                            "both": count.both,
+                        })
+                    })
+                    .collect();
+                json!({
+                    "token": summary.token,
+                    "matched_tokens": summary.matched_tokens,
+                    "activating": summary.activating,
+                    "important": summary.important,
+                    "both": summary.both,
+                    "mean_activating_depth": depth(summary.mean_activating_layer),
+                    "mean_important_depth": depth(summary.mean_important_layer),
+                    "layers": layers,
+                    "top_neurons": summary.top_neurons,
+                })
+            })
+            .collect();
+        model_results.push(json!({
+            "model": model.name(),
+            "num_layers": num_layers,
+            "tokens": summaries,
+        }));
+    }
+
+    Ok(json!({
+        "tokens": tokens,
+        "depth": query.get("depth"),
+        "models": model_results,
+    }))
+}
+
+/// Counts the neurons that tokens activate or are important for in every model with a neuron
+/// store, by relative layer depth, so the tokens can be compared with each other and across
+/// models.
+#[utoipa::path(
+    operation_id = "token_statistics",
+    responses(
+        (status = 200, description = "Counts of neurons for each token, grouped by model, with the counts in each layer, the mean relative depth of the neurons and the neurons whose graphs consider the token most important.", body = String, content_type = "application/json"),
+        (status = "4XX", description = "The query is invalid or a requested model has no neuron store.", body = String),
+        (status = "5XX", description = "Failed to count the neurons.", body = String)
+    ),
+    params(
+        ("token" = Option<String>, Query, description = "Token to count neurons for."),
+        ("tokens" = Option<String>, Query, description = "Comma separated list of tokens to count neurons for, instead of 'token'. Tokens are not trimmed."),
+        ("models" = Option<String>, Query, description = "Comma separated list of models to count neurons in. Defaults to every model with a neuron store."),
+        ("depth" = Option<String>, Query, description = "Only count neurons in layers at the given relative depth: 'early', 'middle', 'late' or a range such as '0.25-0.5'."),
+        ("normalise" = Option<String>, Query, description = "Comma separated normalisations applied before comparing tokens: 'case' and 'whitespace'."),
+        ("limit" = Option<usize>, Query, description = "Maximum number of neurons by graph importance per token and model. Defaults to 10.")
+    )
+)]
+#[get("/api/search/token_stats")]
+pub async fn search_token_statistics(
+    state: web::Data<State>,
+    query: web::Query<serde_json::Value>,
+) -> impl Responder {
+    match token_statistics(state.as_ref(), &query).await {
+        Ok(value) => Response::success(value),
+        Err(error) => {
+            let status = service_error_status(&error);
+            Response::error(error, status)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn depth_ranges_select_layers() {
+        let layers =
+            |range: &str, num_layers| range.parse::<DepthRange>().unwrap().layers(num_layers);
+        assert_eq!(layers("early", 12), Some(0..=3));
+        assert_eq!(layers("middle", 12), Some(4..=7));
+        assert_eq!(layers("late", 12), Some(8..=11));
+        assert_eq!(layers("late", 48), Some(32..=47));
+        assert_eq!(layers("0-1", 1), Some(0..=0));
+        assert_eq!(layers("late", 1), None);
+        assert_eq!(layers("0.5-0.5", 3), None);
+        for invalid in ["deep", "0.5", "0.7-0.2", "0-1.5"] {
+            assert!(invalid.parse::<DepthRange>().is_err(), "'{invalid}' parsed");
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::service_provider::{
+    layers_field, limit_field, string_field, InvalidQuery, NoData, ServiceProviderTrait,
+};
+use crate::{
+    data::{
+        database::{ContextMatch, ContextQuery},
+        ContextPattern, DataTypeHandle, Database, ModelHandle,
+    },
+    server::State,
+};
+
+/// Search for neurons whose max-activating Neuroscope examples have given tokens at given
+/// positions around the max-activating token.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ContextSearch;
+
+#[async_trait]
+impl ServiceProviderTrait for ContextSearch {
+    type ModelPageObject = Vec<ContextMatch>;
+    type LayerPageObject = NoData;
+    type NeuronPageObject = NoData;
+
+    async fn required_data_types(&self, _database: &Database) -> Result<Vec<DataTypeHandle>> {
+        Ok(vec![])
+    }
+
+    async fn model_object(
+        &self,
+        _service_name: &str,
+        state: &State,
+        query: &serde_json::Value,
+        model: &ModelHandle,
+    ) -> Result<Self::ModelPageObject> {
+        let pattern = string_field(query, "pattern")?
+            .ok_or_else(|| {
+                InvalidQuery(
+                    "Query should contain an entry 'pattern' with a string value.".to_owned(),
+                )
+            })?
+            .parse::<ContextPattern>()
+            .map_err(|error| InvalidQuery(error.to_string()))?;
+        let exact = match string_field(query, "exact")? {
+            None | Some("false") => false,
+            Some("true") => true,
+            Some(exact) => {
+                return Err(InvalidQuery(format!(
+                    "Query field 'exact' should be 'true' or 'false'. Found: {exact}"
+                ))
+                .into())
+            }
+        };
+        let data_type = match string_field(query, "data_type")? {
+            Some(data_type_name) => Some(
+                state
+                    .database()
+                    .data_type(data_type_name)
+                    .await?
+                    .ok_or_else(|| {
+                        InvalidQuery(format!("No data object with name '{data_type_name}'."))
+                    })?,
+            ),
+            None => None,
+        };
+        let layers = layers_field(query, model)?;
+        let min_activation = string_field(query, "min_activation")?
+            .map(|activation| {
+                activation.parse::<f32>().map_err(|_| {
+                    InvalidQuery(format!(
+                        "Query field 'min_activation' should be a number. Found: {activation}"
+                    ))
+                })
+            })
+            .transpose()?;
+        let limit = limit_field(query, 100)?;
+
+        model
+            .search_contexts(&ContextQuery {
+                pattern,
+                exact,
+                data_type,
+                layers,
+                min_activation,
+                limit,
+            })
+            .await
+    }
+}
@@ -0,0 +1,104 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::service_provider::{
+    layers_field, string_field, InvalidQuery, NoData, ServiceProviderTrait,
+};
+use crate::{
+    data::{
+        data_objects::EmbeddingNeighbours, DataTypeHandle, Database, EmbeddingMetric, ModelHandle,
+        NeuronIndex,
+    },
+    server::State,
+};
+
+/// Maximum number of neighbours found at once.
+const MAX_K: usize = 100;
+
+/// Finds the neurons of a model whose embeddings, stored in the named data object, are nearest to
+/// that of a neuron.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EmbeddingSearch(String);
+
+impl EmbeddingSearch {
+    pub fn new(data_type_name: String) -> Self {
+        Self(data_type_name)
+    }
+
+    async fn data_type(&self, database: &Database) -> Result<DataTypeHandle> {
+        let Self(ref data_type_name) = self;
+        database.data_type(data_type_name).await?.with_context(|| {
+            format!(
+                "No data object with name '{data_type_name}'. This should have been checked when \
+                 the service was created."
+            )
+        })
+    }
+}
+
+#[async_trait]
+impl ServiceProviderTrait for EmbeddingSearch {
+    type ModelPageObject = NoData;
+    type LayerPageObject = NoData;
+    type NeuronPageObject = EmbeddingNeighbours;
+
+    async fn required_data_types(&self, database: &Database) -> Result<Vec<DataTypeHandle>> {
+        Ok(vec![self.data_type(database).await?])
+    }
+
+    async fn neuron_object(
+        &self,
+        _service_name: &str,
+        state: &State,
+        query: &serde_json::Value,
+        model: &ModelHandle,
+        layer_index: u32,
+        neuron_index: u32,
+    ) -> Result<Self::NeuronPageObject> {
+        let k = string_field(query, "k")?
+            .map(|k| {
+                k.parse::<usize>()
+                    .ok()
+                    .filter(|k| (1..=MAX_K).contains(k))
+                    .ok_or_else(|| {
+                        InvalidQuery(format!("'k' should be an integer between 1 and {MAX_K}."))
+                    })
+            })
+            .transpose()?
+            .unwrap_or(10);
+        let metric = string_field(query, "metric")?
+            .map(|metric| {
+                metric
+                    .parse::<EmbeddingMetric>()
+                    .map_err(|error| InvalidQuery(error.to_string()))
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let layers = layers_field(query, model)?;
+
+        let data_type = self.data_type(state.database()).await?;
+        let index = state.embedding_index(model, &data_type).await?;
+        let neuron = NeuronIndex {
+            layer: layer_index,
+            neuron: neuron_index,
+        };
+        // Searching may build the graph of a large index, which takes a while.
+        tokio::task::spawn_blocking(move || {
+            index
+                .neighbours(neuron, k, metric, |other| {
+                    layers
+                        .as_ref()
+                        .is_none_or(|layers| layers.contains(&other.layer))
+                })?
+                .with_context(|| {
+                    format!(
+                        "Neuron {neuron} has no embedding in data object '{}'.",
+                        data_type.name()
+                    )
+                })
+        })
+        .await
+        .context("Embedding search panicked.")?
+    }
+}
@@ -0,0 +1,90 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::service_provider::{
+    layers_field, limit_field, string_field, InvalidQuery, NoData, ServiceProviderTrait,
+};
+use crate::{
+    data::{
+        database::{ExplanationMatch, ExplanationQuery, InvalidTextQuery},
+        DataTypeHandle, Database, ModelHandle,
+    },
+    server::State,
+};
+
+/// Full text search over the explanations of a model's neurons, from both Neuron Explainer data
+/// and JSON data with `explanation` fields.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ExplanationSearch;
+
+fn number_field(query: &serde_json::Value, field: &str) -> Result<Option<f32>> {
+    string_field(query, field)?
+        .map(|value| {
+            value.parse::<f32>().map_err(|_| {
+                InvalidQuery(format!(
+                    "Query field '{field}' should be a number. Found: {value}"
+                ))
+                .into()
+            })
+        })
+        .transpose()
+}
+
+#[async_trait]
+impl ServiceProviderTrait for ExplanationSearch {
+    type ModelPageObject = Vec<ExplanationMatch>;
+    type LayerPageObject = NoData;
+    type NeuronPageObject = NoData;
+
+    async fn required_data_types(&self, _database: &Database) -> Result<Vec<DataTypeHandle>> {
+        Ok(vec![])
+    }
+
+    async fn model_object(
+        &self,
+        _service_name: &str,
+        state: &State,
+        query: &serde_json::Value,
+        model: &ModelHandle,
+    ) -> Result<Self::ModelPageObject> {
+        let text = string_field(query, "query")?
+            .ok_or_else(|| {
+                InvalidQuery(
+                    "Query should contain an entry 'query' with a string value.".to_owned(),
+                )
+            })?
+            .to_owned();
+        let data_type = match string_field(query, "data_type")? {
+            Some(data_type_name) => Some(
+                state
+                    .database()
+                    .data_type(data_type_name)
+                    .await?
+                    .ok_or_else(|| {
+                        InvalidQuery(format!("No data object with name '{data_type_name}'."))
+                    })?,
+            ),
+            None => None,
+        };
+        let layers = layers_field(query, model)?;
+        let limit = limit_field(query, 100)?;
+
+        let explanation_query = ExplanationQuery {
+            text,
+            data_type,
+            layers,
+            min_score: number_field(query, "min_score")?,
+            max_score: number_field(query, "max_score")?,
+            score_weight: number_field(query, "score_weight")?.unwrap_or(1.),
+            limit,
+        };
+        model
+            .search_explanations(&explanation_query)
+            .await
+            .map_err(|error| match error.downcast::<InvalidTextQuery>() {
+                Ok(invalid_query) => InvalidQuery(invalid_query.to_string()).into(),
+                Err(error) => error,
+            })
+    }
+}
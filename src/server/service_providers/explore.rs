@@ -0,0 +1,117 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::service_provider::{
+    layers_field, string_field, InvalidQuery, NoData, ServiceProviderTrait,
+};
+use crate::{
+    data::{
+        data_objects::RandomNeurons,
+        database::{RandomNeuronQuery, UnknownDataType},
+        DataTypeHandle, Database, ModelHandle, NeuronAttribute,
+    },
+    server::State,
+};
+
+/// Maximum number of neurons sampled at once.
+const MAX_COUNT: usize = 100;
+
+/// Samples random neurons of a model, optionally weighted by a scalar such as activation range,
+/// explanation score or graph size. Samples are reproducible given their seed.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Explore;
+
+/// A seed for a sample without one. Seeds are kept below 2^53 so they survive a round trip
+/// through JavaScript numbers and can be shared.
+fn new_seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default();
+    nanos & ((1 << 53) - 1)
+}
+
+#[async_trait]
+impl ServiceProviderTrait for Explore {
+    type ModelPageObject = RandomNeurons;
+    type LayerPageObject = NoData;
+    type NeuronPageObject = NoData;
+
+    async fn required_data_types(&self, _database: &Database) -> Result<Vec<DataTypeHandle>> {
+        Ok(vec![])
+    }
+
+    async fn model_object(
+        &self,
+        _service_name: &str,
+        state: &State,
+        query: &serde_json::Value,
+        model: &ModelHandle,
+    ) -> Result<Self::ModelPageObject> {
+        let seed = string_field(query, "seed")?
+            .map(|seed| {
+                seed.parse::<u64>().map_err(|_| {
+                    InvalidQuery(format!(
+                        "Seed '{seed}' should be a non-negative integer below 2^64."
+                    ))
+                })
+            })
+            .transpose()?
+            .unwrap_or_else(new_seed);
+        let count = string_field(query, "count")?
+            .map(|count| {
+                count
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|count| (1..=MAX_COUNT).contains(count))
+                    .ok_or_else(|| {
+                        InvalidQuery(format!(
+                            "Count '{count}' should be an integer between 1 and {MAX_COUNT}."
+                        ))
+                    })
+            })
+            .transpose()?
+            .unwrap_or(10);
+        let layers = layers_field(query, model)?;
+        let weight = string_field(query, "weight")?
+            .filter(|weight| !weight.trim().is_empty())
+            .map(|weight| match weight.parse::<NeuronAttribute>() {
+                Ok(attribute @ NeuronAttribute::Scalar { .. }) => Ok(attribute),
+                Ok(attribute) => Err(InvalidQuery(format!(
+                    "Neurons can only be weighted by scalars written 'data_object.name', not \
+                     '{attribute}'."
+                ))),
+                Err(error) => Err(InvalidQuery(error.to_string())),
+            })
+            .transpose()?;
+        let data_types = match string_field(query, "service")? {
+            Some(service_name) => {
+                state
+                    .database()
+                    .service(service_name)
+                    .await?
+                    .ok_or_else(|| InvalidQuery(format!("No service with name '{service_name}'.")))?
+                    .required_data_types()
+                    .await?
+            }
+            None => vec![],
+        };
+
+        let random_query = RandomNeuronQuery {
+            seed,
+            count,
+            layers,
+            weight,
+            data_types,
+        };
+        model.random_neurons(&random_query).await.map_err(|error| {
+            match error.downcast::<UnknownDataType>() {
+                Ok(unknown) => InvalidQuery(unknown.to_string()).into(),
+                Err(error) => error,
+            }
+        })
+    }
+}
@@ -1,8 +1,11 @@
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
-use super::service_provider::ServiceProviderTrait;
+use super::{
+    json_path::{Path, PathError},
+    service_provider::{string_field, InvalidQuery, ServiceProviderTrait},
+};
 use crate::{
     data::{data_types::Json as JsonData, DataTypeHandle, Database, ModelHandle},
     server::State,
@@ -35,6 +38,58 @@ async fn data_type(
     })
 }
 
+fn invalid_path(error: PathError) -> InvalidQuery {
+    InvalidQuery(error.to_string())
+}
+
+/// Parses the query into the path to select and the fields to project the selection onto.
+///
+/// The query may contain one of `get` (a single field or index), `pointer` (a JSON Pointer) and
+/// `path` (see [`json_path`](super::json_path)), and `fields`, a comma separated list of paths to
+/// project the selected value, or each element if it is an array, onto.
+fn query_paths(query: &serde_json::Value) -> Result<(Option<Path>, Option<Path>), InvalidQuery> {
+    let object = query
+        .as_object()
+        .ok_or_else(|| InvalidQuery("Query is not an object.".to_owned()))?;
+    if let Some(key) = object
+        .keys()
+        .find(|key| !["get", "pointer", "path", "fields"].contains(&key.as_str()))
+    {
+        return Err(InvalidQuery(format!(
+            "Invalid query for json service. Unknown field '{key}'. The query may contain 'get', \
+             'pointer', 'path' and 'fields'."
+        )));
+    }
+    let path = match (
+        query.get("get"),
+        string_field(query, "pointer")?,
+        string_field(query, "path")?,
+    ) {
+        (None, None, None) => None,
+        (Some(key), None, None) => match key {
+            serde_json::Value::String(key) => Some(Path::key(key.clone())),
+            serde_json::Value::Number(key) if key.is_u64() => Some(Path::key(key.to_string())),
+            _ => {
+                return Err(InvalidQuery(
+                    "Query 'get' field is not a string or a non-negative integer.".to_owned(),
+                ))
+            }
+        },
+        (None, Some(pointer), None) => Some(Path::from_pointer(pointer).map_err(invalid_path)?),
+        (None, None, Some(path)) => Some(path.parse().map_err(invalid_path)?),
+        _ => {
+            return Err(InvalidQuery(
+                "Query may only contain one of 'get', 'pointer' and 'path'.".to_owned(),
+            ))
+        }
+    };
+    let fields = string_field(query, "fields")?
+        .map(Path::projection)
+        .transpose()
+        .map_err(invalid_path)?;
+    Ok((path, fields))
+}
+
 async fn page(
     data_type_name: &str,
     state: &State,
@@ -43,8 +98,8 @@ async fn page(
     index: Index,
 ) -> Result<serde_json::Value> {
     let model_name = model.name();
+    let (path, fields) = query_paths(query)?;
     let json_object = data_type(state.database(), model, data_type_name).await?;
-    let query = query.as_object().context("Query is not an object.")?;
     let json = json_object
         .page(index)
         .await
@@ -56,41 +111,33 @@ async fn page(
             )
         })?
         .value;
-    if query.is_empty() {
-        Ok(json)
-    } else if let Some(json_index) = query.get("get") {
-        let mut json = json;
-        match json_index {
-            serde_json::Value::String(json_index) => {
-                if let Some(value) = json.get_mut(json_index) {
-                    Some(value)
-                } else {
-                    let int_index = json_index.parse::<usize>().with_context(|| {
-                        format!("No field '{json_index}' exists and the index is not an integer.")
-                    })?;
-                    json.get_mut(int_index)
-                }
-            }
-            serde_json::Value::Number(json_index) => json.get_mut(
-                json_index
-                    .as_u64()
-                    .context("Query 'get' field is not a u64.")? as usize,
-            ),
-            _ => bail!("Query 'get' field is not a string or a number."),
-        }
-        .with_context(|| {
-            format!(
-                "Failed to get json value '{json_index}' for {index} of model '{model_name}' and \
-                 data object '{data_type_name}'.",
-                index = index.error_string()
-            )
-        })
-        .map(serde_json::Value::take)
-    } else {
-        bail!(
-            "Invalid query for json service. Query must be empty or contain a 'get' field. Query: \
-             {query:?}"
+    let context = || {
+        format!(
+            "Failed to select from json data object '{data_type_name}' for {index} of model \
+             '{model_name}'.",
+            index = index.error_string()
         )
+    };
+    let json = match path {
+        Some(path) => path
+            .select(json)
+            .map_err(invalid_path)
+            .with_context(context)?,
+        None => json,
+    };
+    match (fields, json) {
+        (None, json) => Ok(json),
+        (Some(fields), serde_json::Value::Array(values)) => values
+            .into_iter()
+            .map(|value| fields.select(value))
+            .collect::<Result<Vec<_>, _>>()
+            .map(serde_json::Value::Array)
+            .map_err(invalid_path)
+            .with_context(context),
+        (Some(fields), json) => fields
+            .select(json)
+            .map_err(invalid_path)
+            .with_context(context),
     }
 }
 
@@ -0,0 +1,603 @@
+//! Paths selecting parts of JSON values.
+//!
+//! Paths are either JSON Pointers (RFC 6901) such as `/texts/0/tokens`, or use a small path syntax:
+//!
+//! - `texts.tokens` or `texts["tokens"]` selects nested fields. A field of an array is an index.
+//! - `texts[0]` and `texts[-1]` select array elements, counting from the end if negative.
+//! - `texts[1:3]` selects a slice of an array. Either bound may be left out.
+//! - `texts.*` and `texts[*]` select every element of an array or value of an object.
+//! - `{name, stats.max}` selects multiple fields into a new object.
+//!
+//! An optional leading `$` refers to the whole value. If a path contains a wildcard or a slice,
+//! the result is an array of every match and missing values are skipped. Otherwise the result is
+//! the single selected value and a missing value is an error.
+
+use std::{fmt::Display, str::FromStr};
+
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum PathError {
+    #[error("Invalid path '{path}' at position {position}: {message}.")]
+    Syntax {
+        path: String,
+        position: usize,
+        message: String,
+    },
+    #[error("Invalid JSON pointer '{pointer}': {message}.")]
+    Pointer { pointer: String, message: String },
+    #[error("No value at '{at}' in path '{path}': {message}.")]
+    Missing {
+        path: String,
+        at: String,
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(i64),
+    Slice(Option<i64>, Option<i64>),
+    Wildcard,
+    Projection(Vec<(String, Path)>),
+}
+
+impl Segment {
+    fn is_singular(&self) -> bool {
+        match self {
+            // A projection selects one object, even if some of its fields are arrays of matches.
+            Self::Key(_) | Self::Index(_) | Self::Projection(_) => true,
+            Self::Slice(..) | Self::Wildcard => false,
+        }
+    }
+}
+
+/// A segment along with the position in the path text where it ends, for error messages.
+#[derive(Debug, Clone, PartialEq)]
+struct Step {
+    segment: Segment,
+    end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    text: String,
+    steps: Vec<Step>,
+}
+
+impl Path {
+    /// Parses a JSON Pointer. The empty pointer refers to the whole value.
+    pub fn from_pointer(pointer: &str) -> Result<Self, PathError> {
+        let error = |message: String| PathError::Pointer {
+            pointer: pointer.to_owned(),
+            message,
+        };
+        if pointer.is_empty() {
+            return Ok(Self {
+                text: String::new(),
+                steps: vec![],
+            });
+        }
+        let Some(tokens) = pointer.strip_prefix('/') else {
+            return Err(error(
+                "a pointer must be empty or start with '/'".to_owned(),
+            ));
+        };
+        let mut steps = Vec::new();
+        let mut end = 0;
+        for token in tokens.split('/') {
+            end += token.len() + 1;
+            let mut key = String::with_capacity(token.len());
+            let mut chars = token.chars();
+            while let Some(c) = chars.next() {
+                if c != '~' {
+                    key.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some('0') => key.push('~'),
+                    Some('1') => key.push('/'),
+                    _ => {
+                        return Err(error(format!(
+                            "'~' must be followed by '0' or '1' in '{token}'"
+                        )))
+                    }
+                }
+            }
+            steps.push(Step {
+                segment: Segment::Key(key),
+                end,
+            });
+        }
+        Ok(Self {
+            text: pointer.to_owned(),
+            steps,
+        })
+    }
+
+    /// A path selecting a single field of an object or, if the key is an integer, element of an
+    /// array.
+    pub fn key(key: String) -> Self {
+        Self {
+            steps: vec![Step {
+                segment: Segment::Key(key.clone()),
+                end: key.len(),
+            }],
+            text: key,
+        }
+    }
+
+    /// Parses a comma separated list of paths, such as `name, stats.max`, as a projection selecting
+    /// each of them into an object.
+    pub fn projection(fields: &str) -> Result<Self, PathError> {
+        let text = fields;
+        let mut parser = Parser::new(text);
+        let fields = parser.fields()?;
+        if parser.peek().is_some() {
+            return Err(parser.error("expected ','"));
+        }
+        Ok(Self {
+            text: format!("{{{text}}}"),
+            steps: vec![Step {
+                segment: Segment::Projection(fields),
+                end: text.len() + 2,
+            }],
+        })
+    }
+
+    /// Whether the path selects at most one value.
+    pub fn is_singular(&self) -> bool {
+        self.steps.iter().all(|step| step.segment.is_singular())
+    }
+
+    /// Selects the part of the value the path refers to.
+    pub fn select(&self, value: Value) -> Result<Value, PathError> {
+        let singular = self.is_singular();
+        let mut values = self.select_all(value, singular)?;
+        if singular {
+            Ok(values
+                .pop()
+                .expect("A singular path selects exactly one value or fails."))
+        } else {
+            Ok(Value::Array(values))
+        }
+    }
+
+    /// Selects every value the path refers to. If `strict`, missing values are errors and
+    /// otherwise they are skipped.
+    fn select_all(&self, value: Value, strict: bool) -> Result<Vec<Value>, PathError> {
+        let mut values = vec![value];
+        for step in &self.steps {
+            let mut selected = Vec::with_capacity(values.len());
+            for value in values {
+                match self.apply(step, value, strict)? {
+                    Some(values) => selected.extend(values),
+                    None if strict => unreachable!("Strict selection fails instead."),
+                    None => {}
+                }
+            }
+            values = selected;
+        }
+        Ok(values)
+    }
+
+    fn missing(&self, step: &Step, message: impl Display) -> PathError {
+        PathError::Missing {
+            path: self.text.clone(),
+            at: self.text[..step.end].to_owned(),
+            message: message.to_string(),
+        }
+    }
+
+    fn apply(
+        &self,
+        step: &Step,
+        value: Value,
+        strict: bool,
+    ) -> Result<Option<Vec<Value>>, PathError> {
+        let result = match (&step.segment, value) {
+            (Segment::Key(key), Value::Object(mut object)) => object
+                .remove(key)
+                .map(|value| vec![value])
+                .ok_or_else(|| format!("the object has no field '{key}'")),
+            (Segment::Key(key), Value::Array(array)) => match key.parse::<usize>() {
+                Ok(index) => element(array, index as i64),
+                Err(_) => Err(format!("'{key}' is not an index into the array")),
+            },
+            (Segment::Index(index), Value::Array(array)) => element(array, *index),
+            (Segment::Slice(start, end), Value::Array(array)) => {
+                let len = array.len() as i64;
+                let bound = |bound: i64| {
+                    if bound < 0 {
+                        (len + bound).max(0)
+                    } else {
+                        bound.min(len)
+                    }
+                };
+                let start = start.map_or(0, bound) as usize;
+                let end = end.map_or(len, bound) as usize;
+                Ok(array
+                    .into_iter()
+                    .skip(start)
+                    .take(end.saturating_sub(start))
+                    .collect())
+            }
+            (Segment::Wildcard, Value::Array(array)) => Ok(array),
+            (Segment::Wildcard, Value::Object(object)) => {
+                Ok(object.into_iter().map(|(_, value)| value).collect())
+            }
+            (Segment::Wildcard, _) => Ok(vec![]),
+            (Segment::Projection(fields), value) => {
+                let mut object = Map::with_capacity(fields.len());
+                for (name, path) in fields {
+                    let singular = path.is_singular();
+                    let mut values = path.select_all(value.clone(), strict && singular)?;
+                    if !singular {
+                        object.insert(name.clone(), Value::Array(values));
+                    } else if let Some(value) = values.pop() {
+                        object.insert(name.clone(), value);
+                    }
+                }
+                Ok(vec![Value::Object(object)])
+            }
+            (Segment::Key(_) | Segment::Index(_) | Segment::Slice(..), value) => Err(format!(
+                "expected {}, found {}",
+                match step.segment {
+                    Segment::Key(_) => "an object or an array",
+                    _ => "an array",
+                },
+                value_type(&value)
+            )),
+        };
+        match result {
+            Ok(values) => Ok(Some(values)),
+            Err(message) if strict => Err(self.missing(step, message)),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+fn element(array: Vec<Value>, index: i64) -> Result<Vec<Value>, String> {
+    let len = array.len();
+    let position = if index < 0 {
+        len.checked_sub(index.unsigned_abs() as usize)
+    } else {
+        Some(index as usize)
+    };
+    position
+        .and_then(|position| array.into_iter().nth(position))
+        .map(|value| vec![value])
+        .ok_or_else(|| format!("index {index} is out of bounds for an array of length {len}"))
+}
+
+fn value_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+impl FromStr for Path {
+    type Err = PathError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(text);
+        let steps = parser.path(false)?;
+        if parser.peek().is_some() {
+            return Err(parser.error("expected '.', '[' or '{'"));
+        }
+        Ok(Self {
+            text: text.to_owned(),
+            steps,
+        })
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Self { text, position: 0 }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.position += c.len_utf8();
+        }
+        found
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> PathError {
+        PathError::Syntax {
+            path: self.text.to_owned(),
+            position: self.position,
+            message: message.into(),
+        }
+    }
+
+    fn step(&self, segment: Segment) -> Step {
+        Step {
+            segment,
+            end: self.position,
+        }
+    }
+
+    /// Parses a path. A path `nested` in a projection ends at a comma, a closing brace or
+    /// whitespace.
+    fn path(&mut self, nested: bool) -> Result<Vec<Step>, PathError> {
+        let mut steps = Vec::new();
+        let mut first = nested || !self.eat('$');
+        while let Some(c) = self.peek() {
+            if nested && (c == ',' || c == '}' || c.is_whitespace()) {
+                break;
+            }
+            let segment = match c {
+                '[' => self.bracket()?,
+                '{' => self.projection()?,
+                '.' if !first => {
+                    self.bump();
+                    match self.peek() {
+                        Some('*') => {
+                            self.bump();
+                            Segment::Wildcard
+                        }
+                        Some('{') => self.projection()?,
+                        _ => Segment::Key(self.name()?),
+                    }
+                }
+                '*' if first => {
+                    self.bump();
+                    Segment::Wildcard
+                }
+                _ if first => Segment::Key(self.name()?),
+                _ => return Err(self.error("expected '.', '[' or '{'")),
+            };
+            steps.push(self.step(segment));
+            first = false;
+        }
+        if nested && steps.is_empty() {
+            return Err(self.error("expected a field"));
+        }
+        Ok(steps)
+    }
+
+    fn name(&mut self) -> Result<String, PathError> {
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|c| !c.is_whitespace() && !".[]{},*\"'$".contains(c))
+        {
+            self.bump();
+        }
+        if self.position == start {
+            Err(self.error("expected a field name"))
+        } else {
+            Ok(self.text[start..self.position].to_owned())
+        }
+    }
+
+    fn bracket(&mut self) -> Result<Segment, PathError> {
+        self.bump();
+        self.skip_whitespace();
+        let segment = match self.peek() {
+            Some('*') => {
+                self.bump();
+                Segment::Wildcard
+            }
+            Some(quote @ ('"' | '\'')) => {
+                self.bump();
+                let start = self.position;
+                while self.peek().is_some_and(|c| c != quote) {
+                    self.bump();
+                }
+                let key = self.text[start..self.position].to_owned();
+                if !self.eat(quote) {
+                    return Err(self.error(format!("expected a closing {quote}")));
+                }
+                Segment::Key(key)
+            }
+            _ => {
+                let start = self.integer()?;
+                self.skip_whitespace();
+                if self.eat(':') {
+                    self.skip_whitespace();
+                    Segment::Slice(start, self.integer()?)
+                } else {
+                    Segment::Index(start.ok_or_else(|| {
+                        self.error("expected an index, a slice, '*' or a quoted field name")
+                    })?)
+                }
+            }
+        };
+        self.skip_whitespace();
+        if self.eat(']') {
+            Ok(segment)
+        } else {
+            Err(self.error("expected ']'"))
+        }
+    }
+
+    fn integer(&mut self) -> Result<Option<i64>, PathError> {
+        let start = self.position;
+        self.eat('-');
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.bump();
+        }
+        let text = &self.text[start..self.position];
+        if text.is_empty() {
+            Ok(None)
+        } else {
+            text.parse()
+                .map(Some)
+                .map_err(|_| self.error(format!("'{text}' is not a valid index")))
+        }
+    }
+
+    fn projection(&mut self) -> Result<Segment, PathError> {
+        self.bump();
+        let fields = self.fields()?;
+        if self.eat('}') {
+            Ok(Segment::Projection(fields))
+        } else {
+            Err(self.error("expected ',' or '}'"))
+        }
+    }
+
+    /// Parses the comma separated fields of a projection, up to but not including the closing
+    /// brace if there is one.
+    fn fields(&mut self) -> Result<Vec<(String, Path)>, PathError> {
+        let mut fields = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let field_start = self.position;
+            let steps = self.path(true)?;
+            let name = &self.text[field_start..self.position];
+            let steps = steps
+                .into_iter()
+                .map(|step| Step {
+                    segment: step.segment,
+                    end: step.end - field_start,
+                })
+                .collect();
+            fields.push((
+                name.to_owned(),
+                Path {
+                    text: name.to_owned(),
+                    steps,
+                },
+            ));
+            self.skip_whitespace();
+            if !self.eat(',') {
+                return Ok(fields);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn select(path: &str, value: &Value) -> Result<Value, PathError> {
+        path.parse::<Path>()?.select(value.clone())
+    }
+
+    #[test]
+    fn selects_nested_values() {
+        let value = json!({
+            "name": "l0n1",
+            "stats": { "max": 2.5, "min": -1.0 },
+            "texts": [
+                { "tokens": ["a", "b"], "max": 1 },
+                { "tokens": ["c"], "max": 2 },
+                { "tokens": [], "max": 3 },
+            ],
+        });
+        assert_eq!(select("stats.max", &value).unwrap(), json!(2.5));
+        assert_eq!(select("$.texts[1].tokens[0]", &value).unwrap(), json!("c"));
+        assert_eq!(select("texts.0.max", &value).unwrap(), json!(1));
+        assert_eq!(select("texts[-1].max", &value).unwrap(), json!(3));
+        assert_eq!(select("stats['min']", &value).unwrap(), json!(-1.0));
+        assert_eq!(select("texts[*].max", &value).unwrap(), json!([1, 2, 3]));
+        assert_eq!(select("texts[1:].max", &value).unwrap(), json!([2, 3]));
+        assert_eq!(select("texts[:-2].max", &value).unwrap(), json!([1]));
+        assert_eq!(
+            select("texts.*.tokens[0]", &value).unwrap(),
+            json!(["a", "c"])
+        );
+        assert_eq!(
+            select("{name, stats.max}", &value).unwrap(),
+            json!({ "name": "l0n1", "stats.max": 2.5 })
+        );
+        assert_eq!(
+            select("texts[0:2].{max,tokens}", &value).unwrap(),
+            json!([{ "max": 1, "tokens": ["a", "b"] }, { "max": 2, "tokens": ["c"] }])
+        );
+        assert_eq!(
+            Path::projection("name,texts[*].max")
+                .unwrap()
+                .select(value.clone())
+                .unwrap(),
+            json!({ "name": "l0n1", "texts[*].max": [1, 2, 3] })
+        );
+        assert_eq!(
+            Path::from_pointer("/texts/0/tokens/1")
+                .unwrap()
+                .select(value.clone())
+                .unwrap(),
+            json!("b")
+        );
+        assert_eq!(
+            Path::from_pointer("")
+                .unwrap()
+                .select(value.clone())
+                .unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn reports_invalid_paths() {
+        let value = json!({ "stats": { "max": 2.5 }, "texts": [1, 2] });
+        assert!(matches!(
+            "stats..max".parse::<Path>(),
+            Err(PathError::Syntax { position: 6, .. })
+        ));
+        assert!(matches!(
+            "texts[1".parse::<Path>(),
+            Err(PathError::Syntax { position: 7, .. })
+        ));
+        assert!(matches!(
+            "{a,}".parse::<Path>(),
+            Err(PathError::Syntax { position: 3, .. })
+        ));
+        assert!(matches!(
+            Path::from_pointer("texts/0"),
+            Err(PathError::Pointer { .. })
+        ));
+        assert!(matches!(
+            Path::from_pointer("/a~2b"),
+            Err(PathError::Pointer { .. })
+        ));
+        assert_eq!(
+            select("stats.min.value", &value).unwrap_err(),
+            PathError::Missing {
+                path: "stats.min.value".to_owned(),
+                at: "stats.min".to_owned(),
+                message: "the object has no field 'min'".to_owned()
+            }
+        );
+        assert!(matches!(
+            select("texts[5]", &value),
+            Err(PathError::Missing { .. })
+        ));
+        assert_eq!(select("texts[*].max", &value).unwrap(), json!([]));
+    }
+}
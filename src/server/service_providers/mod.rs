@@ -1,9 +1,22 @@
+mod context_search;
+mod embedding_search;
+mod explanation_search;
+mod explore;
 mod json;
+mod json_path;
 mod metadata;
 mod neuron2graph;
 mod neuron2graph_search;
+mod neuron_clusters;
+mod neuron_correspondence;
 mod neuron_explainer;
+mod neuron_query;
+mod neuron_summary;
 mod neuroscope;
+mod sample_index;
 mod service_provider;
-pub use service_provider::ServiceProvider;
+mod token_statistics;
 use service_provider::ServiceProviderTrait;
+pub(super) use service_provider::{limit_field, string_field};
+pub use service_provider::{InvalidQuery, ServiceProvider};
+pub(super) use token_statistics::{query_tokens, token_summaries};
@@ -1,14 +1,14 @@
-use std::{collections::HashSet, str::FromStr};
-
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
-use super::service_provider::{NoData, ServiceProviderTrait};
+use super::service_provider::{
+    layers_field, string_field, InvalidQuery, NoData, ServiceProviderTrait,
+};
 use crate::{
     data::{
-        data_types::NeuronStore as NeuronStoreObject, DataTypeHandle, Database, ModelHandle,
-        NeuronIndex, TokenSearch,
+        DataTypeHandle, Database, LayerRange, ModelHandle, Normalisation, TokenQuery,
+        TokenQueryMatch,
     },
     server::State,
 };
@@ -18,7 +18,7 @@ pub struct Neuron2GraphSearch;
 
 #[async_trait]
 impl ServiceProviderTrait for Neuron2GraphSearch {
-    type ModelPageObject = Vec<NeuronIndex>;
+    type ModelPageObject = Vec<TokenQueryMatch>;
     type LayerPageObject = NoData;
     type NeuronPageObject = NoData;
 
@@ -40,55 +40,26 @@ impl ServiceProviderTrait for Neuron2GraphSearch {
         query: &serde_json::Value,
         model: &ModelHandle,
     ) -> Result<Self::ModelPageObject> {
-        let database = state.database();
-        let neuron_store_object = database
-            .data_type("neuron_store")
-            .await
-            .context("Could not get neuron store data object from database.")?
-            .context("No data object named 'neuron_store' in database.")?;
-        let neuron_store_object: NeuronStoreObject = database
-            .model_data_type(model, &neuron_store_object)
-            .await
-            .with_context(|| {
-                format!(
-                    "Model '{}' has no 'neuron_store' data object. This should have been checked \
-                     earlier.",
-                    model.name()
-                )
-            })?;
-        let neuron_store = neuron_store_object.get_store().await?;
-
-        let query = query["query"]
-            .as_str()
-            .context("Query should contain an entry 'query' with a string value.")?;
+        let neuron_store = state.neuron_store(model).await?;
 
-        let token_searches = query
-            .split(',')
-            .map(TokenSearch::from_str)
-            .collect::<Result<Vec<_>>>()?;
-        let mut results = token_searches
-            .into_iter()
-            .map(|token_search| {
-                let TokenSearch {
-                    token,
-                    search_types,
-                } = token_search;
-                search_types
-                    .into_iter()
-                    .flat_map(|search_type| {
-                        neuron_store
-                            .get(search_type, token.as_str())
-                            .cloned()
-                            .unwrap_or_default()
-                    })
-                    .collect::<HashSet<_>>()
+        let query_string = string_field(query, "query")?.ok_or_else(|| {
+            InvalidQuery("Query should contain an entry 'query' with a string value.".to_owned())
+        })?;
+        let token_query = query_string
+            .parse::<TokenQuery>()
+            .map_err(|error| InvalidQuery(error.to_string()))?;
+        let layers = layers_field(query, model)?
+            .map_or_else(|| LayerRange::all().layers(model.metadata()), Ok)?;
+        let normalisation = string_field(query, "normalise")?
+            .map(|normalisation| {
+                normalisation
+                    .parse::<Normalisation>()
+                    .map_err(|error| InvalidQuery(error.to_string()))
             })
-            .reduce(|a, b| a.intersection(&b).copied().collect::<HashSet<_>>())
-            .with_context(|| "At least one token search should be provided.")?
-            .into_iter()
-            .collect::<Vec<_>>();
-        results.sort_unstable();
+            .transpose()?
+            .unwrap_or_default();
+        let vocabulary = state.token_vocabulary(model).await?;
 
-        Ok(results)
+        token_query.search(&neuron_store, &vocabulary, normalisation, layers)
     }
 }
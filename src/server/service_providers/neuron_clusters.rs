@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::service_provider::{InvalidQuery, NoData, ServiceProviderTrait};
+use crate::{
+    data::{
+        data_objects::{NeuronClusters as NeuronClustersObject, NeuronGraphNode},
+        data_types::NeuronClusters as NeuronClustersData,
+        retrieve::neuron_clusters::NEURON_CLUSTERS,
+        DataTypeHandle, Database, ModelHandle,
+    },
+    server::State,
+};
+
+/// Number of members listed for each cluster when listing all clusters.
+const MAX_LISTED_MEMBERS: usize = 10;
+
+/// The clusters of the similarity graph of a model and the role of each neuron in it. Lists all
+/// clusters with their most central members, or all members of a single cluster given as
+/// `cluster=3`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NeuronClusters;
+
+async fn clusters_data(database: &Database, model: &ModelHandle) -> Result<NeuronClustersData> {
+    let data_type = database
+        .data_type(NEURON_CLUSTERS)
+        .await?
+        .with_context(|| format!("No data object with name '{NEURON_CLUSTERS}'."))?;
+    model.data_type(&data_type).await.with_context(|| {
+        format!(
+            "Failed to get neuron clusters data object for model '{}'.",
+            model.name()
+        )
+    })
+}
+
+#[async_trait]
+impl ServiceProviderTrait for NeuronClusters {
+    type ModelPageObject = NeuronClustersObject;
+    type LayerPageObject = NoData;
+    type NeuronPageObject = NeuronGraphNode;
+
+    async fn required_data_types(&self, database: &Database) -> Result<Vec<DataTypeHandle>> {
+        database
+            .data_type(NEURON_CLUSTERS)
+            .await?
+            .with_context(|| {
+                format!(
+                    "No data object named '{NEURON_CLUSTERS}' in database. This should have been \
+                     checked when service was created."
+                )
+            })
+            .map(|data_type| vec![data_type])
+    }
+
+    async fn model_object(
+        &self,
+        _service_name: &str,
+        state: &State,
+        query: &serde_json::Value,
+        model: &ModelHandle,
+    ) -> Result<Self::ModelPageObject> {
+        let cluster = match query.get("cluster") {
+            Some(serde_json::Value::String(cluster)) => {
+                Some(cluster.parse::<u32>().map_err(|_| {
+                    InvalidQuery(format!(
+                        "Cluster '{cluster}' should be a non-negative integer."
+                    ))
+                })?)
+            }
+            Some(cluster) => {
+                return Err(InvalidQuery(format!(
+                    "Query field 'cluster' should be a string. Found: {cluster}"
+                ))
+                .into())
+            }
+            None => None,
+        };
+        let mut clusters = clusters_data(state.database(), model)
+            .await?
+            .clusters()
+            .await?;
+        match cluster {
+            Some(cluster) => {
+                clusters.clusters.retain(|other| other.id == cluster);
+                if clusters.clusters.is_empty() {
+                    return Err(InvalidQuery(format!(
+                        "Model '{}' has no cluster {cluster}.",
+                        model.name()
+                    ))
+                    .into());
+                }
+            }
+            None => {
+                for cluster in clusters.clusters.iter_mut() {
+                    cluster.members.truncate(MAX_LISTED_MEMBERS);
+                }
+            }
+        }
+        Ok(clusters)
+    }
+
+    async fn neuron_object(
+        &self,
+        _service_name: &str,
+        state: &State,
+        _query: &serde_json::Value,
+        model: &ModelHandle,
+        layer_index: u32,
+        neuron_index: u32,
+    ) -> Result<Self::NeuronPageObject> {
+        clusters_data(state.database(), model)
+            .await?
+            .node(layer_index, neuron_index)
+            .await?
+            .with_context(|| {
+                format!(
+                    "Neuron l{layer_index}n{neuron_index} of model '{}' has no neuron cluster \
+                     data.",
+                    model.name()
+                )
+            })
+    }
+}
@@ -0,0 +1,77 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::service_provider::{InvalidQuery, NoData, ServiceProviderTrait};
+use crate::{
+    data::{
+        data_objects::ModelCorrespondence, data_types::NeuronCorrespondence as CorrespondenceData,
+        DataTypeHandle, Database, ModelHandle,
+    },
+    server::State,
+};
+
+/// The most similar neurons of a neuron in other models, by the tokens their neuron stores share.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NeuronCorrespondence;
+
+const DATA_TYPE_NAME: &str = "neuron_correspondence";
+
+#[async_trait]
+impl ServiceProviderTrait for NeuronCorrespondence {
+    type ModelPageObject = NoData;
+    type LayerPageObject = NoData;
+    type NeuronPageObject = Vec<ModelCorrespondence>;
+
+    async fn required_data_types(&self, database: &Database) -> Result<Vec<DataTypeHandle>> {
+        database
+            .data_type(DATA_TYPE_NAME)
+            .await?
+            .with_context(|| {
+                format!(
+                    "No data object named '{DATA_TYPE_NAME}' in database. This should have been \
+                     checked when service was created."
+                )
+            })
+            .map(|data_type| vec![data_type])
+    }
+
+    async fn neuron_object(
+        &self,
+        _service_name: &str,
+        state: &State,
+        query: &serde_json::Value,
+        model: &ModelHandle,
+        layer_index: u32,
+        neuron_index: u32,
+    ) -> Result<Self::NeuronPageObject> {
+        let models: Option<Vec<&str>> = match query.get("models") {
+            Some(serde_json::Value::String(models)) => Some(models.split(',').collect()),
+            Some(models) => {
+                return Err(InvalidQuery(format!(
+                    "Query field 'models' should be a string. Found: {models}"
+                ))
+                .into())
+            }
+            None => None,
+        };
+        let data_type = state
+            .database()
+            .data_type(DATA_TYPE_NAME)
+            .await?
+            .with_context(|| format!("No data object with name '{DATA_TYPE_NAME}'."))?;
+        let data_type: CorrespondenceData =
+            model.data_type(&data_type).await.with_context(|| {
+                format!(
+                    "Failed to get neuron correspondence data object for model '{}'.",
+                    model.name()
+                )
+            })?;
+        let mut correspondences = data_type.correspondences(layer_index, neuron_index).await?;
+        if let Some(models) = models {
+            correspondences
+                .retain(|correspondence| models.contains(&correspondence.model.as_str()));
+        }
+        Ok(correspondences)
+    }
+}
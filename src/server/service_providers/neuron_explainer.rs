@@ -1,13 +1,15 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
-use super::service_provider::{NoData, ServiceProviderTrait};
+use super::service_provider::ServiceProviderTrait;
 use crate::{
     data::{
-        data_objects::NeuronExplainerPage, data_types::NeuronExplainer as NeuronExplainerData,
-        retrieve::neuron_explainer, DataTypeHandle, Database, ModelHandle, NeuronIndex,
+        data_objects::{NeuronExplainerPage, NeuronExplainerSummary},
+        data_types::NeuronExplainer as NeuronExplainerData,
+        retrieve::neuron_explainer,
+        DataTypeHandle, Database, ModelHandle, NeuronIndex,
     },
     server::State,
 };
@@ -30,10 +32,60 @@ async fn data_type(state: &State, model: &ModelHandle) -> Result<NeuronExplainer
     })
 }
 
+/// Gets the page for the neuron, fetching it from the original source and storing it if it is
+/// missing and the server is not offline. Also returns whether the page was fetched live.
+async fn neuron_page(
+    state: &State,
+    model: &ModelHandle,
+    index: NeuronIndex,
+) -> Result<(NeuronExplainerPage, bool)> {
+    let NeuronIndex {
+        layer: layer_index,
+        neuron: neuron_index,
+    } = index;
+    let data_type = data_type(state, model).await?;
+    if let Some(page) = data_type.neuron_page(layer_index, neuron_index).await? {
+        let live_fetch = data_type.is_live_fetched(layer_index, neuron_index).await?;
+        return Ok((page, live_fetch));
+    }
+
+    let client = state.upstream_client().with_context(|| {
+        format!(
+            "No neuron explainer page exists for neuron {index} in model '{model_name}' and \
+             fetching from source is disabled.",
+            model_name = model.name()
+        )
+    })?;
+    let page =
+        neuron_explainer::fetch_neuron(client, neuron_explainer::model_url(model.name(), index)?)
+            .await
+            .with_context(|| {
+                format!(
+                    "No neuron explainer page exists for neuron {index} in model '{model_name}' \
+                     and fetching from source failed.",
+                    model_name = model.name()
+                )
+            })?;
+    if let Err(error) = data_type
+        .add_live_fetched_neuron_page(layer_index, neuron_index, &page)
+        .await
+    {
+        log::error!("Failed to store live fetched neuron explainer page: {error:?}");
+    } else {
+        // Summarising reads every page of the layer, so the response does not wait for it.
+        state.layer_summaries().mark_stale(
+            model.clone(),
+            data_type.data_type_handle().clone(),
+            layer_index,
+        );
+    }
+    Ok((page, true))
+}
+
 #[async_trait]
 impl ServiceProviderTrait for NeuronExplainer {
-    type ModelPageObject = NoData;
-    type LayerPageObject = NoData;
+    type ModelPageObject = NeuronExplainerSummary;
+    type LayerPageObject = NeuronExplainerSummary;
     type NeuronPageObject = NeuronExplainerPage;
 
     async fn required_data_types(&self, database: &Database) -> Result<Vec<DataTypeHandle>> {
@@ -47,6 +99,46 @@ impl ServiceProviderTrait for NeuronExplainer {
         Ok(vec![data_type])
     }
 
+    async fn model_object(
+        &self,
+        _service_name: &str,
+        state: &State,
+        _query: &serde_json::Value,
+        model: &ModelHandle,
+    ) -> Result<Self::ModelPageObject> {
+        data_type(state, model)
+            .await?
+            .model_page()
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to get neuron explainer model page for model '{}'.",
+                    model.name()
+                )
+            })
+    }
+
+    async fn layer_object(
+        &self,
+        _service_name: &str,
+        state: &State,
+        _query: &serde_json::Value,
+        model: &ModelHandle,
+        layer_index: u32,
+    ) -> Result<Self::LayerPageObject> {
+        data_type(state, model)
+            .await?
+            .layer_page(layer_index)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to get neuron explainer layer page for layer {layer_index} in model \
+                     '{}'.",
+                    model.name()
+                )
+            })
+    }
+
     async fn neuron_object(
         &self,
         _service_name: &str,
@@ -60,26 +152,27 @@ impl ServiceProviderTrait for NeuronExplainer {
             layer: layer_index,
             neuron: neuron_index,
         };
-        let page = if let Some(page) = data_type(state, model)
-            .await?
-            .neuron_page(layer_index, neuron_index)
-            .await?
-        {
-            page
-        } else {
-            neuron_explainer::fetch_neuron(
-                &Client::new(),
-                neuron_explainer::model_url(model.name(), index)?,
-            )
+        neuron_page(state, model, index)
             .await
-            .with_context(|| {
-                format!(
-                    "No neuron explainer page exists for neuron {index} in model '{model_name}' \
-                     and fetching from source failed.",
-                    model_name = model.name()
-                )
-            })?
+            .map(|(page, _live_fetch)| page)
+    }
+
+    async fn neuron_json(
+        &self,
+        _service_name: &str,
+        state: &State,
+        _query: &serde_json::Value,
+        model: &ModelHandle,
+        layer_index: u32,
+        neuron_index: u32,
+    ) -> Result<serde_json::Value> {
+        let index = NeuronIndex {
+            layer: layer_index,
+            neuron: neuron_index,
         };
-        Ok(page)
+        let (page, live_fetch) = neuron_page(state, model, index).await?;
+        let mut value = json!(page);
+        value["live_fetch"] = json!(live_fetch);
+        Ok(value)
     }
 }
@@ -0,0 +1,86 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::service_provider::{
+    layers_field, string_field, InvalidQuery, NoData, ServiceProviderTrait,
+};
+use crate::{
+    data::{
+        data_objects::NeuronQueryPage,
+        database::{NeuronQuery as Query, UnknownDataType},
+        DataTypeHandle, Database, ModelHandle, NeuronFilter, NeuronOrder,
+    },
+    server::State,
+};
+
+/// Filters and sorts the neurons of a model by the scalars extracted from their data, such as
+/// explanation scores, activation ranges and graph sizes.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NeuronQuery;
+
+fn integer_field(query: &serde_json::Value, field: &str, default: usize) -> Result<usize> {
+    string_field(query, field)?
+        .map(|value| {
+            value.parse::<usize>().map_err(|_| {
+                InvalidQuery(format!(
+                    "Query field '{field}' should be a non-negative integer. Found: {value}"
+                ))
+                .into()
+            })
+        })
+        .transpose()
+        .map(|value| value.unwrap_or(default))
+}
+
+#[async_trait]
+impl ServiceProviderTrait for NeuronQuery {
+    type ModelPageObject = NeuronQueryPage;
+    type LayerPageObject = NoData;
+    type NeuronPageObject = NoData;
+
+    async fn required_data_types(&self, _database: &Database) -> Result<Vec<DataTypeHandle>> {
+        Ok(vec![])
+    }
+
+    async fn model_object(
+        &self,
+        _service_name: &str,
+        _state: &State,
+        query: &serde_json::Value,
+        model: &ModelHandle,
+    ) -> Result<Self::ModelPageObject> {
+        let filter = string_field(query, "where")?
+            .filter(|filter| !filter.trim().is_empty())
+            .map(|filter| {
+                filter
+                    .parse::<NeuronFilter>()
+                    .map_err(|error| InvalidQuery(error.to_string()))
+            })
+            .transpose()?;
+        let order = string_field(query, "sort")?
+            .filter(|order| !order.trim().is_empty())
+            .map(|order| {
+                order
+                    .parse::<NeuronOrder>()
+                    .map_err(|error| InvalidQuery(format!("Invalid sort order '{order}'. {error}")))
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let layers = layers_field(query, model)?;
+
+        let neuron_query = Query {
+            filter,
+            order,
+            layers,
+            limit: integer_field(query, "limit", 100)?,
+            offset: integer_field(query, "offset", 0)?,
+        };
+        model.query_neurons(&neuron_query).await.map_err(|error| {
+            match error.downcast::<UnknownDataType>() {
+                Ok(unknown) => InvalidQuery(unknown.to_string()).into(),
+                Err(error) => error,
+            }
+        })
+    }
+}
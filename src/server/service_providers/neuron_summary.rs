@@ -0,0 +1,98 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::service_provider::{InvalidQuery, NoData, ServiceProviderTrait};
+use crate::{
+    data::{
+        data_objects::NeuronSummary as NeuronSummaryObject, DataTypeHandle, Database, ModelHandle,
+        NeuronIndex,
+    },
+    server::State,
+    Index,
+};
+
+/// Small summaries of neurons for previews, for a single neuron or for a batch of neurons given
+/// as `neurons=3_12,5_7`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NeuronSummary;
+
+/// Maximum number of neurons summarised in one request.
+const MAX_NEURONS: usize = 200;
+
+#[async_trait]
+impl ServiceProviderTrait for NeuronSummary {
+    type ModelPageObject = Vec<NeuronSummaryObject>;
+    type LayerPageObject = NoData;
+    type NeuronPageObject = NeuronSummaryObject;
+
+    async fn required_data_types(&self, _database: &Database) -> Result<Vec<DataTypeHandle>> {
+        Ok(vec![])
+    }
+
+    async fn model_object(
+        &self,
+        _service_name: &str,
+        _state: &State,
+        query: &serde_json::Value,
+        model: &ModelHandle,
+    ) -> Result<Self::ModelPageObject> {
+        let neurons = match query.get("neurons") {
+            Some(serde_json::Value::String(neurons)) => neurons,
+            Some(neurons) => {
+                return Err(InvalidQuery(format!(
+                    "Query field 'neurons' should be a string. Found: {neurons}"
+                ))
+                .into())
+            }
+            None => {
+                return Err(InvalidQuery(
+                    "Query should contain an entry 'neurons' with a comma separated list of \
+                     neurons such as '3_12,5_7'."
+                        .to_owned(),
+                )
+                .into())
+            }
+        };
+        let neurons = neurons
+            .split(',')
+            .map(|neuron| {
+                let neuron_index = neuron
+                    .trim()
+                    .parse::<NeuronIndex>()
+                    .map_err(|error| InvalidQuery(format!("Invalid neuron '{neuron}'. {error}")))?;
+                Index::from(neuron_index)
+                    .valid_in_model(model.metadata())
+                    .map_err(|error| InvalidQuery(format!("{error:#}")))?;
+                Ok(neuron_index)
+            })
+            .collect::<Result<Vec<_>, InvalidQuery>>()?;
+        if neurons.len() > MAX_NEURONS {
+            return Err(InvalidQuery(format!(
+                "At most {MAX_NEURONS} neurons can be summarised at once. Found {}.",
+                neurons.len()
+            ))
+            .into());
+        }
+        model.neuron_summaries(neurons).await
+    }
+
+    async fn neuron_object(
+        &self,
+        _service_name: &str,
+        _state: &State,
+        _query: &serde_json::Value,
+        model: &ModelHandle,
+        layer_index: u32,
+        neuron_index: u32,
+    ) -> Result<Self::NeuronPageObject> {
+        model
+            .neuron_summaries(vec![NeuronIndex {
+                layer: layer_index,
+                neuron: neuron_index,
+            }])
+            .await?
+            .pop()
+            .context("Summarising a neuron should give a summary.")
+    }
+}
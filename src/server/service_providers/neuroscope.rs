@@ -150,29 +150,45 @@ impl ServiceProviderTrait for Neuroscope {
         layer_index: u32,
         neuron_index: u32,
     ) -> Result<serde_json::Value> {
-        let page = if let Some(page) = data_type(state, model)
-            .await?
-            .neuron_page(layer_index, neuron_index)
-            .await?
-        {
-            page
-        } else {
-            scrape_neuron_page(
-                model.name(),
-                NeuronIndex {
-                    layer: layer_index,
-                    neuron: neuron_index,
-                },
-            )
-            .await
-            .with_context(|| {
-                format!(
-                    "No neuroscope page exists for neuron l{layer_index}n{neuron_index} in model \
-                     '{model_name}' and fetching from source failed.",
-                    model_name = model.name()
+        let data_type = data_type(state, model).await?;
+        let (page, live_fetch) =
+            if let Some(page) = data_type.neuron_page(layer_index, neuron_index).await? {
+                let live_fetch = data_type.is_live_fetched(layer_index, neuron_index).await?;
+                (page, live_fetch)
+            } else {
+                let client = state.upstream_client().with_context(|| {
+                    format!(
+                        "No neuroscope page exists for neuron l{layer_index}n{neuron_index} in \
+                         model '{model_name}' and fetching from source is disabled.",
+                        model_name = model.name()
+                    )
+                })?;
+                let page = scrape_neuron_page(
+                    client,
+                    model.name(),
+                    NeuronIndex {
+                        layer: layer_index,
+                        neuron: neuron_index,
+                    },
                 )
-            })?
-        };
-        Ok(json!(page))
+                .await
+                .with_context(|| {
+                    format!(
+                        "No neuroscope page exists for neuron l{layer_index}n{neuron_index} in \
+                         model '{model_name}' and fetching from source failed.",
+                        model_name = model.name()
+                    )
+                })?;
+                if let Err(error) = data_type
+                    .add_live_fetched_neuron_page(layer_index, neuron_index, &page)
+                    .await
+                {
+                    log::error!("Failed to store live fetched neuroscope page: {error:?}");
+                }
+                (page, true)
+            };
+        let mut value = json!(page);
+        value["live_fetch"] = json!(live_fetch);
+        Ok(value)
     }
 }
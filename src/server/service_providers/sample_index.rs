@@ -0,0 +1,113 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::service_provider::{
+    layers_field, limit_field, string_field, InvalidQuery, NoData, ServiceProviderTrait,
+};
+use crate::{
+    data::{
+        database::{SampleActivation, SharedSamples},
+        DataTypeHandle, Database, ModelHandle, NeuronIndex,
+    },
+    server::State,
+};
+
+/// Index from the dataset samples of Neuroscope pages to the neurons they activate. The model page
+/// lists the neurons activated by a sample and the neuron page the neurons sharing samples with
+/// the neuron.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SampleIndex;
+
+fn integer_field<T: std::str::FromStr>(
+    query: &serde_json::Value,
+    field: &str,
+) -> Result<Option<T>> {
+    string_field(query, field)?
+        .map(|value| {
+            value.parse::<T>().map_err(|_| {
+                InvalidQuery(format!(
+                    "Query field '{field}' should be a non-negative integer. Found: {value}"
+                ))
+                .into()
+            })
+        })
+        .transpose()
+}
+
+/// The data type, layers and limit common to both pages.
+async fn common_fields(
+    state: &State,
+    query: &serde_json::Value,
+    model: &ModelHandle,
+    default_limit: usize,
+) -> Result<(
+    Option<DataTypeHandle>,
+    Option<std::ops::RangeInclusive<u32>>,
+    usize,
+)> {
+    let data_type = match string_field(query, "data_type")? {
+        Some(data_type_name) => Some(
+            state
+                .database()
+                .data_type(data_type_name)
+                .await?
+                .ok_or_else(|| {
+                    InvalidQuery(format!("No data object with name '{data_type_name}'."))
+                })?,
+        ),
+        None => None,
+    };
+    let layers = layers_field(query, model)?;
+    let limit = limit_field(query, default_limit)?;
+    Ok((data_type, layers, limit))
+}
+
+#[async_trait]
+impl ServiceProviderTrait for SampleIndex {
+    type ModelPageObject = Vec<SampleActivation>;
+    type LayerPageObject = NoData;
+    type NeuronPageObject = Vec<SharedSamples>;
+
+    async fn required_data_types(&self, _database: &Database) -> Result<Vec<DataTypeHandle>> {
+        Ok(vec![])
+    }
+
+    async fn model_object(
+        &self,
+        _service_name: &str,
+        state: &State,
+        query: &serde_json::Value,
+        model: &ModelHandle,
+    ) -> Result<Self::ModelPageObject> {
+        let data_index = integer_field(query, "sample")?.ok_or_else(|| {
+            InvalidQuery(
+                "Query should contain an entry 'sample' with the data index of a sample."
+                    .to_owned(),
+            )
+        })?;
+        let (data_type, layers, limit) = common_fields(state, query, model, 100).await?;
+        model
+            .sample_neurons(data_index, data_type.as_ref(), layers, limit)
+            .await
+    }
+
+    async fn neuron_object(
+        &self,
+        _service_name: &str,
+        state: &State,
+        query: &serde_json::Value,
+        model: &ModelHandle,
+        layer_index: u32,
+        neuron_index: u32,
+    ) -> Result<Self::NeuronPageObject> {
+        let (data_type, layers, limit) = common_fields(state, query, model, 20).await?;
+        let neuron = NeuronIndex {
+            layer: layer_index,
+            neuron: neuron_index,
+        };
+        model
+            .shared_sample_neurons(neuron, data_type.as_ref(), layers, limit)
+            .await
+    }
+}
@@ -1,4 +1,4 @@
-use std::{future::Future, pin::Pin};
+use std::{future::Future, ops::RangeInclusive, pin::Pin};
 
 use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
@@ -8,15 +8,68 @@ use serde_json::json;
 use strum::AsRefStr;
 
 use super::{
-    json::Json, metadata::Metadata, neuron2graph::Neuron2Graph,
-    neuron2graph_search::Neuron2GraphSearch, neuron_explainer::NeuronExplainer,
-    neuroscope::Neuroscope,
+    context_search::ContextSearch, embedding_search::EmbeddingSearch,
+    explanation_search::ExplanationSearch, explore::Explore, json::Json, metadata::Metadata,
+    neuron2graph::Neuron2Graph, neuron2graph_search::Neuron2GraphSearch,
+    neuron_clusters::NeuronClusters, neuron_correspondence::NeuronCorrespondence,
+    neuron_explainer::NeuronExplainer, neuron_query::NeuronQuery, neuron_summary::NeuronSummary,
+    neuroscope::Neuroscope, sample_index::SampleIndex, token_statistics::TokenStatistics,
 };
 use crate::{
-    data::{data_objects::DataObject, DataTypeHandle, Database, ModelHandle},
+    data::{data_objects::DataObject, DataTypeHandle, Database, LayerRange, ModelHandle},
     server::State,
 };
 
+/// Error for a query that is malformed, as opposed to one the service failed to answer. Such
+/// errors are responded to with `400 Bad Request`.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct InvalidQuery(pub String);
+
+/// The value of a string field of a query, if present.
+pub(in crate::server) fn string_field<'a>(
+    query: &'a serde_json::Value,
+    field: &str,
+) -> Result<Option<&'a str>, InvalidQuery> {
+    match query.get(field) {
+        None => Ok(None),
+        Some(serde_json::Value::String(value)) => Ok(Some(value.as_str())),
+        Some(value) => Err(InvalidQuery(format!(
+            "Query field '{field}' should be a string. Found: {value}"
+        ))),
+    }
+}
+
+/// The layers of the model given by the `layers` field of a query, if present.
+pub(in crate::server) fn layers_field(
+    query: &serde_json::Value,
+    model: &ModelHandle,
+) -> Result<Option<RangeInclusive<u32>>, InvalidQuery> {
+    string_field(query, "layers")?
+        .map(|layers| {
+            layers
+                .parse::<LayerRange>()
+                .and_then(|layers| layers.layers(model.metadata()))
+                .map_err(|error| InvalidQuery(format!("Invalid layer range '{layers}'. {error}")))
+        })
+        .transpose()
+}
+
+/// The `limit` field of a query, or the default limit if it is absent.
+pub(in crate::server) fn limit_field(
+    query: &serde_json::Value,
+    default_limit: usize,
+) -> Result<usize, InvalidQuery> {
+    string_field(query, "limit")?
+        .map(|limit| {
+            limit
+                .parse::<usize>()
+                .map_err(|_| InvalidQuery(format!("Limit '{limit}' is not a valid integer.")))
+        })
+        .transpose()
+        .map(|limit| limit.unwrap_or(default_limit))
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub enum NoData {}
 
@@ -186,12 +239,26 @@ pub enum ServiceProvider {
     Neuron2Graph = 3,
     Neuron2GraphSearch = 4,
     Json(Json) = 5,
+    ExplanationSearch = 6,
+    NeuronQuery = 7,
+    SampleIndex = 8,
+    ContextSearch = 9,
+    NeuronCorrespondence = 10,
+    TokenStatistics = 11,
+    NeuronSummary = 12,
+    Explore = 13,
+    EmbeddingSearch(EmbeddingSearch) = 14,
+    NeuronClusters = 15,
 }
 
 impl ServiceProvider {
     pub fn json(data_type_name: String) -> Self {
         ServiceProvider::Json(Json::new(data_type_name))
     }
+
+    pub fn embedding_search(data_type_name: String) -> Self {
+        ServiceProvider::EmbeddingSearch(EmbeddingSearch::new(data_type_name))
+    }
 }
 
 impl ServiceProvider {
@@ -207,6 +274,16 @@ impl ServiceProvider {
             ServiceProvider::Neuron2Graph => Neuron2Graph,
             ServiceProvider::Neuron2GraphSearch => Neuron2GraphSearch,
             ServiceProvider::Json(json) => json,
+            ServiceProvider::ExplanationSearch => ExplanationSearch,
+            ServiceProvider::NeuronQuery => NeuronQuery,
+            ServiceProvider::SampleIndex => SampleIndex,
+            ServiceProvider::ContextSearch => ContextSearch,
+            ServiceProvider::NeuronCorrespondence => NeuronCorrespondence,
+            ServiceProvider::TokenStatistics => TokenStatistics,
+            ServiceProvider::NeuronSummary => NeuronSummary,
+            ServiceProvider::Explore => Explore,
+            ServiceProvider::EmbeddingSearch(embedding_search) => embedding_search,
+            ServiceProvider::NeuronClusters => NeuronClusters,
         } {
             pub fn required_data_types<'a>(
                 &'a self, database: &'a Database,
@@ -0,0 +1,165 @@
+use std::{collections::HashMap, ops::RangeInclusive};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::service_provider::{
+    layers_field, limit_field, string_field, InvalidQuery, NoData, ServiceProviderTrait,
+};
+use crate::{
+    data::{
+        database::TokenImportance, DataTypeHandle, Database, LayerRange, ModelHandle,
+        Normalisation, TokenCounts, TokenPattern, TokenSearchType, TokenSummary,
+    },
+    server::State,
+};
+
+/// How many neurons tokens activate or are important for in each layer of a model, and which
+/// neurons' graphs consider them most important.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TokenStatistics;
+
+/// The tokens of a query, given either as a single `token` or as a comma separated list of
+/// `tokens`. Tokens are not trimmed, as whitespace is part of many tokens.
+pub(in crate::server) fn query_tokens(query: &serde_json::Value) -> Result<Vec<String>> {
+    let tokens: Vec<String> = match (
+        string_field(query, "token")?,
+        string_field(query, "tokens")?,
+    ) {
+        (Some(token), None) => vec![token.to_owned()],
+        (None, Some(tokens)) => tokens.split(',').map(str::to_owned).collect(),
+        _ => {
+            return Err(InvalidQuery(
+                "Query should contain exactly one of the entries 'token' and 'tokens'.".to_owned(),
+            )
+            .into())
+        }
+    };
+    if tokens.iter().any(String::is_empty) {
+        return Err(InvalidQuery("Tokens should not be empty.".to_owned()).into());
+    }
+    Ok(tokens)
+}
+
+/// Summarises the neurons of the model that each token activates or is important for within the
+/// layers, with the `limit` neurons whose graphs consider the token most important.
+pub(in crate::server) async fn token_summaries(
+    state: &State,
+    model: &ModelHandle,
+    tokens: &[String],
+    normalisation: Normalisation,
+    layers: RangeInclusive<u32>,
+    limit: usize,
+) -> Result<Vec<TokenSummary>> {
+    let statistics = state.token_statistics(model).await?;
+    let neuron_store = state.neuron_store(model).await?;
+    let vocabulary = state.token_vocabulary(model).await?;
+
+    let mut summaries = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        let (matched_tokens, counts) = if normalisation.is_identity() {
+            match statistics.get(token) {
+                Some(counts) => (vec![token.clone()], counts.in_layers(&layers)),
+                None => (vec![], TokenCounts::default()),
+            }
+        } else {
+            let matched_tokens: Vec<String> = vocabulary
+                .matching(&TokenPattern::Exact(token.clone()), normalisation)?
+                .into_iter()
+                .map(|(entry, _)| entry.token.clone())
+                .collect();
+            let neuron_store = neuron_store.as_ref();
+            let matched = &matched_tokens;
+            let neurons = |search_type| {
+                matched
+                    .iter()
+                    .filter_map(move |token| neuron_store.get(search_type, token))
+                    .flatten()
+                    .copied()
+            };
+            let counts = TokenCounts::from_neurons(
+                neurons(TokenSearchType::Activating),
+                neurons(TokenSearchType::Important),
+            )
+            .in_layers(&layers);
+            (matched_tokens, counts)
+        };
+
+        let mut top_neurons: HashMap<(u32, u32), f32> = HashMap::new();
+        for matched_token in &matched_tokens {
+            for found in model
+                .top_token_neurons(matched_token, Some(layers.clone()), limit)
+                .await?
+            {
+                let importance = top_neurons
+                    .entry((found.layer, found.neuron))
+                    .or_insert(found.importance);
+                *importance = importance.max(found.importance);
+            }
+        }
+        let mut top_neurons: Vec<TokenImportance> = top_neurons
+            .into_iter()
+            .map(|((layer, neuron), importance)| TokenImportance {
+                layer,
+                neuron,
+                importance,
+            })
+            .collect();
+        top_neurons.sort_unstable_by(|a, b| {
+            b.importance
+                .total_cmp(&a.importance)
+                .then((a.layer, a.neuron).cmp(&(b.layer, b.neuron)))
+        });
+        top_neurons.truncate(limit);
+
+        summaries.push(TokenSummary::new(
+            token.clone(),
+            matched_tokens,
+            &counts,
+            top_neurons,
+        ));
+    }
+    Ok(summaries)
+}
+
+#[async_trait]
+impl ServiceProviderTrait for TokenStatistics {
+    type ModelPageObject = Vec<TokenSummary>;
+    type LayerPageObject = NoData;
+    type NeuronPageObject = NoData;
+
+    async fn required_data_types(&self, database: &Database) -> Result<Vec<DataTypeHandle>> {
+        database
+            .data_type("neuron_store")
+            .await?
+            .context(
+                "No data object named 'neuron_store' in database. This should have been checked \
+                 when service was created.",
+            )
+            .map(|data_type| vec![data_type])
+    }
+
+    async fn model_object(
+        &self,
+        _service_name: &str,
+        state: &State,
+        query: &serde_json::Value,
+        model: &ModelHandle,
+    ) -> Result<Self::ModelPageObject> {
+        let tokens = query_tokens(query)?;
+        let normalisation = string_field(query, "normalise")?
+            .map(|normalisation| {
+                normalisation
+                    .parse::<Normalisation>()
+                    .map_err(|error| InvalidQuery(error.to_string()))
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let layers = layers_field(query, model)?
+            .map_or_else(|| LayerRange::all().layers(model.metadata()), Ok)?;
+        let limit = limit_field(query, 10)?;
+
+        token_summaries(state, model, &tokens, normalisation, layers, limit).await
+    }
+}
@@ -1,37 +1,66 @@
+use actix_cors::Cors;
 use actix_web::{
+    http::Method,
     middleware::{self, TrailingSlash},
     web, App, HttpServer,
 };
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use strum::{AsRefStr, EnumIter, EnumString};
 use utoipa_redoc::{Redoc, Servable};
 
 use crate::{
     cli::ServerConfig,
     data::Database,
     logging,
-    server::{response, State},
+    server::{admin, compare, export, monitoring, rate_limit, response, search, State},
+    util::cancel,
 };
 
-pub async fn start_server(config: ServerConfig) -> Result<()> {
-    logging::log_init_config(&config);
+/// Groups of routes that can be enabled or disabled in the configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumString, AsRefStr, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum RouteSet {
+    /// The API index and the pages of every service.
+    Api,
+    /// The API documentation.
+    Doc,
+    /// Streaming exports of services.
+    Export,
+    /// The `/healthz` and `/readyz` endpoints.
+    Health,
+    /// The Prometheus `/metrics` endpoint.
+    Metrics,
+    /// The admin API. Also requires admin tokens to be configured.
+    Admin,
+}
 
-    let database_path = config.database_path();
-    let database = if database_path.exists() {
-        log::info!("Opening database at {:?}.", database_path);
-        Database::open(database_path).await?
-    } else {
-        log::error!("Database not found at {database_path:?}.");
-        bail!("Database not found at {database_path:?}.");
-    };
-    let url = config.url();
-    let port = config.port();
-    log::info!("Serving DeepDecipher on http://{url}:{port}/");
-    let state = web::Data::new(State::new(database)?);
+fn cors(config: &ServerConfig) -> Cors {
+    let cors = Cors::default()
+        .allowed_methods([Method::GET, Method::HEAD])
+        .allow_any_header()
+        .max_age(3600);
+    match config.allowed_origins() {
+        None => cors.allow_any_origin().send_wildcard(),
+        Some(origins) => origins
+            .iter()
+            .fold(cors, |cors, origin| cors.allowed_origin(origin)),
+    }
+}
 
-    let mut server = HttpServer::new(move || {
-        App::new()
-            .app_data(state.clone())
-            .wrap(middleware::NormalizePath::new(TrailingSlash::Trim))
+fn configure_routes(
+    service_config: &mut web::ServiceConfig,
+    config: &ServerConfig,
+    state: &web::Data<State>,
+) {
+    if config.route_enabled(RouteSet::Admin) && state.admin_enabled() {
+        service_config.service(
+            web::scope("/admin")
+                .wrap(middleware::from_fn(admin::require_admin_token))
+                .configure(admin::configure),
+        );
+    }
+    if config.route_enabled(RouteSet::Doc) {
+        service_config
             .service(Redoc::with_url_and_config(
                 "/doc",
                 state.api_doc().clone(),
@@ -42,19 +71,90 @@ pub async fn start_server(config: ServerConfig) -> Result<()> {
                     .unwrap()
                 },
             ))
+            .service(response::api_doc);
+    }
+    if config.route_enabled(RouteSet::Health) {
+        service_config
+            .service(monitoring::healthz)
+            .service(monitoring::readyz);
+    }
+    if config.route_enabled(RouteSet::Metrics) {
+        service_config.service(monitoring::prometheus_metrics);
+    }
+    if config.route_enabled(RouteSet::Export) {
+        service_config
+            .service(export::export)
+            .service(export::export_graphml);
+    }
+    if config.route_enabled(RouteSet::Api) {
+        service_config
+            .service(search::search_tokens)
+            .service(search::suggest_tokens)
+            .service(search::search_token_statistics)
+            .service(compare::compare)
             .service(response::api_index)
             .service(response::all_model)
             .service(response::all_layer)
             .service(response::all_neuron)
             .service(response::model)
             .service(response::layer)
-            .service(response::neuron)
-            .service(response::api_doc)
-    });
+            .service(response::neuron);
+    }
+}
+
+pub async fn start_server(config: ServerConfig) -> Result<()> {
+    logging::log_init_config(&config);
+
+    let database_path = config
+        .database_path()
+        .context("No database path given on the command line or in the configuration.")?;
+    let database = if database_path.exists() {
+        log::info!("Opening database at {:?}.", database_path);
+        Database::open(database_path).await?
+    } else {
+        log::error!("Database not found at {database_path:?}.");
+        bail!("Database not found at {database_path:?}.");
+    };
+    let url = config.url();
+    let port = config.port();
+    log::info!("Serving DeepDecipher on http://{url}:{port}/");
+    let state = web::Data::new(State::new(database.clone(), &config)?);
+
+    if config.route_enabled(RouteSet::Admin) && state.admin_enabled() {
+        log::info!("Admin API enabled at http://{url}:{port}/admin");
+    }
+
+    let app_config = config.clone();
+    let mut server = HttpServer::new(move || {
+        App::new()
+            .app_data(state.clone())
+            .wrap(middleware::from_fn(rate_limit::limit_rate))
+            .wrap(cors(&app_config))
+            .wrap(middleware::from_fn(monitoring::track_request))
+            .wrap(middleware::NormalizePath::new(TrailingSlash::Trim))
+            .configure(|service_config| configure_routes(service_config, &app_config, &state))
+    })
+    .client_request_timeout(config.client_request_timeout())
+    .keep_alive(config.keep_alive_timeout())
+    .shutdown_timeout(config.shutdown_timeout());
     if let Some(num_workers) = config.num_workers() {
         server = server.workers(num_workers);
     }
-    server.bind((url, port))?.run().await?;
+    let server = server.bind((url, port))?.run();
+
+    // Actix stops the server on SIGINT and SIGTERM. Also stop it if the server was started as a
+    // cancellable operation, such as from the Python bindings, and cancellation is requested.
+    let server_handle = server.handle();
+    tokio::spawn(async move {
+        cancel::cancelled().await;
+        log::info!("Cancellation requested. Stopping server...");
+        server_handle.stop(true).await;
+    });
 
-    bail!("Server stopped unexpectedly.");
+    server.await?;
+    log::info!("Server stopped. Closing database...");
+    database.close().await?;
+    log::info!("Database closed.");
+    log::logger().flush();
+    Ok(())
 }
@@ -0,0 +1,81 @@
+//! Cooperative cancellation of long running operations.
+//!
+//! Operations register themselves with [`start_operation`] and regularly call
+//! [`check_cancelled`], stopping at a point where no write is half done if cancellation was
+//! requested.
+
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    LazyLock,
+};
+
+use tokio::sync::Notify;
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+static RUNNING_OPERATIONS: AtomicUsize = AtomicUsize::new(0);
+static CANCEL_NOTIFY: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+/// Error returned by operations that stopped because cancellation was requested.
+#[derive(Debug, thiserror::Error)]
+#[error("Operation was cancelled.")]
+pub struct Cancelled;
+
+/// Marks an operation as running until it is dropped.
+pub struct OperationGuard {
+    _private: (),
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        RUNNING_OPERATIONS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Registers a cancellable operation. A cancellation request from before any operation was running
+/// is forgotten.
+pub fn start_operation() -> OperationGuard {
+    if RUNNING_OPERATIONS.fetch_add(1, Ordering::SeqCst) == 0 {
+        CANCELLED.store(false, Ordering::SeqCst);
+    }
+    OperationGuard { _private: () }
+}
+
+/// Requests that all running operations stop. Returns `false` if no operation is running, in which
+/// case there is nothing to cancel.
+pub fn request_cancel() -> bool {
+    if RUNNING_OPERATIONS.load(Ordering::SeqCst) == 0 {
+        return false;
+    }
+    CANCELLED.store(true, Ordering::SeqCst);
+    CANCEL_NOTIFY.notify_waiters();
+    true
+}
+
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// Fails with [`Cancelled`] if cancellation has been requested.
+pub fn check_cancelled() -> Result<(), Cancelled> {
+    if is_cancelled() {
+        Err(Cancelled)
+    } else {
+        Ok(())
+    }
+}
+
+/// Whether the error was caused by the operation being cancelled.
+pub fn is_cancellation(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.is::<Cancelled>())
+}
+
+/// Waits until cancellation is requested.
+pub async fn cancelled() {
+    loop {
+        let notified = CANCEL_NOTIFY.notified();
+        if is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+}
@@ -1,2 +1,3 @@
+pub mod cancel;
 mod progress;
 pub use progress::Progress;